@@ -0,0 +1,163 @@
+package org.nsvc;
+
+import android.content.Context;
+import android.media.AudioAttributes;
+import android.media.AudioFocusRequest;
+import android.media.AudioManager;
+
+// Клиент NSVC для Android поверх libvoice_chat.so (см. src/android.rs).
+// Приложение само запрашивает разрешение RECORD_AUDIO до start().
+//
+// Аудиофокус: пока клиент запущен, он держит фокус в режиме связи. Если
+// фокус забрали (звонок, другое голосовое приложение), микрофон замолкает;
+// когда фокус вернулся, прежний режим передачи восстанавливается, а
+// аудиопотоки пересоздаются - система могла их закрыть.
+public final class VoiceClient implements AutoCloseable {
+    // Коды из error_codes и режимы из transmit_modes (см. include/nsvc.h)
+    public static final int SUCCESS = 0;
+    public static final int TRANSMIT_PTT = 0;
+    public static final int TRANSMIT_VOICE_ACTIVATION = 1;
+    public static final int TRANSMIT_CONTINUOUS = 2;
+    public static final int TRANSMIT_TOGGLE = 3;
+
+    static {
+        System.loadLibrary("voice_chat");
+    }
+
+    private final AudioManager audioManager;
+    private final AudioFocusRequest focusRequest;
+    private long handle;
+    // Что задало приложение; при потере фокуса временно сбрасывается
+    private int transmitMode = TRANSMIT_PTT;
+    private boolean transmitting = false;
+    private boolean focusLost = false;
+    private int previousAudioMode = AudioManager.MODE_NORMAL;
+
+    public VoiceClient(Context context, String host, int port) {
+        Context app = context.getApplicationContext();
+        int result = nativeInit(app);
+        if (result != SUCCESS) {
+            throw new IllegalStateException(nativeErrorString(result));
+        }
+        handle = nativeNew(host, port);
+        if (handle == 0) {
+            throw new IllegalStateException("Failed to create voice client for " + host + ":" + port);
+        }
+
+        audioManager = (AudioManager) app.getSystemService(Context.AUDIO_SERVICE);
+        AudioAttributes attributes = new AudioAttributes.Builder()
+                .setUsage(AudioAttributes.USAGE_VOICE_COMMUNICATION)
+                .setContentType(AudioAttributes.CONTENT_TYPE_SPEECH)
+                .build();
+        focusRequest = new AudioFocusRequest.Builder(AudioManager.AUDIOFOCUS_GAIN)
+                .setAudioAttributes(attributes)
+                .setOnAudioFocusChangeListener(this::onAudioFocusChange)
+                .build();
+    }
+
+    public synchronized int start() {
+        previousAudioMode = audioManager.getMode();
+        // Режим связи включает системное эхоподавление и маршрут гарнитуры
+        audioManager.setMode(AudioManager.MODE_IN_COMMUNICATION);
+        audioManager.requestAudioFocus(focusRequest);
+        focusLost = false;
+        int result = nativeStart(handle);
+        if (result != SUCCESS) {
+            releaseAudio();
+        }
+        return result;
+    }
+
+    public synchronized void stop() {
+        nativeStop(handle);
+        releaseAudio();
+    }
+
+    public synchronized void setTransmitting(boolean transmitting) {
+        this.transmitting = transmitting;
+        if (!focusLost) {
+            nativeSetTransmitting(handle, transmitting);
+        }
+    }
+
+    public synchronized int setTransmitMode(int mode) {
+        if (focusLost) {
+            transmitMode = mode;
+            return SUCCESS;
+        }
+        int result = nativeSetTransmitMode(handle, mode);
+        if (result == SUCCESS) {
+            transmitMode = mode;
+        }
+        return result;
+    }
+
+    public synchronized int restartAudio() {
+        return nativeRestartAudio(handle);
+    }
+
+    public synchronized int getConnectionState() {
+        return nativeGetConnectionState(handle);
+    }
+
+    public synchronized int setNickname(String nickname) {
+        return nativeSetNickname(handle, nickname);
+    }
+
+    public synchronized int joinChannel(String name) {
+        return nativeJoinChannel(handle, name);
+    }
+
+    public static String errorString(int code) {
+        return nativeErrorString(code);
+    }
+
+    @Override
+    public synchronized void close() {
+        if (handle != 0) {
+            nativeFree(handle);
+            releaseAudio();
+            handle = 0;
+        }
+    }
+
+    private synchronized void onAudioFocusChange(int change) {
+        switch (change) {
+            case AudioManager.AUDIOFOCUS_LOSS:
+            case AudioManager.AUDIOFOCUS_LOSS_TRANSIENT:
+                focusLost = true;
+                nativeSetTransmitMode(handle, TRANSMIT_PTT);
+                nativeSetTransmitting(handle, false);
+                break;
+            case AudioManager.AUDIOFOCUS_GAIN:
+                if (focusLost) {
+                    focusLost = false;
+                    nativeRestartAudio(handle);
+                    nativeSetTransmitMode(handle, transmitMode);
+                    nativeSetTransmitting(handle, transmitting);
+                }
+                break;
+            default:
+                // Приглушение разговору не нужно: голос остается как есть
+                break;
+        }
+    }
+
+    private void releaseAudio() {
+        audioManager.abandonAudioFocusRequest(focusRequest);
+        audioManager.setMode(previousAudioMode);
+    }
+
+    private static native int nativeInit(Context context);
+    private static native long nativeNew(String host, int port);
+    private static native int nativeStart(long handle);
+    private static native void nativeStop(long handle);
+    private static native void nativeFree(long handle);
+    private static native void nativeSetTransmitting(long handle, boolean transmitting);
+    private static native int nativeSetTransmitMode(long handle, int mode);
+    private static native int nativeRestartAudio(long handle);
+    private static native int nativeGetConnectionState(long handle);
+    private static native int nativeSetNickname(long handle, String nickname);
+    private static native int nativeJoinChannel(long handle, String name);
+    private static native String nativeErrorString(int code);
+}
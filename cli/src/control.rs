@@ -0,0 +1,301 @@
+// Управление идущим звонком из других программ (--control): скрипты
+// AutoHotkey, Stream Deck и т.п. На Windows - именованный канал
+// \\.\pipe\nsvc-call, на остальных системах - Unix-сокет nsvc-call.sock в
+// XDG_RUNTIME_DIR. Команда - объект JSON в строке, ответ - тоже:
+//   {"command":"mute"}                             -> {"ok":true,"muted":true}
+//   {"command":"mute","value":false}               выключить, а не переключить
+//   {"command":"deafen","value":true}              -> {"ok":true,"deafened":true}
+//   {"command":"set_volume","value":0.5}           общая громкость, 0.0-2.0
+//   {"command":"set_volume","user":7,"value":1.5}  громкость участника
+//   {"command":"mute_user","user":7}               заглушить у себя, см. muted.rs
+//   {"command":"stats"}                            -> {"ok":true,"state":"Connected",...}
+//   {"command":"users"}                            -> {"ok":true,"users":[{"id":7,...}]}
+//   {"command":"hang_up"}
+// Ошибка - {"ok":false,"error":"..."}. Команды выполняет главный поток, как
+// клавиши; программ может быть подключено несколько сразу.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use nsvc_core::client::Client;
+use nsvc_core::connection_state_name;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::keys::Input;
+use crate::muted;
+use crate::tui::say;
+
+// Поля команды - как в JSON; лишние поля не мешают
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    // value: None - переключить
+    Mute { value: Option<bool> },
+    Deafen { value: Option<bool> },
+    // user: None - все воспроизведение
+    SetVolume { user: Option<u32>, value: f32 },
+    // value: None - переключить
+    MuteUser { user: u32, value: Option<bool> },
+    Stats,
+    Users,
+    HangUp,
+}
+
+// Открыт, пока жив; Unix-сокет удаляется вместе с ним
+pub struct Control {
+    pub address: PathBuf,
+}
+
+impl Control {
+    // Ошибка - канал уже занят другим звонком или его не создать
+    pub fn start(tx: Sender<Input>) -> io::Result<Control> {
+        let mut listener = endpoint::Listener::bind()?;
+        let address = listener.address();
+        thread::spawn(move || loop {
+            match listener.accept() {
+                Ok((reader, writer)) => {
+                    let tx = tx.clone();
+                    thread::spawn(move || serve(reader, writer, &tx));
+                },
+                Err(e) => {
+                    say!("Control channel error: {}", e);
+                    return;
+                },
+            }
+        });
+        Ok(Control { address })
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.address);
+    }
+}
+
+// Команды одной программы по очереди, пока она не отключится или звонок не кончится
+fn serve(reader: impl Read, mut writer: impl Write, tx: &Sender<Input>) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match parse(&line) {
+            Ok(command) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if tx.send(Input::Control(command, reply_tx)).is_err() {
+                    return;
+                }
+                match reply_rx.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => return,
+                }
+            },
+            Err(e) => failure(&e),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    serde_json::from_str(line).map_err(|e| e.to_string())
+}
+
+// Ответ на команду - строка JSON без перевода строки
+pub fn execute(client: &Client, command: Command) -> String {
+    match command {
+        Command::Mute { value: muted } => {
+            let muted = muted.unwrap_or(!client.is_muted());
+            if muted != client.is_muted() {
+                client.set_muted(muted);
+                say!("{}", if muted { "Muted" } else { "Unmuted" });
+            }
+            json!({"ok": true, "muted": client.is_muted()}).to_string()
+        },
+        Command::Deafen { value: deafened } => {
+            let deafened = deafened.unwrap_or(!client.is_deafened());
+            if deafened != client.is_deafened() {
+                client.set_deafened(deafened);
+                say!("{}", if deafened { "Deafened" } else { "Undeafened" });
+            }
+            json!({"ok": true, "deafened": client.is_deafened()}).to_string()
+        },
+        Command::SetVolume { user, value: volume } => {
+            let result = match user {
+                Some(id) => client.set_user_volume(id, volume),
+                None => client.set_output_volume(volume),
+            };
+            match result {
+                Ok(()) => json!({"ok": true}).to_string(),
+                Err(e) => failure(&e.to_string()),
+            }
+        },
+        Command::MuteUser { user: id, value: muted } => {
+            let muted = muted.unwrap_or(!client.is_user_muted(id));
+            match client.set_user_muted(id, muted) {
+                Ok(()) => {
+                    muted::remember(client);
+                    json!({"ok": true, "user": id, "muted": muted}).to_string()
+                },
+                Err(e) => failure(&e.to_string()),
+            }
+        },
+        Command::Stats => {
+            let stats = client.stats().unwrap_or_default();
+            json!({
+                "ok": true,
+                "state": connection_state_name(client.connection_state()),
+                "muted": client.is_muted(),
+                "deafened": client.is_deafened(),
+                "transmitting": client.is_transmitting(),
+                "buffer_ms": stats.buffer_ms,
+                "loss_percent": tenths(stats.loss_percent),
+                "rtt_ms": stats.rtt_ms,
+                "jitter_ms": tenths(stats.jitter_ms),
+                "bitrate": stats.bitrate,
+                "packets_sent": stats.packets_sent,
+                "packets_received": stats.packets_received,
+                "total_bytes_sent": stats.total_bytes_sent,
+                "total_bytes_received": stats.total_bytes_received,
+                "send_kbps": tenths(stats.send_kbps),
+                "receive_kbps": tenths(stats.receive_kbps),
+            })
+            .to_string()
+        },
+        Command::Users => {
+            let users: Vec<_> = client
+                .users()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|user| json!({"id": user.id, "nickname": user.nickname, "speaking": user.speaking}))
+                .collect();
+            json!({"ok": true, "users": users}).to_string()
+        },
+        Command::HangUp => json!({"ok": true}).to_string(),
+    }
+}
+
+pub fn failure(message: &str) -> String {
+    json!({"ok": false, "error": message}).to_string()
+}
+
+// Дробные показатели - с одним знаком после запятой
+fn tenths(value: f32) -> f64 {
+    (value as f64 * 10.0).round() / 10.0
+}
+
+#[cfg(unix)]
+mod endpoint {
+    use std::io::{self, Read, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+
+    pub struct Listener {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl Listener {
+        pub fn bind() -> io::Result<Listener> {
+            let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+            let path = dir.join("nsvc-call.sock");
+            // Сокет упавшего звонка занимает путь; живой - звонок уже идет
+            if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                if UnixStream::connect(&path).is_ok() {
+                    return Err(io::ErrorKind::AddrInUse.into());
+                }
+                std::fs::remove_file(&path)?;
+            }
+            let listener = UnixListener::bind(&path)?;
+            Ok(Listener { listener, path })
+        }
+
+        pub fn address(&self) -> PathBuf {
+            self.path.clone()
+        }
+
+        // Ждет следующую программу управления
+        pub fn accept(&mut self) -> io::Result<(impl Read + Send + 'static, impl Write + Send + 'static)> {
+            let (stream, _) = self.listener.accept()?;
+            Ok((stream.try_clone()?, stream))
+        }
+    }
+}
+
+#[cfg(windows)]
+mod endpoint {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+    use std::path::PathBuf;
+
+    use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW};
+    use winapi::um::winbase::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    const NAME: &str = r"\\.\pipe\nsvc-call";
+    const BUFFER_SIZE: u32 = 4096;
+
+    // Экземпляр канала ждет своего клиента; следующий создается, когда он подключился
+    pub struct Listener {
+        next: File,
+    }
+
+    // first - первый экземпляр: если имя занято, звонок уже идет
+    fn create(first: bool) -> io::Result<File> {
+        let name: Vec<u16> = NAME.encode_utf16().chain(std::iter::once(0)).collect();
+        let flags = PIPE_ACCESS_DUPLEX | if first { FILE_FLAG_FIRST_PIPE_INSTANCE } else { 0 };
+        let mode = PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS;
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                flags,
+                mode,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_handle(handle as _) })
+    }
+
+    impl Listener {
+        pub fn bind() -> io::Result<Listener> {
+            Ok(Listener { next: create(true)? })
+        }
+
+        pub fn address(&self) -> PathBuf {
+            PathBuf::from(NAME)
+        }
+
+        pub fn accept(&mut self) -> io::Result<(impl Read + Send + 'static, impl Write + Send + 'static)> {
+            let connected = unsafe { ConnectNamedPipe(self.next.as_raw_handle() as _, std::ptr::null_mut()) };
+            // Клиент успел подключиться до ConnectNamedPipe
+            if connected == 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                    return Err(e);
+                }
+            }
+            let pipe = std::mem::replace(&mut self.next, create(false)?);
+            Ok((pipe.try_clone()?, pipe))
+        }
+    }
+}
@@ -0,0 +1,88 @@
+// Кнопка геймпада как клавиша разговора (feature "gamepad", gilrs). Кнопка
+// задается именем из BUTTONS или числовым кодом, как его сообщает система:
+// код нужен кнопкам без раскладки, например задним лепесткам. Работает
+// вместе с клавишей --hotkey; геймпад можно подключить и во время звонка.
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use gilrs::{Button, EventType, Gilrs};
+
+use crate::keys::Input;
+
+const BUTTONS: [(&str, Button); 19] = [
+    ("south", Button::South),
+    ("east", Button::East),
+    ("north", Button::North),
+    ("west", Button::West),
+    ("c", Button::C),
+    ("z", Button::Z),
+    ("left-trigger", Button::LeftTrigger),
+    ("left-trigger2", Button::LeftTrigger2),
+    ("right-trigger", Button::RightTrigger),
+    ("right-trigger2", Button::RightTrigger2),
+    ("select", Button::Select),
+    ("start", Button::Start),
+    ("mode", Button::Mode),
+    ("left-thumb", Button::LeftThumb),
+    ("right-thumb", Button::RightThumb),
+    ("dpad-up", Button::DPadUp),
+    ("dpad-down", Button::DPadDown),
+    ("dpad-left", Button::DPadLeft),
+    ("dpad-right", Button::DPadRight),
+];
+
+#[derive(Clone, Copy)]
+pub enum ButtonId {
+    Named(Button),
+    Code(u32),
+}
+
+pub fn parse_button(value: &str) -> Result<ButtonId, String> {
+    if let Ok(code) = value.parse() {
+        return Ok(ButtonId::Code(code));
+    }
+    BUTTONS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|&(_, button)| ButtonId::Named(button))
+        .ok_or_else(|| {
+            let names: Vec<&str> = BUTTONS.iter().map(|(name, _)| *name).collect();
+            format!("expected a button code or one of: {}", names.join(", "))
+        })
+}
+
+// Слушает геймпады в своем потоке; Ok - имена подключенных сейчас
+pub fn spawn(button: ButtonId, tx: Sender<Input>) -> Result<Vec<String>, String> {
+    let (ready_tx, ready_rx) = mpsc::channel();
+    // Gilrs создается в потоке, который его читает: он не обязан быть Send
+    thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(e) => {
+                let _ = ready_tx.send(Err(e.to_string()));
+                return;
+            },
+        };
+        let names = gilrs.gamepads().map(|(_, gamepad)| gamepad.name().to_string()).collect();
+        let _ = ready_tx.send(Ok(names));
+
+        loop {
+            let Some(event) = gilrs.next_event_blocking(None) else {
+                continue;
+            };
+            let (pressed, pressed_button, code) = match event.event {
+                EventType::ButtonPressed(pressed_button, code) => (true, pressed_button, code),
+                EventType::ButtonReleased(pressed_button, code) => (false, pressed_button, code),
+                _ => continue,
+            };
+            let matches = match button {
+                ButtonId::Named(button) => button == pressed_button,
+                ButtonId::Code(button) => button == code.into_u32(),
+            };
+            if matches && tx.send(Input::Gamepad(pressed)).is_err() {
+                return;
+            }
+        }
+    });
+    ready_rx.recv().unwrap_or_else(|_| Err("gamepad thread failed".to_string()))
+}
@@ -0,0 +1,125 @@
+// Глобальные клавиши через evdev: события читаются прямо из
+// /dev/input/event*, мимо оконной системы. Поэтому клавиши работают под
+// Wayland, где программа не может слушать чужой ввод, и когда терминал не в
+// фокусе (например, поверх игра). Заодно видно отпускание клавиши, и
+// --hotkey работает как настоящий PTT.
+//
+// Нужно право читать устройства, обычно группа input. Без него остаются
+// клавиши терминала, а причина выводится пользователю. evdev сообщает
+// физическую клавишу, а не символ, поэтому символы переводятся по раскладке
+// US; символы без своей клавиши (например '!') читаются только из терминала.
+// Приходят все такие клавиши, а не только назначенные: назначение можно
+// сменить во время звонка, см. hotkeys.rs.
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use crate::keys::Input;
+
+const INPUT_DIR: &str = "/dev/input";
+const EV_KEY: u16 = 1;
+// По ней клавиатура отличается от мыши и кнопки питания
+const KEY_A: u16 = 30;
+const KEY_BACKSLASH: u16 = 43;
+const KEY_SPACE: u16 = 57;
+// Ряды клавиатуры и код первой клавиши ряда
+const KEY_ROWS: [(&str, u16); 4] = [("1234567890-=", 2), ("qwertyuiop[]", 16), ("asdfghjkl;'`", 30), ("zxcvbnm,./", 44)];
+// value в событии EV_KEY; 2 - автоповтор, он не нужен
+const RELEASED: i32 = 0;
+const PRESSED: i32 = 1;
+
+pub struct GlobalKeys {
+    keyboards: Vec<File>,
+}
+
+fn key_code(key: char) -> Option<u16> {
+    match key {
+        ' ' => Some(KEY_SPACE),
+        '\\' => Some(KEY_BACKSLASH),
+        _ => {
+            let key = key.to_ascii_lowercase();
+            KEY_ROWS.iter().find_map(|(row, first)| row.find(key).map(|i| first + i as u16))
+        },
+    }
+}
+
+fn key_char(code: u16) -> Option<char> {
+    match code {
+        KEY_SPACE => Some(' '),
+        KEY_BACKSLASH => Some('\\'),
+        _ => KEY_ROWS.iter().find_map(|(row, first)| row.chars().nth(code.checked_sub(*first)? as usize)),
+    }
+}
+
+// Есть ли у символа своя клавиша
+pub fn can_read(key: char) -> bool {
+    key_code(key).is_some()
+}
+
+// EVIOCGBIT(EV_KEY): какие клавиши есть у устройства
+fn has_keys(file: &File, codes: &[u16]) -> bool {
+    let mut bits = [0u8; libc::KEY_CNT / 8];
+    let request = (2 << 30) | ((bits.len() as u32) << 16) | ((b'E' as u32) << 8) | (0x20 + EV_KEY as u32);
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, bits.as_mut_ptr()) } < 0 {
+        return false;
+    }
+    codes.iter().all(|&code| bits[code as usize / 8] & (1 << (code % 8)) != 0)
+}
+
+// Открывает клавиатуры. Err - почему глобальных клавиш не будет.
+pub fn open() -> Result<GlobalKeys, String> {
+    let entries = fs::read_dir(INPUT_DIR).map_err(|e| format!("{}: {}", INPUT_DIR, e))?;
+    let mut keyboards = Vec::new();
+    let mut denied = false;
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with("event") {
+            continue;
+        }
+        match File::open(entry.path()) {
+            Ok(file) if has_keys(&file, &[KEY_A]) => keyboards.push(file),
+            Ok(_) => {},
+            Err(e) => denied |= e.kind() == ErrorKind::PermissionDenied,
+        }
+    }
+
+    if keyboards.is_empty() {
+        return Err(if denied {
+            format!("no permission to read {}/event* (add the user to the \"input\" group)", INPUT_DIR)
+        } else {
+            format!("no keyboard found in {}", INPUT_DIR)
+        });
+    }
+    Ok(GlobalKeys { keyboards })
+}
+
+impl GlobalKeys {
+    // По потоку на клавиатуру; события приходят в тот же канал, что и ввод терминала
+    pub fn spawn(self, tx: Sender<Input>) {
+        for mut keyboard in self.keyboards {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; mem::size_of::<libc::input_event>()];
+                while keyboard.read_exact(&mut buf).is_ok() {
+                    let event = unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const libc::input_event) };
+                    if event.type_ != EV_KEY {
+                        continue;
+                    }
+                    let Some(key) = key_char(event.code) else {
+                        continue;
+                    };
+                    let input = match event.value {
+                        PRESSED => Input::Press(key),
+                        RELEASED => Input::Release(key),
+                        _ => continue,
+                    };
+                    if tx.send(input).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    }
+}
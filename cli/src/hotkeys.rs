@@ -0,0 +1,413 @@
+// Клавиши во время разговора: разговор, шепот, mute, deafen, запись и клип.
+// Клавиша читается глобально (global_keys.rs), а если так ее не прочитать -
+// из терминала.
+// Назначение меняется, не прерывая звонок: Tab в терминале, затем клавиша,
+// которую меняем, затем новая.
+// '/' в терминале начинает текстовое сообщение каналу: Enter отправляет,
+// Esc отменяет. Если на '/' назначено действие, сообщений нет.
+// Шепот слышат только участники из --whisper-to: ники или ID, которые
+// ищутся в канале при каждом нажатии. Глобальную клавишу шепота держат,
+// клавиша терминала включает и выключает шепот.
+use nsvc_core::client::{Client, TransmitMode};
+use nsvc_core::text::MAX_TEXT_LEN;
+
+use crate::global_keys;
+use crate::record::Recorder;
+use crate::tui::{say, say_error};
+
+const REBIND_KEY: char = '\t';
+pub const CHAT_KEY: char = '/';
+const ESCAPE: char = '\x1b';
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Talk,
+    Whisper,
+    Mute,
+    Deafen,
+    Record,
+    Clip,
+}
+
+impl Action {
+    fn name(self) -> &'static str {
+        match self {
+            Action::Talk => "talk",
+            Action::Whisper => "whisper",
+            Action::Mute => "mute",
+            Action::Deafen => "deafen",
+            Action::Record => "record",
+            Action::Clip => "clip",
+        }
+    }
+}
+
+// Символ вроде ключа --hotkey: печатный ASCII или пробел
+pub fn is_valid_key(key: char) -> bool {
+    key.is_ascii_graphic() || key == ' '
+}
+
+// У каждого действия своя клавиша
+#[derive(Clone, Copy)]
+pub struct Hotkeys {
+    pub talk: Option<char>,
+    pub whisper: Option<char>,
+    pub mute: Option<char>,
+    pub deafen: Option<char>,
+    pub record: Option<char>,
+    pub clip: Option<char>,
+    // Клавиша сообщения (CHAT_KEY); None - сообщений нет, как в самопроверке
+    pub chat: Option<char>,
+}
+
+impl Hotkeys {
+    pub fn any(&self) -> bool {
+        !self.keys().is_empty()
+    }
+
+    fn keys(&self) -> Vec<char> {
+        [self.talk, self.whisper, self.mute, self.deafen, self.record, self.clip].into_iter().flatten().collect()
+    }
+
+    pub fn distinct(&self) -> bool {
+        let keys = self.keys();
+        keys.iter().enumerate().all(|(i, key)| !keys[..i].iter().any(|other| other.eq_ignore_ascii_case(key)))
+    }
+
+    // Глобально клавиши приходят без Shift, поэтому регистр не важен
+    fn action(&self, key: char) -> Option<Action> {
+        let bound = |slot: Option<char>| slot.is_some_and(|bound| bound.eq_ignore_ascii_case(&key));
+        if bound(self.talk) {
+            Some(Action::Talk)
+        } else if bound(self.whisper) {
+            Some(Action::Whisper)
+        } else if bound(self.mute) {
+            Some(Action::Mute)
+        } else if bound(self.deafen) {
+            Some(Action::Deafen)
+        } else if bound(self.record) {
+            Some(Action::Record)
+        } else if bound(self.clip) {
+            Some(Action::Clip)
+        } else {
+            None
+        }
+    }
+
+    // Клавиша сообщения, если она не занята действием
+    pub fn chat_key(&self) -> Option<char> {
+        self.chat.filter(|&key| self.action(key).is_none())
+    }
+
+    fn slot(&mut self, action: Action) -> &mut Option<char> {
+        match action {
+            Action::Talk => &mut self.talk,
+            Action::Whisper => &mut self.whisper,
+            Action::Mute => &mut self.mute,
+            Action::Deafen => &mut self.deafen,
+            Action::Record => &mut self.record,
+            Action::Clip => &mut self.clip,
+        }
+    }
+
+    // Клавиши, которые глобально не прочитать; они работают только в терминале
+    pub fn terminal_only(&self) -> Vec<char> {
+        self.keys().into_iter().filter(|&key| !global_keys::can_read(key)).collect()
+    }
+}
+
+// Глобальную клавишу можно удерживать; терминал сообщает только нажатия
+pub fn can_hold(global: bool, key: char) -> bool {
+    global && global_keys::can_read(key)
+}
+
+fn talk_mode(hold: bool) -> TransmitMode {
+    if hold {
+        TransmitMode::PushToTalk
+    } else {
+        TransmitMode::Toggle
+    }
+}
+
+pub fn talk_mode_for(global: bool, talk: Option<char>) -> TransmitMode {
+    talk_mode(talk.is_none_or(|key| can_hold(global, key)))
+}
+
+// Что сейчас удерживает передачу. Клавиша и кнопка геймпада работают
+// вместе: отпускание одной не обрывает передачу, пока держат другую.
+#[derive(Default)]
+struct Held {
+    key: bool,
+    gamepad: bool,
+}
+
+enum Rebind {
+    Off,
+    // Ждем клавишу, которую меняем
+    Choose,
+    // Ждем новую клавишу
+    NewKey(Action),
+}
+
+pub struct Session<'a> {
+    client: &'a Client,
+    hotkeys: Hotkeys,
+    // Работают ли глобальные клавиши
+    global: bool,
+    held: Held,
+    // Кому шепот, как в --whisper-to, и идет ли он сейчас
+    whisper_to: Vec<String>,
+    whispering: bool,
+    rebind: Rebind,
+    recorder: &'a mut Recorder,
+    // Набираемое сообщение: байты UTF-8, как их прислал терминал.
+    // None - сообщение не набирается.
+    draft: Option<Vec<u8>>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(
+        client: &'a Client,
+        hotkeys: Hotkeys,
+        global: bool,
+        whisper_to: Vec<String>,
+        recorder: &'a mut Recorder,
+    ) -> Self {
+        Session {
+            client,
+            hotkeys,
+            global,
+            held: Held::default(),
+            whisper_to,
+            whispering: false,
+            rebind: Rebind::Off,
+            recorder,
+            draft: None,
+        }
+    }
+
+    // Символ из терминала. Глобально читаемые клавиши уже пришли через
+    // on_global_key, второй раз их не считаем.
+    pub fn on_terminal_key(&mut self, key: char) {
+        if let Some(draft) = &mut self.draft {
+            match key {
+                ESCAPE => self.draft = None,
+                // Backspace стирает символ целиком, а не последний байт
+                '\x7f' | '\x08' => {
+                    while draft.pop().is_some_and(|byte| byte & 0xC0 == 0x80) {}
+                },
+                // Символы приходят из терминала по байту
+                key if draft.len() < MAX_TEXT_LEN && !key.is_ascii_control() => draft.push(key as u8),
+                _ => {},
+            }
+            return;
+        }
+        match self.rebind {
+            Rebind::Off if Some(key) == self.hotkeys.chat_key() => self.draft = Some(Vec::new()),
+            Rebind::Off if key == REBIND_KEY => {
+                say!("Press the key to rebind");
+                self.rebind = Rebind::Choose;
+            },
+            Rebind::Off => {
+                if self.global && global_keys::can_read(key) {
+                    return;
+                }
+                if let Some(action) = self.hotkeys.action(key) {
+                    self.on_action(action, true);
+                }
+            },
+            Rebind::Choose => match self.hotkeys.action(key) {
+                Some(action) => {
+                    say!("Press the new {} key", action.name());
+                    self.rebind = Rebind::NewKey(action);
+                },
+                None => {
+                    say!("No action on '{}'", key);
+                    self.rebind = Rebind::Off;
+                },
+            },
+            Rebind::NewKey(action) => {
+                self.rebind = Rebind::Off;
+                self.rebind_key(action, key);
+            },
+        }
+    }
+
+    pub fn on_global_key(&mut self, key: char, pressed: bool) {
+        // Во время переназначения нажатия выбирают клавишу, а не действуют;
+        // пока набирается сообщение, клавиши - это текст
+        if !matches!(self.rebind, Rebind::Off) || (pressed && self.draft.is_some()) {
+            return;
+        }
+        if let Some(action) = self.hotkeys.action(key) {
+            self.on_action(action, pressed);
+        }
+    }
+
+    // Подсказки для экрана звонка (см. tui.rs): (клавиша, действие)
+    pub fn hints(&self) -> Vec<(String, String)> {
+        let mut hints = Vec::new();
+        if let Some(key) = self.hotkeys.talk {
+            let action = if can_hold(self.global, key) { "hold to talk" } else { "microphone" };
+            hints.push((key.to_string(), action.to_string()));
+        }
+        if let Some(key) = self.hotkeys.whisper {
+            let action = if can_hold(self.global, key) { "hold to whisper" } else { "whisper" };
+            hints.push((key.to_string(), action.to_string()));
+        }
+        let mut hotkeys = self.hotkeys;
+        for action in [Action::Mute, Action::Deafen, Action::Record, Action::Clip] {
+            if let Some(key) = *hotkeys.slot(action) {
+                hints.push((key.to_string(), action.name().to_string()));
+            }
+        }
+        if self.hotkeys.any() {
+            hints.push(("Tab".to_string(), "rebind".to_string()));
+        }
+        if let Some(key) = self.hotkeys.chat_key() {
+            hints.push((key.to_string(), "message".to_string()));
+        }
+        hints
+    }
+
+    // Набираемое сообщение для экрана звонка
+    pub fn draft(&self) -> Option<String> {
+        self.draft.as_ref().map(|draft| String::from_utf8_lossy(draft).into_owned())
+    }
+
+    // Enter: отправляет набранное сообщение каналу. false - сообщение не
+    // набиралось, и Enter значит что-то другое.
+    pub fn on_enter(&mut self) -> bool {
+        let Some(draft) = self.draft.take() else {
+            return false;
+        };
+        let text = String::from_utf8_lossy(&draft);
+        let text = text.trim();
+        if text.is_empty() {
+            return true;
+        }
+        match self.client.send_text(0, text) {
+            Ok(()) => say!("You: {}", text),
+            Err(e) => say_error!("Failed to send the message: {}", e),
+        }
+        true
+    }
+
+    // Пункт меню значка в трее: как нажатие клавиши действия
+    #[cfg(feature = "tray")]
+    pub fn on_menu(&mut self, action: Action) {
+        self.on_action(action, true);
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn on_gamepad(&mut self, pressed: bool) {
+        self.held.gamepad = pressed;
+        if pressed || !self.held.key {
+            self.talk(pressed);
+        }
+    }
+
+    fn rebind_key(&mut self, action: Action, key: char) {
+        if !is_valid_key(key) {
+            say!("Rebinding cancelled");
+            return;
+        }
+        let mut hotkeys = self.hotkeys;
+        *hotkeys.slot(action) = Some(key);
+        if !hotkeys.distinct() {
+            say!("'{}' is already bound", key);
+            return;
+        }
+        self.hotkeys = hotkeys;
+        // Клавишу разговора могли сменить на ту, что нельзя удерживать, или наоборот
+        if action == Action::Talk {
+            self.held.key = false;
+            self.client.set_transmitting(false);
+            let _ = self.client.set_transmit_mode(talk_mode(can_hold(self.global, key)));
+        }
+        if action == Action::Whisper && self.whispering {
+            self.whisper(false);
+        }
+        let place = if can_hold(self.global, key) { "" } else { " in this terminal" };
+        say!("'{}' is now the {} key{}", key, action.name(), place);
+    }
+
+    fn on_action(&mut self, action: Action, pressed: bool) {
+        match action {
+            Action::Talk => {
+                self.held.key = pressed;
+                if pressed || !self.held.gamepad {
+                    self.talk(pressed);
+                }
+            },
+            Action::Whisper => {
+                let hold = self.hotkeys.whisper.is_some_and(|key| can_hold(self.global, key));
+                if hold {
+                    self.whisper(pressed);
+                } else if pressed {
+                    self.whisper(!self.whispering);
+                }
+            },
+            Action::Mute if pressed => {
+                self.client.set_muted(!self.client.is_muted());
+                say!("{}", if self.client.is_muted() { "Muted" } else { "Unmuted" });
+            },
+            Action::Deafen if pressed => {
+                self.client.set_deafened(!self.client.is_deafened());
+                say!("{}", if self.client.is_deafened() { "Deafened" } else { "Undeafened" });
+            },
+            Action::Record if pressed => self.recorder.toggle(self.client),
+            Action::Clip if pressed => self.recorder.save_clip(self.client),
+            // Отпускание важно только клавишам разговора и шепота
+            _ => {},
+        }
+    }
+
+    fn whisper(&mut self, on: bool) {
+        if on == self.whispering {
+            return;
+        }
+        if on {
+            let targets = self.whisper_targets();
+            if targets.is_empty() {
+                say!("Nobody to whisper to: {} not in the channel", self.whisper_to.join(", "));
+                return;
+            }
+            if let Err(e) = self.client.set_whisper_targets(&targets) {
+                say_error!("Failed to whisper: {}", e);
+                return;
+            }
+        }
+        self.whispering = on;
+        self.client.whisper_key(on);
+        if on {
+            say!("Whispering to {}", self.whisper_to.join(", "));
+        } else {
+            say!("Whisper off");
+        }
+    }
+
+    // ID из --whisper-to: числа как есть, ники - тех, кто сейчас в канале
+    fn whisper_targets(&self) -> Vec<u32> {
+        let users = self.client.users().unwrap_or_default();
+        let mut targets = Vec::new();
+        for target in &self.whisper_to {
+            let id = match target.parse::<u32>() {
+                Ok(id) => Some(id),
+                Err(_) => users.iter().find(|user| user.nickname.eq_ignore_ascii_case(target)).map(|user| user.id),
+            };
+            if let Some(id) = id.filter(|id| !targets.contains(id)) {
+                targets.push(id);
+            }
+        }
+        targets
+    }
+
+    fn talk(&self, pressed: bool) {
+        let was = self.client.is_transmitting();
+        self.client.talk_key(pressed);
+        if self.client.is_transmitting() != was {
+            say!("Microphone {}", if self.client.is_transmitting() { "on" } else { "off" });
+        }
+    }
+}
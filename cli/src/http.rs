@@ -0,0 +1,282 @@
+// HTTP для панелей управления в браузере и плагинов Stream Deck (--http):
+// те же команды, что у канала управления (см. control.rs), и поток событий
+// клиента, без FFI и без своего канала. Слушает только 127.0.0.1.
+//   GET  /status   -> как команда stats
+//   GET  /users    -> как команда users
+//   POST /command  тело - команда JSON, как в канале управления
+//   GET  /events   WebSocket: события клиента, по объекту в текстовом сообщении
+//                  {"kind":2,"user_id":7,"code":0,"text":""}
+// Сюда может постучаться и страница чужого сайта, поэтому запросы с
+// заголовком Origin принимаются только со страниц на localhost, а Host
+// проверяется против подмены DNS. Сообщения от страницы в WebSocket
+// не читаются: команды идут через POST /command.
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nsvc_core::client::Event;
+use nsvc_core::websocket::{self, opcodes, Frame};
+
+use crate::control::{self, failure, Command};
+use crate::keys::Input;
+use crate::tui::say;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEADERS_SIZE: usize = 8192;
+// И тело команды, и кадр WebSocket от страницы
+const MAX_BODY_SIZE: usize = 4096;
+// Событий в очереди одной страницы; не успевает читать - лишние теряются
+const SEND_QUEUE: usize = 64;
+
+// Открыт, пока жив процесс; порт 0 - любой свободный
+pub struct Http {
+    pub port: u16,
+    pub events: Events,
+}
+
+impl Http {
+    pub fn start(port: u16, tx: Sender<Input>) -> io::Result<Http> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let port = listener.local_addr()?.port();
+        let events = Events::default();
+        let sockets = events.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        say!("HTTP accept error: {}", e);
+                        continue;
+                    },
+                };
+                let (tx, sockets) = (tx.clone(), sockets.clone());
+                // Оборванный запрос - забота страницы, звонку сообщать нечего
+                thread::spawn(move || {
+                    let _ = serve(stream, &tx, &sockets);
+                });
+            }
+        });
+        Ok(Http { port, events })
+    }
+}
+
+// Страницы, подписанные на /events
+#[derive(Clone, Default)]
+pub struct Events {
+    sockets: Arc<Mutex<Vec<SyncSender<Frame>>>>,
+}
+
+impl Events {
+    // Из сетевого потока клиента: ждать страницы он не может
+    pub fn publish(&self, event: &Event) {
+        let text = serde_json::json!({
+            "kind": event.kind,
+            "user_id": event.user_id,
+            "code": event.code,
+            "text": event.text,
+        })
+        .to_string();
+        self.sockets.lock().unwrap().retain(|socket| {
+            let frame = Frame::new(opcodes::TEXT, text.clone().into_bytes());
+            !matches!(socket.try_send(frame), Err(TrySendError::Disconnected(_)))
+        });
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    // Имена в нижнем регистре
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+fn serve(mut stream: TcpStream, tx: &Sender<Input>, events: &Events) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return respond(&mut stream, "400 Bad Request", None, &failure(&e.to_string()));
+        },
+        Err(e) => return Err(e),
+    };
+    if !request.header("host").is_some_and(is_local) {
+        return respond(&mut stream, "403 Forbidden", None, &failure("host not allowed"));
+    }
+    let origin = request.header("origin");
+    if origin.is_some_and(|origin| !is_local_origin(origin)) {
+        return respond(&mut stream, "403 Forbidden", None, &failure("origin not allowed"));
+    }
+
+    let reply = match (request.method.as_str(), request.path.as_str()) {
+        // Предварительный запрос браузера перед POST с JSON
+        ("OPTIONS", _) => return respond(&mut stream, "204 No Content", origin, ""),
+        ("GET", "/status") => run(tx, Command::Stats),
+        ("GET", "/users") => run(tx, Command::Users),
+        ("POST", "/command") => {
+            let parsed = std::str::from_utf8(&request.body)
+                .map_err(|_| "body is not UTF-8".to_string())
+                .and_then(control::parse);
+            match parsed {
+                Ok(command) => run(tx, command),
+                Err(e) => return respond(&mut stream, "400 Bad Request", origin, &failure(&e)),
+            }
+        },
+        ("GET", "/events") => return upgrade(stream, &request, events),
+        (_, "/status" | "/users" | "/command" | "/events") => {
+            return respond(&mut stream, "405 Method Not Allowed", origin, &failure("method not allowed"));
+        },
+        _ => return respond(&mut stream, "404 Not Found", origin, &failure("not found")),
+    };
+    match reply {
+        Some(reply) => respond(&mut stream, "200 OK", origin, &reply),
+        None => respond(&mut stream, "503 Service Unavailable", origin, &failure("the call is over")),
+    }
+}
+
+// Команду выполняет главный поток, как клавиши; None - звонок уже завершен
+fn run(tx: &Sender<Input>, command: Command) -> Option<String> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    tx.send(Input::Control(command, reply_tx)).ok()?;
+    reply_rx.recv().ok()
+}
+
+// InvalidData - запрос, на который стоит ответить 400
+fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 1024];
+    let end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if data.len() >= MAX_HEADERS_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"));
+        }
+        let received = stream.read(&mut buf)?;
+        if received == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        data.extend_from_slice(&buf[..received]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed request line"));
+    };
+    // Параметры запроса не используются
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let mut request = Request { method: method.to_string(), path, headers, body: Vec::new() };
+
+    let length = match request.header("content-length") {
+        Some(length) => length.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "body too large"));
+    }
+    request.body = data[end + 4..].to_vec();
+    while request.body.len() < length {
+        let received = stream.read(&mut buf)?;
+        if received == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        request.body.extend_from_slice(&buf[..received]);
+    }
+    request.body.truncate(length);
+    Ok(request)
+}
+
+// origin - разрешенный Origin запроса, ему отвечаем заголовками CORS
+fn respond(stream: &mut TcpStream, status: &str, origin: Option<&str>, body: &str) -> io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\n\
+         Connection: close\r\n",
+        status,
+        body.len()
+    );
+    if let Some(origin) = origin {
+        head.push_str(&format!(
+            "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: GET, POST\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\nVary: Origin\r\n",
+            origin
+        ));
+    }
+    write!(stream, "{}\r\n{}", head, body)
+}
+
+// Host или адрес из Origin с портом или без: localhost, 127.0.0.1, [::1]
+fn is_local(authority: &str) -> bool {
+    let host = match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    matches!(host.to_ascii_lowercase().as_str(), "localhost" | "127.0.0.1" | "::1")
+}
+
+fn is_local_origin(origin: &str) -> bool {
+    origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")).is_some_and(is_local)
+}
+
+// HTTP Upgrade; дальше события, пока страница не закроет соединение
+fn upgrade(mut stream: TcpStream, request: &Request, events: &Events) -> io::Result<()> {
+    let key = websocket::upgrade_key(&request.headers);
+    websocket::respond(&mut stream, key)?;
+    if key.is_none() {
+        return Ok(());
+    }
+    stream.set_read_timeout(None)?;
+
+    // Запись в своем потоке: медленная страница не задерживает сетевой поток клиента
+    let (frames_tx, frames) = mpsc::sync_channel::<Frame>(SEND_QUEUE);
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for frame in frames {
+            if websocket::write_frame(&mut writer, &frame).is_err() || frame.opcode == opcodes::CLOSE {
+                break;
+            }
+        }
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+    events.sockets.lock().unwrap().push(frames_tx.clone());
+
+    let result = read_loop(&mut stream, &frames_tx);
+    // Поток записи заметит это на следующем событии
+    let _ = stream.shutdown(Shutdown::Read);
+    result
+}
+
+fn read_loop(stream: &mut TcpStream, frames: &SyncSender<Frame>) -> io::Result<()> {
+    loop {
+        let Frame { opcode, payload, .. } = match websocket::read_frame(stream, MAX_BODY_SIZE) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match opcode {
+            opcodes::PING => {
+                let _ = frames.try_send(Frame::new(opcodes::PONG, payload));
+            },
+            opcodes::CLOSE => {
+                let payload = payload.get(..2).map(<[u8]>::to_vec).unwrap_or_default();
+                let _ = frames.try_send(Frame::new(opcodes::CLOSE, payload));
+                return Ok(());
+            },
+            _ => {},
+        }
+    }
+}
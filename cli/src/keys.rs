@@ -0,0 +1,108 @@
+// Клавиатура во время звонка. С клавишей микрофона терминал переводится в
+// посимвольный режим без эха, и клавиша срабатывает сразу. Без терминала
+// (stdin из файла или канала) и вне Unix символы приходят построчно, после
+// Enter; тогда отбой - только Enter на пустой строке.
+use std::io::Read;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+pub enum Input {
+    // Enter на пустой строке, конец ввода или пункт меню значка в трее
+    HangUp,
+    // Enter в посимвольном режиме или после набранной строки: отправляет
+    // сообщение, а без него в посимвольном режиме - отбой
+    Enter,
+    // Символ из терминала; отпускание терминал не сообщает
+    Key(char),
+    // Глобальные клавиши, см. global_keys.rs
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    Press(char),
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    Release(char),
+    // Кнопка разговора на геймпаде нажата или отпущена, см. gamepad.rs
+    #[cfg(feature = "gamepad")]
+    Gamepad(bool),
+    // Mute или deafen из меню значка, см. tray.rs
+    #[cfg(feature = "tray")]
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    Menu(crate::hotkeys::Action),
+    // Текстовое сообщение: отправитель, личное ли, текст
+    Text(u32, bool, String),
+    // Участник начал или перестал говорить (события SPEAKING_*)
+    Speaking(u32, bool),
+    // Команда из канала управления и куда ответить, см. control.rs
+    Control(crate::control::Command, Sender<String>),
+}
+
+// Посимвольный режим терминала; drop возвращает прежний
+pub struct RawMode {
+    #[cfg(unix)]
+    saved: libc::termios,
+}
+
+impl RawMode {
+    // None - stdin не терминал или режим не сменить
+    #[cfg(unix)]
+    pub fn enable() -> Option<Self> {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return None;
+        }
+        let mut raw = saved;
+        // ISIG остается: Ctrl+C по-прежнему дает SIGINT
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return None;
+        }
+        Some(RawMode { saved })
+    }
+
+    #[cfg(not(unix))]
+    pub fn enable() -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+// Читает stdin в своем потоке: чтение не прервать, а главному потоку
+// нужно еще следить за Ctrl+C
+pub fn spawn_reader(raw: bool, tx: Sender<Input>) {
+    thread::spawn(move || {
+        let mut typed = false;
+        for byte in std::io::stdin().lock().bytes() {
+            let Ok(byte) = byte else {
+                break;
+            };
+            let input = match byte {
+                b'\r' => continue,
+                b'\n' => {
+                    let line = typed;
+                    typed = false;
+                    if raw || line {
+                        Input::Enter
+                    } else {
+                        Input::HangUp
+                    }
+                },
+                _ => {
+                    typed = true;
+                    Input::Key(byte as char)
+                },
+            };
+            if tx.send(input).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Input::HangUp);
+    });
+}
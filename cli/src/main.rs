@@ -0,0 +1,1062 @@
+// NSVC call: голосовой клиент для терминала. Прямой звонок двух
+// пользователей по UDP без сервера идет в тех же пакетах MEDIA, с тем же
+// keep-alive и буфером, что и через сервер; собеседник должен быть доступен
+// напрямую (та же сеть или проброшенный порт).
+//
+// Запуск: nsvc-call --server адрес:порт          разговор через nsvc-server
+//         nsvc-call listen [порт]                ждать звонка (порт 40000)
+//         nsvc-call call адрес:порт [--port порт] позвонить
+//         nsvc-call test-mic [--seconds N]       услышать себя без сети
+//         nsvc-call test-speakers [--sweep]      проверочный сигнал в динамиках
+//         nsvc-call devices                      звуковые устройства
+//         nsvc-call ping адрес:порт              ответ сервера и RTT
+// Общие ключи: --nickname, --mute-user, --bitrate, --device, --output-device, --volume,
+// --activation (--transmit-mode), --vad-threshold, --vad-hangover,
+// --ptt-release, --afk-minutes, --hotkey, --whisper-key, --whisper-to, --mute-key,
+// --deafen-key, --no-cues, --cue-volume,
+// --notification-sound, --record, --record-key, --record-dir, --record-mic,
+// --record-format, --record-tracks, --clip-key, --clip-length, --positional,
+// --overlay-socket, --voice-output, --control, --http, --no-tui, --tray, -v/-q.
+// Ник, заглушенные участники, устройства, громкость, битрейт, режим
+// передачи, настройки детектора голоса, задержка отпускания, выключение
+// забытого микрофона, громкость сигналов, формат записи и длина клипа
+// запоминаются до следующего запуска (см. settings.rs).
+// Микрофон включен (с --hotkey - переключается клавишей, с коротким
+// сигналом); --mute-key и --deafen-key выключают микрофон и звук. На Linux
+// клавиши глобальные, если есть доступ к /dev/input (и под Wayland), и
+// --hotkey надо удерживать; иначе они работают только в терминале. С feature
+// "gamepad" говорить можно и кнопкой геймпада (--gamepad-button). Tab
+// переназначает клавиши во время звонка (см. hotkeys.rs). Разговор можно
+// записать в WAV (см. record.rs). В терминале звонок идет на экране с
+// уровнями, сетью и подсказками (см. tui.rs). Enter или Ctrl+C завершает
+// звонок и выходит с кодом 0 или 130. С feature "tray" и ключом --tray
+// звонок виден значком в трее, и терминал ему не нужен (см. tray.rs).
+// '/' в терминале начинает текстовое сообщение собеседникам; входящие
+// показываются вместе с остальными сообщениями.
+// --positional приглушает тех, кто далеко в игре с Mumble Link (Linux);
+// --overlay-socket сообщает оверлеям поверх игры, кто говорит.
+// --voice-output дублирует голоса собеседников на отдельное (обычно
+// виртуальное) устройство, чтобы OBS сводил их отдельно от игры.
+// --control принимает команды JSON от скриптов и Stream Deck (см. control.rs);
+// --http - то же по HTTP, с событиями по WebSocket (см. http.rs). С feature
+// "metrics" --metrics отдает качество связи Prometheus'у.
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::error::ErrorKind;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use nsvc_core::audio::{self, MockAudio};
+use nsvc_core::client::{Client, ClientBuilder, Notification, TestTone, TransmitMode};
+use nsvc_core::events::event_types;
+use nsvc_core::{connection_states, log_levels, overlay, voice_client_set_log_level};
+
+use crate::hotkeys::Hotkeys;
+use crate::record::{RecordFormat, Recorder};
+use crate::settings::{Settings, Transmit};
+use crate::tui::{say, Tui};
+
+mod control;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+#[cfg(target_os = "linux")]
+mod global_keys;
+mod hotkeys;
+mod http;
+mod keys;
+mod muted;
+mod record;
+mod settings;
+mod shutdown;
+#[cfg(all(feature = "tray", target_os = "linux"))]
+mod tray;
+mod tui;
+
+// Вне Linux глобальных клавиш нет, остаются клавиши терминала
+#[cfg(not(target_os = "linux"))]
+mod global_keys {
+    use std::sync::mpsc::Sender;
+
+    use crate::keys::Input;
+
+    pub struct GlobalKeys;
+
+    pub fn open() -> Result<GlobalKeys, String> {
+        Err("global hotkeys are only supported on Linux".to_string())
+    }
+
+    pub fn can_read(_key: char) -> bool {
+        false
+    }
+
+    impl GlobalKeys {
+        pub fn spawn(self, _tx: Sender<Input>) {}
+    }
+}
+
+// Вне Linux значка в трее нет
+#[cfg(all(feature = "tray", not(target_os = "linux")))]
+mod tray {
+    use std::sync::mpsc::Sender;
+
+    use nsvc_core::client::Client;
+
+    use crate::keys::Input;
+
+    pub struct Tray;
+
+    impl Tray {
+        pub fn spawn(_title: String, _client: &Client, _tx: Sender<Input>) -> Result<Tray, String> {
+            Err("the tray icon is only supported on Linux".to_string())
+        }
+
+        pub fn update(&mut self, _client: &Client) {}
+    }
+}
+
+const DEFAULT_PORT: u16 = 40000;
+// Как часто главный поток проверяет, не пришел ли Ctrl+C
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Код выхода после Ctrl+C, как у оболочки: 128 + SIGINT
+const INTERRUPTED_STATUS: i32 = 130;
+// ping: сколько ждать ответа на handshake и сколько замеров RTT снять
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+const PING_COUNT: usize = 4;
+// RTT обновляется с каждым keep-alive, а он уходит раз в секунду
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// Длина клипа, если --clip-length не задавали
+const DEFAULT_CLIP_SECONDS: u32 = 30;
+// test-speakers и test-mic --seconds: сколько еще ждать после звука, пока устройство его доиграет
+const TEST_TONE_TAIL: Duration = Duration::from_millis(500);
+
+#[derive(Parser)]
+#[command(name = "nsvc-call", version, about = "NSVC voice client for the terminal")]
+struct Cli {
+    #[arg(long, value_name = "HOST:PORT", value_parser = parse_host_port, help = "Talk through an nsvc-server")]
+    server: Option<(String, u16)>,
+
+    #[arg(long, global = true, value_name = "NAME", help = "Name others see next to your voice")]
+    nickname: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        value_delimiter = ',',
+        help = "Never hear these people, by nickname, comma-separated; \"\" forgets the saved ones"
+    )]
+    mute_user: Vec<String>,
+
+    #[arg(long, global = true, value_name = "BPS", help = "Opus bitrate in bits per second")]
+    bitrate: Option<u32>,
+
+    #[arg(long, global = true, value_name = "NAME", help = "Microphone (see `nsvc-call devices`)")]
+    device: Option<String>,
+
+    #[arg(long, global = true, value_name = "NAME", help = "Speakers or headphones")]
+    output_device: Option<String>,
+
+    #[arg(long, global = true, value_name = "NAME", help = "Also play voices here, e.g. on a virtual device for OBS")]
+    voice_output: Option<String>,
+
+    #[arg(long, global = true, value_name = "0.0-2.0", help = "Playback volume, 1.0 - unchanged")]
+    volume: Option<f32>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        visible_alias = "activation",
+        conflicts_with = "hotkey",
+        help = "When the microphone transmits; `voice` - automatically on speech"
+    )]
+    #[cfg_attr(feature = "gamepad", arg(conflicts_with = "gamepad_button"))]
+    transmit_mode: Option<Transmit>,
+
+    #[arg(long, global = true, value_name = "0.0-1.0", help = "Voice detector threshold, share of full amplitude")]
+    vad_threshold: Option<f32>,
+
+    #[arg(long, global = true, value_name = "MS", help = "How long to keep transmitting after speech ends")]
+    vad_hangover: Option<u32>,
+
+    #[arg(long, global = true, value_name = "MS", help = "How long to keep transmitting after the microphone is turned off")]
+    ptt_release: Option<u32>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "MINUTES",
+        help = "Mute the microphone after this long without speech in voice or continuous mode, 0 - never"
+    )]
+    afk_minutes: Option<u32>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEY",
+        value_parser = parse_hotkey,
+        help = "Key that toggles the microphone; it starts muted"
+    )]
+    hotkey: Option<char>,
+
+    #[cfg(feature = "gamepad")]
+    #[arg(
+        long,
+        global = true,
+        value_name = "BUTTON",
+        value_parser = gamepad::parse_button,
+        help = "Gamepad button to hold while talking: a name like left-trigger or a button code"
+    )]
+    gamepad_button: Option<gamepad::ButtonId>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEY",
+        value_parser = parse_hotkey,
+        requires = "whisper_to",
+        help = "Key to hold while talking only to --whisper-to instead of the whole channel"
+    )]
+    whisper_key: Option<char>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME|ID",
+        value_delimiter = ',',
+        help = "Who hears the whisper key: nicknames or user IDs, comma-separated"
+    )]
+    whisper_to: Vec<String>,
+
+    #[arg(long, global = true, value_name = "KEY", value_parser = parse_hotkey, help = "Key that mutes and unmutes the microphone")]
+    mute_key: Option<char>,
+
+    #[arg(long, global = true, value_name = "KEY", value_parser = parse_hotkey, help = "Key that deafens and undeafens")]
+    deafen_key: Option<char>,
+
+    #[arg(long, global = true, help = "No sounds on mute, deafen, microphone on/off, joins and connection changes")]
+    no_cues: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "EVENT=FILE",
+        value_parser = parse_notification_sound,
+        help = "Own WAV or Opus sound for joined, left, connected, reconnecting or disconnected; repeatable"
+    )]
+    notification_sound: Vec<(Notification, PathBuf)>,
+
+    #[arg(long, global = true, value_name = "0.0-1.0", help = "Volume of those sounds")]
+    cue_volume: Option<f32>,
+
+    #[arg(long, global = true, help = "Record the call to a WAV file from the start")]
+    record: bool,
+
+    #[arg(long, global = true, value_name = "KEY", value_parser = parse_hotkey, help = "Key that starts and stops recording")]
+    record_key: Option<char>,
+
+    #[arg(long, global = true, value_name = "DIR", help = "Where to save recordings, the current directory by default")]
+    record_dir: Option<PathBuf>,
+
+    #[arg(long, global = true, help = "Include your own voice in recordings")]
+    record_mic: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "`wav` - one mixed file; `opus` - a compact track per speaker, without re-encoding"
+    )]
+    record_format: Option<RecordFormat>,
+
+    #[arg(long, global = true, help = "Record each speaker to a separate file, for editing voices separately")]
+    record_tracks: bool,
+
+    #[arg(long, global = true, value_name = "KEY", value_parser = parse_hotkey, help = "Key that saves the last seconds of the call")]
+    clip_key: Option<char>,
+
+    #[arg(long, global = true, value_name = "SECONDS", help = "How much of the call --clip-key saves, 30 by default")]
+    clip_length: Option<u32>,
+
+    #[arg(long, global = true, help = "Quieter voices for players far away in a game that supports Mumble Link (Linux)")]
+    positional: bool,
+
+    #[arg(long, global = true, help = "Tell game overlays who is speaking, as JSON lines on a local socket")]
+    overlay_socket: bool,
+
+    #[arg(long, global = true, help = "Accept JSON commands (mute, volume, stats) from scripts on a local pipe or socket")]
+    control: bool,
+
+    #[arg(long, global = true, value_name = "PORT", help = "Serve status, commands and live events over HTTP on localhost")]
+    http: Option<u16>,
+
+    #[cfg(feature = "metrics")]
+    #[arg(long, global = true, value_name = "PORT", help = "Serve call quality metrics for Prometheus on localhost")]
+    metrics: Option<u16>,
+
+    #[arg(long, global = true, help = "Print status lines instead of the full-screen call view")]
+    no_tui: bool,
+
+    #[cfg(feature = "tray")]
+    #[arg(long, global = true, help = "Show a tray icon with mute, deafen and hang up; the call then needs no terminal")]
+    tray: bool,
+
+    #[arg(short, long, global = true, action = ArgAction::Count, help = "More log output (-v, -vv, -vvv)")]
+    verbose: u8,
+
+    #[arg(short, long, global = true, conflicts_with = "verbose", help = "Only log errors")]
+    quiet: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Wait for a direct call")]
+    Listen {
+        #[arg(default_value_t = DEFAULT_PORT)]
+        port: u16,
+    },
+    #[command(about = "Call a peer directly, without a server")]
+    Call {
+        #[arg(value_name = "HOST:PORT", value_parser = parse_host_port)]
+        peer: (String, u16),
+        #[arg(long, default_value_t = 0, help = "Local port (0 - any free)")]
+        port: u16,
+    },
+    #[command(about = "Hear your microphone through the codec, without network")]
+    TestMic {
+        #[arg(
+            long,
+            value_parser = clap::value_parser!(u32).range(1..=10),
+            help = "Record this long, then play it back as others would hear it, instead of hearing yourself live"
+        )]
+        seconds: Option<u32>,
+    },
+    #[command(about = "Play a test sound through the speakers, without network")]
+    TestSpeakers {
+        #[arg(long, help = "A sweep from low to high frequencies instead of a steady tone")]
+        sweep: bool,
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..=30), help = "Length of the sound")]
+        seconds: u32,
+    },
+    #[command(about = "List audio devices")]
+    Devices,
+    #[command(about = "Check that a server answers and measure the round-trip time")]
+    Ping {
+        #[arg(value_name = "HOST:PORT", value_parser = parse_host_port)]
+        server: (String, u16),
+    },
+}
+
+enum Mode {
+    Server { host: String, port: u16 },
+    Listen(u16),
+    Call { peer: String, port: u16, local_port: u16 },
+    Loopback,
+    MicCheck(u32),
+    TestSpeakers { sweep: bool, seconds: u32 },
+    Devices,
+    Ping { host: String, port: u16 },
+}
+
+// Адрес IPv6 - в скобках: [::1]:40000
+fn parse_host_port(value: &str) -> Result<(String, u16), String> {
+    let (host, port) = value.rsplit_once(':').ok_or("expected host:port")?;
+    let port = port.parse::<u16>().map_err(|_| format!("invalid port '{}'", port))?;
+    Ok((host.trim_matches(['[', ']']).to_string(), port))
+}
+
+fn parse_hotkey(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(key), None) if hotkeys::is_valid_key(key) => Ok(key),
+        _ => Err("expected a single character key".to_string()),
+    }
+}
+
+fn parse_notification_sound(value: &str) -> Result<(Notification, PathBuf), String> {
+    let (event, path) = value.split_once('=').ok_or("expected EVENT=FILE")?;
+    let kind = match event {
+        "joined" => Notification::UserJoined,
+        "left" => Notification::UserLeft,
+        "connected" => Notification::Connected,
+        "reconnecting" => Notification::Reconnecting,
+        "disconnected" => Notification::Disconnected,
+        _ => return Err(format!("unknown event {}", event)),
+    };
+    Ok((kind, PathBuf::from(path)))
+}
+
+impl Cli {
+    fn hotkeys(&self) -> Hotkeys {
+        Hotkeys {
+            talk: self.hotkey,
+            whisper: self.whisper_key,
+            mute: self.mute_key,
+            deafen: self.deafen_key,
+            record: self.record_key,
+            clip: self.clip_key,
+            chat: Some(hotkeys::CHAT_KEY),
+        }
+    }
+
+    fn mode(&self) -> Result<Mode, clap::Error> {
+        if !self.hotkeys().distinct() {
+            return Err(Cli::command().error(ErrorKind::ArgumentConflict, "--hotkey, --whisper-key, --mute-key, --deafen-key, --record-key and --clip-key must differ"));
+        }
+        let mode = match (&self.command, &self.server) {
+            (None, Some((host, port))) => Mode::Server { host: host.clone(), port: *port },
+            (Some(_), Some(_)) => {
+                return Err(Cli::command().error(ErrorKind::ArgumentConflict, "--server cannot be used with a command"));
+            },
+            (None, None) => {
+                return Err(Cli::command().error(ErrorKind::MissingRequiredArgument, "expected a command or --server HOST:PORT"));
+            },
+            (Some(Command::Listen { port }), None) => Mode::Listen(*port),
+            (Some(Command::Call { peer, port }), None) => Mode::Call {
+                peer: peer.0.clone(),
+                port: peer.1,
+                local_port: *port,
+            },
+            (Some(Command::TestMic { seconds: None }), None) => Mode::Loopback,
+            (Some(Command::TestMic { seconds: Some(seconds) }), None) => Mode::MicCheck(*seconds),
+            (Some(Command::TestSpeakers { sweep, seconds }), None) => {
+                Mode::TestSpeakers { sweep: *sweep, seconds: *seconds }
+            },
+            (Some(Command::Devices), None) => Mode::Devices,
+            (Some(Command::Ping { server }), None) => Mode::Ping { host: server.0.clone(), port: server.1 },
+        };
+        Ok(mode)
+    }
+
+    // Без ключей - только предупреждения и ошибки
+    fn log_level(&self) -> i32 {
+        if self.quiet {
+            return log_levels::ERROR;
+        }
+        match self.verbose {
+            0 => log_levels::WARNING,
+            1 => log_levels::INFO,
+            2 => log_levels::DEBUG,
+            _ => log_levels::TRACE,
+        }
+    }
+
+    // Сохраненные настройки с ключами поверх; пустое имя - устройство по умолчанию
+    fn settings(&self, saved: Settings) -> Settings {
+        let device = |arg: &Option<String>, saved: Option<String>| match arg {
+            Some(name) => (!name.is_empty()).then(|| name.clone()),
+            None => saved,
+        };
+        Settings {
+            nickname: self.nickname.clone().or(saved.nickname),
+            muted_users: if self.mute_user.is_empty() {
+                saved.muted_users
+            } else {
+                self.mute_user.iter().filter(|name| !name.is_empty()).cloned().collect()
+            },
+            input_device: device(&self.device, saved.input_device),
+            output_device: device(&self.output_device, saved.output_device),
+            volume: self.volume.or(saved.volume),
+            bitrate: self.bitrate.or(saved.bitrate),
+            transmit_mode: self.transmit_mode.or(saved.transmit_mode),
+            vad_threshold: self.vad_threshold.or(saved.vad_threshold),
+            vad_hangover_ms: self.vad_hangover.or(saved.vad_hangover_ms),
+            ptt_release_ms: self.ptt_release.or(saved.ptt_release_ms),
+            afk_minutes: self.afk_minutes.or(saved.afk_minutes),
+            cue_volume: self.cue_volume.or(saved.cue_volume),
+            record_format: self.record_format.or(saved.record_format),
+            clip_seconds: self.clip_length.or(saved.clip_seconds),
+        }
+    }
+}
+
+// Настройки, общие для всех режимов со звуком. С --hotkey режим задает
+// клавиша: глобальная (см. global_keys.rs) удерживается как PTT, а терминал
+// не сообщает об отпускании, и каждое нажатие переключает микрофон.
+fn configure(mut builder: ClientBuilder, settings: &Settings, hotkey_mode: Option<TransmitMode>) -> ClientBuilder {
+    if let Some(nickname) = &settings.nickname {
+        builder = builder.nickname(nickname);
+    }
+    if !settings.muted_users.is_empty() {
+        builder = builder.muted_users(&settings.muted_users);
+    }
+    if let Some(bitrate) = settings.bitrate {
+        builder = builder.bitrate(bitrate);
+    }
+    if let Some(device) = &settings.input_device {
+        builder = builder.input_device(device);
+    }
+    if let Some(device) = &settings.output_device {
+        builder = builder.output_device(device);
+    }
+    if let Some(threshold) = settings.vad_threshold {
+        builder = builder.vad(true, threshold);
+    }
+    if let Some(hangover) = settings.vad_hangover_ms {
+        builder = builder.vad_hangover(hangover);
+    }
+    if let Some(release) = settings.ptt_release_ms {
+        builder = builder.ptt_release(release);
+    }
+    if let Some(volume) = settings.cue_volume {
+        builder = builder.cue_volume(volume);
+    }
+    if let Some(minutes) = settings.afk_minutes {
+        builder = builder.afk_timeout(minutes);
+    }
+    match (hotkey_mode, settings.transmit_mode) {
+        (Some(mode), _) => builder.transmit_mode(mode),
+        (None, Some(mode)) => builder.transmit_mode(mode.into()),
+        (None, None) => builder,
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mode = cli.mode().unwrap_or_else(|e| e.exit());
+    voice_client_set_log_level(cli.log_level());
+
+    let status = match mode {
+        Mode::Devices => list_devices(),
+        Mode::Ping { host, port } => ping(&host, port),
+        Mode::MicCheck(seconds) => mic_check(&cli, seconds),
+        Mode::TestSpeakers { sweep, seconds } => test_speakers(&cli, sweep, seconds),
+        mode => talk(&cli, mode),
+    };
+    std::process::exit(status);
+}
+
+fn list_devices() -> i32 {
+    let backend = audio::default_backend();
+    for (title, names) in [("Input devices:", backend.input_devices()), ("Output devices:", backend.output_devices())] {
+        println!("{}", title);
+        if names.is_empty() {
+            println!("  (none)");
+        }
+        for name in names {
+            println!("  {}", name);
+        }
+    }
+    0
+}
+
+// Клиент для проверок без сети: устройства, громкость и настройки
+// передачи - из настроек; клиент не запускается, сокет не используется
+fn local_client(settings: &Settings) -> Option<Client> {
+    let client = match configure(ClientBuilder::listen(0), settings, None).build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to create client: {}", e);
+            return None;
+        }
+    };
+    if let Some(volume) = settings.volume {
+        if let Err(e) = client.set_output_volume(volume) {
+            eprintln!("Invalid volume {}: {}", volume, e);
+            return None;
+        }
+    }
+    Some(client)
+}
+
+// Запись с микрофона, потом ее проигрывание через кодек, как в разговоре
+fn mic_check(cli: &Cli, seconds: u32) -> i32 {
+    let settings = cli.settings(Settings::load());
+    let Some(client) = local_client(&settings) else {
+        return 1;
+    };
+    if let Err(e) = client.mic_check(seconds) {
+        eprintln!("Failed to start microphone check: {}", e);
+        return 1;
+    }
+    settings.save();
+    let length = Duration::from_secs(seconds as u64);
+    println!("Recording for {} s, say something", seconds);
+    thread::sleep(length);
+    println!("Playing back what others would hear");
+    thread::sleep(length + TEST_TONE_TAIL);
+    0
+}
+
+// Проверочный сигнал на устройстве вывода из настроек и с их громкостью
+fn test_speakers(cli: &Cli, sweep: bool, seconds: u32) -> i32 {
+    let settings = cli.settings(Settings::load());
+    let Some(client) = local_client(&settings) else {
+        return 1;
+    };
+    let tone = if sweep { TestTone::Sweep } else { TestTone::Steady };
+    if let Err(e) = client.play_test_tone(tone, seconds) {
+        eprintln!("Failed to play test sound: {}", e);
+        return 1;
+    }
+    let device = settings.output_device.as_deref().unwrap_or("the default output device");
+    println!("Playing a test {} on {}", if sweep { "sweep" } else { "tone" }, device);
+    settings.save();
+    thread::sleep(Duration::from_secs(seconds as u64) + TEST_TONE_TAIL);
+    0
+}
+
+// Handshake с сервером без звука: устройства не открываются, вместо
+// микрофона - пустой MockAudio
+fn ping(host: &str, port: u16) -> i32 {
+    let client = match ClientBuilder::server(host, port)
+        .audio_backend(Arc::new(MockAudio::new(Vec::new())))
+        .build()
+        .and_then(|client| client.start().map(|_| client))
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to start client: {}", e);
+            return 1;
+        }
+    };
+
+    let deadline = Instant::now() + PING_TIMEOUT;
+    while !client.is_connected() {
+        if client.connection_state() == connection_states::FAILED || Instant::now() >= deadline {
+            eprintln!("No answer from {}:{}", host, port);
+            client.stop();
+            return 1;
+        }
+        thread::sleep(CONNECT_POLL_INTERVAL);
+    }
+    println!("Connected to {}:{}", host, port);
+
+    let mut samples = Vec::with_capacity(PING_COUNT);
+    for _ in 0..PING_COUNT {
+        thread::sleep(PING_INTERVAL);
+        match client.stats().map(|stats| stats.rtt_ms) {
+            Ok(rtt) if rtt > 0 => {
+                println!("Reply from {}:{}: time={} ms", host, port, rtt);
+                samples.push(rtt);
+            },
+            _ => println!("No keep-alive reply yet"),
+        }
+    }
+    client.stop();
+
+    match (samples.iter().min(), samples.iter().max()) {
+        (Some(min), Some(max)) => {
+            let avg = samples.iter().sum::<u32>() / samples.len() as u32;
+            println!("RTT min/avg/max = {}/{}/{} ms", min, avg, max);
+            0
+        },
+        _ => 1,
+    }
+}
+
+fn talk(cli: &Cli, mode: Mode) -> i32 {
+    // Заголовок экрана звонка; самопроверке без сети экран не нужен
+    let (builder, title) = match &mode {
+        Mode::Server { host, port } => {
+            println!("Connecting to {}:{}", host, port);
+            (ClientBuilder::server(host, *port), Some(format!("Server {}:{}", host, port)))
+        },
+        Mode::Listen(port) => {
+            println!("Waiting for a call on port {}", port);
+            (ClientBuilder::listen(*port), Some(format!("Listening on port {}", port)))
+        },
+        Mode::Call { peer, port, local_port } => {
+            println!("Calling {}:{}", peer, port);
+            (ClientBuilder::call(peer, *port, *local_port), Some(format!("Call to {}:{}", peer, port)))
+        },
+        // Сокет клиента в самопроверке не используется
+        _ => {
+            println!("Microphone test: you should hear yourself");
+            (ClientBuilder::listen(0), None)
+        },
+    };
+    let settings = cli.settings(Settings::load());
+    let mut hotkeys = cli.hotkeys();
+    // В самопроверке писать некому
+    if title.is_none() {
+        hotkeys.chat = None;
+    }
+    let global_keys = hotkeys.any().then(|| open_global_keys(&hotkeys)).flatten();
+    let (tx, input) = mpsc::channel();
+    let gamepad = open_gamepad(cli, tx.clone());
+    // Удерживать можно глобальную клавишу и кнопку геймпада; клавиша
+    // терминала только переключает
+    let talk_mode = hotkeys::talk_mode_for(global_keys.is_some(), hotkeys.talk);
+    let hold = talk_mode == TransmitMode::PushToTalk;
+    let hotkey_mode = (hotkeys.talk.is_some() || gamepad).then_some(talk_mode);
+    let mut builder = configure(builder, &settings, hotkey_mode)
+        .cues(!cli.no_cues)
+        .transmit_cues(!cli.no_cues)
+        .notification_cues(!cli.no_cues);
+    // Буфер повтора держим, только если клип есть чем сохранить
+    if hotkeys.clip.is_some() {
+        builder = builder.clip_length(settings.clip_seconds.unwrap_or(DEFAULT_CLIP_SECONDS));
+    }
+    let client = match builder.build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to create client: {}", e);
+            return 1;
+        }
+    };
+    for (kind, path) in &cli.notification_sound {
+        if let Err(e) = client.set_notification_sound(*kind, Some(path)) {
+            eprintln!("Failed to load {}: {}", path.display(), e);
+            return 1;
+        }
+    }
+    if let Some(volume) = settings.volume {
+        if let Err(e) = client.set_output_volume(volume) {
+            eprintln!("Invalid volume {}: {}", volume, e);
+            return 1;
+        }
+    }
+    // Без Mumble Link звонок идет как обычно
+    if cli.positional {
+        if let Err(e) = client.set_positional_audio(true) {
+            eprintln!("Positional audio is unavailable: {}", e);
+        }
+    }
+    if cli.overlay_socket {
+        match client.start_overlay_socket(None) {
+            Ok(()) => println!("Overlay socket: {}", overlay::default_path().display()),
+            Err(e) => eprintln!("Failed to open the overlay socket: {}", e),
+        }
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(port) = cli.metrics {
+        match client.start_metrics(port) {
+            Ok(()) => println!("Metrics: http://127.0.0.1:{}/metrics", port),
+            Err(e) => eprintln!("Failed to serve metrics: {}", e),
+        }
+    }
+    // Устройство откроется с запуском; не откроется - звонок идет без него
+    if let Some(device) = &cli.voice_output {
+        if let Err(e) = client.set_voice_output_device(Some(device)) {
+            eprintln!("Invalid voice output device {}: {}", device, e);
+        }
+    }
+
+    let (started, action) = match mode {
+        Mode::Loopback => (client.start_loopback(), "stop"),
+        _ => (client.start(), "hang up"),
+    };
+    if let Err(e) = started {
+        eprintln!("Failed to start audio: {}", e);
+        return 1;
+    }
+    // Запомнить стоит только то, с чем звук заработал
+    settings.save();
+    if hotkeys.talk.is_none() && !gamepad {
+        client.set_transmitting(true);
+    }
+    let record_format = settings.record_format.unwrap_or_default();
+    let mut recorder = Recorder::new(cli.record_dir.clone(), record_format, cli.record_tracks, cli.record_mic);
+    if cli.record {
+        recorder.start(&client);
+    }
+    // Без канала управления звонок идет как обычно
+    let _control = match cli.control.then(|| control::Control::start(tx.clone())) {
+        Some(Ok(control)) => {
+            println!("Control channel: {}", control.address.display());
+            Some(control)
+        },
+        Some(Err(e)) => {
+            eprintln!("Failed to open the control channel: {}", e);
+            None
+        },
+        None => None,
+    };
+    let http = match cli.http.map(|port| http::Http::start(port, tx.clone())) {
+        Some(Ok(http)) => {
+            println!("HTTP control: http://127.0.0.1:{}", http.port);
+            Some(http)
+        },
+        Some(Err(e)) => {
+            eprintln!("Failed to start the HTTP server: {}", e);
+            None
+        },
+        None => None,
+    };
+    let mut view = CallView {
+        #[cfg(feature = "tray")]
+        tray: open_tray(cli, title.clone(), &client, tx.clone()),
+        tui: title.filter(|_| !cli.no_tui).and_then(|title| Tui::enter(title, cli.log_level())),
+        http,
+        hints: Vec::new(),
+        echo_draft: false,
+        shown_draft: None,
+    };
+    if gamepad {
+        view.hints.push(("Gamepad".to_string(), if hold { "hold to talk" } else { "microphone" }.to_string()));
+    }
+    view.hints.push(("Enter".to_string(), action.to_string()));
+    if view.tui.is_none() {
+        let whisper = hotkeys.whisper.map(|key| (key, hotkeys::can_hold(global_keys.is_some(), key)));
+        print_hints(&hotkeys, hold, whisper, gamepad, action);
+    }
+
+    let whisper_to = cli.whisper_to.clone();
+    let status = wait_for_hang_up(&client, hotkeys, global_keys, whisper_to, &mut recorder, view, (tx, input));
+    recorder.stop(&client);
+    // Прощание уходит в stop()
+    client.stop();
+    // Во сколько обошелся звонок на лимитном подключении; самопроверка сеть не тратит
+    if let Some(stats) = client.stats().ok().filter(|stats| stats.total_bytes_sent + stats.total_bytes_received > 0) {
+        println!(
+            "Traffic: {:.1} MB sent, {:.1} MB received",
+            stats.total_bytes_sent as f64 / 1_000_000.0,
+            stats.total_bytes_received as f64 / 1_000_000.0
+        );
+    }
+    status
+}
+
+// Подсказки построчно, когда экрана звонка нет
+// whisper - клавиша шепота и можно ли ее удерживать
+fn print_hints(hotkeys: &Hotkeys, hold: bool, whisper: Option<(char, bool)>, gamepad: bool, action: &str) {
+    match hotkeys.talk {
+        Some(key) if hold => println!("Hold '{}' to talk", key),
+        Some(key) => println!("Press '{}' to toggle the microphone", key),
+        None => {},
+    }
+    if gamepad {
+        println!("{} the gamepad button to talk", if hold { "Hold" } else { "Press" });
+    }
+    if let Some((key, hold)) = whisper {
+        println!("{} '{}' to whisper", if hold { "Hold" } else { "Press" }, key);
+    }
+    if let Some(key) = hotkeys.mute {
+        println!("Press '{}' to mute or unmute", key);
+    }
+    if let Some(key) = hotkeys.deafen {
+        println!("Press '{}' to deafen or undeafen", key);
+    }
+    if let Some(key) = hotkeys.record {
+        println!("Press '{}' to start or stop recording", key);
+    }
+    if let Some(key) = hotkeys.clip {
+        println!("Press '{}' to save the last seconds of the call", key);
+    }
+    if hotkeys.any() {
+        println!("Press Tab to rebind a key");
+    }
+    if let Some(key) = hotkeys.chat_key() {
+        println!("Type '{}' and a message, then Enter, to send it to the call", key);
+    }
+    println!("Press Enter or Ctrl+C to {}", action);
+}
+
+// Входящее сообщение. В прямом звонке отправитель 0 - это собеседник, и
+// сообщение всегда личное.
+fn show_text(client: &Client, from: u32, private: bool, text: &str) {
+    if from == 0 {
+        say!("Peer: {}", text);
+        return;
+    }
+    let name = client
+        .users()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|user| user.id == from && !user.nickname.is_empty())
+        .map_or_else(|| format!("#{}", from), |user| user.nickname);
+    say!("{}{}: {}", name, if private { " (private)" } else { "" }, text);
+}
+
+// None - глобальных клавиш нет; причина уже выведена
+fn open_global_keys(hotkeys: &Hotkeys) -> Option<global_keys::GlobalKeys> {
+    match global_keys::open() {
+        Ok(global) => {
+            for key in hotkeys.terminal_only() {
+                eprintln!("'{}' has no key of its own and works only in this terminal", key);
+            }
+            Some(global)
+        },
+        Err(reason) => {
+            eprintln!("Global hotkeys unavailable: {}; keys work only in this terminal", reason);
+            None
+        },
+    }
+}
+
+// true - кнопка геймпада слушается; причина отказа уже выведена
+#[cfg(feature = "gamepad")]
+fn open_gamepad(cli: &Cli, tx: Sender<keys::Input>) -> bool {
+    let Some(button) = cli.gamepad_button else {
+        return false;
+    };
+    match gamepad::spawn(button, tx) {
+        Ok(names) if names.is_empty() => println!("No gamepad connected yet"),
+        Ok(names) => println!("Gamepad: {}", names.join(", ")),
+        Err(e) => {
+            eprintln!("Gamepad unavailable: {}", e);
+            return false;
+        },
+    }
+    true
+}
+
+#[cfg(not(feature = "gamepad"))]
+fn open_gamepad(_cli: &Cli, _tx: Sender<keys::Input>) -> bool {
+    false
+}
+
+// None - значок не просили или его некому показать; причина уже выведена
+#[cfg(feature = "tray")]
+fn open_tray(cli: &Cli, title: Option<String>, client: &Client, tx: Sender<keys::Input>) -> Option<tray::Tray> {
+    if !cli.tray {
+        return None;
+    }
+    let title = title.unwrap_or_else(|| "Microphone test".to_string());
+    match tray::Tray::spawn(format!("NSVC: {}", title), client, tx) {
+        Ok(tray) => Some(tray),
+        Err(e) => {
+            eprintln!("Tray icon unavailable: {}", e);
+            None
+        },
+    }
+}
+
+// Где виден ход звонка, кроме построчного вывода
+struct CallView {
+    #[cfg(feature = "tray")]
+    tray: Option<tray::Tray>,
+    tui: Option<Tui>,
+    // События клиента страницам, подписанным по WebSocket
+    http: Option<http::Http>,
+    // Подсказки экрана звонка сверх клавиш из hotkeys
+    hints: Vec<(String, String)>,
+    // Без экрана и без эха терминала набираемое сообщение печатаем сами
+    echo_draft: bool,
+    shown_draft: Option<String>,
+}
+
+impl CallView {
+    fn has_tray(&self) -> bool {
+        #[cfg(feature = "tray")]
+        let has_tray = self.tray.is_some();
+        #[cfg(not(feature = "tray"))]
+        let has_tray = false;
+        has_tray
+    }
+
+    // Вызывается и по таймауту ожидания: уровни и сеть меняются сами
+    fn refresh(&mut self, client: &Client, session: &hotkeys::Session) {
+        #[cfg(feature = "tray")]
+        if let Some(tray) = &mut self.tray {
+            tray.update(client);
+        }
+        let draft = session.draft();
+        if let Some(tui) = &mut self.tui {
+            let mut hints = session.hints();
+            hints.extend(self.hints.iter().cloned());
+            tui.draw(client, &hints, draft.as_deref());
+        } else if self.echo_draft && draft != self.shown_draft {
+            // Строка набора перерисовывается на месте
+            print!("\r\x1b[2K");
+            if let Some(draft) = &draft {
+                print!("Message: {}", draft);
+            }
+            let _ = std::io::stdout().flush();
+            self.shown_draft = draft;
+        }
+    }
+
+    // Сообщения печатаются с начала строки: строку набора убираем, refresh
+    // вернет ее
+    fn hide_draft(&mut self) {
+        if self.shown_draft.take().is_some() {
+            print!("\r\x1b[2K");
+            let _ = std::io::stdout().flush();
+        }
+    }
+}
+
+// Код выхода: 0 - Enter, конец ввода или меню значка, INTERRUPTED_STATUS - Ctrl+C
+fn wait_for_hang_up(
+    client: &Client,
+    hotkeys: Hotkeys,
+    global_keys: Option<global_keys::GlobalKeys>,
+    whisper_to: Vec<String>,
+    recorder: &mut Recorder,
+    mut view: CallView,
+    (tx, input): (Sender<keys::Input>, Receiver<keys::Input>),
+) -> i32 {
+    shutdown::install();
+    // Посимвольный ввод нужен клавишам в терминале и экрану звонка; с
+    // глобальными клавишами он только прячет эхо нажатий
+    let raw_mode = if hotkeys.any() || view.tui.is_some() { keys::RawMode::enable() } else { None };
+    // Со значком и без терминала конец ввода не значит отбой: звонок
+    // завершают из меню
+    if !view.has_tray() || std::io::stdin().is_terminal() {
+        keys::spawn_reader(raw_mode.is_some(), tx.clone());
+    }
+    view.echo_draft = raw_mode.is_some();
+    // Сообщения и речь приходят в сетевом потоке; ник ищет главный
+    let tx_events = tx.clone();
+    let http_events = view.http.as_ref().map(|http| http.events.clone());
+    let _ = client.set_event_callback(move |event| {
+        if let Some(http_events) = &http_events {
+            http_events.publish(&event);
+        }
+        match event.kind {
+            event_types::TEXT_MESSAGE => {
+                let _ = tx_events.send(keys::Input::Text(event.user_id, event.code != 0, event.text));
+            },
+            event_types::SPEAKING_STARTED | event_types::SPEAKING_STOPPED => {
+                let speaking = event.kind == event_types::SPEAKING_STARTED;
+                let _ = tx_events.send(keys::Input::Speaking(event.user_id, speaking));
+            },
+            event_types::AFK_MUTED => say!("No speech for {} min, microphone muted", event.code),
+            _ => {},
+        }
+    });
+    let mut session = hotkeys::Session::new(client, hotkeys, global_keys.is_some(), whisper_to, recorder);
+    if let Some(global_keys) = global_keys {
+        global_keys.spawn(tx);
+    }
+    loop {
+        if shutdown::requested() {
+            say!("Interrupted, hanging up");
+            return INTERRUPTED_STATUS;
+        }
+        view.refresh(client, &session);
+        let received = input.recv_timeout(SHUTDOWN_POLL_INTERVAL);
+        if received.is_ok() {
+            view.hide_draft();
+        }
+        match received {
+            Ok(keys::Input::Key(key)) => session.on_terminal_key(key),
+            Ok(keys::Input::Enter) => {
+                if !session.on_enter() && raw_mode.is_some() {
+                    return 0;
+                }
+            },
+            Ok(keys::Input::Text(from, private, text)) => show_text(client, from, private, &text),
+            Ok(keys::Input::Speaking(id, speaking)) => {
+                if let Some(tui) = &mut view.tui {
+                    tui.on_speaking(id, speaking);
+                }
+            },
+            Ok(keys::Input::Control(command, reply)) => {
+                let hang_up = matches!(command, control::Command::HangUp);
+                let _ = reply.send(control::execute(client, command));
+                if hang_up {
+                    say!("Hang up requested over the control channel");
+                    return 0;
+                }
+            },
+            Ok(keys::Input::Press(key)) => session.on_global_key(key, true),
+            Ok(keys::Input::Release(key)) => session.on_global_key(key, false),
+            #[cfg(feature = "gamepad")]
+            Ok(keys::Input::Gamepad(pressed)) => session.on_gamepad(pressed),
+            #[cfg(feature = "tray")]
+            Ok(keys::Input::Menu(action)) => session.on_menu(action),
+            Err(RecvTimeoutError::Timeout) => {},
+            Ok(keys::Input::HangUp) | Err(RecvTimeoutError::Disconnected) => return 0,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+// Участники, заглушенные только у себя (--mute-user, команда mute_user в
+// control.rs): их голос не слышен и не пишется, что бы ни разрешал сервер.
+// ID сервер выдает заново при каждом входе, поэтому в настройках хранятся
+// ники: библиотека получает их при создании клиента (см. configure в
+// main.rs) и глушит участника, как только он появляется в канале.
+use nsvc_core::client::Client;
+
+use crate::settings::Settings;
+
+// Запоминает ники заглушенных после mute_user
+pub fn remember(client: &Client) {
+    let Ok(muted_users) = client.muted_users() else {
+        return;
+    };
+    let mut settings = Settings::load();
+    settings.muted_users = muted_users;
+    settings.save();
+}
@@ -0,0 +1,137 @@
+// Запись звонка: --record пишет с начала звонка, --record-key включает и
+// выключает запись. Файл - nsvc-call-ГГГГММДД-ЧЧММСС.wav в --record-dir
+// (по умолчанию текущий каталог); с --record-mic в запись попадает и свой
+// голос. С --record-tracks у каждого собеседника свой файл
+// nsvc-call-..._<ID>_<ник>.wav (свой голос - _mic.wav), чтобы править голоса
+// по отдельности. С --record-format opus дорожки пишутся всегда, в Opus без
+// перекодирования.
+// --clip-key сохраняет туда же последние --clip-length секунд разговора
+// (nsvc-clip-...), даже если запись не шла.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use nsvc_core::client::Client;
+
+use crate::tui::{say, say_error};
+
+#[derive(Clone, Copy, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordFormat {
+    // Общий микс
+    #[default]
+    Wav,
+    // Дорожка на собеседника, меньше и без нагрузки на процессор
+    Opus,
+}
+
+impl RecordFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Wav => "wav",
+            RecordFormat::Opus => "opus",
+        }
+    }
+}
+
+pub struct Recorder {
+    dir: PathBuf,
+    format: RecordFormat,
+    // Дорожка на собеседника
+    tracks: bool,
+    include_mic: bool,
+    // Файл идущей записи
+    path: Option<PathBuf>,
+}
+
+impl Recorder {
+    pub fn new(dir: Option<PathBuf>, format: RecordFormat, tracks: bool, include_mic: bool) -> Self {
+        Recorder { dir: dir.unwrap_or_else(|| PathBuf::from(".")), format, tracks, include_mic, path: None }
+    }
+
+    // Две записи в одну секунду не затирают друг друга. Дорожки Opus
+    // называются по имени записи с суффиксом, поэтому сверяется начало имени.
+    fn next_path(&self, prefix: &str) -> PathBuf {
+        let taken = |stem: &str| {
+            fs::read_dir(&self.dir).is_ok_and(|entries| {
+                entries.flatten().any(|entry| entry.file_name().to_string_lossy().starts_with(stem))
+            })
+        };
+        let time = format!("{}-{}", prefix, Local::now().format("%Y%m%d-%H%M%S"));
+        let mut stem = time.clone();
+        let mut n = 1;
+        while taken(&stem) {
+            stem = format!("{}-{}", time, n);
+            n += 1;
+        }
+        self.dir.join(stem).with_extension(self.format.extension())
+    }
+
+    // Как путь записи видит пользователь; tracks - файлы дорожек
+    fn describe(&self, path: &Path, tracks: bool) -> String {
+        match self.format {
+            RecordFormat::Wav if !tracks => path.display().to_string(),
+            format => format!("{}_*.{}", path.with_extension("").display(), format.extension()),
+        }
+    }
+
+    fn create_dir(&self) -> bool {
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            say_error!("Failed to create {}: {}", self.dir.display(), e);
+            return false;
+        }
+        true
+    }
+
+    pub fn start(&mut self, client: &Client) {
+        if !self.create_dir() {
+            return;
+        }
+        let path = self.next_path("nsvc-call");
+        let started = if self.tracks {
+            client.start_recording_tracks(&path, self.include_mic)
+        } else {
+            client.start_recording(&path, self.include_mic)
+        };
+        match started {
+            Ok(()) => {
+                say!("Recording to {}", self.describe(&path, self.tracks));
+                self.path = Some(path);
+            },
+            Err(e) => say_error!("Failed to record to {}: {}", self.describe(&path, self.tracks), e),
+        }
+    }
+
+    pub fn stop(&mut self, client: &Client) {
+        let Some(path) = self.path.take() else {
+            return;
+        };
+        match client.stop_recording() {
+            Ok(()) => say!("Recording saved to {}", self.describe(&path, self.tracks)),
+            Err(e) => say_error!("Recording {} failed: {}", self.describe(&path, self.tracks), e),
+        }
+    }
+
+    pub fn save_clip(&self, client: &Client) {
+        if !self.create_dir() {
+            return;
+        }
+        let path = self.next_path("nsvc-clip");
+        match client.save_clip(&path, self.include_mic) {
+            Ok(()) => say!("Clip saved to {}", self.describe(&path, false)),
+            Err(e) => say_error!("Failed to save clip: {}", e),
+        }
+    }
+
+    // Запись, прерванную ошибкой, клавиша начинает заново
+    pub fn toggle(&mut self, client: &Client) {
+        if self.path.is_some() && client.is_recording() {
+            self.stop(client);
+        } else {
+            self.stop(client);
+            self.start(client);
+        }
+    }
+}
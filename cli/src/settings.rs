@@ -0,0 +1,109 @@
+// Настройки между запусками: ник, заглушенные участники, устройства,
+// громкость, битрейт, режим передачи, детектор голоса, задержка отпускания,
+// выключение забытого микрофона, громкость сигналов, формат записи и длина
+// клипа последнего разговора.
+// Файл - nsvc/nsvc-call.toml в каталоге настроек пользователя (~/.config
+// на Linux, %APPDATA% на Windows, ~/Library/Application Support на macOS).
+// Ключ командной строки важнее сохраненного значения и сохраняется сам;
+// --device "" возвращает устройство по умолчанию.
+//
+//   nickname = "Player"
+//   muted_users = ["Loud Guy"]
+//   input_device = "USB Headset"
+//   volume = 0.8
+//   bitrate = 32000
+//   transmit_mode = "voice"
+//   vad_threshold = 0.02
+//   vad_hangover_ms = 300
+//   ptt_release_ms = 250
+//   afk_minutes = 30
+//   cue_volume = 0.5
+//   record_format = "opus"
+//   clip_seconds = 60
+use std::fs;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use nsvc_core::client::TransmitMode;
+
+use crate::record::RecordFormat;
+
+#[derive(Clone, Copy, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transmit {
+    // Без --hotkey микрофон включен все время
+    Ptt,
+    // Клавиша --hotkey включает и выключает микрофон
+    Toggle,
+    // Только когда слышен голос
+    Voice,
+    Continuous,
+}
+
+impl From<Transmit> for TransmitMode {
+    fn from(mode: Transmit) -> Self {
+        match mode {
+            Transmit::Ptt => TransmitMode::PushToTalk,
+            Transmit::Toggle => TransmitMode::Toggle,
+            Transmit::Voice => TransmitMode::VoiceActivation,
+            Transmit::Continuous => TransmitMode::Continuous,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub nickname: Option<String>,
+    pub muted_users: Vec<String>,
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub volume: Option<f32>,
+    pub bitrate: Option<u32>,
+    pub transmit_mode: Option<Transmit>,
+    pub vad_threshold: Option<f32>,
+    pub vad_hangover_ms: Option<u32>,
+    pub ptt_release_ms: Option<u32>,
+    pub afk_minutes: Option<u32>,
+    pub cue_volume: Option<f32>,
+    pub record_format: Option<RecordFormat>,
+    pub clip_seconds: Option<u32>,
+}
+
+fn path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("nsvc").join("nsvc-call.toml"))
+}
+
+impl Settings {
+    // Нет файла - все по умолчанию; испорченный файл не мешает запуску
+    pub fn load() -> Self {
+        let Some(path) = path() else {
+            return Settings::default();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Settings::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring saved settings {}: {}", path.display(), e);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let Some(path) = path() else {
+            return;
+        };
+        let result = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                fs::write(&path, text).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            eprintln!("Failed to save settings to {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Ctrl+C и SIGTERM не убивают процесс, а только ставят флаг: главный поток
+// сам завершает звонок, и собеседник получает прощание вместо обрыва.
+// Обработчик сигнала может лишь записать атомарную переменную - все
+// остальное делает главный поток.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        REQUESTED.store(true, Ordering::SeqCst);
+    }
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+// Ctrl+C, Ctrl+Break и закрытие окна консоли
+#[cfg(windows)]
+pub fn install() {
+    use winapi::shared::minwindef::{BOOL, DWORD, TRUE};
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+
+    unsafe extern "system" fn on_event(_: DWORD) -> BOOL {
+        REQUESTED.store(true, Ordering::SeqCst);
+        TRUE
+    }
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_event), TRUE);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
@@ -0,0 +1,143 @@
+// Значок в трее (feature "tray"): StatusNotifierItem через D-Bus (ksni),
+// его показывают KDE, GNOME с расширением AppIndicator, XFCE и большинство
+// панелей. Значок показывает, идет ли передача и выключены ли микрофон и
+// звук; меню переключает mute и deafen и завершает звонок. Со значком
+// звонку не нужно окно терминала: без терминала на stdin его завершают из
+// меню или сигналом.
+use std::sync::mpsc::Sender;
+
+use ksni::blocking::{Handle, TrayMethods};
+use ksni::menu::{CheckmarkItem, StandardItem};
+use ksni::{MenuItem, ToolTip};
+use nsvc_core::client::Client;
+
+use crate::hotkeys::Action;
+use crate::keys::Input;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct State {
+    transmitting: bool,
+    muted: bool,
+    deafened: bool,
+}
+
+impl State {
+    fn of(client: &Client) -> Self {
+        State {
+            transmitting: client.is_transmitting(),
+            muted: client.is_muted(),
+            deafened: client.is_deafened(),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        if self.deafened {
+            "Deafened"
+        } else if self.muted {
+            "Muted"
+        } else if self.transmitting {
+            "Transmitting"
+        } else {
+            "Listening"
+        }
+    }
+}
+
+struct Icon {
+    title: String,
+    state: State,
+    // Пункты меню выполняет главный поток, как клавиши
+    tx: Sender<Input>,
+}
+
+impl Icon {
+    fn send(&self, input: Input) {
+        let _ = self.tx.send(input);
+    }
+}
+
+impl ksni::Tray for Icon {
+    fn id(&self) -> String {
+        "nsvc-call".into()
+    }
+
+    fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    // Имена из темы значков freedesktop
+    fn icon_name(&self) -> String {
+        let name = if self.state.deafened || self.state.muted {
+            "microphone-sensitivity-muted"
+        } else if self.state.transmitting {
+            "microphone-sensitivity-high"
+        } else {
+            "audio-input-microphone"
+        };
+        name.into()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.title.clone(),
+            description: self.state.describe().into(),
+            ..Default::default()
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        vec![
+            CheckmarkItem {
+                label: "Mute".into(),
+                checked: self.state.muted,
+                activate: Box::new(|icon: &mut Self| icon.send(Input::Menu(Action::Mute))),
+                ..Default::default()
+            }
+            .into(),
+            CheckmarkItem {
+                label: "Deafen".into(),
+                checked: self.state.deafened,
+                activate: Box::new(|icon: &mut Self| icon.send(Input::Menu(Action::Deafen))),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Hang up".into(),
+                icon_name: "call-stop".into(),
+                activate: Box::new(|icon: &mut Self| icon.send(Input::HangUp)),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
+
+pub struct Tray {
+    handle: Handle<Icon>,
+    state: State,
+}
+
+impl Tray {
+    // Ошибка - нет D-Bus сессии или панели, которая показывает значки
+    pub fn spawn(title: String, client: &Client, tx: Sender<Input>) -> Result<Tray, String> {
+        let state = State::of(client);
+        let handle = Icon { title, state, tx }.spawn().map_err(|e| e.to_string())?;
+        Ok(Tray { handle, state })
+    }
+
+    // Значок перерисовывается, только если состояние сменилось
+    pub fn update(&mut self, client: &Client) {
+        let state = State::of(client);
+        if state != self.state {
+            self.state = state;
+            self.handle.update(move |icon| icon.state = state);
+        }
+    }
+}
+
+impl Drop for Tray {
+    fn drop(&mut self) {
+        self.handle.shutdown().wait();
+    }
+}
@@ -0,0 +1,296 @@
+// Экран звонка в терминале (ratatui): состояние соединения, уровни
+// микрофона и воспроизведения, буфер, потери и RTT, трафик, кто говорит, и
+// подсказки по клавишам. Экран занимает альтернативный буфер терминала;
+// сообщения CLI и журнал клиента показываются на нем и печатаются снова
+// после выхода, чтобы пути записей и ошибки остались в истории терминала.
+// С --no-tui или когда вывод не в терминал - построчный вывод, как раньше.
+use std::collections::BTreeSet;
+use std::ffi::CStr;
+use std::io::{self, IsTerminal, Stdout};
+use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::crossterm::{cursor, execute};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, Paragraph};
+use ratatui::{Frame, Terminal};
+use nsvc_core::client::Client;
+use nsvc_core::{connection_state_name, connection_states, voice_client_set_log_callback};
+
+// Сообщения, пока экран открыт: (текст, ошибка). None - экрана нет,
+// сообщения печатаются сразу.
+static MESSAGES: Mutex<Option<Vec<(String, bool)>>> = Mutex::new(None);
+// Больше на экране не поместится, а после выхода столько никто не прочтет
+const MAX_MESSAGES: usize = 200;
+// Шкалы уровня в дБ от полной громкости: тише - пустая шкала
+const METER_FLOOR_DB: f32 = -60.0;
+// Пик спадает постепенно, иначе шкала мигает между опросами
+const METER_DECAY: f32 = 0.7;
+
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::tui::show(format!($($arg)*), false)
+    };
+}
+
+macro_rules! say_error {
+    ($($arg:tt)*) => {
+        $crate::tui::show(format!($($arg)*), true)
+    };
+}
+
+pub(crate) use {say, say_error};
+
+// println! или eprintln!, а при открытом экране - строка на нем
+pub fn show(text: String, error: bool) {
+    let mut messages = MESSAGES.lock().unwrap();
+    match messages.as_mut() {
+        Some(messages) => {
+            if messages.len() == MAX_MESSAGES {
+                messages.remove(0);
+            }
+            messages.push((text, error));
+        },
+        None if error => eprintln!("{}", text),
+        None => println!("{}", text),
+    }
+}
+
+// Журнал клиента писал бы в stderr поверх экрана
+extern "C" fn on_log(_level: i32, message: *const c_char, _userdata: *mut c_void) {
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+    show(message, true);
+}
+
+pub struct Tui {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    // Куда звоним, например "Server example.org:40000"
+    title: String,
+    input_level: f32,
+    output_level: f32,
+    // Кто говорит, по событиям SPEAKING_* - они уже сглажены
+    speaking: BTreeSet<u32>,
+}
+
+impl Tui {
+    // None - вывод не в терминал или экран не открыть. Журнал клиента с
+    // уровнем не ниже log_level идет на экран, а не в stderr и файл.
+    pub fn enter(title: String, log_level: i32) -> Option<Tui> {
+        if !io::stdout().is_terminal() {
+            return None;
+        }
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide).ok()?;
+        let terminal = match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => terminal,
+            Err(_) => {
+                let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+                return None;
+            },
+        };
+        *MESSAGES.lock().unwrap() = Some(Vec::new());
+        voice_client_set_log_callback(Some(on_log), std::ptr::null_mut(), log_level);
+        Some(Tui { terminal, title, input_level: 0.0, output_level: 0.0, speaking: BTreeSet::new() })
+    }
+
+    pub fn on_speaking(&mut self, id: u32, speaking: bool) {
+        if speaking {
+            self.speaking.insert(id);
+        } else {
+            self.speaking.remove(&id);
+        }
+    }
+
+    // hints: (клавиша, действие); draft - набираемое сообщение, оно
+    // показывается вместо подсказок
+    pub fn draw(&mut self, client: &Client, hints: &[(String, String)], draft: Option<&str>) {
+        // Пики с прошлого опроса; у незапущенного клиента уровней нет
+        let decay = |shown: f32, peak: Option<f32>| peak.unwrap_or(0.0).max(shown * METER_DECAY);
+        self.input_level = decay(self.input_level, client.input_level());
+        self.output_level = decay(self.output_level, client.output_level());
+        let (input_level, output_level) = (self.input_level, self.output_level);
+        // После переподключения клиент забывает, кто говорил, не сообщая об этом
+        if client.connection_state() != connection_states::CONNECTED {
+            self.speaking.clear();
+        }
+        let messages = MESSAGES.lock().unwrap().clone().unwrap_or_default();
+        let title = &self.title;
+        let speaking = &self.speaking;
+        // Ошибка вывода в терминал не повод обрывать звонок
+        let _ = self.terminal.draw(|frame| {
+            let view = View { title, hints, draft, input_level, output_level, speaking, messages: &messages };
+            render(frame, client, &view);
+        });
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        voice_client_set_log_callback(None, std::ptr::null_mut(), 0);
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+        let messages = MESSAGES.lock().unwrap().take().unwrap_or_default();
+        for (text, error) in messages {
+            show(text, error);
+        }
+    }
+}
+
+// Все, что рисуется, кроме состояния клиента
+struct View<'a> {
+    title: &'a str,
+    hints: &'a [(String, String)],
+    draft: Option<&'a str>,
+    input_level: f32,
+    output_level: f32,
+    speaking: &'a BTreeSet<u32>,
+    messages: &'a [(String, bool)],
+}
+
+fn render(frame: &mut Frame, client: &Client, view: &View) {
+    let [header, input, output, network, traffic, speaking, log, hints] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Length(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(Paragraph::new(header_line(client, view.title)), header);
+    frame.render_widget(meter("Microphone", view.input_level), input);
+    frame.render_widget(meter("Output", view.output_level), output);
+    frame.render_widget(Paragraph::new(network_line(client)), network);
+    frame.render_widget(Paragraph::new(traffic_line(client)), traffic);
+    frame.render_widget(Paragraph::new(speaking_line(client, view.speaking)), speaking);
+
+    // Последние сообщения, сколько поместится
+    let block = Block::bordered().title("Messages");
+    let visible = block.inner(log).height as usize;
+    let messages = view.messages;
+    let lines: Vec<Line> = messages[messages.len().saturating_sub(visible)..]
+        .iter()
+        .map(|(text, error)| {
+            let style = if *error { Style::default().fg(Color::Yellow) } else { Style::default() };
+            Line::styled(text.as_str(), style)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), log);
+
+    let reversed = Style::default().add_modifier(Modifier::REVERSED);
+    let mut spans = Vec::new();
+    if let Some(draft) = view.draft {
+        spans.push(Span::styled(" Message ", reversed));
+        spans.push(Span::raw(format!(" {}", draft)));
+        spans.push(Span::styled(" ", reversed));
+        spans.push(Span::raw("  Enter send  Esc cancel"));
+    } else {
+        for (key, action) in view.hints {
+            spans.push(Span::styled(format!(" {} ", key), reversed));
+            spans.push(Span::raw(format!(" {}  ", action)));
+        }
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), hints);
+}
+
+fn header_line(client: &Client, title: &str) -> Line<'static> {
+    let state = client.connection_state();
+    let color = match state {
+        connection_states::CONNECTED => Color::Green,
+        connection_states::FAILED | connection_states::DISCONNECTED => Color::Red,
+        _ => Color::Yellow,
+    };
+    let mut spans = vec![
+        Span::styled(title.to_string(), Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw("  "),
+        Span::styled(connection_state_name(state), Style::default().fg(color)),
+    ];
+    let flags = [
+        (client.is_transmitting(), "MIC ON", Color::Green),
+        (client.is_muted(), "MUTED", Color::Red),
+        (client.is_deafened(), "DEAFENED", Color::Red),
+        (client.is_recording(), "REC", Color::Red),
+        (client.is_positional_audio_active(), "POSITIONAL", Color::Cyan),
+    ];
+    for (on, label, color) in flags {
+        if on {
+            spans.push(Span::raw("  "));
+            spans.push(Span::styled(label, Style::default().fg(color).add_modifier(Modifier::BOLD)));
+        }
+    }
+    Line::from(spans)
+}
+
+fn meter(title: &'static str, peak: f32) -> Gauge<'static> {
+    let db = 20.0 * peak.max(f32::MIN_POSITIVE).log10();
+    let ratio = ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0);
+    let label = if db > METER_FLOOR_DB { format!("{:.0} dB", db) } else { "silence".to_string() };
+    // Почти полная шкала - близко к перегрузке
+    let color = if ratio > 0.95 { Color::Red } else { Color::Green };
+    Gauge::default()
+        .block(Block::bordered().title(title))
+        .gauge_style(Style::default().fg(color))
+        .ratio(ratio as f64)
+        .label(label)
+}
+
+fn network_line(client: &Client) -> String {
+    let Ok(stats) = client.stats() else {
+        return String::new();
+    };
+    let rtt = match stats.rtt_ms {
+        0 => "-".to_string(),
+        rtt => format!("{} ms", rtt),
+    };
+    format!(
+        "Buffer {} ms   Loss {:.1}%   RTT {}   Jitter {:.1} ms   Bitrate {} kbps",
+        stats.buffer_ms,
+        stats.loss_percent,
+        rtt,
+        stats.jitter_ms,
+        stats.bitrate / 1000
+    )
+}
+
+// Для лимитного подключения: сколько идет сейчас и сколько ушло за звонок
+fn traffic_line(client: &Client) -> String {
+    let Ok(stats) = client.stats() else {
+        return String::new();
+    };
+    format!(
+        "Up {:.0} kbps   Down {:.0} kbps   Used {} sent, {} received",
+        stats.send_kbps,
+        stats.receive_kbps,
+        megabytes(stats.total_bytes_sent),
+        megabytes(stats.total_bytes_received)
+    )
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+// В прямом звонке списка участников нет: говорит собеседник
+fn speaking_line(client: &Client, speaking: &BTreeSet<u32>) -> String {
+    let users = client.users().unwrap_or_default();
+    let own_id = client.user_id();
+    let speaking: Vec<String> = speaking
+        .iter()
+        .filter(|id| Some(**id) != own_id)
+        .map(|id| match users.iter().find(|user| user.id == *id && !user.nickname.is_empty()) {
+            Some(user) => user.nickname.clone(),
+            None if own_id.is_none() => "Peer".to_string(),
+            None => format!("#{}", id),
+        })
+        .collect();
+    if speaking.is_empty() {
+        "Nobody is speaking".to_string()
+    } else {
+        format!("Speaking: {}", speaking.join(", "))
+    }
+}
@@ -0,0 +1,86 @@
+// Защита от забытого открытого микрофона: если в активации голосом или
+// непрерывной передаче микрофон открыт, а речи нет afk_minutes минут (см.
+// VoiceClientConfig и voice_client_set_afk_timeout), клиент выключает
+// микрофон, как voice_client_set_muted, и сообщает событием AFK_MUTED.
+// Речь отличается от шума громкостью над фоном: фон идет за тихими кадрами
+// и медленно поднимается, поэтому ровный гул вентилятора речью не считается,
+// а щелчки клавиатуры слишком коротки.
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::SAMPLE_RATE;
+
+// Тише этого речи нет, какой бы тихой ни была комната
+const SPEECH_MIN_LEVEL: f32 = 0.01;
+// Во сколько раз речь громче фона (~10 дБ)
+const SPEECH_OVER_FLOOR: f32 = 3.0;
+// Столько подряд должен длиться звук над фоном, мс
+const SPEECH_MIN_MS: u64 = 100;
+// Доля разницы, на которую фон сдвигается за кадр: вниз - быстро, вверх -
+// за секунды, чтобы фраза не успела стать фоном
+const FLOOR_FALL: f32 = 0.1;
+const FLOOR_RISE: f32 = 0.002;
+
+// Таймаут, общий для потока кодера и хоста
+#[derive(Default)]
+pub(crate) struct AfkGuard {
+    // 0 - защита выключена
+    minutes: AtomicU32,
+}
+
+impl AfkGuard {
+    pub fn new(minutes: u32) -> Self {
+        AfkGuard { minutes: AtomicU32::new(minutes) }
+    }
+
+    pub fn minutes(&self) -> u32 {
+        self.minutes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_minutes(&self, minutes: u32) {
+        self.minutes.store(minutes, Ordering::Relaxed);
+    }
+}
+
+// Счет тишины в потоке кодера
+#[derive(Default)]
+pub(crate) struct Detector {
+    // RMS фона; None - кадров еще не было
+    floor: Option<f32>,
+    // Отсчеты подряд над фоном и без речи
+    loud_run: u64,
+    quiet: u64,
+}
+
+impl Detector {
+    // Кадр открытого микрофона; true - речи не было minutes минут, и счет
+    // начинается заново
+    pub fn on_frame(&mut self, frame: &[f32], minutes: u32) -> bool {
+        if minutes == 0 || frame.is_empty() {
+            self.reset();
+            return false;
+        }
+        let level = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        let floor = self.floor.get_or_insert(level);
+        let speech = level >= SPEECH_MIN_LEVEL && level > *floor * SPEECH_OVER_FLOOR;
+        let rate = if level < *floor { FLOOR_FALL } else { FLOOR_RISE };
+        *floor += (level - *floor) * rate;
+
+        self.loud_run = if speech { self.loud_run + frame.len() as u64 } else { 0 };
+        if self.loud_run >= SAMPLE_RATE as u64 * SPEECH_MIN_MS / 1000 {
+            self.quiet = 0;
+            return false;
+        }
+        self.quiet += frame.len() as u64;
+        if self.quiet >= SAMPLE_RATE as u64 * 60 * minutes as u64 {
+            self.quiet = 0;
+            return true;
+        }
+        false
+    }
+
+    // Микрофон закрыт или режим не тот: молчание не в счет
+    pub fn reset(&mut self) {
+        self.loud_run = 0;
+        self.quiet = 0;
+    }
+}
@@ -0,0 +1,148 @@
+// Android: JNI-обертка над FFI для класса org.nsvc.VoiceClient
+// (android/src/main/java/org/nsvc/VoiceClient.java). Звук на Android cpal
+// ведет через AAudio, ему нужны JavaVM и Context приложения - их передает
+// nativeInit. Аудиофокус и режим связи держит Java-класс: он ближе к
+// AudioManager и жизненному циклу приложения.
+//
+// Сборка (cargo-ndk, API 26+ из-за AAudio):
+//   cargo ndk -t arm64-v8a -t armeabi-v7a -P 26 -o app/src/main/jniLibs build --release -p nsvc-ffi
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::sync::OnceLock;
+
+use jni::objects::{JClass, JObject, JString};
+use jni::sys::{jboolean, jint, jlong, jstring, JNI_TRUE};
+use jni::JNIEnv;
+
+use crate::*;
+
+// Итог первого nativeInit: повторные вызовы возвращают его же
+static INIT: OnceLock<jint> = OnceLock::new();
+
+// Описатель клиента в Java - long
+fn handle(client: jlong) -> *mut c_void {
+    client as usize as *mut c_void
+}
+
+// Строка Java в C-строку для функций FFI; None - null или '\0' внутри
+fn c_string(env: &mut JNIEnv, value: &JString) -> Option<CString> {
+    if value.is_null() {
+        return None;
+    }
+    let value: String = env.get_string(value).ok()?.into();
+    CString::new(value).ok()
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeInit(env: JNIEnv, _class: JClass, context: JObject) -> jint {
+    // ndk-context можно инициализировать только один раз за процесс
+    *INIT.get_or_init(|| {
+        let (vm, context) = match (env.get_java_vm(), env.new_global_ref(context)) {
+            (Ok(vm), Ok(context)) => (vm, context),
+            _ => return fail(error_codes::NULL_POINTER, "nativeInit: failed to get JavaVM or Context"),
+        };
+        unsafe {
+            ndk_context::initialize_android_context(
+                vm.get_java_vm_pointer() as *mut c_void,
+                context.as_obj().as_raw() as *mut c_void,
+            );
+        }
+        // Глобальная ссылка на Context нужна AAudio до конца процесса
+        std::mem::forget(context);
+        error_codes::SUCCESS
+    })
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeNew(
+    mut env: JNIEnv,
+    _class: JClass,
+    host: JString,
+    port: jint,
+) -> jlong {
+    match c_string(&mut env, &host) {
+        Some(host) => voice_client_new(host.as_ptr(), port as u16) as usize as jlong,
+        None => 0,
+    }
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeStart(_env: JNIEnv, _class: JClass, client: jlong) -> jint {
+    voice_client_start(handle(client))
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeStop(_env: JNIEnv, _class: JClass, client: jlong) {
+    voice_client_stop(handle(client))
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeFree(_env: JNIEnv, _class: JClass, client: jlong) {
+    voice_client_free(handle(client))
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeSetTransmitting(
+    _env: JNIEnv,
+    _class: JClass,
+    client: jlong,
+    transmitting: jboolean,
+) {
+    voice_client_set_transmitting(handle(client), transmitting == JNI_TRUE)
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeSetTransmitMode(
+    _env: JNIEnv,
+    _class: JClass,
+    client: jlong,
+    mode: jint,
+) -> jint {
+    voice_client_set_transmit_mode(handle(client), mode)
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeRestartAudio(_env: JNIEnv, _class: JClass, client: jlong) -> jint {
+    voice_client_restart_audio(handle(client))
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeGetConnectionState(
+    _env: JNIEnv,
+    _class: JClass,
+    client: jlong,
+) -> jint {
+    voice_client_get_connection_state(handle(client))
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeSetNickname(
+    mut env: JNIEnv,
+    _class: JClass,
+    client: jlong,
+    nickname: JString,
+) -> jint {
+    match c_string(&mut env, &nickname) {
+        Some(nickname) => voice_client_set_nickname(handle(client), nickname.as_ptr()),
+        None => fail(error_codes::NULL_POINTER, "nativeSetNickname: nickname is null or not a C string"),
+    }
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeJoinChannel(
+    mut env: JNIEnv,
+    _class: JClass,
+    client: jlong,
+    name: JString,
+) -> jint {
+    match c_string(&mut env, &name) {
+        Some(name) => voice_client_join_channel(handle(client), name.as_ptr()),
+        None => fail(error_codes::NULL_POINTER, "nativeJoinChannel: name is null or not a C string"),
+    }
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "system" fn Java_org_nsvc_VoiceClient_nativeErrorString(env: JNIEnv, _class: JClass, code: jint) -> jstring {
+    let description = error_codes::description(code).to_string_lossy();
+    env.new_string(description).map_or(std::ptr::null_mut(), |s| s.into_raw())
+}
@@ -0,0 +1,256 @@
+// Захват и воспроизведение звука клиента. По умолчанию - устройства cpal
+// (CpalBackend); MockAudio для машин без звуковой карты (CI) подает в
+// захват звук из WAV и собирает все, что клиент воспроизвел, так что
+// путь кодирование -> сеть -> декодирование проверяется целиком. Без
+// feature "audio" cpal не собирается, и по умолчанию устройств нет (NoAudio).
+//
+//   let audio = Arc::new(MockAudio::from_wav("speech.wav")?);
+//   let client = ClientBuilder::call("127.0.0.1", 40001, 40000).audio_backend(audio.clone()).build()?;
+//   ...
+//   let received = audio.played();
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{NsvcError, SAMPLE_RATE};
+
+#[cfg(feature = "audio")]
+mod cpal_backend;
+#[cfg(feature = "audio")]
+pub use cpal_backend::CpalBackend;
+mod shared;
+pub use shared::SharedAudio;
+
+// Моно f32 с частотой SAMPLE_RATE, кусками любого размера
+pub type CaptureCallback = Box<dyn FnMut(&[f32]) + Send>;
+pub type PlaybackCallback = Box<dyn FnMut(&mut [f32]) + Send>;
+// Устройство пропало (например, отключили гарнитуру); аргумент - причина
+pub type DeviceLostCallback = Box<dyn FnMut(String) + Send>;
+
+// Работающий поток звука; drop его останавливает.
+// Поток cpal не Send из-за ограничений части платформ. Хост и раньше
+// вызывал клиента из любых своих потоков; здесь поток только хранится под
+// замком и удаляется при остановке.
+pub struct AudioStream(#[allow(dead_code)] Box<dyn Any>);
+
+unsafe impl Send for AudioStream {}
+
+impl AudioStream {
+    pub fn new(stream: impl Any) -> Self {
+        AudioStream(Box::new(stream))
+    }
+}
+
+pub trait AudioBackend: Send + Sync {
+    // Запускает захват с устройства device (None - по умолчанию);
+    // возвращает поток и имя устройства
+    fn open_input(
+        &self,
+        device: Option<&str>,
+        on_data: CaptureCallback,
+        on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError>;
+
+    fn open_output(
+        &self,
+        device: Option<&str>,
+        on_data: PlaybackCallback,
+        on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError>;
+
+    // Имена устройств для open_input и open_output; пусто, если выбирать не из чего
+    fn input_devices(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn output_devices(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// Бэкенд клиента, если хост не задал свой
+pub fn default_backend() -> Arc<dyn AudioBackend> {
+    #[cfg(feature = "audio")]
+    return Arc::new(CpalBackend);
+    #[cfg(not(feature = "audio"))]
+    return Arc::new(NoAudio);
+}
+
+// Сборка без звуковых устройств: звук только через свой AudioBackend
+#[cfg(not(feature = "audio"))]
+pub struct NoAudio;
+
+#[cfg(not(feature = "audio"))]
+impl AudioBackend for NoAudio {
+    fn open_input(&self, _: Option<&str>, _: CaptureCallback, _: DeviceLostCallback) -> Result<(AudioStream, String), NsvcError> {
+        Err(NsvcError::NoInputDevice("built without the audio feature".to_string()))
+    }
+
+    fn open_output(&self, _: Option<&str>, _: PlaybackCallback, _: DeviceLostCallback) -> Result<(AudioStream, String), NsvcError> {
+        Err(NsvcError::NoOutputDevice("built without the audio feature".to_string()))
+    }
+}
+
+// Кусок звука, которым MockAudio кормит клиента и забирает у него: 10 мс
+const MOCK_CHUNK: usize = SAMPLE_RATE as usize / 100;
+const MOCK_CHUNK_INTERVAL: Duration = Duration::from_millis(10);
+
+// Звук без устройств. Захват отдает загруженные сэмплы в реальном времени
+// кусками по 10 мс, после них - тишину; воспроизведение забирает звук с той
+// же скоростью и копит его для played().
+pub struct MockAudio {
+    input: Arc<Mutex<VecDeque<f32>>>,
+    played: Arc<Mutex<Vec<f32>>>,
+}
+
+impl MockAudio {
+    pub fn new(samples: Vec<f32>) -> Self {
+        MockAudio {
+            input: Arc::new(Mutex::new(samples.into())),
+            played: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    // WAV с частотой SAMPLE_RATE: PCM 16 бит или float 32 бита; каналы сводятся в моно
+    pub fn from_wav(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(read_wav(&std::fs::read(path)?)?))
+    }
+
+    // Добавляет звук в конец захвата
+    pub fn feed(&self, samples: &[f32]) {
+        self.input.lock().unwrap().extend(samples);
+    }
+
+    // Весь захват уже отдан клиенту
+    pub fn input_finished(&self) -> bool {
+        self.input.lock().unwrap().is_empty()
+    }
+
+    // Все, что клиент воспроизвел с запуска
+    pub fn played(&self) -> Vec<f32> {
+        self.played.lock().unwrap().clone()
+    }
+}
+
+// Поток, который раз в 10 мс вызывает tick; останавливается вместе с AudioStream
+struct MockStream {
+    stop: Arc<AtomicBool>,
+}
+
+impl MockStream {
+    fn spawn(mut tick: impl FnMut() + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        thread::spawn(move || {
+            let mut next = Instant::now();
+            while !stop_thread.load(Ordering::SeqCst) {
+                tick();
+                next += MOCK_CHUNK_INTERVAL;
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+        });
+        MockStream { stop }
+    }
+}
+
+impl Drop for MockStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl AudioBackend for MockAudio {
+    fn open_input(
+        &self,
+        _device: Option<&str>,
+        mut on_data: CaptureCallback,
+        _on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError> {
+        let input = self.input.clone();
+        let stream = MockStream::spawn(move || {
+            let mut chunk = [0.0f32; MOCK_CHUNK];
+            {
+                let mut input = input.lock().unwrap();
+                let available = input.len().min(MOCK_CHUNK);
+                for (sample, value) in chunk.iter_mut().zip(input.drain(..available)) {
+                    *sample = value;
+                }
+            }
+            on_data(&chunk);
+        });
+        Ok((AudioStream::new(stream), "mock input".to_string()))
+    }
+
+    fn open_output(
+        &self,
+        _device: Option<&str>,
+        mut on_data: PlaybackCallback,
+        _on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError> {
+        let played = self.played.clone();
+        let stream = MockStream::spawn(move || {
+            let mut chunk = [0.0f32; MOCK_CHUNK];
+            on_data(&mut chunk);
+            played.lock().unwrap().extend_from_slice(&chunk);
+        });
+        Ok((AudioStream::new(stream), "mock output".to_string()))
+    }
+}
+
+fn invalid_wav(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid WAV: {}", reason))
+}
+
+// Сэмплы моно из файла WAV
+fn read_wav(data: &[u8]) -> io::Result<Vec<f32>> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(invalid_wav("not a RIFF/WAVE file"));
+    }
+
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = u32::from_le_bytes([data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]]) as usize;
+        let body = data.get(offset + 8..offset + 8 + size).ok_or_else(|| invalid_wav("truncated chunk"))?;
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err(invalid_wav("short fmt chunk"));
+                }
+                let tag = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+                let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if rate != SAMPLE_RATE {
+                    return Err(invalid_wav(&format!("sample rate {} Hz, expected {}", rate, SAMPLE_RATE)));
+                }
+                if channels == 0 {
+                    return Err(invalid_wav("no channels"));
+                }
+                format = Some((tag, channels, bits));
+            },
+            b"data" => {
+                let (tag, channels, bits) = format.ok_or_else(|| invalid_wav("data before fmt"))?;
+                let samples: Vec<f32> = match (tag, bits) {
+                    (1, 16) => body.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0).collect(),
+                    (3, 32) => body.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect(),
+                    _ => return Err(invalid_wav(&format!("unsupported format {} with {} bits", tag, bits))),
+                };
+                return Ok(samples
+                    .chunks_exact(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                    .collect());
+            },
+            _ => {},
+        }
+        // Куски выровнены по четному размеру
+        offset += 8 + size + (size & 1);
+    }
+    Err(invalid_wav("no data chunk"))
+}
@@ -0,0 +1,221 @@
+// Звуковые устройства системы через cpal (feature "audio")
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, SampleRate, StreamConfig, SupportedInputConfigs, SupportedOutputConfigs, SupportedStreamConfigRange};
+use tracing::{info, warn};
+
+use super::{AudioBackend, AudioStream, CaptureCallback, DeviceLostCallback, PlaybackCallback};
+use crate::logging::AUDIO;
+use crate::{NsvcError, SAMPLE_RATE};
+
+pub struct CpalBackend;
+
+impl AudioBackend for CpalBackend {
+    fn open_input(
+        &self,
+        device: Option<&str>,
+        mut on_data: CaptureCallback,
+        mut on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError> {
+        let host = cpal::default_host();
+
+        let input_device = match device {
+            Some(name) => find_device(host.input_devices().ok(), name),
+            None => host.default_input_device(),
+        };
+        let input_device = match input_device {
+            Some(dev) => dev,
+            None => {
+                return Err(NsvcError::NoInputDevice(device.unwrap_or("default").to_string()));
+            }
+        };
+
+        let name = input_device.name().unwrap_or_default();
+        info!(target: AUDIO, "Using input device: {:?}", name);
+
+        // Поиск подходящих конфигураций для входного устройства
+        let input_config = match input_device.supported_input_configs() {
+            Ok(configs) => {
+                match find_suitable_input_config(configs, SAMPLE_RATE, 1) {
+                    Some(config) => {
+                        info!(target: AUDIO, "Selected input config: {:?}", config);
+                        config
+                    },
+                    None => {
+                        return Err(NsvcError::UnsupportedSampleFormat("input"));
+                    }
+                }
+            },
+            Err(e) => {
+                return Err(NsvcError::InputStream(format!("failed to get configs: {}", e)));
+            }
+        };
+
+        // Создание конфигурации потока на основе найденных параметров
+        let input_stream_config = StreamConfig {
+            channels: input_config.channels(),
+            sample_rate: SampleRate(SAMPLE_RATE),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let input_stream = match input_device.build_input_stream(
+            &input_stream_config,
+            move |data: &[f32], _: &_| on_data(data),
+            move |err| {
+                warn!(target: AUDIO, "Input stream error: {:?}", err);
+                if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                    on_lost(err.to_string());
+                }
+            },
+            None,
+        ) {
+            Ok(stream) => stream,
+            Err(e) => {
+                return Err(NsvcError::InputStream(format!("failed to build stream: {}", e)));
+            }
+        };
+
+        if let Err(e) = input_stream.play() {
+            return Err(NsvcError::InputStream(format!("failed to play stream: {}", e)));
+        }
+        Ok((AudioStream::new(input_stream), name))
+    }
+
+    fn open_output(
+        &self,
+        device: Option<&str>,
+        mut on_data: PlaybackCallback,
+        mut on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError> {
+        let host = cpal::default_host();
+
+        let output_device = match device {
+            Some(name) => find_device(host.output_devices().ok(), name),
+            None => host.default_output_device(),
+        };
+        let output_device = match output_device {
+            Some(dev) => dev,
+            None => {
+                return Err(NsvcError::NoOutputDevice(device.unwrap_or("default").to_string()));
+            }
+        };
+
+        let name = output_device.name().unwrap_or_default();
+        info!(target: AUDIO, "Using output device: {:?}", name);
+
+        // Поиск подходящих конфигураций для выходного устройства
+        let output_config = match output_device.supported_output_configs() {
+            Ok(configs) => {
+                match find_suitable_output_config(configs, SAMPLE_RATE, 1) {
+                    Some(config) => {
+                        info!(target: AUDIO, "Selected output config: {:?}", config);
+                        config
+                    },
+                    None => {
+                        return Err(NsvcError::UnsupportedSampleFormat("output"));
+                    }
+                }
+            },
+            Err(e) => {
+                return Err(NsvcError::OutputStream(format!("failed to get configs: {}", e)));
+            }
+        };
+
+        // Создание конфигурации потока на основе найденных параметров
+        let output_stream_config = StreamConfig {
+            channels: output_config.channels(),
+            sample_rate: SampleRate(SAMPLE_RATE),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let output_stream = match output_device.build_output_stream(
+            &output_stream_config,
+            move |data: &mut [f32], _: &_| on_data(data),
+            move |err| {
+                warn!(target: AUDIO, "Output stream error: {:?}", err);
+                if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                    on_lost(err.to_string());
+                }
+            },
+            None,
+        ) {
+            Ok(stream) => stream,
+            Err(e) => {
+                return Err(NsvcError::OutputStream(format!("failed to build stream: {}", e)));
+            }
+        };
+
+        if let Err(e) = output_stream.play() {
+            return Err(NsvcError::OutputStream(format!("failed to play stream: {}", e)));
+        }
+        Ok((AudioStream::new(output_stream), name))
+    }
+
+    fn input_devices(&self) -> Vec<String> {
+        device_names(cpal::default_host().input_devices().ok())
+    }
+
+    fn output_devices(&self) -> Vec<String> {
+        device_names(cpal::default_host().output_devices().ok())
+    }
+}
+
+fn device_names(devices: Option<impl Iterator<Item = cpal::Device>>) -> Vec<String> {
+    devices.into_iter().flatten().filter_map(|dev| dev.name().ok()).collect()
+}
+
+// Устройство с указанным именем среди доступных
+fn find_device(devices: Option<impl Iterator<Item = cpal::Device>>, name: &str) -> Option<cpal::Device> {
+    devices?.find(|dev| dev.name().is_ok_and(|n| n == name))
+}
+
+// Функция для поиска подходящей конфигурации аудио для входа
+fn find_suitable_input_config(
+    configs: SupportedInputConfigs,
+    target_sample_rate: u32,
+    target_channels: u16,
+) -> Option<SupportedStreamConfigRange> {
+    configs
+        .filter(|config| config.channels() == target_channels)
+        .filter(|config| {
+            let min_rate = config.min_sample_rate().0;
+            let max_rate = config.max_sample_rate().0;
+            target_sample_rate >= min_rate && target_sample_rate <= max_rate
+        })
+        .max_by(|a, b| {
+            // Предпочтение отдаем F32, затем I16, затем I32
+            let format_priority = |format: SampleFormat| match format {
+                SampleFormat::F32 => 3,
+                SampleFormat::I16 => 2,
+                SampleFormat::I32 => 1,
+                _ => 0,
+            };
+
+            format_priority(a.sample_format()).cmp(&format_priority(b.sample_format()))
+        })
+}
+
+// Функция для поиска подходящей конфигурации аудио для выхода
+fn find_suitable_output_config(
+    configs: SupportedOutputConfigs,
+    target_sample_rate: u32,
+    target_channels: u16,
+) -> Option<SupportedStreamConfigRange> {
+    configs
+        .filter(|config| config.channels() == target_channels)
+        .filter(|config| {
+            let min_rate = config.min_sample_rate().0;
+            let max_rate = config.max_sample_rate().0;
+            target_sample_rate >= min_rate && target_sample_rate <= max_rate
+        })
+        .max_by(|a, b| {
+            // Предпочтение отдаем F32, затем I16, затем I32
+            let format_priority = |format: SampleFormat| match format {
+                SampleFormat::F32 => 3,
+                SampleFormat::I16 => 2,
+                SampleFormat::I32 => 1,
+                _ => 0,
+            };
+
+            format_priority(a.sample_format()).cmp(&format_priority(b.sample_format()))
+        })
+}
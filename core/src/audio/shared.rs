@@ -0,0 +1,173 @@
+// Одни устройства на несколько клиентов (см. voice_client_share_audio):
+// например, слушать два канала сразу и говорить в один. Захват с микрофона
+// раздается всем, кто его открыл, а воспроизведение всех смешивается в один
+// поток вывода. Настоящее устройство открывается с первым клиентом (его
+// выбором устройства) и закрывается с последним. Callback'и устройства
+// замок не ждут: пока его держит открытие или закрытие потока клиента
+// (открытие устройства бывает долгим), кусок захвата пропускается, а вывод
+// играет тишину.
+use std::sync::{Arc, Mutex};
+
+use super::{AudioBackend, AudioStream, CaptureCallback, DeviceLostCallback, PlaybackCallback};
+use crate::NsvcError;
+
+pub struct SharedAudio {
+    inner: Arc<dyn AudioBackend>,
+    input: Arc<Mutex<Hub<CaptureCallback>>>,
+    output: Arc<Mutex<Hub<PlaybackCallback>>>,
+}
+
+// Клиенты одного устройства и его поток
+struct Hub<C> {
+    next_id: u64,
+    clients: Vec<(u64, C, Arc<Mutex<DeviceLostCallback>>)>,
+    // (поток устройства, его имя); None - никто не открыл
+    device: Option<(AudioStream, String)>,
+    // Устройство пропало: следующий клиент откроет его заново
+    lost: bool,
+    // Звук одного клиента перед смешиванием; растет до размера буфера устройства
+    scratch: Vec<f32>,
+}
+
+impl<C> Default for Hub<C> {
+    fn default() -> Self {
+        Hub { next_id: 0, clients: Vec::new(), device: None, lost: false, scratch: Vec::new() }
+    }
+}
+
+// Поток клиента; drop убирает его из Hub, последний закрывает устройство
+struct Subscription<C: Send + 'static> {
+    hub: Arc<Mutex<Hub<C>>>,
+    id: u64,
+}
+
+impl<C: Send + 'static> Drop for Subscription<C> {
+    fn drop(&mut self) {
+        let device = {
+            let mut hub = self.hub.lock().unwrap();
+            hub.clients.retain(|(id, _, _)| *id != self.id);
+            if hub.clients.is_empty() {
+                hub.device.take()
+            } else {
+                None
+            }
+        };
+        // Вне замка: остановка устройства может ждать конца callback'а
+        drop(device);
+    }
+}
+
+impl SharedAudio {
+    pub fn new(inner: Arc<dyn AudioBackend>) -> Self {
+        SharedAudio { inner, input: Arc::default(), output: Arc::default() }
+    }
+}
+
+// Добавляет клиента; open открывает устройство, если его еще нет. Клиенты
+// пропавшего устройства переходят на открытое заново.
+fn subscribe<C: Send + 'static>(
+    hub: &Arc<Mutex<Hub<C>>>,
+    on_data: C,
+    on_lost: DeviceLostCallback,
+    open: impl FnOnce(DeviceLostCallback) -> Result<(AudioStream, String), NsvcError>,
+) -> Result<(AudioStream, String), NsvcError> {
+    let stale = {
+        let mut hub = hub.lock().unwrap();
+        if hub.lost {
+            hub.lost = false;
+            hub.device.take()
+        } else {
+            None
+        }
+    };
+    drop(stale);
+
+    let mut locked = hub.lock().unwrap();
+    if locked.device.is_none() {
+        locked.device = Some(open(lost_callback(hub))?);
+    }
+    let id = locked.next_id;
+    locked.next_id += 1;
+    locked.clients.push((id, on_data, Arc::new(Mutex::new(on_lost))));
+    let name = locked.device.as_ref().map(|(_, name)| name.clone()).unwrap_or_default();
+    Ok((AudioStream::new(Subscription { hub: hub.clone(), id }), name))
+}
+
+// Пропажа устройства - всем его клиентам; их обработчики могут закрыть свои
+// потоки, поэтому вызываются вне замка
+fn lost_callback<C: Send + 'static>(hub: &Arc<Mutex<Hub<C>>>) -> DeviceLostCallback {
+    let hub = hub.clone();
+    Box::new(move |reason: String| {
+        let handlers: Vec<_> = {
+            let mut hub = hub.lock().unwrap();
+            hub.lost = true;
+            hub.clients.iter().map(|(_, _, on_lost)| on_lost.clone()).collect()
+        };
+        for on_lost in handlers {
+            (on_lost.lock().unwrap())(reason.clone());
+        }
+    })
+}
+
+impl AudioBackend for SharedAudio {
+    fn open_input(
+        &self,
+        device: Option<&str>,
+        on_data: CaptureCallback,
+        on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError> {
+        let hub = self.input.clone();
+        subscribe(&self.input, on_data, on_lost, |on_device_lost| {
+            let on_device_data = Box::new(move |data: &[f32]| {
+                if let Ok(mut hub) = hub.try_lock() {
+                    for (_, on_data, _) in hub.clients.iter_mut() {
+                        on_data(data);
+                    }
+                }
+            });
+            self.inner.open_input(device, on_device_data, on_device_lost)
+        })
+    }
+
+    fn open_output(
+        &self,
+        device: Option<&str>,
+        on_data: PlaybackCallback,
+        on_lost: DeviceLostCallback,
+    ) -> Result<(AudioStream, String), NsvcError> {
+        let hub = self.output.clone();
+        subscribe(&self.output, on_data, on_lost, |on_device_lost| {
+            let on_device_data = Box::new(move |data: &mut [f32]| {
+                data.fill(0.0);
+                let Ok(mut hub) = hub.try_lock() else {
+                    return;
+                };
+                let Hub { clients, scratch, .. } = &mut *hub;
+                if scratch.len() < data.len() {
+                    scratch.resize(data.len(), 0.0);
+                }
+                let scratch = &mut scratch[..data.len()];
+                for (_, on_data, _) in clients.iter_mut() {
+                    // Клиент может не писать ничего, например остановленный
+                    scratch.fill(0.0);
+                    on_data(scratch);
+                    for (out, sample) in data.iter_mut().zip(scratch.iter()) {
+                        *out += sample;
+                    }
+                }
+                for sample in data.iter_mut() {
+                    *sample = sample.clamp(-1.0, 1.0);
+                }
+            });
+            self.inner.open_output(device, on_device_data, on_device_lost)
+        })
+    }
+
+    fn input_devices(&self) -> Vec<String> {
+        self.inner.input_devices()
+    }
+
+    fn output_devices(&self) -> Vec<String> {
+        self.inner.output_devices()
+    }
+}
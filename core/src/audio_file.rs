@@ -0,0 +1,166 @@
+// Звуковые файлы для передачи в микрофон (см. soundboard.rs): WAV (PCM 8,
+// 16, 24, 32 бит или float) и Ogg/Opus. Каналы сводятся в моно, частота
+// приводится к SAMPLE_RATE. Ogg Vorbis без своего декодера не читается.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use opus::{Channels, Decoder};
+
+use crate::{ogg, MAX_DECODED_FRAME, SAMPLE_RATE};
+
+// Дольше - почти наверняка не тот файл, а память на него уйдет вся
+const MAX_SECONDS: usize = 600;
+
+const WAVE_PCM: u16 = 1;
+const WAVE_FLOAT: u16 = 3;
+const WAVE_EXTENSIBLE: u16 = 0xFFFE;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Отсчеты моно при SAMPLE_RATE; формат - по началу файла, не по расширению
+pub(crate) fn load(path: &Path) -> io::Result<Vec<f32>> {
+    let data = fs::read(path)?;
+    let (samples, rate) = if data.starts_with(b"RIFF") {
+        read_wav(&data)?
+    } else if data.starts_with(b"OggS") {
+        (read_ogg_opus(&data)?, SAMPLE_RATE)
+    } else {
+        return Err(invalid("not a WAV or Ogg file"));
+    };
+    if samples.len() / rate as usize > MAX_SECONDS {
+        return Err(invalid(format!("file is longer than {} seconds", MAX_SECONDS)));
+    }
+    Ok(resample(samples, rate))
+}
+
+fn read_wav(data: &[u8]) -> io::Result<(Vec<f32>, u32)> {
+    if data.get(8..12) != Some(b"WAVE") {
+        return Err(invalid("not a WAVE file"));
+    }
+    let mut format = None;
+    let mut pos = 12;
+    while let Some(header) = data.get(pos..pos + 8) {
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        // Оборванный блок data - файл дописывался; берем, что есть
+        let body = &data[pos + 8..data.len().min(pos + 8 + size)];
+        match &header[..4] {
+            b"fmt " => format = Some(WavFormat::parse(body)?),
+            b"data" => {
+                let format = format.ok_or_else(|| invalid("WAV data before format"))?;
+                return Ok((format.decode(body), format.rate));
+            },
+            _ => {},
+        }
+        // Блоки выровнены по двум байтам
+        pos += 8 + size + size % 2;
+    }
+    Err(invalid("WAV file has no data"))
+}
+
+#[derive(Clone, Copy)]
+struct WavFormat {
+    float: bool,
+    channels: usize,
+    rate: u32,
+    bits: u16,
+}
+
+impl WavFormat {
+    fn parse(fmt: &[u8]) -> io::Result<Self> {
+        let field = |at: usize| fmt.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        let (Some(mut tag), Some(channels), Some(bits)) = (field(0), field(2), field(14)) else {
+            return Err(invalid("broken WAV format"));
+        };
+        let rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+        // У WAVE_FORMAT_EXTENSIBLE настоящий формат - в начале GUID подформата
+        if tag == WAVE_EXTENSIBLE {
+            tag = field(24).ok_or_else(|| invalid("broken WAV format"))?;
+        }
+        let format = WavFormat { float: tag == WAVE_FLOAT, channels: channels as usize, rate, bits };
+        let supported = match tag {
+            WAVE_PCM => matches!(bits, 8 | 16 | 24 | 32),
+            WAVE_FLOAT => bits == 32,
+            _ => false,
+        };
+        if !supported || channels == 0 || rate == 0 {
+            return Err(invalid(format!("unsupported WAV format {} ({} bit)", tag, bits)));
+        }
+        Ok(format)
+    }
+
+    fn sample(&self, bytes: &[u8]) -> f32 {
+        match (self.float, self.bits) {
+            (true, _) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            (false, 8) => (bytes[0] as f32 - 128.0) / 128.0,
+            (false, 16) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            (false, 24) => i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2_147_483_648.0,
+            _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2_147_483_648.0,
+        }
+    }
+
+    // Кадры со всеми каналами, сведенные в моно
+    fn decode(&self, data: &[u8]) -> Vec<f32> {
+        let width = self.bits as usize / 8;
+        data.chunks_exact(width * self.channels)
+            .map(|frame| frame.chunks_exact(width).map(|bytes| self.sample(bytes)).sum::<f32>() / self.channels as f32)
+            .collect()
+    }
+}
+
+fn read_ogg_opus(data: &[u8]) -> io::Result<Vec<f32>> {
+    let packets = ogg::read_packets(data);
+    let head = packets.first().filter(|head| head.starts_with(b"OpusHead") && head.len() >= 19);
+    let Some(head) = head else {
+        return Err(invalid("Ogg file is not Opus (Vorbis and other codecs are not supported)"));
+    };
+    let channels = match (head[9], head[18]) {
+        (1, 0) => Channels::Mono,
+        (2, 0) => Channels::Stereo,
+        _ => return Err(invalid(format!("unsupported Opus channel layout ({} channels)", head[9]))),
+    };
+    let count = head[9] as usize;
+    // Отсчеты в начале, которые кодер выдал до звука
+    let mut pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+    let mut decoder =
+        Decoder::new(SAMPLE_RATE, channels).map_err(|e| io::Error::other(format!("decoder: {:?}", e)))?;
+
+    let mut pcm = vec![0f32; MAX_DECODED_FRAME * count];
+    let mut samples = Vec::new();
+    // Второй пакет - OpusTags
+    for packet in packets.iter().skip(2) {
+        let decoded = match decoder.decode_float(packet, &mut pcm, false) {
+            Ok(decoded) => decoded,
+            Err(e) => return Err(invalid(format!("Opus decoding error: {:?}", e))),
+        };
+        let frames = pcm[..decoded * count].chunks_exact(count).map(|frame| frame.iter().sum::<f32>() / count as f32);
+        let skip = pre_skip.min(decoded);
+        pre_skip -= skip;
+        samples.extend(frames.skip(skip));
+        if samples.len() / SAMPLE_RATE as usize > MAX_SECONDS {
+            break;
+        }
+    }
+    Ok(samples)
+}
+
+// Линейная интерполяция: для голоса и коротких звуков ее хватает
+fn resample(samples: Vec<f32>, rate: u32) -> Vec<f32> {
+    if rate == SAMPLE_RATE || samples.is_empty() {
+        return samples;
+    }
+    let len = (samples.len() as u64 * SAMPLE_RATE as u64 / rate as u64) as usize;
+    let step = rate as f64 / SAMPLE_RATE as f64;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = samples[index];
+            let b = samples.get(index + 1).copied().unwrap_or(a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
@@ -0,0 +1,40 @@
+// Указатели, которые хост передает в C API. Точки входа остаются обычными
+// функциями, как voice_client_new: что указатель либо NULL, либо
+// действителен на время вызова - часть контракта с хостом, и проверить это
+// Rust не может. Поэтому все разыменования аргументов собраны здесь, а NULL
+// проверяется всегда.
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+// Строка хоста; None для NULL
+pub(crate) fn string<'a>(ptr: *const c_char) -> Option<&'a CStr> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(ptr) })
+}
+
+// Массив хоста из len элементов; при len = 0 указатель не читается
+pub(crate) fn slice<'a, T>(ptr: *const T, len: usize) -> Option<&'a [T]> {
+    if len == 0 {
+        return Some(&[]);
+    }
+    if ptr.is_null() {
+        return None;
+    }
+    Some(unsafe { std::slice::from_raw_parts(ptr, len) })
+}
+
+// Структура хоста только для чтения
+pub(crate) fn get<'a, T>(ptr: *const T) -> Option<&'a T> {
+    unsafe { ptr.as_ref() }
+}
+
+// Записывает value в переменную хоста; false для NULL
+pub(crate) fn set<T>(ptr: *mut T, value: T) -> bool {
+    if ptr.is_null() {
+        return false;
+    }
+    unsafe { ptr.write(value) };
+    true
+}
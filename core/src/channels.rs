@@ -0,0 +1,180 @@
+// Каналы на сервере: клиент слышит только тех, кто в одном с ним канале.
+// Без JOIN клиент попадает в канал по умолчанию. LEAVE возвращает туда же.
+// Канал может быть закрыт паролем: он едет в JOIN после имени через '\0'
+// (в имени управляющих символов нет). Неверный пароль - ответ DENIED.
+use std::sync::Mutex;
+
+use crate::{control_packet, control_type, control_types, CONTROL_HEADER_SIZE};
+
+pub const DEFAULT_CHANNEL: &str = "lobby";
+pub const MAX_NAME_LEN: usize = 64;
+pub const MAX_PASSWORD_LEN: usize = 128;
+
+// Имя канала: непустое, без управляющих символов, не длиннее MAX_NAME_LEN байт
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_NAME_LEN && !name.chars().any(char::is_control)
+}
+
+pub fn is_valid_password(password: &str) -> bool {
+    password.len() <= MAX_PASSWORD_LEN && !password.contains('\0')
+}
+
+pub fn join_packet(name: &str, password: &str) -> Vec<u8> {
+    let mut body = name.as_bytes().to_vec();
+    if !password.is_empty() {
+        body.push(0);
+        body.extend_from_slice(password.as_bytes());
+    }
+    control_packet(control_types::CHANNEL_JOIN, &body)
+}
+
+pub fn leave_packet() -> Vec<u8> {
+    control_packet(control_types::CHANNEL_LEAVE, &[])
+}
+
+pub fn joined_packet(name: &str) -> Vec<u8> {
+    control_packet(control_types::CHANNEL_JOINED, name.as_bytes())
+}
+
+// DENIED: имя канала, '\0', причина отказа
+pub fn denied_packet(name: &str, reason: &str) -> Vec<u8> {
+    let mut body = name.as_bytes().to_vec();
+    body.push(0);
+    body.extend_from_slice(reason.as_bytes());
+    control_packet(control_types::CHANNEL_DENIED, &body)
+}
+
+pub fn list_request_packet() -> Vec<u8> {
+    control_packet(control_types::CHANNEL_LIST_REQUEST, &[])
+}
+
+// Список: число каналов (u16), затем для каждого длина имени (u8), имя и
+// число участников (u16). Хвост, не влезающий в датаграмму, отбрасывается.
+pub fn list_packet(channels: &[(String, u16)], max_size: usize) -> Vec<u8> {
+    let mut entries = Vec::new();
+    let mut count: u16 = 0;
+    for (name, users) in channels {
+        if CONTROL_HEADER_SIZE + 2 + entries.len() + 3 + name.len() > max_size {
+            break;
+        }
+        entries.push(name.len() as u8);
+        entries.extend_from_slice(name.as_bytes());
+        entries.extend_from_slice(&users.to_be_bytes());
+        count += 1;
+    }
+
+    let mut body = Vec::with_capacity(2 + entries.len());
+    body.extend_from_slice(&count.to_be_bytes());
+    body.extend_from_slice(&entries);
+    control_packet(control_types::CHANNEL_LIST, &body)
+}
+
+// Имя канала из JOINED
+pub fn parse_name(packet: &[u8]) -> Option<String> {
+    let name = std::str::from_utf8(packet.get(CONTROL_HEADER_SIZE..)?).ok()?;
+    is_valid_name(name).then(|| name.to_string())
+}
+
+// Имя и пароль (пустой, если не задан) из JOIN или имя и причина из DENIED
+pub fn parse_join(packet: &[u8]) -> Option<(String, String)> {
+    let body = std::str::from_utf8(packet.get(CONTROL_HEADER_SIZE..)?).ok()?;
+    let (name, rest) = body.split_once('\0').unwrap_or((body, ""));
+    is_valid_name(name).then(|| (name.to_string(), rest.to_string()))
+}
+
+pub fn parse_list(packet: &[u8]) -> Option<Vec<(String, u16)>> {
+    let body = packet.get(CONTROL_HEADER_SIZE..)?;
+    let count = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let mut pos = 2;
+    let mut channels = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let len = *body.get(pos)? as usize;
+        let name = std::str::from_utf8(body.get(pos + 1..pos + 1 + len)?).ok()?;
+        let users = body.get(pos + 1 + len..pos + 3 + len)?;
+        channels.push((name.to_string(), u16::from_be_bytes([users[0], users[1]])));
+        pos += 3 + len;
+    }
+
+    Some(channels)
+}
+
+// Состояние каналов на стороне клиента
+#[derive(Default)]
+pub struct ChannelState {
+    // Куда клиент хочет попасть (имя и пароль); JOIN повторяется, пока
+    // сервер не подтвердит или не откажет
+    wanted: Mutex<Option<(String, String)>>,
+    // Канал, подтвержденный сервером
+    current: Mutex<Option<String>>,
+    list: Mutex<Vec<(String, u16)>>,
+}
+
+impl ChannelState {
+    pub fn set_wanted(&self, wanted: Option<(String, String)>) {
+        *self.wanted.lock().unwrap() = wanted;
+    }
+
+    pub fn current(&self) -> Option<String> {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub fn list(&self) -> Vec<(String, u16)> {
+        self.list.lock().unwrap().clone()
+    }
+
+    // После переподключения сервер нас не помнит
+    pub fn reset(&self) {
+        *self.current.lock().unwrap() = None;
+    }
+
+    // JOIN, который нужно (пере)отправить, если канал еще не подтвержден
+    pub fn pending_join(&self) -> Option<Vec<u8>> {
+        let (name, password) = self.wanted.lock().unwrap().clone()?;
+        if self.current().as_deref() == Some(name.as_str()) {
+            return None;
+        }
+        Some(join_packet(&name, &password))
+    }
+
+    // Обрабатывает DENIED: перестает проситься в канал и возвращает
+    // описание отказа. None, если пакет не DENIED.
+    pub fn on_denied(&self, packet: &[u8]) -> Option<String> {
+        if control_type(packet) != control_types::CHANNEL_DENIED {
+            return None;
+        }
+        let (name, reason) = parse_join(packet)?;
+
+        let mut wanted = self.wanted.lock().unwrap();
+        if wanted.as_ref().is_some_and(|(wanted_name, _)| *wanted_name == name) {
+            *wanted = None;
+        }
+        Some(format!("access to channel '{}' denied: {}", name, reason))
+    }
+
+    // Обрабатывает JOINED/CHANNEL_LIST. Возвращает true, если пакет наш.
+    pub fn on_packet(&self, packet: &[u8]) -> bool {
+        match control_type(packet) {
+            control_types::CHANNEL_JOINED => {
+                if let Some(name) = parse_name(packet) {
+                    // Сервер мог перенести нас сам (администратор): не проситься обратно
+                    let mut wanted = self.wanted.lock().unwrap();
+                    if wanted.as_ref().is_some_and(|(wanted_name, _)| *wanted_name != name) {
+                        *wanted = Some((name.clone(), String::new()));
+                    }
+                    drop(wanted);
+
+                    *self.current.lock().unwrap() = Some(name);
+                }
+                true
+            },
+            control_types::CHANNEL_LIST => {
+                if let Some(list) = parse_list(packet) {
+                    *self.list.lock().unwrap() = list;
+                }
+                true
+            },
+            _ => false,
+        }
+    }
+}
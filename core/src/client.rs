@@ -0,0 +1,704 @@
+// Rust API клиента без сырых указателей: настройки через ClientBuilder,
+// ошибки - Result с NsvcError. Под ним тот же клиент и те же конструкторы, что у FFI,
+// поэтому поведение у Rust-программ (nsvc-call) и у хостов на C одинаковое.
+//
+//   let client = ClientBuilder::server("example.org", 40000).nickname("Player").build()?;
+//   client.set_event_callback(|event| println!("{:?}", event))?;
+//   client.start()?;
+//   client.set_transmitting(true);
+use std::ffi::{CStr, CString};
+use std::net::Ipv4Addr;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::audio::AudioBackend;
+use crate::config::{Settings, VoiceClientConfig};
+use crate::*;
+
+pub type Result<T> = std::result::Result<T, NsvcError>;
+
+fn check(code: i32) -> Result<()> {
+    if code == error_codes::SUCCESS {
+        Ok(())
+    } else {
+        Err(NsvcError::from_code(code))
+    }
+}
+
+// Строки с NUL внутри в C не передать
+fn c_string(value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| NsvcError::InvalidParam("string contains a NUL byte".to_string()))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransmitMode {
+    PushToTalk,
+    VoiceActivation,
+    Continuous,
+    Toggle,
+}
+
+impl TransmitMode {
+    fn code(self) -> i32 {
+        match self {
+            TransmitMode::PushToTalk => transmit_modes::PTT,
+            TransmitMode::VoiceActivation => transmit_modes::VOICE_ACTIVATION,
+            TransmitMode::Continuous => transmit_modes::CONTINUOUS,
+            TransmitMode::Toggle => transmit_modes::TOGGLE,
+        }
+    }
+}
+
+// Проверочный сигнал, см. test_tones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestTone {
+    Steady,
+    Sweep,
+}
+
+impl TestTone {
+    fn code(self) -> i32 {
+        match self {
+            TestTone::Steady => test_tones::STEADY,
+            TestTone::Sweep => test_tones::SWEEP,
+        }
+    }
+}
+
+// Затухание с расстоянием, см. rolloff_models
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RolloffModel {
+    Linear,
+    Inverse,
+}
+
+impl RolloffModel {
+    fn code(self) -> i32 {
+        match self {
+            RolloffModel::Linear => rolloff_models::LINEAR,
+            RolloffModel::Inverse => rolloff_models::INVERSE,
+        }
+    }
+}
+
+// Уведомление в динамиках, см. notification_sounds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notification {
+    UserJoined,
+    UserLeft,
+    Connected,
+    Reconnecting,
+    Disconnected,
+}
+
+impl Notification {
+    fn code(self) -> i32 {
+        match self {
+            Notification::UserJoined => notification_sounds::USER_JOINED,
+            Notification::UserLeft => notification_sounds::USER_LEFT,
+            Notification::Connected => notification_sounds::CONNECTED,
+            Notification::Reconnecting => notification_sounds::RECONNECTING,
+            Notification::Disconnected => notification_sounds::DISCONNECTED,
+        }
+    }
+}
+
+// Событие клиента; поля как у callback событий, см. events::event_types
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub kind: i32,
+    pub user_id: u32,
+    pub code: i32,
+    pub text: String,
+}
+
+// Участник текущего канала, см. voice_client_get_users
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: u32,
+    pub nickname: String,
+    pub speaking: bool,
+}
+
+type EventHandler = Box<dyn Fn(Event) + Send + Sync>;
+
+extern "C" fn on_event(event: i32, user_id: u32, code: i32, text: *const c_char, userdata: *mut c_void) {
+    let handler = unsafe { &*(userdata as *const EventHandler) };
+    let text = unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned();
+    handler(Event { kind: event, user_id, code, text });
+}
+
+// Куда подключается клиент
+enum Target {
+    Server { host: String, port: u16 },
+    Lan { port: u16, name: String },
+    Direct { peer: Option<(String, u16)>, local_port: u16 },
+    Multicast { group: Ipv4Addr, port: u16 },
+}
+
+pub struct ClientBuilder {
+    target: Target,
+    config: VoiceClientConfig,
+    input_device: Option<String>,
+    output_device: Option<String>,
+    nickname: Option<String>,
+    muted_users: Vec<String>,
+    credentials: Option<(Option<String>, Option<String>)>,
+    audio: Option<Arc<dyn AudioBackend>>,
+}
+
+impl ClientBuilder {
+    fn new(target: Target) -> Self {
+        ClientBuilder {
+            target,
+            config: VoiceClientConfig::default(),
+            input_device: None,
+            output_device: None,
+            nickname: None,
+            muted_users: Vec::new(),
+            credentials: None,
+            audio: None,
+        }
+    }
+
+    // Клиент сервера nsvc-server
+    pub fn server(host: &str, port: u16) -> Self {
+        Self::new(Target::Server { host: host.to_string(), port })
+    }
+
+    // LAN без сервера, соседи через mDNS; port 0 - любой свободный
+    pub fn lan(port: u16, name: &str) -> Self {
+        Self::new(Target::Lan { port, name: name.to_string() })
+    }
+
+    // Прямой звонок собеседнику
+    pub fn call(host: &str, port: u16, local_port: u16) -> Self {
+        Self::new(Target::Direct { peer: Some((host.to_string(), port)), local_port })
+    }
+
+    // Ожидание прямого звонка на local_port
+    pub fn listen(local_port: u16) -> Self {
+        Self::new(Target::Direct { peer: None, local_port })
+    }
+
+    pub fn multicast(group: Ipv4Addr, port: u16) -> Self {
+        Self::new(Target::Multicast { group, port })
+    }
+
+    // Ограничения значений - как у VoiceClientConfig; проверяются в build()
+    pub fn bitrate(mut self, bitrate: u32) -> Self {
+        self.config.bitrate = bitrate;
+        self
+    }
+
+    pub fn frame_size(mut self, frame_size: u32) -> Self {
+        self.config.frame_size = frame_size;
+        self
+    }
+
+    pub fn vad(mut self, enabled: bool, threshold: f32) -> Self {
+        self.config.vad_enabled = enabled;
+        self.config.vad_threshold = threshold;
+        self
+    }
+
+    // Звуковые сигналы при смене mute и deafen
+    pub fn cues(mut self, enabled: bool) -> Self {
+        self.config.cues_enabled = enabled;
+        self
+    }
+
+    // Сигналы начала и конца передачи
+    pub fn transmit_cues(mut self, enabled: bool) -> Self {
+        self.config.transmit_cues_enabled = enabled;
+        self
+    }
+
+    // Уведомления о входе и выходе участников и о соединении с сервером
+    pub fn notification_cues(mut self, enabled: bool) -> Self {
+        self.config.notification_cues_enabled = enabled;
+        self
+    }
+
+    // Громкость сигналов, 0.0..=1.0
+    pub fn cue_volume(mut self, volume: f32) -> Self {
+        self.config.cue_volume = volume;
+        self
+    }
+
+    // Сколько передавать после конца голоса, мс
+    pub fn vad_hangover(mut self, hangover_ms: u32) -> Self {
+        self.config.vad_hangover_ms = hangover_ms;
+        self
+    }
+
+    // Сколько передавать после отпускания клавиши разговора, мс
+    pub fn ptt_release(mut self, release_ms: u32) -> Self {
+        self.config.ptt_release_ms = release_ms;
+        self
+    }
+
+    // Буфер повтора для save_clip, секунды; 0 - выключен
+    pub fn clip_length(mut self, seconds: u32) -> Self {
+        self.config.clip_seconds = seconds;
+        self
+    }
+
+    // Выключать микрофон после стольких минут без речи; 0 - не выключать
+    pub fn afk_timeout(mut self, minutes: u32) -> Self {
+        self.config.afk_minutes = minutes;
+        self
+    }
+
+    pub fn buffer_ms(mut self, buffer_ms: u32) -> Self {
+        self.config.buffer_ms = buffer_ms;
+        self
+    }
+
+    // Возможности из handshake::features
+    pub fn features(mut self, features: u32) -> Self {
+        self.config.features = features;
+        self
+    }
+
+    pub fn transmit_mode(mut self, mode: TransmitMode) -> Self {
+        self.config.transmit_mode = mode.code();
+        self
+    }
+
+    pub fn input_device(mut self, name: &str) -> Self {
+        self.input_device = Some(name.to_string());
+        self
+    }
+
+    pub fn output_device(mut self, name: &str) -> Self {
+        self.output_device = Some(name.to_string());
+        self
+    }
+
+    pub fn nickname(mut self, nickname: &str) -> Self {
+        self.nickname = Some(nickname.to_string());
+        self
+    }
+
+    // Ники, заглушенные в прошлых сессиях, см. Client::muted_users
+    pub fn muted_users(mut self, nicknames: &[String]) -> Self {
+        self.muted_users = nicknames.to_vec();
+        self
+    }
+
+    pub fn credentials(mut self, key: Option<&str>, password: Option<&str>) -> Self {
+        self.credentials = Some((key.map(str::to_string), password.map(str::to_string)));
+        self
+    }
+
+    // Звук не с устройств системы, например audio::MockAudio в тестах
+    pub fn audio_backend(mut self, backend: Arc<dyn AudioBackend>) -> Self {
+        self.audio = Some(backend);
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let muted_users = c_string(&self.muted_users.join("\n"))?;
+        let config = VoiceClientConfig { muted_users: muted_users.as_ptr(), ..self.config };
+        let mut settings =
+            Settings::from_config(&config).map_err(|e| NsvcError::InvalidParam(format!("client config: {}", e)))?;
+        settings.input_device = self.input_device.filter(|name| !name.is_empty());
+        settings.output_device = self.output_device.filter(|name| !name.is_empty());
+        if let Some(audio) = self.audio {
+            settings.audio = audio;
+        }
+
+        let handle = match &self.target {
+            Target::Server { host, port } => new_server_client(host, *port, settings),
+            Target::Lan { port, name } => new_lan_client(*port, name, settings),
+            Target::Direct { peer, local_port } => {
+                new_direct_client(peer.as_ref().map(|(host, port)| (host.as_str(), *port)), *local_port, settings)
+            },
+            Target::Multicast { group, port } => new_multicast_client(*group, *port, settings),
+        }?;
+        // Дальше при ошибке клиент освободит Drop
+        let client = Client { handle: handle as usize, handlers: Mutex::new(Vec::new()) };
+
+        if let Some(nickname) = &self.nickname {
+            let nickname = c_string(nickname)?;
+            check(voice_client_set_nickname(client.handle(), nickname.as_ptr()))?;
+        }
+        if let Some((key, password)) = &self.credentials {
+            let key = key.as_deref().map(c_string).transpose()?;
+            let password = password.as_deref().map(c_string).transpose()?;
+            check(voice_client_set_credentials(
+                client.handle(),
+                key.as_ref().map_or(std::ptr::null(), |k| k.as_ptr()),
+                password.as_ref().map_or(std::ptr::null(), |p| p.as_ptr()),
+            ))?;
+        }
+        Ok(client)
+    }
+}
+
+pub struct Client {
+    // Описатель из CLIENTS; как число, чтобы Client был Send + Sync
+    handle: usize,
+    // Прежние обработчики живут до Drop: поток клиента мог уже взять старый
+    // callback, когда его заменили
+    handlers: Mutex<Vec<Arc<EventHandler>>>,
+}
+
+impl Client {
+    fn handle(&self) -> *mut c_void {
+        self.handle as *mut c_void
+    }
+
+    pub fn start(&self) -> Result<()> {
+        let client = CLIENTS.get(self.handle()).ok_or(NsvcError::InvalidHandle)?;
+        start_client(&client)
+    }
+
+    // Самопроверка без сети: свой голос через кодер сразу в динамики
+    pub fn start_loopback(&self) -> Result<()> {
+        let client = CLIENTS.get(self.handle()).ok_or(NsvcError::InvalidHandle)?;
+        start_loopback(&client)
+    }
+
+    // Итог - событием STARTED
+    pub fn start_async(&self) -> Result<()> {
+        check(voice_client_start_async(self.handle()))
+    }
+
+    pub fn stop(&self) {
+        voice_client_stop(self.handle())
+    }
+
+    // Вызывается из сетевых и аудиопотоков клиента
+    pub fn set_event_callback(&self, handler: impl Fn(Event) + Send + Sync + 'static) -> Result<()> {
+        let handler: Arc<EventHandler> = Arc::new(Box::new(handler));
+        let userdata = Arc::as_ptr(&handler) as *mut c_void;
+        let mut handlers = self.handlers.lock().unwrap();
+        check(voice_client_set_event_callback(self.handle(), Some(on_event), userdata))?;
+        handlers.push(handler);
+        Ok(())
+    }
+
+    pub fn set_transmitting(&self, transmitting: bool) {
+        voice_client_set_transmitting(self.handle(), transmitting)
+    }
+
+    // Клавиша разговора нажата или отпущена; действие зависит от режима
+    pub fn talk_key(&self, pressed: bool) {
+        voice_client_talk_key(self.handle(), pressed);
+    }
+
+    // Кому шепот, не больше MAX_WHISPER_TARGETS; пустой список - никому
+    pub fn set_whisper_targets(&self, user_ids: &[u32]) -> Result<()> {
+        check(voice_client_set_whisper_targets(self.handle(), user_ids.as_ptr(), user_ids.len()))
+    }
+
+    // Клавиша шепота: пока нажата, голос слышат только адресаты шепота
+    pub fn whisper_key(&self, pressed: bool) {
+        voice_client_whisper_key(self.handle(), pressed);
+    }
+
+    pub fn is_transmitting(&self) -> bool {
+        voice_client_is_transmitting(self.handle())
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        voice_client_set_muted(self.handle(), muted);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        voice_client_is_muted(self.handle())
+    }
+
+    // Заглушенный клиент и не слышит, и не передает
+    pub fn set_deafened(&self, deafened: bool) {
+        voice_client_set_deafened(self.handle(), deafened);
+    }
+
+    pub fn is_deafened(&self) -> bool {
+        voice_client_is_deafened(self.handle())
+    }
+
+    // Запись разговора в WAV, см. voice_client_start_recording
+    pub fn start_recording(&self, path: &Path, include_mic: bool) -> Result<()> {
+        let path = path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?;
+        let path = c_string(path)?;
+        check(voice_client_start_recording(self.handle(), path.as_ptr(), include_mic))
+    }
+
+    // По файлу на собеседника, см. voice_client_start_recording_tracks
+    pub fn start_recording_tracks(&self, path: &Path, include_mic: bool) -> Result<()> {
+        let path = path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?;
+        let path = c_string(path)?;
+        check(voice_client_start_recording_tracks(self.handle(), path.as_ptr(), include_mic))
+    }
+
+    pub fn stop_recording(&self) -> Result<()> {
+        check(voice_client_stop_recording(self.handle()))
+    }
+
+    pub fn is_recording(&self) -> bool {
+        voice_client_is_recording(self.handle())
+    }
+
+    pub fn set_clip_length(&self, seconds: u32) -> Result<()> {
+        check(voice_client_set_clip_length(self.handle(), seconds))
+    }
+
+    pub fn set_afk_timeout(&self, minutes: u32) -> Result<()> {
+        check(voice_client_set_afk_timeout(self.handle(), minutes))
+    }
+
+    // Последние секунды разговора в файл, см. voice_client_save_clip
+    pub fn save_clip(&self, path: &Path, include_mic: bool) -> Result<()> {
+        let path = path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?;
+        let path = c_string(path)?;
+        check(voice_client_save_clip(self.handle(), path.as_ptr(), include_mic))
+    }
+
+    // Звуковой файл собеседникам, см. voice_client_play_file_to_mic
+    pub fn play_file_to_mic(&self, path: &Path, mix_with_mic: bool) -> Result<()> {
+        let path = path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?;
+        let path = c_string(path)?;
+        check(voice_client_play_file_to_mic(self.handle(), path.as_ptr(), mix_with_mic))
+    }
+
+    // Записанное сообщение каналу, см. voice_client_send_clip
+    pub fn send_clip(&self, path: &Path) -> Result<()> {
+        let path = path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?;
+        let path = c_string(path)?;
+        check(voice_client_send_clip(self.handle(), path.as_ptr()))
+    }
+
+    pub fn stop_file_to_mic(&self) -> Result<()> {
+        check(voice_client_stop_file_to_mic(self.handle()))
+    }
+
+    pub fn is_playing_file(&self) -> bool {
+        voice_client_is_playing_file(self.handle())
+    }
+
+    // Громкость по расстоянию в игре, см. voice_client_set_positional_audio
+    pub fn set_positional_audio(&self, enabled: bool) -> Result<()> {
+        check(voice_client_set_positional_audio(self.handle(), enabled))
+    }
+
+    pub fn is_positional_audio_active(&self) -> bool {
+        voice_client_is_positional_audio_active(self.handle())
+    }
+
+    // Положения от самой игры, см. voice_client_set_listener_position
+    pub fn set_listener_position(&self, [x, y, z]: [f32; 3]) -> Result<()> {
+        check(voice_client_set_listener_position(self.handle(), x, y, z))
+    }
+
+    pub fn set_user_position(&self, user_id: u32, [x, y, z]: [f32; 3]) -> Result<()> {
+        check(voice_client_set_user_position(self.handle(), user_id, x, y, z))
+    }
+
+    pub fn clear_positions(&self) -> Result<()> {
+        check(voice_client_clear_positions(self.handle()))
+    }
+
+    pub fn set_rolloff(&self, model: RolloffModel, min_distance: f32, max_distance: f32) -> Result<()> {
+        check(voice_client_set_rolloff(self.handle(), model.code(), min_distance, max_distance))
+    }
+
+    // Свой звук уведомления из файла; None - снова тон, см.
+    // voice_client_set_notification_sound
+    pub fn set_notification_sound(&self, kind: Notification, path: Option<&Path>) -> Result<()> {
+        let path = match path {
+            Some(path) => path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?,
+            None => "",
+        };
+        let path = c_string(path)?;
+        check(voice_client_set_notification_sound(self.handle(), kind.code(), path.as_ptr()))
+    }
+
+    // Сокет для оверлеев; None - overlay::default_path(), см.
+    // voice_client_start_overlay_socket
+    pub fn start_overlay_socket(&self, path: Option<&Path>) -> Result<()> {
+        let path = match path {
+            Some(path) => {
+                let path = path.to_str().ok_or_else(|| NsvcError::InvalidParam("path is not valid UTF-8".to_string()))?;
+                Some(c_string(path)?)
+            },
+            None => None,
+        };
+        let path = path.as_ref().map_or(std::ptr::null(), |path| path.as_ptr());
+        check(voice_client_start_overlay_socket(self.handle(), path))
+    }
+
+    pub fn stop_overlay_socket(&self) -> Result<()> {
+        check(voice_client_stop_overlay_socket(self.handle()))
+    }
+
+    // Метрики для Prometheus на localhost, см. voice_client_start_metrics
+    pub fn start_metrics(&self, port: u16) -> Result<()> {
+        check(voice_client_start_metrics(self.handle(), port))
+    }
+
+    pub fn stop_metrics(&self) -> Result<()> {
+        check(voice_client_stop_metrics(self.handle()))
+    }
+
+    // Проверка микрофона записью, см. voice_client_mic_check
+    pub fn mic_check(&self, seconds: u32) -> Result<()> {
+        check(voice_client_mic_check(self.handle(), seconds))
+    }
+
+    pub fn stop_mic_check(&self) -> Result<()> {
+        check(voice_client_stop_mic_check(self.handle()))
+    }
+
+    // Проверка динамиков, см. voice_client_play_test_tone
+    pub fn play_test_tone(&self, tone: TestTone, seconds: u32) -> Result<()> {
+        check(voice_client_play_test_tone(self.handle(), tone.code(), seconds))
+    }
+
+    pub fn stop_test_tone(&self) -> Result<()> {
+        check(voice_client_stop_test_tone(self.handle()))
+    }
+
+    pub fn set_transmit_mode(&self, mode: TransmitMode) -> Result<()> {
+        check(voice_client_set_transmit_mode(self.handle(), mode.code()))
+    }
+
+    pub fn join_channel(&self, name: &str, password: Option<&str>) -> Result<()> {
+        let name = c_string(name)?;
+        let password = password.map(c_string).transpose()?;
+        let password = password.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+        check(voice_client_join_channel_with_password(self.handle(), name.as_ptr(), password))
+    }
+
+    pub fn leave_channel(&self) -> Result<()> {
+        check(voice_client_leave_channel(self.handle()))
+    }
+
+    // to_user 0 - всему каналу
+    pub fn send_text(&self, to_user: u32, text: &str) -> Result<()> {
+        let text = c_string(text)?;
+        check(voice_client_send_text(self.handle(), to_user, text.as_ptr()))
+    }
+
+    pub fn set_user_volume(&self, user_id: u32, volume: f32) -> Result<()> {
+        check(voice_client_set_user_volume(self.handle(), user_id, volume))
+    }
+
+    pub fn set_user_muted(&self, user_id: u32, muted: bool) -> Result<()> {
+        check(voice_client_set_user_muted(self.handle(), user_id, muted))
+    }
+
+    pub fn is_user_muted(&self, user_id: u32) -> bool {
+        voice_client_is_user_muted(self.handle(), user_id)
+    }
+
+    // Ники заглушенных у себя - сохранить для ClientBuilder::muted_users
+    pub fn muted_users(&self) -> Result<Vec<String>> {
+        let mut buf = vec![0 as c_char; 256];
+        loop {
+            match voice_client_get_muted_users(self.handle(), buf.as_mut_ptr(), buf.len()) {
+                error_codes::BUFFER_TOO_SMALL => buf.resize(buf.len() * 2, 0),
+                code => {
+                    check(code)?;
+                    break;
+                },
+            }
+        }
+        let list = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+        Ok(list.lines().map(str::to_string).collect())
+    }
+
+    // Заменяет ники заглушенных у себя, как ClientBuilder::muted_users
+    pub fn set_muted_users(&self, nicknames: &[String]) -> Result<()> {
+        let nicknames = c_string(&nicknames.join("\n"))?;
+        check(voice_client_set_muted_users(self.handle(), nicknames.as_ptr()))
+    }
+
+    // Громкость всего воспроизведения, 0.0..=MAX_OUTPUT_VOLUME
+    pub fn set_output_volume(&self, volume: f32) -> Result<()> {
+        check(voice_client_set_output_volume(self.handle(), volume))
+    }
+
+    // Голоса собеседников еще и на это устройство, для захвата в OBS;
+    // None выключает, см. voice_client_set_voice_output_device
+    pub fn set_voice_output_device(&self, name: Option<&str>) -> Result<()> {
+        let name = c_string(name.unwrap_or(""))?;
+        check(voice_client_set_voice_output_device(self.handle(), name.as_ptr()))
+    }
+
+    // Микрофон и динамики вместе с other, например для второго сервера;
+    // None - снова свои. Оба клиента остановлены, см. voice_client_share_audio
+    pub fn share_audio(&self, other: Option<&Client>) -> Result<()> {
+        let other = other.map_or(std::ptr::null_mut(), Client::handle);
+        check(voice_client_share_audio(self.handle(), other))
+    }
+
+    // None - сервер еще не выдал ID
+    pub fn user_id(&self) -> Option<u32> {
+        let mut user_id = 0;
+        check(voice_client_get_user_id(self.handle(), &mut user_id)).ok()?;
+        (user_id != 0).then_some(user_id)
+    }
+
+    // Состояние из connection_states
+    pub fn connection_state(&self) -> i32 {
+        voice_client_get_connection_state(self.handle())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        voice_client_is_connected(self.handle())
+    }
+
+    pub fn stats(&self) -> Result<stats::VoiceClientStats> {
+        let mut stats = stats::VoiceClientStats::default();
+        check(voice_client_get_stats(self.handle(), &mut stats))?;
+        Ok(stats)
+    }
+
+    // Пики громкости (0..1) с прошлого вызова; None - клиент не запущен
+    pub fn input_level(&self) -> Option<f32> {
+        let mut level = 0.0;
+        check(voice_client_get_preview_level(self.handle(), &mut level)).ok()?;
+        Some(level)
+    }
+
+    pub fn output_level(&self) -> Option<f32> {
+        let mut level = 0.0;
+        check(voice_client_get_output_level(self.handle(), &mut level)).ok()?;
+        Some(level)
+    }
+
+    pub fn users(&self) -> Result<Vec<User>> {
+        // Буфер растет, пока список не поместится
+        let mut buf = vec![0 as c_char; 1024];
+        loop {
+            match voice_client_get_users(self.handle(), buf.as_mut_ptr(), buf.len()) {
+                error_codes::BUFFER_TOO_SMALL => buf.resize(buf.len() * 2, 0),
+                code => {
+                    check(code)?;
+                    break;
+                },
+            }
+        }
+        let list = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+        Ok(list
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let id = fields.next()?.parse().ok()?;
+                let nickname = fields.next()?.to_string();
+                let speaking = fields.next()? == "1";
+                Some(User { id, nickname, speaking })
+            })
+            .collect())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        voice_client_free(self.handle());
+    }
+}
@@ -0,0 +1,143 @@
+// Буфер повтора: последние N секунд разговора в виде пакетов Opus - тех
+// же, что пришли и ушли по сети, поэтому буфер занимает немного и ничего не
+// перекодирует, пока клип не попросили. voice_client_save_clip сохраняет
+// его в файл: удачный или важный момент можно поймать уже после того, как
+// он случился. Формат - как у записи (см. recording.rs): .opus и .ogg -
+// дорожки без перекодирования (см. multitrack.rs), иначе общий микс в WAV.
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use opus::Decoder;
+use tracing::{info, warn};
+
+use crate::error::NsvcError;
+use crate::logging::{AUDIO, CODEC};
+use crate::multitrack::{self, Track, Tracks};
+use crate::recording::{self, WavWriter};
+use crate::{ogg, users, CHANNELS, MAX_DECODED_FRAME, SAMPLE_RATE};
+
+#[derive(Clone)]
+struct Packet {
+    at: Instant,
+    track: Track,
+    opus: Vec<u8>,
+}
+
+pub(crate) struct ClipBuffer {
+    // 0 - буфер выключен
+    length_ms: AtomicU64,
+    packets: Mutex<VecDeque<Packet>>,
+    // Ники для имен дорожек
+    roster: Arc<users::Roster>,
+}
+
+impl ClipBuffer {
+    pub fn new(length: Duration, roster: Arc<users::Roster>) -> Self {
+        ClipBuffer {
+            length_ms: AtomicU64::new(length.as_millis() as u64),
+            packets: Mutex::new(VecDeque::new()),
+            roster,
+        }
+    }
+
+    fn length(&self) -> Duration {
+        Duration::from_millis(self.length_ms.load(Ordering::Relaxed))
+    }
+
+    // Короче прежнего - лишнее выбрасывается со следующим пакетом
+    pub fn set_length(&self, length: Duration) {
+        self.length_ms.store(length.as_millis() as u64, Ordering::Relaxed);
+        if length.is_zero() {
+            self.packets.lock().unwrap().clear();
+        }
+    }
+
+    pub fn on_packet(&self, track: Track, opus: &[u8]) {
+        let length = self.length();
+        if length.is_zero() {
+            return;
+        }
+        let mut packets = self.packets.lock().unwrap();
+        forget_old(&mut packets, length);
+        packets.push_back(Packet { at: Instant::now(), track, opus: opus.to_vec() });
+    }
+
+    // Свой голос - только с include_mic
+    pub fn save(&self, path: &Path, include_mic: bool) -> Result<(), NsvcError> {
+        let length = self.length();
+        if length.is_zero() {
+            return Err(NsvcError::InvalidParam("replay buffer is off".to_string()));
+        }
+        let packets: Vec<Packet> = {
+            let mut packets = self.packets.lock().unwrap();
+            forget_old(&mut packets, length);
+            packets.iter().filter(|packet| include_mic || packet.track != Track::Mic).cloned().collect()
+        };
+        let Some(start) = packets.first().map(|packet| packet.at) else {
+            return Err(NsvcError::Recording(io::Error::other("replay buffer is empty")));
+        };
+
+        let position = |packet: &Packet| multitrack::samples_in(packet.at.duration_since(start));
+        if multitrack::is_ogg(path) {
+            recording::check_dir(path)?;
+            let mut tracks = Tracks::new(path, include_mic, self.roster.clone());
+            for packet in &packets {
+                tracks.write_at(packet.track, &packet.opus, position(packet));
+            }
+            tracks.finish().map_err(NsvcError::Recording)?;
+        } else {
+            let pcm = mix(&packets, position);
+            let mut wav = WavWriter::create(path).map_err(NsvcError::Recording)?;
+            wav.write(&pcm).and_then(|_| wav.finish()).map_err(NsvcError::Recording)?;
+        }
+        info!(target: AUDIO, "Saved clip to {}", path.display());
+        Ok(())
+    }
+}
+
+fn forget_old(packets: &mut VecDeque<Packet>, length: Duration) {
+    let now = Instant::now();
+    while packets.front().is_some_and(|packet| now.duration_since(packet.at) > length) {
+        packets.pop_front();
+    }
+}
+
+// Декодирует дорожки и сводит их в одну. Пакет встает туда, где он
+// пришел, но не раньше конца предыдущего пакета своей дорожки.
+fn mix(packets: &[Packet], position: impl Fn(&Packet) -> u64) -> Vec<i16> {
+    let mut decoders: HashMap<Track, (Decoder, usize)> = HashMap::new();
+    let mut pcm = vec![0i16; MAX_DECODED_FRAME];
+    let mut mixed: Vec<f32> = Vec::new();
+    for packet in packets {
+        let Some(samples) = ogg::packet_samples(&packet.opus) else {
+            continue;
+        };
+        let (decoder, cursor) = match decoders.entry(packet.track) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match Decoder::new(SAMPLE_RATE, CHANNELS) {
+                Ok(decoder) => entry.insert((decoder, 0)),
+                Err(e) => {
+                    warn!(target: CODEC, "Decoder creation error: {:?}", e);
+                    continue;
+                },
+            },
+        };
+        let Ok(decoded) = decoder.decode(&packet.opus, &mut pcm, false) else {
+            continue;
+        };
+        let offset = (*cursor).max(position(packet).saturating_sub(samples) as usize);
+        if mixed.len() < offset + decoded {
+            mixed.resize(offset + decoded, 0.0);
+        }
+        for (out, &sample) in mixed[offset..].iter_mut().zip(&pcm[..decoded]) {
+            *out += sample as f32 / 32768.0;
+        }
+        *cursor = offset + decoded;
+    }
+    mixed.iter().map(|&sample| (sample.clamp(-1.0, 1.0) * 32767.0) as i16).collect()
+}
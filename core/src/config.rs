@@ -0,0 +1,241 @@
+// Настройки клиента одной структурой: новые параметры добавляются полем, а
+// не отдельной функцией FFI. Значения по умолчанию задаются только здесь;
+// хост заполняет ими структуру через voice_client_config_init и меняет
+// нужные поля.
+use std::os::raw::c_char;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio::{self, AudioBackend};
+use crate::handshake::{self, features};
+use crate::{c_ptr, transmit_modes, users, SAMPLE_RATE};
+
+pub const DEFAULT_SERVER_PORT: u16 = 40000;
+pub const DEFAULT_BITRATE: u32 = 64000;
+pub const DEFAULT_FRAME_SIZE: u32 = 480; // 10 мс
+pub const DEFAULT_VAD_THRESHOLD: f32 = 0.01; // 1% от максимальной амплитуды
+pub const DEFAULT_VAD_SILENCE_INTERVAL_MS: u32 = 500;
+pub const DEFAULT_VAD_HANGOVER_MS: u32 = 200;
+pub const DEFAULT_PTT_RELEASE_MS: u32 = 200;
+pub const DEFAULT_BUFFER_MS: u32 = 200;
+pub const DEFAULT_FEATURES: u32 =
+    features::DTX | features::SEQUENCE | features::SESSION_TOKEN | features::CLIP | features::WHISPER;
+pub const DEFAULT_TRANSMIT_MODE: i32 = transmit_modes::PTT;
+
+const MIN_BITRATE: u32 = 6000;
+const MAX_BITRATE: u32 = 510000;
+// Меньше одного кадра буфер не переживет ни одного опоздания
+const MIN_BUFFER_MS: u32 = 20;
+const MAX_BUFFER_MS: u32 = 5000;
+const MAX_VAD_HANGOVER_MS: u32 = 5000;
+const MAX_PTT_RELEASE_MS: u32 = 2000;
+const MAX_CUE_VOLUME: f32 = 1.0;
+pub(crate) const MAX_CLIP_SECONDS: u32 = 600;
+pub(crate) const MAX_AFK_MINUTES: u32 = 24 * 60;
+// Возможности, которые клиент умеет запрашивать
+pub(crate) const SUPPORTED_FEATURES: u32 =
+    features::DTX | features::FEC | features::SEQUENCE | features::SESSION_TOKEN | features::CLIP | features::WHISPER;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VoiceClientConfig {
+    // Адрес сервера: IP или имя хоста
+    pub server_host: *const c_char,
+    pub server_port: u16,
+    // Начальный битрейт Opus, бит/с (6000-510000); сервер может снизить
+    pub bitrate: u32,
+    // Кадр в отсчетах при 48 кГц: 120, 240, 480, 960, 1920 или 2880
+    pub frame_size: u32,
+    // Детектор голоса: кадры тишины не кодируются, вместо них изредка
+    // уходит пакет тишины. Выключен - кодируется каждый кадр.
+    pub vad_enabled: bool,
+    // Порог тишины по амплитуде, 0..1
+    pub vad_threshold: f32,
+    // Как часто повторять пакет тишины, мс
+    pub vad_silence_interval_ms: u32,
+    // Имена устройств как в системе; NULL или "" - устройство по умолчанию
+    pub input_device: *const c_char,
+    pub output_device: *const c_char,
+    // Сколько звука держать в очереди воспроизведения каждого источника, мс
+    pub buffer_ms: u32,
+    // Возможности из handshake::features, которые запрашиваем у сервера
+    pub features: u32,
+    // Режим передачи из transmit_modes
+    pub transmit_mode: i32,
+    // Сколько еще передавать после того, как детектор услышал тишину, мс:
+    // затихающие окончания слов не обрезаются, паузы между словами не рвут фразу
+    pub vad_hangover_ms: u32,
+    // Звуковые сигналы при смене mute и deafen
+    pub cues_enabled: bool,
+    // Сколько еще передавать после отпускания клавиши PTT или
+    // voice_client_set_transmitting(false), мс; 0 - обрывать сразу
+    pub ptt_release_ms: u32,
+    // Сигналы начала и конца передачи в PTT и TOGGLE
+    pub transmit_cues_enabled: bool,
+    // Громкость всех сигналов, 0..1
+    pub cue_volume: f32,
+    // Сколько последних секунд разговора держать для voice_client_save_clip
+    // (до 600); 0 - буфер выключен
+    pub clip_seconds: u32,
+    // Уведомления: вход и выход участников, подключение и обрыв связи
+    pub notification_cues_enabled: bool,
+    // Выключить микрофон, если в VOICE_ACTIVATION или CONTINUOUS столько
+    // минут слышен только шум (до 1440, см. afk.rs); 0 - не выключать
+    pub afk_minutes: u32,
+    // Ники, заглушенные у себя (voice_client_set_user_muted), по одному в
+    // строке: участник глушится, как только появляется в канале. Список для
+    // следующей сессии отдает voice_client_get_muted_users; NULL - никого.
+    pub muted_users: *const c_char,
+}
+
+impl Default for VoiceClientConfig {
+    fn default() -> Self {
+        VoiceClientConfig {
+            server_host: std::ptr::null(),
+            server_port: DEFAULT_SERVER_PORT,
+            bitrate: DEFAULT_BITRATE,
+            frame_size: DEFAULT_FRAME_SIZE,
+            vad_enabled: true,
+            vad_threshold: DEFAULT_VAD_THRESHOLD,
+            vad_silence_interval_ms: DEFAULT_VAD_SILENCE_INTERVAL_MS,
+            input_device: std::ptr::null(),
+            output_device: std::ptr::null(),
+            buffer_ms: DEFAULT_BUFFER_MS,
+            features: DEFAULT_FEATURES,
+            transmit_mode: DEFAULT_TRANSMIT_MODE,
+            vad_hangover_ms: DEFAULT_VAD_HANGOVER_MS,
+            cues_enabled: true,
+            ptt_release_ms: DEFAULT_PTT_RELEASE_MS,
+            transmit_cues_enabled: true,
+            cue_volume: MAX_CUE_VOLUME,
+            clip_seconds: 0,
+            notification_cues_enabled: true,
+            afk_minutes: 0,
+            muted_users: std::ptr::null(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Vad {
+    pub enabled: bool,
+    pub threshold: f32,
+    pub silence_interval: Duration,
+    // Задержка перед тишиной в отсчетах
+    pub hangover_samples: usize,
+}
+
+// Проверенные настройки, с которыми создается клиент
+pub struct Settings {
+    pub bitrate: u32,
+    pub frame_size: usize,
+    pub vad: Vad,
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub buffer_samples: usize,
+    pub features: u32,
+    pub transmit_mode: i32,
+    pub cues: bool,
+    pub ptt_release: Duration,
+    pub transmit_cues: bool,
+    pub cue_volume: f32,
+    pub clip_length: Duration,
+    pub notification_cues: bool,
+    pub afk_minutes: u32,
+    pub muted_users: Vec<String>,
+    // Из C всегда устройства системы; другой звук задается через client::ClientBuilder
+    pub audio: Arc<dyn AudioBackend>,
+}
+
+impl Settings {
+    pub fn from_config(config: &VoiceClientConfig) -> Result<Self, String> {
+        if !(MIN_BITRATE..=MAX_BITRATE).contains(&config.bitrate) {
+            return Err(format!("bitrate {} out of range", config.bitrate));
+        }
+        if !handshake::VALID_FRAME_SIZES.contains(&(config.frame_size as usize)) {
+            return Err(format!("unsupported frame size {}", config.frame_size));
+        }
+        if !(0.0..=1.0).contains(&config.vad_threshold) {
+            return Err(format!("VAD threshold {} out of range", config.vad_threshold));
+        }
+        if config.vad_hangover_ms > MAX_VAD_HANGOVER_MS {
+            return Err(format!("VAD hangover {} ms out of range", config.vad_hangover_ms));
+        }
+        if config.ptt_release_ms > MAX_PTT_RELEASE_MS {
+            return Err(format!("PTT release delay {} ms out of range", config.ptt_release_ms));
+        }
+        if !(0.0..=MAX_CUE_VOLUME).contains(&config.cue_volume) {
+            return Err(format!("cue volume {} out of range", config.cue_volume));
+        }
+        if config.clip_seconds > MAX_CLIP_SECONDS {
+            return Err(format!("replay buffer {} s out of range", config.clip_seconds));
+        }
+        if config.afk_minutes > MAX_AFK_MINUTES {
+            return Err(format!("AFK timeout {} min out of range", config.afk_minutes));
+        }
+        if !(MIN_BUFFER_MS..=MAX_BUFFER_MS).contains(&config.buffer_ms) {
+            return Err(format!("buffer {} ms out of range", config.buffer_ms));
+        }
+        if config.features & !SUPPORTED_FEATURES != 0 {
+            return Err(format!("unsupported features 0x{:x}", config.features & !SUPPORTED_FEATURES));
+        }
+        if !is_valid_transmit_mode(config.transmit_mode) {
+            return Err(format!("unknown transmit mode {}", config.transmit_mode));
+        }
+        let muted_users = nickname_list(config.muted_users)?;
+
+        Ok(Settings {
+            bitrate: config.bitrate,
+            frame_size: config.frame_size as usize,
+            vad: Vad {
+                enabled: config.vad_enabled,
+                threshold: config.vad_threshold,
+                silence_interval: Duration::from_millis(config.vad_silence_interval_ms as u64),
+                hangover_samples: (SAMPLE_RATE as usize * config.vad_hangover_ms as usize) / 1000,
+            },
+            input_device: device_name(config.input_device),
+            output_device: device_name(config.output_device),
+            buffer_samples: (SAMPLE_RATE as usize * config.buffer_ms as usize) / 1000,
+            features: config.features,
+            transmit_mode: config.transmit_mode,
+            cues: config.cues_enabled,
+            ptt_release: Duration::from_millis(config.ptt_release_ms as u64),
+            transmit_cues: config.transmit_cues_enabled,
+            cue_volume: config.cue_volume,
+            clip_length: Duration::from_secs(config.clip_seconds as u64),
+            notification_cues: config.notification_cues_enabled,
+            afk_minutes: config.afk_minutes,
+            muted_users,
+            audio: audio::default_backend(),
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::from_config(&VoiceClientConfig::default()).expect("default config is valid")
+    }
+}
+
+pub fn is_valid_transmit_mode(mode: i32) -> bool {
+    (transmit_modes::PTT..=transmit_modes::TOGGLE).contains(&mode)
+}
+
+// Ники из C по одному в строке; пустые строки пропускаются
+pub(crate) fn nickname_list(list: *const c_char) -> Result<Vec<String>, String> {
+    let Some(list) = c_ptr::string(list) else {
+        return Ok(Vec::new());
+    };
+    let list = list.to_string_lossy();
+    let names: Vec<String> = list.lines().filter(|line| !line.is_empty()).map(str::to_string).collect();
+    match names.iter().find(|name| !users::is_valid_nickname(name)) {
+        Some(name) => Err(format!("invalid nickname {:?}", name)),
+        None => Ok(names),
+    }
+}
+
+// Имя устройства из C; NULL и пустая строка - устройство по умолчанию
+pub fn device_name(name: *const c_char) -> Option<String> {
+    let name = c_ptr::string(name)?.to_string_lossy().into_owned();
+    if name.is_empty() { None } else { Some(name) }
+}
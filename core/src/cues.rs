@@ -0,0 +1,154 @@
+// Короткие звуковые сигналы в динамиках: смена mute и deafen, начало и
+// конец передачи, а также уведомления - кто-то вошел в канал или вышел,
+// клиент подключился, переподключается или отключился. Их слышно и в
+// полноэкранной игре, где индикатор хоста не виден. Сигнал - два тона:
+// вверх - микрофон или звук включился (вошел, подключился), вниз -
+// выключился. Генерируется локально и в сеть не уходит. Вместо тона
+// уведомления хост может задать свой файл (voice_client_set_notification_sound).
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::{audio_file, notification_sounds, PlaybackMixer, SourceKey, SAMPLE_RATE};
+
+// Источник сигналов в микшере; в сеть этот адрес не попадает
+pub(crate) const CUE_SOURCE: SourceKey = (SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0), 0);
+
+const FADE_MS: usize = 5;
+const CUE_AMPLITUDE: f32 = 0.2;
+const MUTE_TONES: (f32, f32) = (440.0, 660.0);
+// Звук глушится октавой ниже, чтобы не спутать с микрофоном
+const DEAFEN_TONES: (f32, f32) = (262.0, 523.0);
+// Передача - выше и короче: она переключается часто
+const TRANSMIT_TONES: (f32, f32) = (880.0, 1175.0);
+// Вход и выход - мягче и ниже передачи, соединение - шире по интервалу
+const PRESENCE_TONES: (f32, f32) = (523.0, 784.0);
+const CONNECTION_TONES: (f32, f32) = (392.0, 784.0);
+// Переподключение - вниз на кварту: связь пропала, но клиент ее ищет
+const RECONNECT_TONES: (f32, f32) = (659.0, 494.0);
+const TONE_MS: usize = 60;
+const TRANSMIT_TONE_MS: usize = 35;
+const PRESENCE_TONE_MS: usize = 50;
+const CONNECTION_TONE_MS: usize = 80;
+// Файл уведомления длиннее - почти наверняка не тот файл
+const MAX_NOTIFICATION_SECONDS: usize = 5;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Cue {
+    Mute(bool),
+    Deafen(bool),
+    // true - передача началась
+    Transmit(bool),
+    // Уведомление, см. notification_sounds
+    Notification(i32),
+}
+
+// Какие сигналы играть и насколько громко
+#[derive(Clone, Copy)]
+pub(crate) struct CueSettings {
+    pub mute: bool,
+    pub transmit: bool,
+    pub notifications: bool,
+    // 0..1 от обычной громкости сигнала
+    pub volume: f32,
+}
+
+impl CueSettings {
+    pub fn enabled(&self, cue: Cue) -> bool {
+        let enabled = match cue {
+            Cue::Mute(_) | Cue::Deafen(_) => self.mute,
+            Cue::Transmit(_) => self.transmit,
+            Cue::Notification(_) => self.notifications,
+        };
+        enabled && self.volume > 0.0
+    }
+}
+
+// Отсчеты сигнала при SAMPLE_RATE
+pub(crate) fn cue_samples(cue: Cue, volume: f32) -> Vec<f32> {
+    // Для mute и deafen "включено" значит, что микрофон или звук выключен
+    let (tones, rising, tone_ms) = match cue {
+        Cue::Mute(muted) => (MUTE_TONES, !muted, TONE_MS),
+        Cue::Deafen(deafened) => (DEAFEN_TONES, !deafened, TONE_MS),
+        Cue::Transmit(transmitting) => (TRANSMIT_TONES, transmitting, TRANSMIT_TONE_MS),
+        Cue::Notification(notification_sounds::USER_JOINED) => (PRESENCE_TONES, true, PRESENCE_TONE_MS),
+        Cue::Notification(notification_sounds::USER_LEFT) => (PRESENCE_TONES, false, PRESENCE_TONE_MS),
+        Cue::Notification(notification_sounds::CONNECTED) => (CONNECTION_TONES, true, CONNECTION_TONE_MS),
+        Cue::Notification(notification_sounds::RECONNECTING) => (RECONNECT_TONES, true, CONNECTION_TONE_MS),
+        Cue::Notification(_) => (CONNECTION_TONES, false, CONNECTION_TONE_MS),
+    };
+    let (first, second) = if rising { tones } else { (tones.1, tones.0) };
+
+    let tone_len = SAMPLE_RATE as usize * tone_ms / 1000;
+    let fade_len = SAMPLE_RATE as usize * FADE_MS / 1000;
+    let amplitude = CUE_AMPLITUDE * volume;
+    let mut samples = Vec::with_capacity(tone_len * 2);
+    for frequency in [first, second] {
+        samples.extend((0..tone_len).map(|i| {
+            // Края сглажены, иначе тон начинается и кончается щелчком
+            let fade = (i.min(tone_len - 1 - i) as f32 / fade_len as f32).min(1.0);
+            (TAU * frequency * i as f32 / SAMPLE_RATE as f32).sin() * amplitude * fade
+        }));
+    }
+    samples
+}
+
+pub(crate) fn is_notification(kind: i32) -> bool {
+    (notification_sounds::USER_JOINED..=notification_sounds::DISCONNECTED).contains(&kind)
+}
+
+// Файл для voice_client_set_notification_sound, см. audio_file.rs
+pub(crate) fn load_notification(path: &Path) -> io::Result<Vec<f32>> {
+    let samples = audio_file::load(path)?;
+    if samples.len() > SAMPLE_RATE as usize * MAX_NOTIFICATION_SECONDS {
+        let message = format!("notification sound is longer than {} seconds", MAX_NOTIFICATION_SECONDS);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    Ok(samples)
+}
+
+// Играет сигналы в микшер клиента; нужен и трекеру соединения, у которого
+// нет самого клиента
+pub(crate) struct CuePlayer {
+    settings: CueSettings,
+    // Файлы вместо тонов уведомлений, по notification_sounds
+    sounds: Mutex<HashMap<i32, Vec<f32>>>,
+    playback: Arc<Mutex<PlaybackMixer>>,
+    running: Arc<AtomicBool>,
+}
+
+impl CuePlayer {
+    pub fn new(settings: CueSettings, playback: Arc<Mutex<PlaybackMixer>>, running: Arc<AtomicBool>) -> Self {
+        CuePlayer { settings, sounds: Mutex::default(), playback, running }
+    }
+
+    // Сигнал слышен, только пока открыт вывод
+    pub fn play(&self, cue: Cue) {
+        if !self.settings.enabled(cue) || !self.running.load(Ordering::SeqCst) {
+            return;
+        }
+        let volume = self.settings.volume;
+        let sound = match cue {
+            Cue::Notification(kind) => self.sounds.lock().unwrap().get(&kind).cloned(),
+            _ => None,
+        };
+        let samples = match sound {
+            Some(samples) => samples.into_iter().map(|s| s * volume).collect(),
+            None => cue_samples(cue, volume),
+        };
+        self.playback.lock().unwrap().push_cue(samples);
+    }
+
+    // None - снова сгенерированный тон
+    pub fn set_sound(&self, kind: i32, samples: Option<Vec<f32>>) {
+        let mut sounds = self.sounds.lock().unwrap();
+        match samples {
+            Some(samples) => sounds.insert(kind, samples),
+            None => sounds.remove(&kind),
+        };
+    }
+}
@@ -0,0 +1,70 @@
+// Прямой звонок двух клиентов без сервера. Звонящий знает адрес собеседника
+// заранее; принимающий ждет на известном порту, и собеседником становится
+// тот, кто первым пришлет управляющий пакет NSVC. Если собеседник замолчал,
+// место освобождается для следующего звонка.
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+use crate::is_control_packet;
+use crate::logging::NET;
+
+// Столько молчания - и принимающая сторона снова ждет звонка
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct DirectCall {
+    // Адрес собеседника и время его последнего пакета
+    peer: Mutex<Option<(SocketAddr, Instant)>>,
+    // Принимаем звонок, а не звоним сами
+    listening: bool,
+}
+
+impl DirectCall {
+    pub fn call(peer: SocketAddr) -> Self {
+        DirectCall {
+            peer: Mutex::new(Some((peer, Instant::now()))),
+            listening: false,
+        }
+    }
+
+    pub fn listen() -> Self {
+        DirectCall {
+            peer: Mutex::new(None),
+            listening: true,
+        }
+    }
+
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.peer.lock().unwrap().map(|(addr, _)| addr)
+    }
+
+    // Пакет от собеседника? Принимающая сторона запоминает первого,
+    // кто прислал пакет NSVC, и позже - следующего, если прежний пропал.
+    pub fn accept(&self, from: SocketAddr, data: &[u8]) -> bool {
+        let mut peer = self.peer.lock().unwrap();
+        match *peer {
+            Some((addr, ref mut last)) if addr == from => {
+                *last = Instant::now();
+                true
+            },
+            Some((_, last)) if !self.listening || last.elapsed() < PEER_TIMEOUT => false,
+            _ if !is_control_packet(data) => false,
+            _ => {
+                info!(target: NET, "Incoming direct call from {}", from);
+                *peer = Some((from, Instant::now()));
+                true
+            },
+        }
+    }
+
+    // Собеседник попрощался: принимающая сторона сразу ждет следующего звонка
+    pub fn on_goodbye(&self, from: SocketAddr) {
+        let mut peer = self.peer.lock().unwrap();
+        if self.listening && peer.is_some_and(|(addr, _)| addr == from) {
+            info!(target: NET, "Direct call from {} ended", from);
+            *peer = None;
+        }
+    }
+}
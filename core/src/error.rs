@@ -0,0 +1,110 @@
+// Ошибки клиента для Rust-кода. Внутренние функции возвращают NsvcError,
+// а в коды error_codes для FFI они переводятся в одном месте - code().
+use std::io;
+
+use thiserror::Error;
+
+use crate::error_codes;
+
+#[derive(Debug, Error)]
+pub enum NsvcError {
+    #[error("invalid client handle")]
+    InvalidHandle,
+    #[error("invalid IP address: {0}")]
+    InvalidIp(String),
+    #[error("failed to resolve address {0}")]
+    Resolve(String),
+    #[error("socket bind error: {0}")]
+    SocketBind(#[source] io::Error),
+    #[error("failed to start LAN discovery: {0}")]
+    Discovery(String),
+    #[error("socket option error: {0}")]
+    SocketOption(#[source] io::Error),
+    #[error("send error: {0}")]
+    Send(#[source] io::Error),
+    #[error("no input device available: {0}")]
+    NoInputDevice(String),
+    #[error("no output device available: {0}")]
+    NoOutputDevice(String),
+    #[error("no suitable {0} configuration found")]
+    UnsupportedSampleFormat(&'static str),
+    #[error("input stream error: {0}")]
+    InputStream(String),
+    #[error("output stream error: {0}")]
+    OutputStream(String),
+    #[error("encoder creation error: {0}")]
+    Encoder(#[source] opus::Error),
+    #[error("invalid parameter: {0}")]
+    InvalidParam(String),
+    #[error("client is not running")]
+    NotRunning,
+    #[error("STUN request failed: {0}")]
+    Stun(String),
+    #[error("buffer too small")]
+    BufferTooSmall,
+    #[error("incompatible protocol: {0}")]
+    ProtocolMismatch(String),
+    #[error("access denied: {0}")]
+    AccessDenied(String),
+    #[error("failed to open log file: {0}")]
+    LogFile(#[source] io::Error),
+    #[error("failed to write recording: {0}")]
+    Recording(#[source] io::Error),
+    #[error("failed to read audio file: {0}")]
+    AudioFile(#[source] io::Error),
+    #[error("failed to open Mumble Link: {0}")]
+    MumbleLink(#[source] io::Error),
+    #[error("async runtime error: {0}")]
+    Runtime(#[source] io::Error),
+    // Код FFI, для которого нет подробностей
+    #[error("{}", code_description(.0))]
+    Code(i32),
+}
+
+fn code_description(code: &i32) -> String {
+    error_codes::description(*code).to_string_lossy().into_owned()
+}
+
+impl NsvcError {
+    // Код для FFI
+    pub fn code(&self) -> i32 {
+        match self {
+            NsvcError::InvalidHandle => error_codes::NULL_POINTER,
+            NsvcError::InvalidIp(_) => error_codes::INVALID_IP,
+            NsvcError::Resolve(_) => error_codes::INVALID_SERVER_ADDR,
+            NsvcError::SocketBind(_) => error_codes::SOCKET_BIND_FAILED,
+            NsvcError::Discovery(_) => error_codes::SOCKET_BIND_FAILED,
+            NsvcError::SocketOption(_) => error_codes::SOCKET_OPTION_FAILED,
+            NsvcError::Send(_) => error_codes::SOCKET_CONNECT_FAILED,
+            NsvcError::NoInputDevice(_) => error_codes::NO_INPUT_DEVICE,
+            NsvcError::NoOutputDevice(_) => error_codes::NO_OUTPUT_DEVICE,
+            NsvcError::UnsupportedSampleFormat(_) => error_codes::UNSUPPORTED_SAMPLE_FORMAT,
+            NsvcError::InputStream(_) => error_codes::INPUT_STREAM_FAILED,
+            NsvcError::OutputStream(_) => error_codes::OUTPUT_STREAM_FAILED,
+            NsvcError::Encoder(_) => error_codes::ENCODER_INIT_FAILED,
+            NsvcError::InvalidParam(_) => error_codes::INVALID_AUDIO_PARAM,
+            NsvcError::NotRunning => error_codes::NOT_RUNNING,
+            NsvcError::Stun(_) => error_codes::STUN_FAILED,
+            NsvcError::BufferTooSmall => error_codes::BUFFER_TOO_SMALL,
+            NsvcError::ProtocolMismatch(_) => error_codes::PROTOCOL_MISMATCH,
+            NsvcError::AccessDenied(_) => error_codes::ACCESS_DENIED,
+            NsvcError::LogFile(_) => error_codes::LOG_FILE_FAILED,
+            NsvcError::Recording(_) => error_codes::RECORDING_FAILED,
+            NsvcError::AudioFile(_) => error_codes::AUDIO_FILE_FAILED,
+            NsvcError::MumbleLink(_) => error_codes::MUMBLE_LINK_FAILED,
+            // Сетевые задачи не запустить - для хоста это как сокет, который не открылся
+            NsvcError::Runtime(_) => error_codes::SOCKET_BIND_FAILED,
+            NsvcError::Code(code) => *code,
+        }
+    }
+
+    // Обратно из кода, который вернула FFI-функция; подробностей у кода нет
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            error_codes::NULL_POINTER => NsvcError::InvalidHandle,
+            error_codes::NOT_RUNNING => NsvcError::NotRunning,
+            error_codes::BUFFER_TOO_SMALL => NsvcError::BufferTooSmall,
+            _ => NsvcError::Code(code),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// События для хоста: вместо опроса состояния и разбора журнала хост
+// получает типизированные уведомления через один callback.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::sync::{Condvar, Mutex};
+
+// Сколько событий ждет voice_client_poll_events; сверх этого старые выбрасываются
+const MAX_QUEUED_EVENTS: usize = 1024;
+
+// Типы событий и смысл полей callback
+pub mod event_types {
+    // code - новое состояние из connection_states
+    pub const CONNECTED: i32 = 1;
+    pub const DISCONNECTED: i32 = 2;
+    // code: DEVICE_INPUT, DEVICE_OUTPUT или DEVICE_VOICE_OUTPUT; text - имя устройства или причина потери
+    pub const DEVICE_CHANGED: i32 = 3;
+    // user_id и ник в text
+    pub const USER_JOINED: i32 = 4;
+    pub const USER_LEFT: i32 = 5;
+    // По громкости звука участника, сглажены: начало - после
+    // users::SPEAKING_START_DELAY громкого звука, конец - после
+    // users::SPEAKING_TIMEOUT тишины (см. users::SPEAKING_ON_LEVEL)
+    pub const SPEAKING_STARTED: i32 = 6;
+    pub const SPEAKING_STOPPED: i32 = 7;
+    // code из error_codes, text - подробности
+    pub const ERROR: i32 = 8;
+    // user_id - отправитель, code: 1 - личное, 0 - всему каналу; text - сообщение
+    pub const TEXT_MESSAGE: i32 = 9;
+    // Завершился voice_client_start_async: code - SUCCESS или код из
+    // error_codes, text - описание ошибки
+    pub const STARTED: i32 = 10;
+    // code: 1 - включено, 0 - выключено (voice_client_set_muted и _set_deafened)
+    pub const MUTE_CHANGED: i32 = 11;
+    pub const DEAFEN_CHANGED: i32 = 12;
+    // user_id начал передавать записанный клип (voice_client_send_clip)
+    pub const CLIP_STARTED: i32 = 13;
+    // Микрофон выключен защитой от забытого открытого микрофона (см.
+    // afk.rs); code - минуты без речи. Приходит после MUTE_CHANGED.
+    pub const AFK_MUTED: i32 = 14;
+}
+
+pub const DEVICE_INPUT: i32 = 0;
+pub const DEVICE_OUTPUT: i32 = 1;
+// Отдельный вывод голосов, см. voice_client_set_voice_output_device
+pub const DEVICE_VOICE_OUTPUT: i32 = 2;
+
+// Callback событий: (тип из event_types, ID участника или 0, код, текст,
+// userdata). Текст действителен только на время вызова. Вызывается из
+// сетевых и аудиопотоков клиента, а в режиме очереди - из потока, который
+// вызвал voice_client_poll_events.
+pub type EventCallback =
+    extern "C" fn(event: i32, user_id: u32, code: i32, text: *const c_char, userdata: *mut c_void);
+
+struct QueuedEvent {
+    event: i32,
+    user_id: u32,
+    code: i32,
+    text: String,
+}
+
+thread_local! {
+    // Клиенты, чьи callback'и этот поток вызывает прямо сейчас
+    static CALLING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Default)]
+struct Gate {
+    closed: bool,
+    // Вызовы хоста, которые идут сейчас
+    active: usize,
+}
+
+#[derive(Default)]
+pub struct EventSink {
+    // userdata хранится как usize, чтобы структура оставалась Send + Sync
+    pub callback: Mutex<Option<(EventCallback, usize)>>,
+    // Some - режим очереди: события ждут poll в потоке хоста
+    queue: Mutex<Option<VecDeque<QueuedEvent>>>,
+    // Через него идут все callback'и клиента: событий, ошибок и состояния
+    gate: Mutex<Gate>,
+    idle: Condvar,
+}
+
+impl EventSink {
+    pub fn emit(&self, event: i32, user_id: u32, code: i32, text: &str) {
+        if let Some(queue) = self.queue.lock().unwrap().as_mut() {
+            if queue.len() >= MAX_QUEUED_EVENTS {
+                queue.pop_front();
+            }
+            queue.push_back(QueuedEvent { event, user_id, code, text: text.to_string() });
+            return;
+        }
+        self.dispatch(event, user_id, code, text);
+    }
+
+    // Включает или выключает очередь; при выключении накопленное теряется
+    pub fn set_queued(&self, queued: bool) {
+        let mut queue = self.queue.lock().unwrap();
+        if queued != queue.is_some() {
+            *queue = queued.then(VecDeque::new);
+        }
+    }
+
+    // Вызывает callback для накопленных событий в текущем потоке и
+    // возвращает их число
+    pub fn poll(&self) -> usize {
+        // Забираем очередь целиком: callback может сам вызывать функции клиента
+        let events: Vec<QueuedEvent> = match self.queue.lock().unwrap().as_mut() {
+            Some(queue) => queue.drain(..).collect(),
+            None => return 0,
+        };
+        for queued in &events {
+            self.dispatch(queued.event, queued.user_id, queued.code, &queued.text);
+        }
+        events.len()
+    }
+
+    fn dispatch(&self, event: i32, user_id: u32, code: i32, text: &str) {
+        self.call_host(|| {
+            let callback = *self.callback.lock().unwrap();
+            if let Some((func, userdata)) = callback {
+                let text = CString::new(text.replace('\0', "")).unwrap_or_default();
+                func(event, user_id, code, text.as_ptr(), userdata as *mut c_void);
+            }
+        });
+    }
+
+    // Вызов callback'а хоста; после close не выполняется
+    pub fn call_host(&self, call: impl FnOnce()) {
+        {
+            let mut gate = self.gate.lock().unwrap();
+            if gate.closed {
+                return;
+            }
+            gate.active += 1;
+        }
+        CALLING.with(|calling| calling.borrow_mut().push(self.id()));
+        call();
+        CALLING.with(|calling| calling.borrow_mut().pop());
+        self.gate.lock().unwrap().active -= 1;
+        self.idle.notify_all();
+    }
+
+    // Текущий поток сейчас внутри callback'а этого клиента
+    pub fn in_host_call(&self) -> bool {
+        self.own_calls() > 0
+    }
+
+    // Хост больше не получает ничего: новые вызовы не начинаются, а идущие в
+    // других потоках дожидаемся - после этого хост может освобождать userdata.
+    // Вызов изнутри callback'а свой не ждет.
+    pub fn close(&self) {
+        *self.callback.lock().unwrap() = None;
+        *self.queue.lock().unwrap() = None;
+        let own = self.own_calls();
+        let mut gate = self.gate.lock().unwrap();
+        gate.closed = true;
+        while gate.active > own {
+            gate = self.idle.wait(gate).unwrap();
+        }
+    }
+
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn own_calls(&self) -> usize {
+        CALLING.with(|calling| calling.borrow().iter().filter(|&&id| id == self.id()).count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn close_waits_for_calls_in_progress() {
+        let sink = Arc::new(EventSink::default());
+        let finished = Arc::new(AtomicBool::new(false));
+        let (started_tx, started_rx) = mpsc::channel();
+        let caller = {
+            let sink = sink.clone();
+            let finished = finished.clone();
+            thread::spawn(move || {
+                sink.call_host(|| {
+                    started_tx.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(100));
+                    finished.store(true, Ordering::SeqCst);
+                })
+            })
+        };
+        started_rx.recv().unwrap();
+        sink.close();
+        assert!(finished.load(Ordering::SeqCst));
+
+        let mut called = false;
+        sink.call_host(|| called = true);
+        assert!(!called);
+        caller.join().unwrap();
+    }
+
+    #[test]
+    fn close_inside_a_callback_does_not_wait_for_itself() {
+        let sink = EventSink::default();
+        sink.call_host(|| {
+            assert!(sink.in_host_call());
+            sink.close();
+        });
+        assert!(!sink.in_host_call());
+    }
+}
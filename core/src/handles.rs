@@ -0,0 +1,39 @@
+// Таблица описателей для FFI. Хост получает не адрес объекта, а номер
+// записи в таблице. Номера не переиспользуются, поэтому вызов с уже
+// освобожденным или выдуманным описателем просто не находит объект, а не
+// читает освобожденную память. Объект живет, пока его держит хоть один
+// вызов, даже если хост тем временем его освободил.
+use std::collections::BTreeMap;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub struct Registry<T> {
+    entries: Mutex<BTreeMap<usize, Arc<T>>>,
+    // 0 не выдается: в C это NULL
+    next: AtomicUsize,
+}
+
+impl<T> Registry<T> {
+    pub const fn new() -> Self {
+        Registry {
+            entries: Mutex::new(BTreeMap::new()),
+            next: AtomicUsize::new(1),
+        }
+    }
+
+    // Для C описатель выглядит как указатель, но никуда не указывает
+    pub fn insert(&self, value: T) -> *mut c_void {
+        let handle = self.next.fetch_add(1, Ordering::Relaxed);
+        self.entries.lock().unwrap().insert(handle, Arc::new(value));
+        handle as *mut c_void
+    }
+
+    pub fn get(&self, handle: *mut c_void) -> Option<Arc<T>> {
+        self.entries.lock().unwrap().get(&(handle as usize)).cloned()
+    }
+
+    pub fn remove(&self, handle: *mut c_void) -> Option<Arc<T>> {
+        self.entries.lock().unwrap().remove(&(handle as usize))
+    }
+}
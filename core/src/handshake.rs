@@ -0,0 +1,334 @@
+// Рукопожатие с сервером: клиент отправляет HELLO с версией протокола и
+// параметрами кодека, сервер отвечает ACCEPT с согласованными параметрами
+// или REJECT с причиной. Голос идет только после ACCEPT.
+// Версия протокола едет в заголовке каждого управляющего пакета; в HELLO
+// клиент дополнительно сообщает минимальную поддерживаемую версию, сервер
+// выбирает общую и отвечает ею в заголовке ACCEPT или шлет VERSION_MISMATCH.
+// После параметров HELLO несет ключ клиента, пароль сервера и ник, ACCEPT -
+// выданный клиенту ID участника; старые стороны эти хвосты не читают.
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{
+    control_packet, control_type, control_types, control_version, is_supported_version, CONTROL_HEADER_SIZE,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+
+pub const HELLO_INTERVAL: Duration = Duration::from_millis(500);
+// Старые серверы HELLO не понимают; после этого срока работаем по-старому
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+// Причина REJECT при отказе в доступе начинается с этого префикса
+pub const ACCESS_DENIED_PREFIX: &str = "access denied";
+pub const MAX_CREDENTIAL_LEN: usize = 255;
+
+// Флаги возможностей
+pub mod features {
+    pub const DTX: u32 = 1 << 0;
+    pub const FEC: u32 = 1 << 1;
+    pub const ENCRYPTION: u32 = 1 << 2;
+    // Голос в пакетах MEDIA с SSRC и номером пакета
+    pub const SEQUENCE: u32 = 1 << 3;
+    // Сервер выдает в ACCEPT секретный токен, и клиент шлет его в MEDIA
+    // вместо SSRC: голос с подделанного адреса сервер отбросит
+    pub const SESSION_TOKEN: u32 = 1 << 4;
+    // Клиент различает клипы (MEDIA_CLIP); без флага сервер шлет их ему
+    // обычным MEDIA
+    pub const CLIP: u32 = 1 << 5;
+    // Сервер пересылает шепот (MEDIA_WHISPER) только его адресатам
+    pub const WHISPER: u32 = 1 << 6;
+}
+
+// Длительности кадра Opus при 48 кГц: 2.5, 5, 10, 20, 40, 60 мс
+pub const VALID_FRAME_SIZES: [usize; 6] = [120, 240, 480, 960, 1920, 2880];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionParams {
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub frame_size: u16,
+    pub bitrate: u32,
+    pub features: u32,
+}
+
+impl SessionParams {
+    const ENCODED_SIZE: usize = 15;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.sample_rate.to_be_bytes());
+        buf.push(self.channels);
+        buf.extend_from_slice(&self.frame_size.to_be_bytes());
+        buf.extend_from_slice(&self.bitrate.to_be_bytes());
+        buf.extend_from_slice(&self.features.to_be_bytes());
+    }
+
+    fn read(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::ENCODED_SIZE {
+            return None;
+        }
+        Some(SessionParams {
+            sample_rate: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            channels: data[4],
+            frame_size: u16::from_be_bytes([data[5], data[6]]),
+            bitrate: u32::from_be_bytes([data[7], data[8], data[9], data[10]]),
+            features: u32::from_be_bytes([data[11], data[12], data[13], data[14]]),
+        })
+    }
+}
+
+// Ключ - идентификатор клиента для списков доступа сервера
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Credentials {
+    pub key: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn is_valid(&self) -> bool {
+        self.key.len() <= MAX_CREDENTIAL_LEN && self.password.len() <= MAX_CREDENTIAL_LEN
+    }
+
+    // Длина (u8) и байты ключа, затем пароля
+    fn write(&self, buf: &mut Vec<u8>) {
+        for field in [&self.key, &self.password] {
+            let bytes = &field.as_bytes()[..field.len().min(MAX_CREDENTIAL_LEN)];
+            buf.push(bytes.len() as u8);
+            buf.extend_from_slice(bytes);
+        }
+    }
+
+    // Отсутствующий хвост - пустые ключ и пароль. Возвращает и число прочитанных байт.
+    fn read(data: &[u8]) -> Option<(Self, usize)> {
+        let mut fields = [String::new(), String::new()];
+        let mut pos = 0;
+        for field in fields.iter_mut() {
+            let len = match data.get(pos) {
+                Some(len) => *len as usize,
+                None => break,
+            };
+            *field = String::from_utf8(data.get(pos + 1..pos + 1 + len)?.to_vec()).ok()?;
+            pos += 1 + len;
+        }
+        let [key, password] = fields;
+        Some((Credentials { key, password }, pos))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Outcome {
+    #[default]
+    Pending,
+    Accepted(SessionParams),
+    Rejected(String),
+    // Нет общей версии протокола
+    VersionMismatch { server_min: u8, server_max: u8 },
+    // Сервер не ответил - старый протокол без рукопожатия
+    Legacy,
+}
+
+#[derive(Default)]
+pub struct Handshake {
+    outcome: Mutex<Outcome>,
+    // Копия may_stream для callback захвата: там Mutex брать нельзя
+    streaming: AtomicBool,
+    // ID участника из ACCEPT (0 - сервер его не выдал)
+    user_id: AtomicU32,
+    // Токен сессии из ACCEPT (0 - не согласован)
+    session_token: AtomicU32,
+}
+
+// Разобранный сервером HELLO
+pub struct Hello {
+    pub min_version: u8,
+    pub max_version: u8,
+    pub params: SessionParams,
+    pub credentials: Credentials,
+    // Пустой, если клиент ник не задал
+    pub nickname: String,
+}
+
+// HELLO: минимальная версия протокола + желаемые параметры + учетные данные
+// + ник (максимальная версия - в заголовке)
+pub fn hello_packet(params: &SessionParams, credentials: &Credentials, nickname: &str) -> Vec<u8> {
+    let mut body = Vec::with_capacity(1 + SessionParams::ENCODED_SIZE + 3 + nickname.len());
+    body.push(MIN_PROTOCOL_VERSION);
+    params.write(&mut body);
+    credentials.write(&mut body);
+    let nickname = &nickname.as_bytes()[..nickname.len().min(MAX_CREDENTIAL_LEN)];
+    body.push(nickname.len() as u8);
+    body.extend_from_slice(nickname);
+    control_packet(control_types::HELLO, &body)
+}
+
+// ACCEPT: итоговые параметры сессии + ID участника + токен сессии
+// (только если согласован SESSION_TOKEN)
+pub fn accept_packet(params: &SessionParams, user_id: u32, session_token: u32) -> Vec<u8> {
+    let mut body = Vec::with_capacity(SessionParams::ENCODED_SIZE + 8);
+    params.write(&mut body);
+    body.extend_from_slice(&user_id.to_be_bytes());
+    if params.features & features::SESSION_TOKEN != 0 {
+        body.extend_from_slice(&session_token.to_be_bytes());
+    }
+    control_packet(control_types::HELLO_ACCEPT, &body)
+}
+
+// REJECT: причина отказа текстом
+pub fn reject_packet(reason: &str) -> Vec<u8> {
+    control_packet(control_types::HELLO_REJECT, reason.as_bytes())
+}
+
+// VERSION_MISMATCH: диапазон версий, которые понимает отвечающая сторона
+pub fn version_mismatch_packet() -> Vec<u8> {
+    control_packet(control_types::VERSION_MISMATCH, &[MIN_PROTOCOL_VERSION, PROTOCOL_VERSION])
+}
+
+// Разбирает HELLO на стороне сервера
+pub fn parse_hello(packet: &[u8]) -> Option<Hello> {
+    if control_type(packet) != control_types::HELLO {
+        return None;
+    }
+    let body = packet.get(CONTROL_HEADER_SIZE..)?;
+    let min_version = *body.first()?;
+    let params = SessionParams::read(&body[1..])?;
+    let rest = &body[1 + SessionParams::ENCODED_SIZE..];
+    let (credentials, used) = Credentials::read(rest)?;
+    let nickname = match rest.get(used) {
+        Some(len) => String::from_utf8(rest.get(used + 1..used + 1 + *len as usize)?.to_vec()).ok()?,
+        None => String::new(),
+    };
+    Some(Hello {
+        min_version,
+        max_version: control_version(packet),
+        params,
+        credentials,
+        nickname,
+    })
+}
+
+// Проверяет, что параметры, навязанные сервером, клиент способен выполнить
+pub fn validate(params: &SessionParams, sample_rate: u32, channels: u8) -> Result<(), String> {
+    if params.sample_rate != sample_rate || params.channels != channels {
+        return Err(format!(
+            "unsupported audio format {} Hz x{} (client supports {} Hz x{})",
+            params.sample_rate, params.channels, sample_rate, channels
+        ));
+    }
+    if !VALID_FRAME_SIZES.contains(&(params.frame_size as usize)) {
+        return Err(format!("unsupported frame size {}", params.frame_size));
+    }
+    if params.features & features::ENCRYPTION != 0 {
+        return Err("server requires encryption, which this client does not support".to_string());
+    }
+    Ok(())
+}
+
+impl Handshake {
+    pub fn new() -> Self {
+        Handshake::default()
+    }
+
+    pub fn user_id(&self) -> u32 {
+        self.user_id.load(Ordering::Relaxed)
+    }
+
+    pub fn session_token(&self) -> u32 {
+        self.session_token.load(Ordering::Relaxed)
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome.lock().unwrap().clone()
+    }
+
+    pub fn set_outcome(&self, outcome: Outcome) {
+        let mut current = self.outcome.lock().unwrap();
+        *current = outcome;
+        self.mirror(&current);
+    }
+
+    // Можно ли передавать голос; без блокировок, зовется из callback захвата
+    pub fn may_stream(&self) -> bool {
+        self.streaming.load(Ordering::Acquire)
+    }
+
+    fn mirror(&self, outcome: &Outcome) {
+        let streaming = matches!(outcome, Outcome::Accepted(_) | Outcome::Legacy);
+        self.streaming.store(streaming, Ordering::Release);
+    }
+
+    // Согласована ли возможность с сервером
+    pub fn has_feature(&self, feature: u32) -> bool {
+        match *self.outcome.lock().unwrap() {
+            Outcome::Accepted(params) => params.features & feature != 0,
+            _ => false,
+        }
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        matches!(*self.outcome.lock().unwrap(), Outcome::Rejected(_) | Outcome::VersionMismatch { .. })
+    }
+
+    // Обрабатывает ACCEPT/REJECT от сервера. Возвращает true, если пакет был ответом.
+    pub fn on_reply(&self, packet: &[u8]) -> bool {
+        let mut outcome = self.outcome.lock().unwrap();
+        let replied = self.apply_reply(&mut outcome, packet);
+        self.mirror(&outcome);
+        replied
+    }
+
+    fn apply_reply(&self, outcome: &mut Outcome, packet: &[u8]) -> bool {
+        let body = &packet[CONTROL_HEADER_SIZE..];
+        // REJECT посреди сессии - сервер отключил нас
+        if control_type(packet) == control_types::HELLO_REJECT && matches!(*outcome, Outcome::Accepted(_)) {
+            *outcome = Outcome::Rejected(String::from_utf8_lossy(body).into_owned());
+            return true;
+        }
+        if *outcome != Outcome::Pending {
+            return false;
+        }
+
+        match control_type(packet) {
+            // Сервер выбрал версию, которую мы не умеем
+            control_types::HELLO_ACCEPT if !is_supported_version(control_version(packet)) => {
+                let version = control_version(packet);
+                *outcome = Outcome::VersionMismatch {
+                    server_min: version,
+                    server_max: version,
+                };
+                true
+            },
+            control_types::HELLO_ACCEPT => match SessionParams::read(body) {
+                Some(params) => {
+                    let read_u32 = |offset: usize| {
+                        body.get(offset..offset + 4)
+                            .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                    };
+                    let user_id = read_u32(SessionParams::ENCODED_SIZE);
+                    // Сервер без токена при согласованном SESSION_TOKEN - ошибка сервера;
+                    // тогда шлем голос как обычно и полагаемся на проверку адреса
+                    let token = if params.features & features::SESSION_TOKEN != 0 {
+                        read_u32(SessionParams::ENCODED_SIZE + 4)
+                    } else {
+                        0
+                    };
+                    self.user_id.store(user_id, Ordering::Relaxed);
+                    self.session_token.store(token, Ordering::Relaxed);
+                    *outcome = Outcome::Accepted(params);
+                    true
+                },
+                None => false,
+            },
+            control_types::HELLO_REJECT => {
+                *outcome = Outcome::Rejected(String::from_utf8_lossy(body).into_owned());
+                true
+            },
+            control_types::VERSION_MISMATCH if body.len() >= 2 => {
+                *outcome = Outcome::VersionMismatch {
+                    server_min: body[0],
+                    server_max: body[1],
+                };
+                true
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// LAN-режим без сервера: клиенты объявляют себя через mDNS/DNS-SD
+// (_nsvc._udp.local.) и шлют голос напрямую всем найденным соседям
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use tracing::{info, warn};
+
+use crate::logging::NET;
+
+pub const SERVICE_TYPE: &str = "_nsvc._udp.local.";
+
+pub struct LanSession {
+    daemon: ServiceDaemon,
+    own_fullname: String,
+    // Полное имя сервиса -> адрес соседа
+    peers: Mutex<HashMap<String, SocketAddr>>,
+    // Ошибки отправки по соседям: ушедший без прощания сосед дает ошибку
+    // на каждый кадр, в журнал идет только каждая сотая
+    send_errors: Mutex<HashMap<SocketAddr, u64>>,
+}
+
+impl LanSession {
+    // Регистрирует наш сервис и запускает поиск соседей
+    pub fn start(name: &str, port: u16) -> Result<Arc<Self>, String> {
+        let daemon = ServiceDaemon::new().map_err(|e| format!("mDNS daemon error: {}", e))?;
+
+        // Суффикс защищает от совпадения имен у нескольких клиентов
+        let instance = format!("{}-{:04x}", name, rand::random::<u16>());
+        let host_name = format!("{}.local.", instance);
+        let info = ServiceInfo::new(SERVICE_TYPE, &instance, &host_name, "", port, None)
+            .map_err(|e| format!("mDNS service info error: {}", e))?
+            .enable_addr_auto();
+        let own_fullname = info.get_fullname().to_string();
+
+        daemon.register(info).map_err(|e| format!("mDNS register error: {}", e))?;
+        let events = daemon.browse(SERVICE_TYPE).map_err(|e| format!("mDNS browse error: {}", e))?;
+
+        let session = Arc::new(LanSession {
+            daemon,
+            own_fullname,
+            peers: Mutex::new(HashMap::new()),
+            send_errors: Mutex::new(HashMap::new()),
+        });
+
+        info!(target: NET, "LAN: advertising {} on port {}", session.own_fullname, port);
+
+        let browser = session.clone();
+        thread::spawn(move || {
+            // Канал закрывается, когда демон останавливается
+            while let Ok(event) = events.recv() {
+                browser.handle_event(event);
+            }
+            info!(target: NET, "LAN discovery stopped");
+        });
+
+        Ok(session)
+    }
+
+    fn handle_event(&self, event: ServiceEvent) {
+        match event {
+            ServiceEvent::ServiceResolved(info) => {
+                if info.get_fullname() == self.own_fullname {
+                    return;
+                }
+
+                let addresses = info.get_addresses();
+                // Предпочитаем IPv4: сокет клиента слушает 0.0.0.0
+                let ip = addresses
+                    .iter()
+                    .find(|ip| matches!(ip, IpAddr::V4(_)))
+                    .or(addresses.iter().next());
+                if let Some(ip) = ip {
+                    let addr = SocketAddr::new(*ip, info.get_port());
+                    let previous = self.peers.lock().unwrap().insert(info.get_fullname().to_string(), addr);
+                    if previous != Some(addr) {
+                        info!(target: NET, "LAN peer discovered: {} at {}", info.get_fullname(), addr);
+                    }
+                }
+            },
+            ServiceEvent::ServiceRemoved(_, fullname) => {
+                if let Some(addr) = self.peers.lock().unwrap().remove(&fullname) {
+                    self.send_errors.lock().unwrap().remove(&addr);
+                    info!(target: NET, "LAN peer left: {} ({})", fullname, addr);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    pub fn peers(&self) -> Vec<SocketAddr> {
+        self.peers.lock().unwrap().values().copied().collect()
+    }
+
+    // Отмечает ошибку отправки соседу и возвращает их число
+    pub fn on_send_error(&self, peer: SocketAddr) -> u64 {
+        let mut errors = self.send_errors.lock().unwrap();
+        let count = errors.entry(peer).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    pub fn is_peer(&self, addr: SocketAddr) -> bool {
+        self.peers.lock().unwrap().values().any(|peer| *peer == addr)
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.daemon.unregister(&self.own_fullname);
+        if let Err(e) = self.daemon.shutdown() {
+            warn!(target: NET, "mDNS shutdown error: {}", e);
+        }
+        self.peers.lock().unwrap().clear();
+        self.send_errors.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,180 @@
+// Журнал клиента на tracing. Сообщения идут с уровнем и целью подсистемы
+// (AUDIO, NET, CODEC, остальное - CLIENT), а NsvcLayer пишет их в callback
+// хоста, если он задан, иначе в stderr и файл журнала. Файл журнала и время
+// в записях - с feature "file-log" (chrono).
+//
+// Для C-хостов слой ставится глобальным подписчиком при создании первого
+// клиента. Rust-программа со своим подписчиком получает события в него, а
+// прежний вывод может добавить через registry().with(logging::layer()).
+use std::borrow::Cow;
+use std::ffi::CString;
+use std::fmt::{self, Write as _};
+#[cfg(feature = "file-log")]
+use std::fs;
+#[cfg(feature = "file-log")]
+use std::io::Write as _;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+use crate::{log_levels, LogCallback};
+
+// Цели событий: по ним хост включает и выключает подсистемы
+pub const CLIENT: &str = "nsvc";
+pub const AUDIO: &str = "nsvc::audio";
+pub const NET: &str = "nsvc::net";
+pub const CODEC: &str = "nsvc::codec";
+
+// Callback журнала, userdata и минимальный уровень. Пока callback не задан,
+// журнал идет в stderr и файл LOG_FILE.
+pub(crate) static LOG_CALLBACK: Mutex<Option<(LogCallback, usize, i32)>> = Mutex::new(None);
+
+// Сообщения ниже этого уровня отбрасываются для любого получателя.
+// По умолчанию пишется все, кроме сведений о каждом пакете (TRACE).
+pub(crate) static LOG_LEVEL: AtomicI32 = AtomicI32::new(log_levels::DEBUG);
+
+// Файл журнала без callback; None - только stderr
+#[cfg(feature = "file-log")]
+pub(crate) static LOG_FILE: Mutex<Option<Cow<'static, str>>> = Mutex::new(Some(Cow::Borrowed("voice_client.log")));
+#[cfg(not(feature = "file-log"))]
+pub(crate) static LOG_FILE: Mutex<Option<Cow<'static, str>>> = Mutex::new(None);
+
+// Ротация файла журнала: дорос до LOG_MAX_BYTES - становится <путь>.1,
+// прежние сдвигаются, хранится LOG_KEEP_FILES старых. 0 байт - без ротации.
+pub const DEFAULT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_LOG_KEEP_FILES: u32 = 3;
+pub const MAX_LOG_KEEP_FILES: u32 = 100;
+pub(crate) static LOG_MAX_BYTES: AtomicU64 = AtomicU64::new(DEFAULT_LOG_MAX_BYTES);
+pub(crate) static LOG_KEEP_FILES: AtomicU32 = AtomicU32::new(DEFAULT_LOG_KEEP_FILES);
+
+// Ставит NsvcLayer глобальным подписчиком, если подписчика еще нет
+pub(crate) fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let _ = tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer()));
+    });
+}
+
+// Слой с выводом библиотеки для своего подписчика Rust-программы
+pub fn layer() -> NsvcLayer {
+    NsvcLayer
+}
+
+pub struct NsvcLayer;
+
+impl<S: Subscriber> Layer<S> for NsvcLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = level_code(metadata.level());
+        if level < LOG_LEVEL.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let message = visitor.message + &visitor.fields;
+
+        // Копируем, чтобы callback мог сам вызывать функции клиента
+        let callback = *LOG_CALLBACK.lock().unwrap();
+        if let Some((func, userdata, min_level)) = callback {
+            if level >= min_level {
+                let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+                func(level, message.as_ptr(), userdata as *mut c_void);
+            }
+            return;
+        }
+
+        let log_entry = format!("{}{} {}: {}", timestamp(), metadata.level(), metadata.target(), message);
+        eprintln!("{}", log_entry);
+
+        // Блокировка держится и на время ротации: два потока не сдвинут файлы дважды
+        #[cfg(feature = "file-log")]
+        if let Some(path) = LOG_FILE.lock().unwrap().as_deref() {
+            write_to_file(path, &log_entry);
+        }
+    }
+}
+
+// "[время] " в начале записи
+#[cfg(feature = "file-log")]
+fn timestamp() -> String {
+    format!("[{}] ", chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"))
+}
+
+#[cfg(not(feature = "file-log"))]
+fn timestamp() -> String {
+    String::new()
+}
+
+#[cfg(feature = "file-log")]
+fn write_to_file(path: &str, log_entry: &str) {
+    let open = || fs::OpenOptions::new().append(true).create(true).open(path);
+    let Ok(mut file) = open() else {
+        return;
+    };
+
+    let max_bytes = LOG_MAX_BYTES.load(Ordering::Relaxed);
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if max_bytes > 0 && size > 0 && size + log_entry.len() as u64 + 1 > max_bytes {
+        drop(file);
+        rotate(path, LOG_KEEP_FILES.load(Ordering::Relaxed));
+        file = match open() {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+    }
+    let _ = writeln!(file, "{}", log_entry);
+}
+
+// path.N-1 -> path.N, ..., path -> path.1; path.keep затирается
+#[cfg(feature = "file-log")]
+fn rotate(path: &str, keep: u32) {
+    if keep == 0 {
+        let _ = fs::remove_file(path);
+        return;
+    }
+    for n in (1..keep).rev() {
+        let _ = fs::rename(format!("{}.{}", path, n), format!("{}.{}", path, n + 1));
+    }
+    let _ = fs::rename(path, format!("{}.1", path));
+}
+
+// TRACE - сведения о каждом пакете, в log_levels он ниже DEBUG
+fn level_code(level: &Level) -> i32 {
+    match *level {
+        Level::TRACE => log_levels::TRACE,
+        Level::DEBUG => log_levels::DEBUG,
+        Level::INFO => log_levels::INFO,
+        Level::WARN => log_levels::WARNING,
+        Level::ERROR => log_levels::ERROR,
+    }
+}
+
+// Текст события и его поля в виде " имя=значение"
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
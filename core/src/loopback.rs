@@ -0,0 +1,73 @@
+// Самопроверка без сети: кадры из очереди отправки сразу декодируются и
+// идут в буфер воспроизведения, как голос собеседника. Пользователь слышит
+// себя через тот же кодер с теми же настройками Opus и тот же буфер, что в
+// разговоре, и так проверяет микрофон, динамики и кодек. Сокет не нужен:
+// сетевые потоки в этом режиме не запускаются, цикл крутит network.rs.
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use opus::Decoder;
+use tracing::warn;
+
+use crate::logging::CODEC;
+use crate::*;
+
+// Источник в микшере; в сеть этот адрес не попадает
+const LOOPBACK_SOURCE: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+pub(crate) struct Loopback {
+    pub queue: Arc<send_queue::SendQueue>,
+    playback_buffer: Arc<Mutex<PlaybackMixer>>,
+    stats: Arc<stats::Stats>,
+    decoder: Option<Decoder>,
+    pcm: Vec<i16>,
+    samples: Vec<f32>,
+}
+
+impl Loopback {
+    pub fn new(client: &VoiceClient) -> Self {
+        let decoder = Decoder::new(SAMPLE_RATE, CHANNELS)
+            .map_err(|e| warn!(target: CODEC, "Decoder creation error: {:?}", e))
+            .ok();
+        Loopback {
+            queue: client.send_queue.clone(),
+            playback_buffer: client.playback_buffer.clone(),
+            stats: client.stats.clone(),
+            decoder,
+            pcm: vec![0i16; MAX_DECODED_FRAME],
+            samples: Vec::with_capacity(MAX_DECODED_FRAME),
+        }
+    }
+
+    // Пакет, который ушел бы в сеть: MEDIA или Opus без заголовка
+    pub fn on_packet(&mut self, packet: &[u8]) {
+        let (ssrc, opus) = if is_control_packet(packet) {
+            match parse_media_packet(packet) {
+                Some((ssrc, _, range)) => (ssrc, &packet[range]),
+                None => return,
+            }
+        } else {
+            (0, packet)
+        };
+        // Пакет тишины
+        if opus.len() <= 1 {
+            return;
+        }
+        let Some(decoder) = &mut self.decoder else {
+            return;
+        };
+
+        self.stats.on_sent(packet.len());
+        self.stats.on_received(packet.len());
+        match decoder.decode(opus, &mut self.pcm, false) {
+            Ok(samples) => {
+                self.samples.clear();
+                self.samples.extend(self.pcm[..samples].iter().map(|&s| s as f32 / 32768.0));
+                if let Ok(mut playback) = self.playback_buffer.lock() {
+                    playback.push((LOOPBACK_SOURCE, ssrc), &self.samples);
+                }
+            },
+            Err(e) => warn!(target: CODEC, "Decoding error: {:?}", e),
+        }
+    }
+}
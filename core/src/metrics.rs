@@ -0,0 +1,173 @@
+// Метрики клиента для Prometheus (feature "metrics"): статистика сессии
+// (см. stats.rs) и состояние соединения в текстовом формате на
+// http://127.0.0.1:порт/metrics, чтобы строить графики качества долгих
+// сессий. Слушает только localhost; без feature сервер не открывается.
+use std::fmt::Write;
+use std::io;
+use std::sync::Mutex;
+
+use crate::connection_states;
+use crate::stats::VoiceClientStats;
+
+// Снимок метрик на запрос; None - клиента уже нет
+pub type Scrape = Box<dyn Fn() -> Option<String> + Send + Sync>;
+
+#[derive(Default)]
+pub struct Metrics {
+    server: Mutex<Option<server::Server>>,
+}
+
+impl Metrics {
+    // Уже открытый порт закрывается и открывается заново
+    pub fn start(&self, port: u16, scrape: Scrape) -> io::Result<()> {
+        let mut server = self.server.lock().unwrap();
+        *server = None;
+        *server = Some(server::Server::start(port, scrape)?);
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        *self.server.lock().unwrap() = None;
+    }
+}
+
+pub fn render(stats: &VoiceClientStats, state: i32) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+        let _ = write!(out, "# HELP nsvc_{0} {1}\n# TYPE nsvc_{0} {2}\nnsvc_{0} {3}\n", name, help, kind, value);
+    };
+    let connected = if state == connection_states::CONNECTED { 1.0 } else { 0.0 };
+    metric("connected", "gauge", "1 while connected to the server or peer.", connected);
+    metric("packets_sent_total", "counter", "Packets sent.", stats.packets_sent as f64);
+    metric("sent_bytes_total", "counter", "Bytes sent.", stats.bytes_sent as f64);
+    metric("packets_received_total", "counter", "Voice packets received.", stats.packets_received as f64);
+    metric("received_bytes_total", "counter", "Bytes received.", stats.bytes_received as f64);
+    let total_sent = stats.total_bytes_sent as f64;
+    metric("network_sent_bytes_total", "counter", "All traffic sent, with UDP and IP headers.", total_sent);
+    let total_received = stats.total_bytes_received as f64;
+    metric("network_received_bytes_total", "counter", "All traffic received, with UDP and IP headers.", total_received);
+    metric("packets_lost_total", "counter", "Voice packets lost, by sequence numbers.", stats.packets_lost as f64);
+    metric("packet_loss_ratio", "gauge", "Share of voice packets lost so far.", stats.loss_percent as f64 / 100.0);
+    metric("jitter_seconds", "gauge", "Smoothed packet interarrival jitter.", stats.jitter_ms as f64 / 1000.0);
+    metric("buffer_seconds", "gauge", "Audio waiting for playback.", stats.buffer_ms as f64 / 1000.0);
+    let underruns = stats.underruns as f64;
+    metric("playback_underruns_total", "counter", "Voice dropouts: the output device ran out of audio.", underruns);
+    metric("encode_seconds", "gauge", "Smoothed time to encode one frame.", stats.encode_us as f64 / 1_000_000.0);
+    metric("bitrate_bits_per_second", "gauge", "Current encoder bitrate.", stats.bitrate as f64);
+    // Пока RTT не измерен, ряда нет: ноль на графике был бы ложью
+    if stats.rtt_ms > 0 {
+        metric("rtt_seconds", "gauge", "Round-trip time to the server or peer.", stats.rtt_ms as f64 / 1000.0);
+    }
+    out
+}
+
+#[cfg(feature = "metrics")]
+mod server {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    use tracing::{debug, info, warn};
+
+    use super::Scrape;
+    use crate::logging::CLIENT;
+
+    // Как часто поток приема проверяет, не пора ли закрыть порт
+    const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub struct Server {
+        stop: Arc<AtomicBool>,
+        accept: Option<JoinHandle<()>>,
+    }
+
+    impl Server {
+        pub fn start(port: u16, scrape: Scrape) -> io::Result<Server> {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+            listener.set_nonblocking(true)?;
+            info!(target: CLIENT, "Metrics at http://127.0.0.1:{}/metrics", port);
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let stop_accept = stop.clone();
+            // Prometheus опрашивает редко: запросы по очереди, в потоке приема
+            let accept = thread::spawn(move || {
+                while !stop_accept.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = serve(stream, &scrape) {
+                                debug!(target: CLIENT, "Metrics request error: {}", e);
+                            }
+                        },
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+                        Err(e) => {
+                            warn!(target: CLIENT, "Metrics socket error: {}", e);
+                            thread::sleep(ACCEPT_INTERVAL);
+                        },
+                    }
+                }
+            });
+            Ok(Server { stop, accept: Some(accept) })
+        }
+    }
+
+    fn serve(mut stream: TcpStream, scrape: &Scrape) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Заголовки не нужны, но их надо дочитать: иначе закрытие сокета сбросит ответ
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let path = request_line.split(' ').nth(1).unwrap_or_default();
+        let (status, body) = match path.split('?').next() {
+            Some("/metrics") => match scrape() {
+                Some(body) => ("200 OK", body),
+                None => ("503 Service Unavailable", "client is gone\n".to_string()),
+            },
+            _ => ("404 Not Found", "see /metrics\n".to_string()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            // Порт свободен, только когда поток бросит сокет: его могут сразу
+            // открыть снова. Последнюю ссылку на клиент может отпустить и сам
+            // этот поток после запроса - себя он не ждет.
+            if let Some(accept) = self.accept.take() {
+                if accept.thread().id() != thread::current().id() {
+                    let _ = accept.join();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+mod server {
+    use std::io;
+
+    use super::Scrape;
+
+    pub struct Server;
+
+    impl Server {
+        pub fn start(_port: u16, _scrape: Scrape) -> io::Result<Server> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "built without the metrics feature"))
+        }
+    }
+}
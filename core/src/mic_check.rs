@@ -0,0 +1,118 @@
+// Проверка микрофона записью: несколько секунд с микрофона, затем они же в
+// динамиках - пропущенные через то же, что и голос в разговоре: детектор
+// голоса в текущем режиме передачи и Opus с текущими битрейтом и размером
+// кадра. Пользователь слышит себя так, как его услышат другие, а не сырой
+// микрофон. В отличие от самопроверки (loopback.rs) свой голос звучит
+// после, а не поверх речи. Сеть не нужна; у запущенного клиента микрофон
+// занят разговором, и проверка недоступна.
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use opus::{Application, Bitrate, Decoder, Encoder};
+use rtrb::RingBuffer;
+use tracing::{info, warn};
+
+use crate::logging::{AUDIO, CODEC};
+use crate::*;
+
+// Пишет duration с микрофона из настроек, затем проигрывает (см. play_local).
+// Новая проверка прерывает прежнюю.
+pub(crate) fn start(client: Arc<VoiceClient>, duration: Duration) -> Result<(), NsvcError> {
+    let capacity = (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+    let (mut producer, mut consumer) = RingBuffer::<f32>::new(capacity);
+    let input_level = client.input_level.clone();
+    // Уровень для индикатора - как у проверки микрофона без записи
+    let on_data = Box::new(move |data: &[f32]| {
+        update_level(&input_level, data);
+        let count = data.len().min(producer.slots());
+        if let Ok(chunk) = producer.write_chunk_uninit(count) {
+            chunk.fill_from_iter(data.iter().copied());
+        }
+    });
+
+    let device = client.input_device.lock().unwrap().clone();
+    *client.preview_stream.lock().unwrap() = None;
+    *client.local_output.lock().unwrap() = None;
+    let end = {
+        let mut slot = client.mic_check_stream.lock().unwrap();
+        *slot = None;
+        let (stream, name) = audio_backend(&client).open_input(device.as_deref(), on_data, Box::new(|_| {}))?;
+        info!(target: AUDIO, "Mic check: recording from {:?} for {:.1} s", name, duration.as_secs_f32());
+        let end = Instant::now() + duration;
+        *slot = Some((end, stream));
+        end
+    };
+
+    thread::spawn(move || {
+        thread::sleep(duration);
+        // Прерванная или замененная проверка не проигрывается
+        {
+            let mut slot = client.mic_check_stream.lock().unwrap();
+            if slot.as_ref().is_none_or(|(slot_end, _)| *slot_end != end) {
+                return;
+            }
+            *slot = None;
+        }
+        let mut recorded = Vec::with_capacity(capacity);
+        if let Ok(chunk) = consumer.read_chunk(consumer.slots()) {
+            let (first, second) = chunk.as_slices();
+            recorded.extend_from_slice(first);
+            recorded.extend_from_slice(second);
+            chunk.commit_all();
+        }
+        info!(target: AUDIO, "Mic check: playing back");
+        if let Err(e) = play_local(&client, as_heard(&client, &recorded)) {
+            warn!(target: AUDIO, "Mic check playback failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+// Запись такой, какой она дошла бы до собеседника. Кадры, которые детектор
+// не передал бы, заменяются тишиной.
+fn as_heard(client: &VoiceClient, samples: &[f32]) -> Vec<f32> {
+    let frame_size = client.frame_size.load(Ordering::Relaxed);
+    let mode = client.transmit_mode.load(Ordering::Relaxed);
+    let codec = Encoder::new(SAMPLE_RATE, CHANNELS, Application::Audio)
+        .and_then(|encoder| Decoder::new(SAMPLE_RATE, CHANNELS).map(|decoder| (encoder, decoder)));
+    let (mut encoder, mut decoder) = match codec {
+        Ok(codec) => codec,
+        Err(e) => {
+            warn!(target: CODEC, "Mic check codec error, playing unprocessed: {:?}", e);
+            return samples.to_vec();
+        },
+    };
+    let bitrate = client.bitrate.load(Ordering::Relaxed) as i32;
+    if let Err(e) = encoder.set_bitrate(Bitrate::Bits(bitrate)).and_then(|_| encoder.set_vbr(true)) {
+        warn!(target: CODEC, "Failed to configure encoder: {:?}", e);
+    }
+
+    let mut heard = Vec::with_capacity(samples.len());
+    let mut pcm = Vec::with_capacity(frame_size);
+    let mut decoded = vec![0i16; MAX_DECODED_FRAME];
+    let mut encoded = [0u8; MAX_PACKET_SIZE];
+    let mut silent_run = 0usize;
+    for frame in samples.chunks_exact(frame_size) {
+        let detected_silence = detects_silence(mode, &client.vad, frame);
+        silent_run = if detected_silence { silent_run + frame_size } else { 0 };
+        if detected_silence && silent_run > client.vad.hangover_samples {
+            heard.resize(heard.len() + frame_size, 0.0);
+            continue;
+        }
+        pcm.clear();
+        pcm.extend(frame.iter().map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16));
+        let result = encoder
+            .encode(&pcm, &mut encoded)
+            .and_then(|len| decoder.decode(&encoded[..len], &mut decoded, false));
+        match result {
+            Ok(len) => heard.extend(decoded[..len].iter().map(|&s| s as f32 / 32768.0)),
+            Err(e) => {
+                warn!(target: CODEC, "Mic check coding error: {:?}", e);
+                heard.resize(heard.len() + frame_size, 0.0);
+            },
+        }
+    }
+    heard
+}
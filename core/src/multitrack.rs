@@ -0,0 +1,212 @@
+// Запись по дорожкам: у каждого собеседника свой файл, у своего голоса -
+// свой, чтобы голоса можно было править по отдельности (подкаст, разбор
+// рейда). Дорожки пишутся из пакетов Opus: в .opus и .ogg - как пришли,
+// без перекодирования, в WAV - декодированными. Файлы - рядом с path:
+// <имя>_<ID>[_<ник>].<расширение>, для адресов без ID - <имя>_<адрес>, свой
+// голос - <имя>_mic. Дорожки синхронны: каждая начинается в момент старта
+// записи (до первого пакета - тишина), паузы заполняются тишиной, и в конце
+// все дополняются до одной длины - в редакторе их достаточно выровнять по
+// началу.
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use opus::Decoder;
+use tracing::{info, warn};
+
+use crate::logging::AUDIO;
+use crate::ogg::{self, OggWriter};
+use crate::recording::WavWriter;
+use crate::{users, SourceKey, CHANNELS, MAX_DECODED_FRAME, SAMPLE_RATE};
+
+// Тишина в WAV пишется кусками, а не одним буфером на всю паузу
+const SILENCE_CHUNK: usize = SAMPLE_RATE as usize / 10;
+
+// Собеседник или свой голос
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Track {
+    Remote(SourceKey),
+    Mic,
+}
+
+impl Track {
+    // У собеседника через сервер SSRC - его ID; без SSRC различаем по адресу
+    fn suffix(self, roster: &users::Roster) -> String {
+        match self {
+            Track::Remote((_, ssrc)) if ssrc != 0 => match roster.name(ssrc) {
+                Some(name) => format!("{}_{}", ssrc, file_name_part(&name)),
+                None => ssrc.to_string(),
+            },
+            Track::Remote((addr, _)) => file_name_part(&addr.to_string()),
+            Track::Mic => "mic".to_string(),
+        }
+    }
+}
+
+// Ник или адрес без символов, которые не везде допустимы в имени файла
+fn file_name_part(text: &str) -> String {
+    text.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+// Отсчеты при SAMPLE_RATE за время elapsed
+pub(crate) fn samples_in(elapsed: Duration) -> u64 {
+    elapsed.as_micros() as u64 * SAMPLE_RATE as u64 / 1_000_000
+}
+
+enum TrackWriter {
+    Ogg(OggWriter),
+    Wav {
+        wav: WavWriter,
+        decoder: Decoder,
+        pcm: Vec<i16>,
+        // Отсчетов в файле
+        written: u64,
+    },
+}
+
+impl TrackWriter {
+    fn create(path: &Path, ogg: bool) -> io::Result<Self> {
+        if ogg {
+            return OggWriter::create(path, &[]).map(TrackWriter::Ogg);
+        }
+        let decoder = Decoder::new(SAMPLE_RATE, CHANNELS).map_err(|e| io::Error::other(format!("decoder: {:?}", e)))?;
+        Ok(TrackWriter::Wav { wav: WavWriter::create(path)?, decoder, pcm: vec![0; MAX_DECODED_FRAME], written: 0 })
+    }
+
+    fn pad_to(&mut self, position: u64) -> io::Result<()> {
+        match self {
+            TrackWriter::Ogg(writer) => writer.pad_to(position),
+            TrackWriter::Wav { wav, written, .. } => {
+                let silence = [0i16; SILENCE_CHUNK];
+                while *written < position {
+                    let len = (position - *written).min(SILENCE_CHUNK as u64) as usize;
+                    wav.write(&silence[..len])?;
+                    *written += len as u64;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn write(&mut self, opus: &[u8], samples: u64) -> io::Result<()> {
+        match self {
+            TrackWriter::Ogg(writer) => writer.write_packet(opus, samples),
+            TrackWriter::Wav { wav, decoder, pcm, written } => {
+                // Испорченный пакет пропускается, как при воспроизведении
+                let Ok(decoded) = decoder.decode(opus, pcm, false) else {
+                    return Ok(());
+                };
+                wav.write(&pcm[..decoded])?;
+                *written += decoded as u64;
+                Ok(())
+            },
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            TrackWriter::Ogg(mut writer) => writer.finish(),
+            TrackWriter::Wav { wav, .. } => wav.finish(),
+        }
+    }
+}
+
+pub(crate) struct Tracks {
+    // path без расширения и само расширение
+    stem: PathBuf,
+    extension: String,
+    ogg: bool,
+    pub include_mic: bool,
+    started: Instant,
+    roster: Arc<users::Roster>,
+    writers: HashMap<Track, TrackWriter>,
+    // Конец самого длинного пакета, в отсчетах от начала записи
+    end: u64,
+    // Первая ошибка записи; после нее дорожки больше не пишутся
+    pub error: Option<io::Error>,
+}
+
+impl Tracks {
+    pub fn new(path: &Path, include_mic: bool, roster: Arc<users::Roster>) -> Self {
+        let extension = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default();
+        Tracks {
+            stem: path.with_extension(""),
+            extension,
+            ogg: is_ogg(path),
+            include_mic,
+            started: Instant::now(),
+            roster,
+            writers: HashMap::new(),
+            end: 0,
+            error: None,
+        }
+    }
+
+    // Как пути дорожек видит пользователь
+    pub fn pattern(&self) -> String {
+        format!("{}_*.{}", self.stem.display(), self.extension)
+    }
+
+    fn track_path(&self, track: Track) -> PathBuf {
+        let mut path = self.stem.as_os_str().to_owned();
+        path.push(format!("_{}.{}", track.suffix(&self.roster), self.extension));
+        path.into()
+    }
+
+    pub fn write(&mut self, track: Track, opus: &[u8]) {
+        self.write_at(track, opus, samples_in(self.started.elapsed()));
+    }
+
+    // position - когда пакет пришел, в отсчетах от начала записи
+    pub fn write_at(&mut self, track: Track, opus: &[u8], position: u64) {
+        if self.error.is_some() {
+            return;
+        }
+        let Some(samples) = ogg::packet_samples(opus) else {
+            return;
+        };
+        if !self.writers.contains_key(&track) {
+            let path = self.track_path(track);
+            match TrackWriter::create(&path, self.ogg) {
+                Ok(writer) => {
+                    info!(target: AUDIO, "Recording track {}", path.display());
+                    self.writers.insert(track, writer);
+                },
+                Err(e) => return self.fail(e),
+            }
+        }
+        let Some(writer) = self.writers.get_mut(&track) else {
+            return;
+        };
+        let start = position.saturating_sub(samples);
+        self.end = self.end.max(start + samples);
+        // Паузы до пакета - тишиной, чтобы дорожка шла от старта записи
+        if let Err(e) = writer.pad_to(start).and_then(|_| writer.write(opus, samples)) {
+            self.fail(e);
+        }
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        warn!(target: AUDIO, "Recording write error: {}", e);
+        self.error = Some(e);
+    }
+
+    // Дополняет дорожки тишиной до общей длины и закрывает их
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut result = self.error.take().map_or(Ok(()), Err);
+        for (_, mut writer) in self.writers.drain() {
+            let finished = writer.pad_to(self.end).and_then(|_| writer.finish());
+            result = result.and(finished);
+        }
+        result
+    }
+}
+
+// .opus и .ogg - пакеты как пришли, остальное - WAV
+pub(crate) fn is_ogg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("opus") || e.eq_ignore_ascii_case("ogg"))
+}
@@ -0,0 +1,142 @@
+// Mumble Link: общая память /MumbleLink.<uid>, в которую многие игры (и
+// моды к ним) каждый кадр пишут положение игрока и камеры. Формат задан
+// Mumble, плагин на каждую игру не нужен. Блок создаем сами, если игра
+// еще не запущена: она откроет тот же. Только Linux; wchar_t там 32 бита.
+use crate::positional::Vec3;
+
+// Что игра сообщила в последнем кадре
+pub struct Pose {
+    // Где игрок: от него слышат нас
+    pub avatar: Vec3,
+    // Откуда игрок слушает (камера); у версии 1 - то же, что avatar
+    pub camera: Vec3,
+    // Сервер, команда и т.п.: слышат друг друга только с одинаковым
+    // контекстом
+    pub context: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+mod shm {
+    use std::ffi::CString;
+    use std::io;
+    use std::ptr::{addr_of, NonNull};
+
+    use super::{Pose, Vec3};
+
+    // Раскладка LinkedMem из Mumble; направления, имена и описание не
+    // читаем, но они задают смещения остальных полей
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct LinkedMem {
+        ui_version: u32,
+        ui_tick: u32,
+        avatar_position: Vec3,
+        avatar_front: Vec3,
+        avatar_top: Vec3,
+        name: [u32; 256],
+        camera_position: Vec3,
+        camera_front: Vec3,
+        camera_top: Vec3,
+        identity: [u32; 256],
+        context_len: u32,
+        context: [u8; 256],
+        description: [u32; 2048],
+    }
+
+    pub struct MumbleLink {
+        mem: NonNull<LinkedMem>,
+        // ui_tick растет каждый кадр; стоит - игра закрыта или не пишет
+        last_tick: u32,
+    }
+
+    // Память только читаем, пишет в нее игра
+    unsafe impl Send for MumbleLink {}
+
+    impl MumbleLink {
+        pub fn open() -> io::Result<MumbleLink> {
+            let uid = unsafe { libc::getuid() };
+            let name = CString::new(format!("/MumbleLink.{}", uid)).unwrap();
+            let size = std::mem::size_of::<LinkedMem>();
+            unsafe {
+                let fd = libc::shm_open(name.as_ptr(), libc::O_RDWR | libc::O_CREAT, libc::S_IRUSR | libc::S_IWUSR);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Новый блок из нулей: ui_version 0 - игры еще нет
+                let mut stat: libc::stat = std::mem::zeroed();
+                if libc::fstat(fd, &mut stat) != 0
+                    || ((stat.st_size as usize) < size && libc::ftruncate(fd, size as libc::off_t) != 0)
+                {
+                    let e = io::Error::last_os_error();
+                    libc::close(fd);
+                    return Err(e);
+                }
+                let mem = libc::mmap(
+                    std::ptr::null_mut(),
+                    size,
+                    libc::PROT_READ,
+                    libc::MAP_SHARED,
+                    fd,
+                    0,
+                );
+                let e = io::Error::last_os_error();
+                libc::close(fd);
+                if mem == libc::MAP_FAILED {
+                    return Err(e);
+                }
+                Ok(MumbleLink { mem: NonNull::new_unchecked(mem as *mut LinkedMem), last_tick: 0 })
+            }
+        }
+
+        // None - игра не подключена или не обновляла блок с прошлого раза
+        pub fn read(&mut self) -> Option<Pose> {
+            let mem = self.mem.as_ptr();
+            // Игра пишет без синхронизации: читаем каждое поле заново
+            let (version, tick) = unsafe {
+                (addr_of!((*mem).ui_version).read_volatile(), addr_of!((*mem).ui_tick).read_volatile())
+            };
+            if version == 0 || tick == self.last_tick {
+                return None;
+            }
+            self.last_tick = tick;
+            let avatar = unsafe { addr_of!((*mem).avatar_position).read_volatile() };
+            if version < 2 {
+                return Some(Pose { avatar, camera: avatar, context: Vec::new() });
+            }
+            let (camera, context_len, context) = unsafe {
+                (
+                    addr_of!((*mem).camera_position).read_volatile(),
+                    addr_of!((*mem).context_len).read_volatile(),
+                    addr_of!((*mem).context).read_volatile(),
+                )
+            };
+            let context = context[..(context_len as usize).min(context.len())].to_vec();
+            Some(Pose { avatar, camera, context })
+        }
+    }
+
+    impl Drop for MumbleLink {
+        fn drop(&mut self) {
+            unsafe {
+                libc::munmap(self.mem.as_ptr() as *mut libc::c_void, std::mem::size_of::<LinkedMem>());
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use shm::MumbleLink;
+
+#[cfg(not(target_os = "linux"))]
+pub struct MumbleLink;
+
+#[cfg(not(target_os = "linux"))]
+impl MumbleLink {
+    pub fn open() -> std::io::Result<MumbleLink> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Mumble Link is only supported on Linux"))
+    }
+
+    pub fn read(&mut self) -> Option<Pose> {
+        None
+    }
+}
@@ -0,0 +1,36 @@
+// Выключенный микрофон (mute) и заглушенный звук (deafen) - отдельно от
+// режима передачи: PTT, переключение и активация голосом не включают
+// микрофон, пока он выключен. Заглушенный клиент и сам молчит: говорить,
+// не слыша ответов, некому. Снятие deafen возвращает прежний mute.
+// Смена состояния сопровождается сигналом, см. cues.rs.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Default)]
+pub(crate) struct MuteState {
+    muted: AtomicBool,
+    deafened: AtomicBool,
+}
+
+impl MuteState {
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::SeqCst)
+    }
+
+    pub fn is_deafened(&self) -> bool {
+        self.deafened.load(Ordering::SeqCst)
+    }
+
+    // Голос не передается ни в каком режиме
+    pub fn mic_blocked(&self) -> bool {
+        self.is_muted() || self.is_deafened()
+    }
+
+    // true - состояние изменилось
+    pub fn set_muted(&self, muted: bool) -> bool {
+        self.muted.swap(muted, Ordering::SeqCst) != muted
+    }
+
+    pub fn set_deafened(&self, deafened: bool) -> bool {
+        self.deafened.swap(deafened, Ordering::SeqCst) != deafened
+    }
+}
@@ -0,0 +1,849 @@
+// Сетевые циклы клиента: прием, отправка из очереди и keep-alive с
+// обслуживанием соединения (таймауты, повторный JOIN, TURN, P2P).
+// Логика шага - в Receiver, Sender и KeepAlive; крутят их либо три своих
+// потока, либо, с feature "tokio", задачи tokio. Задачи идут в runtime
+// хоста, если клиента запускают изнутри него, иначе - в общий runtime
+// библиотеки, так что async-приложение не держит лишних потоков на клиента.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use opus::Decoder;
+
+use crate::*;
+
+// Прием: разбор датаграмм, декодеры источников и события ростера
+struct Receiver {
+    link: Arc<ServerLink>,
+    connection: Arc<ConnectionTracker>,
+    stun: Arc<StunState>,
+    handshake: Arc<handshake::Handshake>,
+    channels: Arc<channels::ChannelState>,
+    errors: Arc<ErrorReporter>,
+    roster: Arc<users::Roster>,
+    events: Arc<events::EventSink>,
+    stats: Arc<stats::Stats>,
+    playback_buffer: Arc<Mutex<PlaybackMixer>>,
+    recording: Arc<recording::Recording>,
+    clip: Arc<clip::ClipBuffer>,
+    positional: Arc<positional::Positional>,
+    overlay: Arc<overlay::Overlay>,
+    cues: Arc<cues::CuePlayer>,
+    pcm: Vec<i16>,
+    // pcm после громкости; переиспользуется, чтобы не выделять память на пакет
+    samples: Vec<f32>,
+    // Отдельный декодер на каждый источник: у каждого свой поток Opus
+    sources: HashMap<SourceKey, RemoteSource>,
+    packet_counter: u64,
+    last_receive_time: Instant,
+    last_events_check: Instant,
+}
+
+impl Receiver {
+    fn new(client: &VoiceClient) -> Self {
+        Receiver {
+            link: client.link.clone(),
+            connection: client.connection.clone(),
+            stun: client.stun.clone(),
+            handshake: client.handshake.clone(),
+            channels: client.channels.clone(),
+            errors: client.errors.clone(),
+            roster: client.roster.clone(),
+            events: client.events.clone(),
+            stats: client.stats.clone(),
+            playback_buffer: client.playback_buffer.clone(),
+            recording: client.recording.clone(),
+            clip: client.clip.clone(),
+            positional: client.positional.clone(),
+            overlay: client.overlay.clone(),
+            cues: client.cues.clone(),
+            pcm: vec![0i16; MAX_DECODED_FRAME],
+            samples: Vec::with_capacity(MAX_DECODED_FRAME),
+            sources: HashMap::new(),
+            packet_counter: 0,
+            last_receive_time: Instant::now(),
+            last_events_check: Instant::now(),
+        }
+    }
+
+    // Раз в EVENTS_CHECK_INTERVAL: кто начал или перестал говорить, кто пришел
+    // и ушел, что случилось с соединением транспорта
+    fn check_events(&mut self) {
+        if self.last_events_check.elapsed() < EVENTS_CHECK_INTERVAL {
+            return;
+        }
+        self.last_events_check = Instant::now();
+        while let Some(event) = self.link.transport.poll_event() {
+            match event {
+                transport::TransportEvent::Connected(peer) => {
+                    info!(target: NET, "Transport connected to {}", peer);
+                },
+                transport::TransportEvent::Disconnected(reason) => {
+                    warn!(target: NET, "Transport disconnected: {}", reason);
+                    if self.connection.state() == connection_states::CONNECTED {
+                        self.connection.transition(connection_states::RECONNECTING);
+                    }
+                },
+            }
+        }
+        self.roster.expire_identities();
+        self.roster.poll_speaking();
+        // Сколько бы ни вошло и ни вышло за раз, звучит одно уведомление
+        let mut notification = None;
+        for change in self.roster.take_changes() {
+            match change {
+                users::Change::Joined(..) => notification = Some(notification_sounds::USER_JOINED),
+                users::Change::Left(..) => notification = notification.or(Some(notification_sounds::USER_LEFT)),
+                _ => {},
+            }
+            self.overlay.publish(&self.roster, &change);
+            emit_roster_change(&self.events, &self.roster, change);
+        }
+        if let Some(kind) = notification {
+            self.cues.play(cues::Cue::Notification(kind));
+        }
+    }
+
+    fn on_datagram(&mut self, data: &[u8], from: SocketAddr) {
+            self.link.traffic.on_received(data.len(), from);
+            // Punch-пакеты P2P приходят с адресов кандидатов, а не от сервера
+            if is_control_packet(data) && self.link.p2p.on_punch(from, data, &*self.link.transport) {
+                return;
+            }
+
+            let from_peer = self.link.p2p.peer() == Some(from);
+            let from_lan = self.link.lan().is_some_and(|lan| lan.is_peer(from));
+            let from_direct = self.link.direct.as_ref().is_some_and(|direct| direct.accept(from, data));
+            let mut ssrc = 0;
+            let payload = if from_peer {
+                self.link.p2p.on_peer_packet();
+                0..data.len()
+            } else if from_lan || from_direct {
+                0..data.len()
+            } else if self.link.multicast.is_some() {
+                match self.link.multicast_payload(data) {
+                    Some((packet_ssrc, range)) => {
+                        ssrc = packet_ssrc;
+                        range
+                    },
+                    None => return,
+                }
+            } else {
+                match self.link.server_payload(from, data) {
+                    Some(range) => range,
+                    None => {
+                        if !self.stun.handle_packet(from, data) && !self.link.is_turn_server(from) {
+                            debug!(target: NET, "Ignoring packet from unknown source {}", from);
+                        }
+                        return;
+                    }
+                }
+            };
+
+            // После отказа сервера остаемся в Failed, что бы ни приходило
+            if !from_peer && !self.handshake.is_rejected() {
+                self.connection.on_server_packet();
+            }
+
+            let mut packet = &data[payload];
+            let mut source_key = (from, ssrc);
+            let mut sequence = None;
+            let mut is_clip = false;
+
+            if is_control_packet(packet) {
+                let was_accepted = matches!(self.handshake.outcome(), handshake::Outcome::Accepted(_));
+                if self.handshake.on_reply(packet) {
+                    // Отказ посреди сессии: администратор сервера нас выгнал
+                    if was_accepted {
+                        if let handshake::Outcome::Rejected(reason) = self.handshake.outcome() {
+                            self.errors.report(error_codes::ACCESS_DENIED, &reason);
+                            self.connection.transition(connection_states::FAILED);
+                        }
+                    }
+                    return;
+                }
+                // Пакеты чужой версии протокола не разбираем
+                if !is_supported_version(control_version(packet)) {
+                    return;
+                }
+                if let Some(reason) = self.channels.on_denied(packet) {
+                    self.errors.report(error_codes::ACCESS_DENIED, &reason);
+                    return;
+                }
+                // channels.rs общий с web/, где нет журнала: смену канала пишем здесь
+                let channel = self.channels.current();
+                if self.channels.on_packet(packet) {
+                    let joined = self.channels.current();
+                    if let Some(name) = joined.filter(|joined| channel.as_ref() != Some(joined)) {
+                        info!(target: NET, "Joined channel '{}'", name);
+                    }
+                    return;
+                }
+                if self.roster.on_packet(packet) {
+                    return;
+                }
+                // С сервером ники приходят в ROSTER, объявлениям соседей не верим
+                if let Some((id, name)) = users::parse_identity(packet) {
+                    if self.link.server_addr.is_none() && id != self.link.media_ssrc.load(Ordering::Relaxed) {
+                        self.roster.on_identity(id, name);
+                    }
+                    return;
+                }
+                // Текст принимаем от сервера и от собеседника в прямом звонке:
+                // P2P-пиры и соседи в LAN могли бы подписаться чужим ID
+                if let Some((sender, flags, text)) = text::parse_text(packet) {
+                    if !from_peer && (from_direct || self.link.server_addr.is_some()) {
+                        let private = flags & text::FLAG_PRIVATE != 0;
+                        self.events.emit(events::event_types::TEXT_MESSAGE, sender, private as i32, text);
+                    }
+                    return;
+                }
+                // Положение в игре - от тех же, что и текст
+                if let Some((sender, position, context)) = positional::parse_position(packet) {
+                    if !from_peer && (from_direct || self.link.server_addr.is_some()) {
+                        self.positional.on_remote(sender, position, context);
+                    }
+                    return;
+                }
+                if control_type(packet) == control_types::GOODBYE {
+                    if let Some(direct) = self.link.direct.as_ref().filter(|_| from_direct) {
+                        direct.on_goodbye(from);
+                    }
+                    return;
+                }
+                if control_type(packet) == control_types::KEEP_ALIVE {
+                    if let Some(rtt) = self.link.keep_alive_rtt(packet) {
+                        self.connection.set_rtt(rtt);
+                    } else if from_direct {
+                        // При прямом звонке собеседник заменяет сервер: возвращаем
+                        // его keep-alive, чтобы он мог измерить RTT
+                        let _ = self.link.transport.send_datagram(packet, from);
+                    }
+                    return;
+                }
+                if is_media_type(control_type(packet)) {
+                    is_clip = control_type(packet) == control_types::MEDIA_CLIP;
+                    match parse_media_packet(packet) {
+                        Some((media_ssrc, seq, range)) => {
+                            // От сервера и собеседников SSRC - это ID участника
+                            if range.len() > 1
+                                && self.roster.on_voice(media_ssrc)
+                                && self.link.server_addr.is_some()
+                            {
+                                if let Err(e) = self.link.send(&users::roster_request_packet()) {
+                                    warn!(target: NET, "Roster request error: {}", e);
+                                }
+                            }
+                            source_key = (from, media_ssrc);
+                            sequence = Some(seq);
+                            packet = &packet[range];
+                        },
+                        None => return,
+                    }
+                } else {
+                    if control_type(packet) == control_types::P2P_CANDIDATES
+                        && self.link.p2p.on_candidates(packet, self.link.transport.clone())
+                    {
+                        announce_p2p_candidates(&self.link, &self.stun);
+                    }
+                    return;
+                }
+            }
+
+            let size = packet.len();
+
+            // Пропускаем keep-alive пакеты
+            if size <= 1 {
+                return;
+            }
+            // Участника, заглушенного у нас, не декодируем вовсе
+            if self.roster.is_muted(source_key.1) {
+                return;
+            }
+
+            if size > 1 {
+                self.packet_counter += 1;
+                self.stats.on_received(data.len());
+
+                if self.packet_counter.is_multiple_of(1000) {
+                    // Забываем декодеры давно молчащих источников, в том числе
+                    // тех, чьи пакеты только отбрасываются
+                    self.sources.retain(|_, s| s.last_packet.elapsed() < SOURCE_IDLE_TIMEOUT);
+                }
+
+                let source = match self.sources.entry(source_key) {
+                    std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        match Decoder::new(SAMPLE_RATE, CHANNELS) {
+                            Ok(decoder) => entry.insert(RemoteSource {
+                                decoder,
+                                last_packet: Instant::now(),
+                                replay: replay::ReplayWindow::new(),
+                                duplicates_dropped: 0,
+                                replays_dropped: 0,
+                                jitter: stats::Jitter::default(),
+                                last_clip_packet: None,
+                            }),
+                            Err(e) => {
+                                warn!(target: CODEC, "Decoder creation error: {:?}", e);
+                                return;
+                            }
+                        }
+                    }
+                };
+
+                // Повторно декодированный дубликат дает слышимое заикание
+                if let Some(seq) = sequence {
+                    let previous = source.replay.highest();
+                    match source.replay.check(seq) {
+                        replay::Verdict::Fresh => {
+                            self.stats.on_sequence(previous, seq);
+                            self.stats.set_jitter(source.jitter.on_packet(seq));
+                        },
+                        // Переподключился с тем же ID: старая нумерация больше не в счет
+                        replay::Verdict::Restarted => {
+                            info!(
+                                target: NET,
+                                "Source {} (ssrc {:08x}) restarted numbering at #{}",
+                                source_key.0, source_key.1, seq
+                            );
+                            self.stats.on_sequence(None, seq);
+                            self.stats.set_jitter(source.jitter.on_packet(seq));
+                        },
+                        replay::Verdict::Duplicate => {
+                            source.duplicates_dropped += 1;
+                            if source.duplicates_dropped % 100 == 1 {
+                                warn!(
+                                    target: NET,
+                                    "Dropped duplicate packet #{} from {} (ssrc {:08x}), {} duplicates so far",
+                                    seq, source_key.0, source_key.1, source.duplicates_dropped
+                                );
+                            }
+                            return;
+                        },
+                        replay::Verdict::TooOld => {
+                            source.replays_dropped += 1;
+                            if source.replays_dropped % 50 == 1 {
+                                warn!(
+                                    target: NET,
+                                    "Dropped stale or replayed packet #{} from {} (ssrc {:08x}), {} dropped so far",
+                                    seq, source_key.0, source_key.1, source.replays_dropped
+                                );
+                            }
+                            return;
+                        },
+                    }
+                }
+                source.last_packet = Instant::now();
+                if is_clip {
+                    if source.last_clip_packet.is_none_or(|last| last.elapsed() > CLIP_GAP) {
+                        self.events.emit(events::event_types::CLIP_STARTED, source_key.1, 0, "");
+                    }
+                    source.last_clip_packet = Some(source.last_packet);
+                }
+                self.recording.on_remote_packet(source_key, packet);
+                self.clip.on_packet(multitrack::Track::Remote(source_key), packet);
+
+                match source.decoder.decode(packet, &mut self.pcm, false) {
+                    Ok(samples) => {
+                        let receive_time = Instant::now();
+                        let delay = receive_time.duration_since(self.last_receive_time);
+                        self.last_receive_time = receive_time;
+
+                        // Говорит ли участник - по звуку, а не по пакетам: в
+                        // непрерывной передаче они идут и в тишине
+                        if sequence.is_some() {
+                            self.roster.on_level(source_key.1, frame_rms(&self.pcm[..samples]));
+                        }
+                        let volume = self.roster.volume(source_key.1) * self.positional.gain(source_key.1);
+                        self.samples.clear();
+                        self.samples.extend(self.pcm[..samples].iter().map(|&s| (s as f32) / 32768.0 * volume));
+
+                        let mut audio_buf = match self.playback_buffer.lock() {
+                            Ok(b) => b,
+                            Err(_) => return,
+                        };
+
+                        audio_buf.push(source_key, &self.samples);
+
+                        let buffer_ms = (audio_buf.buffered_samples() as f32 / SAMPLE_RATE as f32 * 1000.0) as u32;
+                        trace!(target: NET, packet = self.packet_counter, size, ?delay, buffer_ms, "Received packet");
+                    },
+                    Err(e) => {
+                        warn!(target: CODEC, "Decoding error: {:?}", e);
+                    }
+                }
+            }
+    }
+}
+
+// Громкость кадра для индикатора речи, 0.0-1.0
+fn frame_rms(pcm: &[i16]) -> f32 {
+    if pcm.is_empty() {
+        return 0.0;
+    }
+    let energy: f64 = pcm.iter().map(|&s| (s as f64 / 32768.0).powi(2)).sum();
+    (energy / pcm.len() as f64).sqrt() as f32
+}
+
+// Отправка из очереди с выдержкой PACING_INTERVAL
+struct Sender {
+    link: Arc<ServerLink>,
+    queue: Arc<send_queue::SendQueue>,
+    stats: Arc<stats::Stats>,
+    last_send: Instant,
+    reported_drops: u64,
+}
+
+impl Sender {
+    fn new(client: &VoiceClient) -> Self {
+        Sender {
+            link: client.link.clone(),
+            queue: client.send_queue.clone(),
+            stats: client.stats.clone(),
+            last_send: Instant::now(),
+            reported_drops: 0,
+        }
+    }
+
+    // Сколько подождать перед следующей отправкой
+    fn pacing_delay(&self) -> Duration {
+        send_queue::PACING_INTERVAL.saturating_sub(self.last_send.elapsed())
+    }
+
+    // false - буфер сокета полон, стоит повторить через миллисекунду.
+    // Сокет неблокирующий: немного ждем, а не теряем пакет сразу
+    fn try_send(&self, packet: &[u8], attempts: u32) -> bool {
+        match self.link.send_media(packet) {
+            Ok(sent) => {
+                self.stats.on_sent(sent);
+                true
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock && attempts < 5 => false,
+            Err(e) => {
+                warn!(target: NET, "Send error: {}", e);
+                true
+            }
+        }
+    }
+
+    fn on_finished(&mut self) {
+        self.last_send = Instant::now();
+
+        let dropped = self.queue.dropped();
+        if dropped != self.reported_drops {
+            warn!(target: NET, "Send queue overflow: {} packets dropped in total", dropped);
+            self.reported_drops = dropped;
+        }
+    }
+}
+
+// Keep-alive и обслуживание соединения раз в MAINTENANCE_INTERVAL
+struct KeepAlive {
+    running: Arc<AtomicBool>,
+    link: Arc<ServerLink>,
+    stun: Arc<StunState>,
+    is_transmitting: Arc<AtomicBool>,
+    mute: Arc<mute::MuteState>,
+    connection: Arc<ConnectionTracker>,
+    handshake: Arc<handshake::Handshake>,
+    interval_ms: Arc<AtomicU64>,
+    channels: Arc<channels::ChannelState>,
+    nickname: Arc<Mutex<String>>,
+    counter: u64,
+    last_keep_alive: Option<Instant>,
+    last_identity: Option<Instant>,
+    last_maintenance: Instant,
+    target: String,
+}
+
+impl KeepAlive {
+    fn new(client: &VoiceClient) -> Self {
+        KeepAlive {
+            running: client.running.clone(),
+            link: client.link.clone(),
+            stun: client.stun.clone(),
+            is_transmitting: client.is_transmitting.clone(),
+            mute: client.mute.clone(),
+            connection: client.connection.clone(),
+            handshake: client.handshake.clone(),
+            interval_ms: client.keep_alive_interval_ms.clone(),
+            channels: client.channels.clone(),
+            nickname: client.nickname.clone(),
+            counter: 0,
+            last_keep_alive: None,
+            last_identity: None,
+            last_maintenance: Instant::now(),
+            target: match client.link.server_addr {
+                Some(addr) => addr.to_string(),
+                None => "LAN peers".to_string(),
+            },
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.load(Ordering::Relaxed))
+    }
+
+    // Пауза перед следующим tick. tick сам решает, что пора делать, поэтому
+    // просыпаемся часто: остановка не должна ждать цикл по секунде
+    fn wait(&self) -> Duration {
+        self.interval().min(EVENTS_CHECK_INTERVAL)
+    }
+
+    // false - сервер пропал и клиент остановлен, цикл пора завершать
+    fn tick(&mut self) -> bool {
+        let interval = self.interval();
+        // Голос сам держит соединение; без него (в том числе при mute) нужен keep-alive
+        let sending_voice = self.is_transmitting.load(Ordering::SeqCst) && !self.mute.mic_blocked();
+        if self.last_keep_alive.is_none_or(|t| t.elapsed() >= interval) && !sending_voice {
+            self.last_keep_alive = Some(Instant::now());
+            self.counter += 1;
+            // До рукопожатия и со старыми серверами - однобайтовый keep-alive
+            let result = if matches!(self.handshake.outcome(), handshake::Outcome::Accepted(_)) {
+                self.link.send_keep_alive(&self.link.keep_alive_packet())
+            } else {
+                self.link.send_keep_alive(&[0u8; 1])
+            };
+            match result {
+                Ok(_) => {
+                    trace!(target: NET, packet = self.counter, to = %self.target, rtt = ?self.connection.rtt(), "Sent keep-alive");
+                },
+                Err(e) => {
+                    warn!(target: NET, "Keep-alive send error: {}", e);
+                    if self.connection.state() == connection_states::CONNECTED {
+                        self.connection.transition(connection_states::RECONNECTING);
+                    }
+                }
+            }
+        }
+
+        // Без сервера ники соседям раздаем сами, даже пока идет голос
+        let identity_due = self.last_identity.is_none_or(|t| t.elapsed() >= users::IDENTITY_INTERVAL);
+        if self.link.server_addr.is_none() && identity_due {
+            self.last_identity = Some(Instant::now());
+            let nickname = self.nickname.lock().unwrap().clone();
+            if !nickname.is_empty() {
+                let packet = users::identity_packet(self.link.media_ssrc.load(Ordering::Relaxed), &nickname);
+                if let Err(e) = self.link.send_keep_alive(&packet) {
+                    debug!(target: NET, "Identity send error: {}", e);
+                }
+            }
+        }
+
+        if self.last_maintenance.elapsed() < MAINTENANCE_INTERVAL {
+            return true;
+        }
+        self.last_maintenance = Instant::now();
+        self.connection.check_timeouts();
+
+        // Сервер пропал совсем: останавливаем циклы, чтобы не крутить
+        // прием и декодирование впустую, и сообщаем хосту через callback
+        if self.link.server_addr.is_some() && self.connection.server_silence_exceeded() {
+            warn!(target: NET, "No packets from {} for too long, disconnecting", self.target);
+            self.running.store(false, Ordering::SeqCst);
+            if let Some(turn) = self.link.turn() {
+                turn.release();
+            }
+            self.link.p2p.reset("Server silent");
+            self.connection.transition(connection_states::DISCONNECTED);
+            return false;
+        }
+
+        // JOIN мог потеряться, а после переподключения сервер нас не помнит
+        if self.link.server_addr.is_some() && matches!(self.handshake.outcome(), handshake::Outcome::Accepted(_)) {
+            if let Some(join) = self.channels.pending_join() {
+                if let Err(e) = self.link.send(&join) {
+                    warn!(target: NET, "Channel join send error: {}", e);
+                }
+            }
+        }
+
+        // Прямой путь не отвечает - пробуем TURN relay, если он настроен
+        if let Some(turn) = self.link.turn() {
+            if turn.is_active() {
+                turn.maintain();
+            } else if self.connection.waiting_for().is_some_and(|d| d > SERVER_TIMEOUT) {
+                if let Some(server_addr) = self.link.server_addr {
+                    turn.start_allocation(server_addr);
+                }
+            }
+        }
+
+        self.link.p2p.maintain(&*self.link.transport);
+        if self.link.p2p.should_announce() {
+            announce_p2p_candidates(&self.link, &self.stun);
+        }
+        true
+    }
+}
+
+// Сколько циклов сети еще идет: остановка клиента ждет их выхода, чтобы
+// после voice_client_free ни один не обратился к хосту
+#[derive(Default)]
+pub(crate) struct Workers {
+    count: Mutex<usize>,
+    done: Condvar,
+}
+
+// Держит цикл в счете, пока жив
+pub(crate) struct Worker(Arc<Workers>);
+
+impl Workers {
+    fn enter(self: &Arc<Self>) -> Worker {
+        *self.count.lock().unwrap() += 1;
+        Worker(self.clone())
+    }
+
+    pub(crate) fn wait(&self) {
+        // Однопоточный runtime хоста не выполнит задачи, пока мы его держим;
+        // к хосту они и так не пробьются, см. events::EventSink::close
+        #[cfg(feature = "tokio")]
+        if tokio::runtime::Handle::try_current()
+            .is_ok_and(|handle| handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::CurrentThread)
+        {
+            return;
+        }
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            count = self.done.wait(count).unwrap();
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        *self.0.count.lock().unwrap() -= 1;
+        self.0.done.notify_all();
+    }
+}
+
+// Запускает прием, отправку и keep-alive; они работают, пока client.running
+#[cfg(not(feature = "tokio"))]
+pub(crate) fn spawn(client: &VoiceClient) -> Result<(), NsvcError> {
+    use std::thread;
+
+    let running = client.running.clone();
+    let mut receiver = Receiver::new(client);
+    let worker = client.workers.enter();
+    thread::spawn(move || {
+        let _worker = worker;
+        info!(target: NET, "Starting audio receiver thread");
+
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        while running.load(Ordering::SeqCst) {
+            receiver.check_events();
+            match receiver.link.transport.recv(&mut buf) {
+                Ok((received, from)) => receiver.on_datagram(&buf[..received], from),
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1));
+                },
+                Err(e) => {
+                    warn!(target: NET, "Receive error: {}", e);
+                }
+            }
+        }
+
+        info!(target: NET, "Audio receiver thread stopped");
+    });
+
+    let running = client.running.clone();
+    let mut sender = Sender::new(client);
+    let worker = client.workers.enter();
+    thread::spawn(move || {
+        let _worker = worker;
+        info!(target: NET, "Starting send thread");
+
+        while running.load(Ordering::SeqCst) {
+            let packet = match sender.queue.pop(Duration::from_millis(100)) {
+                Some(packet) => packet,
+                None => continue,
+            };
+            let delay = sender.pacing_delay();
+            if !delay.is_zero() {
+                thread::sleep(delay);
+            }
+            let mut attempts = 0;
+            while !sender.try_send(&packet, attempts) {
+                attempts += 1;
+                thread::sleep(Duration::from_millis(1));
+            }
+            sender.queue.recycle(packet);
+            sender.on_finished();
+        }
+
+        info!(target: NET, "Send thread stopped");
+    });
+
+    let running = client.running.clone();
+    let mut keep_alive = KeepAlive::new(client);
+    let worker = client.workers.enter();
+    thread::spawn(move || {
+        let _worker = worker;
+        info!(target: NET, "Starting keep-alive thread");
+
+        while running.load(Ordering::SeqCst) {
+            thread::sleep(keep_alive.wait());
+            if !keep_alive.tick() {
+                break;
+            }
+        }
+
+        info!(target: NET, "Keep-alive thread stopped");
+    });
+    Ok(())
+}
+
+// Самопроверка без сети (см. loopback.rs): один цикл вместо приема и отправки
+#[cfg(not(feature = "tokio"))]
+pub(crate) fn spawn_loopback(client: &VoiceClient) -> Result<(), NsvcError> {
+    let running = client.running.clone();
+    let mut loopback = loopback::Loopback::new(client);
+    let worker = client.workers.enter();
+    std::thread::spawn(move || {
+        let _worker = worker;
+        info!(target: AUDIO, "Starting loopback thread");
+
+        while running.load(Ordering::SeqCst) {
+            if let Some(packet) = loopback.queue.pop(Duration::from_millis(100)) {
+                loopback.on_packet(&packet);
+                loopback.queue.recycle(packet);
+            }
+        }
+
+        info!(target: AUDIO, "Loopback thread stopped");
+    });
+    Ok(())
+}
+
+// Runtime хоста, если клиента запускают из него; иначе общий runtime библиотеки.
+// Хост, который завершает свой runtime, сначала останавливает клиента
+#[cfg(feature = "tokio")]
+fn runtime() -> Result<tokio::runtime::Handle, NsvcError> {
+    use std::sync::OnceLock;
+
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return Ok(handle);
+    }
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime.handle().clone());
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("nsvc-net")
+        .enable_all()
+        .build()
+        .map_err(NsvcError::Runtime)?;
+    Ok(RUNTIME.get_or_init(|| runtime).handle().clone())
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) fn spawn(client: &VoiceClient) -> Result<(), NsvcError> {
+    let runtime = runtime()?;
+    // Принимаем через копию сокета, зарегистрированную в реакторе tokio;
+    // отправка и STUN по-прежнему идут через неблокирующий std-сокет
+    let socket = {
+        let _guard = runtime.enter();
+        let Some(socket) = client.link.transport.udp_socket() else {
+            return Err(NsvcError::Runtime(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "tokio backend needs a UDP transport",
+            )));
+        };
+        let socket = socket.try_clone().map_err(NsvcError::Runtime)?;
+        tokio::net::UdpSocket::from_std(socket).map_err(NsvcError::Runtime)?
+    };
+
+    let running = client.running.clone();
+    let mut receiver = Receiver::new(client);
+    let worker = client.workers.enter();
+    runtime.spawn(async move {
+        let _worker = worker;
+        info!(target: NET, "Starting audio receiver task");
+
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        while running.load(Ordering::SeqCst) {
+            receiver.check_events();
+            // Ждем не дольше EVENTS_CHECK_INTERVAL: иначе в тишине не заметим
+            // остановку и смену говорящих
+            match tokio::time::timeout(EVENTS_CHECK_INTERVAL, socket.recv_from(&mut buf)).await {
+                Ok(Ok((received, from))) => receiver.on_datagram(&buf[..received], from),
+                Ok(Err(e)) => warn!(target: NET, "Receive error: {}", e),
+                Err(_) => {},
+            }
+        }
+
+        info!(target: NET, "Audio receiver task stopped");
+    });
+
+    let running = client.running.clone();
+    let mut sender = Sender::new(client);
+    let worker = client.workers.enter();
+    runtime.spawn(async move {
+        let _worker = worker;
+        info!(target: NET, "Starting send task");
+
+        while running.load(Ordering::SeqCst) {
+            let packet = match sender.queue.pop_async(Duration::from_millis(100)).await {
+                Some(packet) => packet,
+                None => continue,
+            };
+            let delay = sender.pacing_delay();
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            let mut attempts = 0;
+            while !sender.try_send(&packet, attempts) {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            sender.queue.recycle(packet);
+            sender.on_finished();
+        }
+
+        info!(target: NET, "Send task stopped");
+    });
+
+    let running = client.running.clone();
+    let mut keep_alive = KeepAlive::new(client);
+    let worker = client.workers.enter();
+    runtime.spawn(async move {
+        let _worker = worker;
+        info!(target: NET, "Starting keep-alive task");
+
+        while running.load(Ordering::SeqCst) {
+            tokio::time::sleep(keep_alive.wait()).await;
+            if !keep_alive.tick() {
+                break;
+            }
+        }
+
+        info!(target: NET, "Keep-alive task stopped");
+    });
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) fn spawn_loopback(client: &VoiceClient) -> Result<(), NsvcError> {
+    let running = client.running.clone();
+    let mut loopback = loopback::Loopback::new(client);
+    let worker = client.workers.enter();
+    runtime()?.spawn(async move {
+        let _worker = worker;
+        info!(target: AUDIO, "Starting loopback task");
+
+        while running.load(Ordering::SeqCst) {
+            if let Some(packet) = loopback.queue.pop_async(Duration::from_millis(100)).await {
+                loopback.on_packet(&packet);
+                loopback.queue.recycle(packet);
+            }
+        }
+
+        info!(target: AUDIO, "Loopback task stopped");
+    });
+    Ok(())
+}
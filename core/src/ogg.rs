@@ -0,0 +1,172 @@
+// Файлы Ogg/Opus (RFC 7845) из готовых пакетов Opus - без перекодирования.
+// Пишут их запись разговора в клиенте (recording.rs) и запись каналов на
+// сервере. Паузы заполняются пакетами тишины, поэтому дорожки одной записи
+// начинаются в момент ее старта и сводятся по времени. Читает их
+// audio_file.rs - файлы для передачи в микрофон.
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::SAMPLE_RATE;
+
+// Пакет Opus с 20 мс тишины (CELT, полная полоса)
+pub const SILENCE_PACKET: [u8; 3] = [0xF8, 0xFF, 0xFE];
+pub const SILENCE_SAMPLES: u64 = 960;
+
+// Флаги заголовка страницы
+const FIRST_PAGE: u8 = 0x02;
+const LAST_PAGE: u8 = 0x04;
+
+// Число отсчетов (48 кГц) в пакете Opus по его TOC-байту (RFC 6716, 3.1)
+pub fn packet_samples(packet: &[u8]) -> Option<u64> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+    // Длительность кадра в отсчетах 48 кГц
+    let frame = match config {
+        0..=11 => [480, 960, 1920, 2880][(config & 3) as usize],
+        12..=15 => [480, 960][(config & 1) as usize],
+        _ => [120, 240, 480, 960][(config & 3) as usize],
+    };
+    let frames = match toc & 3 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1)? & 0x3F) as u64,
+    };
+    Some(frame * frames)
+}
+
+// CRC страниц Ogg: полином 0x04C11DB7 без отражения
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0;
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// Пакеты первого логического потока файла, склеенные из сегментов страниц.
+// Оборванная последняя страница (файл дописывался, когда его прервали)
+// отбрасывается, прочитанное до нее остается.
+pub fn read_packets(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut serial = None;
+    let mut pos = 0;
+    while let Some(header) = data.get(pos..pos + 27).filter(|header| header.starts_with(b"OggS")) {
+        let page_serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        let Some(lacing) = data.get(pos + 27..pos + 27 + header[26] as usize) else {
+            break;
+        };
+        let mut body = pos + 27 + lacing.len();
+        let end = body + lacing.iter().map(|&len| len as usize).sum::<usize>();
+        if end > data.len() {
+            break;
+        }
+        if *serial.get_or_insert(page_serial) == page_serial {
+            // Сегмент короче 255 байт завершает пакет
+            for &len in lacing {
+                packet.extend_from_slice(&data[body..body + len as usize]);
+                body += len as usize;
+                if len < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+        }
+        pos = end;
+    }
+    packets
+}
+
+// Поток Ogg с одним логическим потоком Opus, по пакету на страницу.
+// Последний пакет придерживается, чтобы пометить его страницу концом потока.
+pub struct OggWriter {
+    file: BufWriter<File>,
+    serial: u32,
+    page_seq: u32,
+    granule: u64,
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl OggWriter {
+    // tags - комментарии OpusTags вида "DATE=..."
+    pub fn create(path: &Path, tags: &[String]) -> io::Result<Self> {
+        let mut writer = OggWriter {
+            file: BufWriter::new(File::create(path)?),
+            serial: rand::random(),
+            page_seq: 0,
+            granule: 0,
+            pending: None,
+        };
+
+        // OpusHead: версия 1, моно, без pre-skip, исходная частота, без усиления
+        let mut head = b"OpusHead".to_vec();
+        head.push(1);
+        head.push(1);
+        head.extend_from_slice(&0u16.to_le_bytes());
+        head.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        head.push(0);
+        writer.write_page(&head, 0, FIRST_PAGE)?;
+
+        let vendor = b"NSVC";
+        let mut opus_tags = b"OpusTags".to_vec();
+        opus_tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        opus_tags.extend_from_slice(vendor);
+        opus_tags.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+        for tag in tags {
+            opus_tags.extend_from_slice(&(tag.len() as u32).to_le_bytes());
+            opus_tags.extend_from_slice(tag.as_bytes());
+        }
+        writer.write_page(&opus_tags, 0, 0)?;
+
+        Ok(writer)
+    }
+
+    fn write_page(&mut self, packet: &[u8], granule: u64, flags: u8) -> io::Result<()> {
+        let mut page = Vec::with_capacity(27 + packet.len() / 255 + 1 + packet.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0);
+        page.push(flags);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.page_seq.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push((packet.len() / 255 + 1) as u8);
+        page.extend(std::iter::repeat_n(255u8, packet.len() / 255));
+        page.push((packet.len() % 255) as u8);
+        page.extend_from_slice(packet);
+
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        self.page_seq += 1;
+        self.file.write_all(&page)
+    }
+
+    pub fn write_packet(&mut self, packet: &[u8], samples: u64) -> io::Result<()> {
+        if let Some((previous, granule)) = self.pending.take() {
+            self.write_page(&previous, granule, 0)?;
+        }
+        self.granule += samples;
+        self.pending = Some((packet.to_vec(), self.granule));
+        Ok(())
+    }
+
+    // Дополняет поток тишиной до заданной позиции
+    pub fn pad_to(&mut self, position: u64) -> io::Result<()> {
+        while self.granule + SILENCE_SAMPLES <= position {
+            self.write_packet(&SILENCE_PACKET, SILENCE_SAMPLES)?;
+        }
+        Ok(())
+    }
+
+    // Повторный вызов только сбрасывает буфер
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some((last, granule)) = self.pending.take() {
+            self.write_page(&last, granule, LAST_PAGE)?;
+        }
+        self.file.flush()
+    }
+}
@@ -0,0 +1,186 @@
+// Кто говорит - для оверлеев поверх игр. Клиент слушает локальный
+// Unix-сокет и пишет каждому подключившемуся поток JSON, по объекту на
+// строку: сначала участники канала, потом изменения.
+//   {"event":"joined","id":7,"nickname":"anna","speaking":false}
+//   {"event":"speaking","id":7,"nickname":"anna","speaking":true}
+//   {"event":"left","id":7,"nickname":"anna","speaking":false}
+// Себя в потоке нет, как и в событиях USER_JOINED. Оверлей, который не
+// успевает читать, отключается: ждать его сетевой поток не может.
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::users;
+
+// Сокет по умолчанию: в XDG_RUNTIME_DIR, он доступен только пользователю
+pub fn default_path() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    dir.join("nsvc-overlay.sock")
+}
+
+fn line(event: &str, id: u32, nickname: &str, speaking: bool) -> String {
+    format!(
+        "{{\"event\":\"{}\",\"id\":{},\"nickname\":\"{}\",\"speaking\":{}}}\n",
+        event,
+        id,
+        escape(nickname),
+        speaking
+    )
+}
+
+// Строка JSON: кавычки, обратная косая черта и управляющие символы
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Default)]
+pub struct Overlay {
+    server: Mutex<Option<server::Server>>,
+}
+
+impl Overlay {
+    // Уже открытый сокет закрывается и открывается заново по path
+    pub fn start(&self, path: PathBuf, roster: Arc<users::Roster>) -> io::Result<()> {
+        let mut server = self.server.lock().unwrap();
+        *server = None;
+        *server = Some(server::Server::start(path, roster)?);
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        *self.server.lock().unwrap() = None;
+    }
+
+    pub fn publish(&self, roster: &users::Roster, change: &users::Change) {
+        let server = self.server.lock().unwrap();
+        let Some(server) = server.as_ref() else {
+            return;
+        };
+        let line = match change {
+            users::Change::Joined(id, name) | users::Change::Listed(id, name) => line("joined", *id, name, false),
+            users::Change::Left(id, name) => line("left", *id, name, false),
+            users::Change::Speaking(id, speaking) => {
+                line("speaking", *id, &roster.name(*id).unwrap_or_default(), *speaking)
+            },
+        };
+        server.send(&line);
+    }
+}
+
+#[cfg(unix)]
+mod server {
+    use std::io::{self, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use tracing::{info, warn};
+
+    use super::line;
+    use crate::logging::CLIENT;
+    use crate::users;
+
+    // Как часто поток приема проверяет, не пора ли закрыть сокет
+    const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub struct Server {
+        path: PathBuf,
+        overlays: Arc<Mutex<Vec<UnixStream>>>,
+        stop: Arc<AtomicBool>,
+    }
+
+    impl Server {
+        pub fn start(path: PathBuf, roster: Arc<users::Roster>) -> io::Result<Server> {
+            // Сокет, оставшийся после упавшего клиента, занимает путь; живой -
+            // значит, оверлеи уже обслуживает другой клиент
+            if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                if UnixStream::connect(&path).is_ok() {
+                    return Err(io::ErrorKind::AddrInUse.into());
+                }
+                std::fs::remove_file(&path)?;
+            }
+            let listener = UnixListener::bind(&path)?;
+            listener.set_nonblocking(true)?;
+            info!(target: CLIENT, "Overlay socket listening at {}", path.display());
+
+            let overlays = Arc::new(Mutex::new(Vec::new()));
+            let stop = Arc::new(AtomicBool::new(false));
+            let (overlays_accept, stop_accept) = (overlays.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop_accept.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            // Под замком: изменения, пришедшие во время приветствия, его дождутся
+                            let mut overlays = overlays_accept.lock().unwrap();
+                            if let Some(stream) = welcome(stream, &roster) {
+                                overlays.push(stream);
+                            }
+                        },
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+                        Err(e) => {
+                            warn!(target: CLIENT, "Overlay socket error: {}", e);
+                            thread::sleep(ACCEPT_INTERVAL);
+                        },
+                    }
+                }
+            });
+            Ok(Server { path, overlays, stop })
+        }
+
+        pub fn send(&self, line: &str) {
+            self.overlays.lock().unwrap().retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+        }
+    }
+
+    // Новому оверлею - кто уже в канале
+    fn welcome(mut stream: UnixStream, roster: &users::Roster) -> Option<UnixStream> {
+        stream.set_nonblocking(true).ok()?;
+        let own_id = roster.own_id();
+        for (id, name, speaking) in roster.users() {
+            if id != own_id {
+                stream.write_all(line("joined", id, &name, speaking).as_bytes()).ok()?;
+            }
+        }
+        Some(stream)
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Именованные каналы Windows пока не поддерживаются
+#[cfg(not(unix))]
+mod server {
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use crate::users;
+
+    pub struct Server;
+
+    impl Server {
+        pub fn start(_path: PathBuf, _roster: Arc<users::Roster>) -> io::Result<Server> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "overlay socket is only supported on Unix"))
+        }
+
+        pub fn send(&self, _line: &str) {}
+    }
+}
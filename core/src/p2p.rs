@@ -0,0 +1,306 @@
+// P2P-режим для разговора двоих: клиенты обмениваются кандидатами через
+// сервер, пробивают NAT встречными UDP-пакетами и дальше шлют голос напрямую.
+// Если пробить не удалось, голос продолжает идти через сервер.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::logging::NET;
+use crate::{control_packet, control_type, control_types, CONTROL_HEADER_SIZE};
+use crate::transport::Transport;
+
+const PUNCH_INTERVAL: Duration = Duration::from_millis(100);
+const PUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+// Как часто рассылаем кандидатов, пока прямой путь не установлен
+const CANDIDATES_INTERVAL: Duration = Duration::from_secs(5);
+// Пауза перед повторной попыткой после неудачи
+const RETRY_AFTER_FAILURE: Duration = Duration::from_secs(30);
+// Если от пира ничего не приходит, возвращаемся на сервер
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct P2pState {
+    peer_token: Option<u32>,
+    candidates: Vec<SocketAddr>,
+    punching: bool,
+    peer_addr: Option<SocketAddr>,
+    last_peer_packet: Instant,
+    last_candidates_sent: Option<Instant>,
+    failed_at: Option<Instant>,
+}
+
+pub struct P2pSession {
+    enabled: AtomicBool,
+    // Случайный токен, по которому пир узнает наши punch-пакеты
+    token: u32,
+    state: Mutex<P2pState>,
+}
+
+fn write_addr(buf: &mut Vec<u8>, addr: SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            buf.push(4);
+            buf.extend_from_slice(&ip.octets());
+        },
+        IpAddr::V6(ip) => {
+            buf.push(6);
+            buf.extend_from_slice(&ip.octets());
+        },
+    }
+    buf.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+fn read_addr(data: &[u8], pos: &mut usize) -> Option<SocketAddr> {
+    let family = *data.get(*pos)?;
+    let ip_len = match family {
+        4 => 4,
+        6 => 16,
+        _ => return None,
+    };
+    let start = *pos + 1;
+    let end = start + ip_len + 2;
+    if end > data.len() {
+        return None;
+    }
+
+    let ip = if family == 4 {
+        IpAddr::V4(Ipv4Addr::new(data[start], data[start + 1], data[start + 2], data[start + 3]))
+    } else {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&data[start..start + 16]);
+        IpAddr::V6(Ipv6Addr::from(octets))
+    };
+    let port = u16::from_be_bytes([data[start + ip_len], data[start + ip_len + 1]]);
+    *pos = end;
+
+    Some(SocketAddr::new(ip, port))
+}
+
+fn read_token(data: &[u8]) -> Option<u32> {
+    let body = data.get(CONTROL_HEADER_SIZE..CONTROL_HEADER_SIZE + 4)?;
+    Some(u32::from_be_bytes([body[0], body[1], body[2], body[3]]))
+}
+
+// Локальный адрес интерфейса, через который идет трафик к серверу
+pub fn local_candidate(transport: &dyn Transport, server_addr: SocketAddr) -> Option<SocketAddr> {
+    let port = transport.local_addr().ok()?.port();
+    let bind_addr = if server_addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let probe = UdpSocket::bind(bind_addr).ok()?;
+    probe.connect(server_addr).ok()?;
+    let ip = probe.local_addr().ok()?.ip();
+    Some(SocketAddr::new(ip, port))
+}
+
+impl P2pSession {
+    pub fn new() -> Self {
+        P2pSession {
+            enabled: AtomicBool::new(false),
+            token: rand::random(),
+            state: Mutex::new(P2pState {
+                peer_token: None,
+                candidates: Vec::new(),
+                punching: false,
+                peer_addr: None,
+                last_peer_packet: Instant::now(),
+                last_candidates_sent: None,
+                failed_at: None,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            self.reset("P2P disabled");
+        }
+    }
+
+    // Адрес пира, если прямой путь установлен
+    pub fn peer(&self) -> Option<SocketAddr> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.state.lock().unwrap().peer_addr
+    }
+
+    pub fn reset(&self, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.peer_addr.is_some() || state.punching {
+            warn!(target: NET, "{}, falling back to server relay", reason);
+        }
+        state.peer_token = None;
+        state.candidates.clear();
+        state.punching = false;
+        state.peer_addr = None;
+    }
+
+    // Нужно ли сейчас разослать кандидатов через сервер
+    pub fn should_announce(&self) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        let state = self.state.lock().unwrap();
+        if state.peer_addr.is_some() || state.punching {
+            return false;
+        }
+        if state.failed_at.is_some_and(|t| t.elapsed() < RETRY_AFTER_FAILURE) {
+            return false;
+        }
+        state.last_candidates_sent.is_none_or(|t| t.elapsed() >= CANDIDATES_INTERVAL)
+    }
+
+    pub fn candidates_packet(&self, candidates: &[SocketAddr]) -> Vec<u8> {
+        self.state.lock().unwrap().last_candidates_sent = Some(Instant::now());
+
+        let mut body = Vec::with_capacity(5 + candidates.len() * 19);
+        body.extend_from_slice(&self.token.to_be_bytes());
+        body.push(candidates.len() as u8);
+        for addr in candidates {
+            write_addr(&mut body, *addr);
+        }
+        control_packet(control_types::P2P_CANDIDATES, &body)
+    }
+
+    // Кандидаты пира, пришедшие через сервер; запускает пробивание NAT.
+    // Возвращает true, если пробивание только что началось и пиру нужно
+    // ответить своими кандидатами.
+    pub fn on_candidates(self: &Arc<Self>, data: &[u8], transport: Arc<dyn Transport>) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        let peer_token = match read_token(data) {
+            Some(token) => token,
+            None => return false,
+        };
+        let mut pos = CONTROL_HEADER_SIZE + 4;
+        let count = match data.get(pos) {
+            Some(count) => *count as usize,
+            None => return false,
+        };
+        pos += 1;
+
+        let mut candidates = Vec::with_capacity(count);
+        for _ in 0..count {
+            match read_addr(data, &mut pos) {
+                Some(addr) => candidates.push(addr),
+                None => break,
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(known) = state.peer_token {
+            if known != peer_token {
+                // Кандидаты от второго собеседника - это уже не разговор двоих
+                drop(state);
+                self.reset("More than one peer in the channel");
+                self.state.lock().unwrap().failed_at = Some(Instant::now());
+                return false;
+            }
+        }
+        if state.peer_addr.is_some() || state.punching {
+            return false;
+        }
+
+        info!(target: NET, "P2P: received {} candidates from peer, starting hole punching", candidates.len());
+        state.peer_token = Some(peer_token);
+        state.candidates = candidates;
+        state.punching = true;
+        drop(state);
+
+        let session = self.clone();
+        thread::spawn(move || session.punch(transport));
+        true
+    }
+
+    fn punch(&self, transport: Arc<dyn Transport>) {
+        let packet = control_packet(control_types::P2P_PUNCH, &self.token.to_be_bytes());
+        let started = Instant::now();
+
+        while started.elapsed() < PUNCH_TIMEOUT && self.is_enabled() {
+            let candidates = {
+                let state = self.state.lock().unwrap();
+                if state.peer_addr.is_some() || !state.punching {
+                    return;
+                }
+                state.candidates.clone()
+            };
+
+            for addr in candidates {
+                let _ = transport.send_datagram(&packet, addr);
+            }
+            thread::sleep(PUNCH_INTERVAL);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.peer_addr.is_none() && state.punching {
+            warn!(target: NET, "P2P: hole punching failed, staying on server relay");
+            state.punching = false;
+            state.failed_at = Some(Instant::now());
+        }
+    }
+
+    // Обрабатывает PUNCH/PUNCH_ACK с любого адреса. Возвращает true, если пакет наш.
+    pub fn on_punch(&self, from: SocketAddr, data: &[u8], transport: &dyn Transport) -> bool {
+        let kind = control_type(data);
+        if kind != control_types::P2P_PUNCH && kind != control_types::P2P_PUNCH_ACK {
+            return false;
+        }
+
+        let token = match read_token(data) {
+            Some(token) => token,
+            None => return false,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if !self.is_enabled() || state.peer_token != Some(token) {
+            return true;
+        }
+
+        if kind == control_types::P2P_PUNCH {
+            let ack = control_packet(control_types::P2P_PUNCH_ACK, &self.token.to_be_bytes());
+            let _ = transport.send_datagram(&ack, from);
+        }
+
+        state.last_peer_packet = Instant::now();
+        if state.peer_addr != Some(from) {
+            info!(target: NET, "P2P: direct path to peer established via {}", from);
+            state.peer_addr = Some(from);
+            state.punching = false;
+            state.failed_at = None;
+        }
+
+        true
+    }
+
+    pub fn on_peer_packet(&self) {
+        self.state.lock().unwrap().last_peer_packet = Instant::now();
+    }
+
+    // Вызывается из keep-alive потока: поддерживает дыру в NAT и следит за пиром
+    pub fn maintain(&self, transport: &dyn Transport) {
+        let (peer, last_packet) = {
+            let state = self.state.lock().unwrap();
+            match state.peer_addr {
+                Some(peer) => (peer, state.last_peer_packet),
+                None => return,
+            }
+        };
+
+        if last_packet.elapsed() > PEER_TIMEOUT {
+            self.reset("P2P: peer stopped responding");
+            return;
+        }
+
+        let packet = control_packet(control_types::P2P_PUNCH, &self.token.to_be_bytes());
+        let _ = transport.send_datagram(&packet, peer);
+    }
+}
@@ -0,0 +1,267 @@
+// Позиционный звук: голос тех, кто далеко в игре, становится тише.
+// Положения берутся из Mumble Link (см. mumble_link.rs) - клиент читает
+// положение игрока и рассылает его участникам канала - или их задает сама
+// игра (voice_client_set_listener_position и _set_user_position). Вывод
+// моно, поэтому положение задает только громкость, не направление. Тело
+// POSITION: ID участника (u32), x, y, z (f32) и контекст игры. Клиент
+// пишет свой SSRC (0 для сервера), сервер при пересылке - ID отправителя.
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::logging::NET;
+use crate::mumble_link::MumbleLink;
+use crate::{control_packet, control_type, control_types, rolloff_models, VoiceClient, CONTROL_HEADER_SIZE};
+
+// Метры в системе координат игры
+pub type Vec3 = [f32; 3];
+
+pub const MAX_CONTEXT_LEN: usize = 255;
+// Затухание по умолчанию: ближе 2 м - полная громкость, дальше 50 м - тишина
+const DEFAULT_MIN_DISTANCE: f32 = 2.0;
+const DEFAULT_MAX_DISTANCE: f32 = 50.0;
+// Как часто читаем Mumble Link и рассылаем положение
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Игра столько не обновляла блок - она закрыта или на паузе
+const GAME_TIMEOUT: Duration = Duration::from_secs(5);
+// Положение участника, от которого столько нет новых, забываем
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn position_packet(user_id: u32, position: Vec3, context: &[u8]) -> Vec<u8> {
+    let context = &context[..context.len().min(MAX_CONTEXT_LEN)];
+    let mut body = Vec::with_capacity(16 + context.len());
+    body.extend_from_slice(&user_id.to_be_bytes());
+    for axis in position {
+        body.extend_from_slice(&axis.to_be_bytes());
+    }
+    body.extend_from_slice(context);
+    control_packet(control_types::POSITION, &body)
+}
+
+// (ID участника, положение, контекст); None для чужих и испорченных пакетов
+pub fn parse_position(packet: &[u8]) -> Option<(u32, Vec3, &[u8])> {
+    if control_type(packet) != control_types::POSITION {
+        return None;
+    }
+    let body = packet.get(CONTROL_HEADER_SIZE..)?;
+    let context = body.get(16..)?;
+    if context.len() > MAX_CONTEXT_LEN {
+        return None;
+    }
+    let word = |at: usize| [body[at], body[at + 1], body[at + 2], body[at + 3]];
+    let position = [f32::from_be_bytes(word(4)), f32::from_be_bytes(word(8)), f32::from_be_bytes(word(12))];
+    if !position.iter().all(|axis| axis.is_finite()) {
+        return None;
+    }
+    Some((u32::from_be_bytes(word(0)), position, context))
+}
+
+// Как громкость падает с расстоянием, см. rolloff_models
+#[derive(Clone, Copy)]
+pub struct Rolloff {
+    pub model: i32,
+    // Ближе - полная громкость
+    pub min_distance: f32,
+    // Дальше - тишина
+    pub max_distance: f32,
+}
+
+impl Default for Rolloff {
+    fn default() -> Self {
+        Rolloff {
+            model: rolloff_models::LINEAR,
+            min_distance: DEFAULT_MIN_DISTANCE,
+            max_distance: DEFAULT_MAX_DISTANCE,
+        }
+    }
+}
+
+impl Rolloff {
+    pub fn is_valid(&self) -> bool {
+        let model_ok = match self.model {
+            rolloff_models::LINEAR => true,
+            // На него делится расстояние
+            rolloff_models::INVERSE => self.min_distance > 0.0,
+            _ => false,
+        };
+        model_ok && self.max_distance.is_finite() && (0.0..self.max_distance).contains(&self.min_distance)
+    }
+
+    fn gain(&self, distance: f32) -> f32 {
+        if distance <= self.min_distance {
+            return 1.0;
+        }
+        if distance >= self.max_distance {
+            return 0.0;
+        }
+        match self.model {
+            // Как в OpenAL: вдвое дальше - вдвое тише
+            rolloff_models::INVERSE => self.min_distance / distance,
+            _ => (self.max_distance - distance) / (self.max_distance - self.min_distance),
+        }
+    }
+}
+
+struct Located {
+    position: Vec3,
+    context: Vec<u8>,
+    // Когда пришло из Mumble Link; None - задано игрой и не устаревает
+    updated: Option<Instant>,
+}
+
+impl Located {
+    fn is_fresh(&self, timeout: Duration) -> bool {
+        self.updated.is_none_or(|updated| updated.elapsed() < timeout)
+    }
+}
+
+#[derive(Default)]
+pub struct Positional {
+    // Открытый блок Mumble Link; None - Mumble Link не читаем
+    link: Mutex<Option<MumbleLink>>,
+    // Поток опроса запущен
+    polling: AtomicBool,
+    // Откуда слушаем мы (камера в игре)
+    listener: Mutex<Option<Located>>,
+    // Где участники, по ID (SSRC)
+    remote: Mutex<HashMap<u32, Located>>,
+    rolloff: Mutex<Rolloff>,
+}
+
+impl Positional {
+    pub fn enable(&self) -> io::Result<()> {
+        let mut link = self.link.lock().unwrap();
+        if link.is_none() {
+            *link = Some(MumbleLink::open()?);
+        }
+        Ok(())
+    }
+
+    // Положения из Mumble Link забываются; заданные игрой остаются
+    pub fn disable(&self) {
+        *self.link.lock().unwrap() = None;
+        let mut listener = self.listener.lock().unwrap();
+        if listener.as_ref().is_some_and(|listener| listener.updated.is_some()) {
+            *listener = None;
+        }
+        self.remote.lock().unwrap().retain(|_, located| located.updated.is_none());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.link.lock().unwrap().is_some()
+    }
+
+    // Игра сейчас сообщает положение
+    pub fn is_active(&self) -> bool {
+        self.listener.lock().unwrap().as_ref().is_some_and(|listener| listener.is_fresh(GAME_TIMEOUT))
+    }
+
+    // Положения, заданные игрой; Mumble Link, если он включен, заменит их своими
+    pub fn set_listener(&self, position: Vec3) {
+        *self.listener.lock().unwrap() = Some(Located { position, context: Vec::new(), updated: None });
+    }
+
+    pub fn set_remote(&self, id: u32, position: Vec3) {
+        self.remote.lock().unwrap().insert(id, Located { position, context: Vec::new(), updated: None });
+    }
+
+    // Все снова слышны с полной громкостью, пока положения не придут снова
+    pub fn clear(&self) {
+        *self.listener.lock().unwrap() = None;
+        self.remote.lock().unwrap().clear();
+    }
+
+    pub fn set_rolloff(&self, rolloff: Rolloff) {
+        *self.rolloff.lock().unwrap() = rolloff;
+    }
+
+    // Новое положение игрока для рассылки: (где он, контекст)
+    fn poll(&self) -> Option<(Vec3, Vec<u8>)> {
+        let pose = self.link.lock().unwrap().as_mut()?.read();
+        let mut listener = self.listener.lock().unwrap();
+        let Some(pose) = pose else {
+            if listener.as_ref().is_some_and(|listener| !listener.is_fresh(GAME_TIMEOUT)) {
+                *listener = None;
+            }
+            return None;
+        };
+        let updated = Some(Instant::now());
+        *listener = Some(Located { position: pose.camera, context: pose.context.clone(), updated });
+        Some((pose.avatar, pose.context))
+    }
+
+    pub fn on_remote(&self, id: u32, position: Vec3, context: &[u8]) {
+        let located = Located { position, context: context.to_vec(), updated: Some(Instant::now()) };
+        let mut remote = self.remote.lock().unwrap();
+        remote.retain(|_, other| other.is_fresh(REMOTE_TIMEOUT));
+        remote.insert(id, located);
+    }
+
+    // Множитель громкости участника. 1.0, если кто-то из нас не в игре или
+    // мы в разных играх и серверах (контекстах).
+    pub fn gain(&self, id: u32) -> f32 {
+        let listener = self.listener.lock().unwrap();
+        let Some(listener) = listener.as_ref().filter(|listener| listener.is_fresh(GAME_TIMEOUT)) else {
+            return 1.0;
+        };
+        let remote = self.remote.lock().unwrap();
+        let Some(speaker) = remote.get(&id).filter(|speaker| speaker.is_fresh(REMOTE_TIMEOUT)) else {
+            return 1.0;
+        };
+        if speaker.context != listener.context {
+            return 1.0;
+        }
+        let distance = listener
+            .position
+            .iter()
+            .zip(speaker.position)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum::<f32>()
+            .sqrt();
+        self.rolloff.lock().unwrap().gain(distance)
+    }
+}
+
+// Поток опроса Mumble Link; живет, пока клиент запущен и позиционный звук
+// включен. Второй поток не запускается.
+pub(crate) fn spawn(client: &VoiceClient) {
+    let positional = client.positional.clone();
+    if positional.polling.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let link = client.link.clone();
+    let running = client.running.clone();
+    let is_needed = move |positional: &Arc<Positional>| running.load(Ordering::SeqCst) && positional.is_enabled();
+    thread::spawn(move || loop {
+        while is_needed(&positional) {
+            if let Some((position, context)) = positional.poll() {
+                // Как текст: собеседнику в прямом звонке или серверу
+                let sent = match &link.direct {
+                    Some(direct) => match direct.peer() {
+                        Some(peer) => {
+                            let own_id = link.media_ssrc.load(Ordering::Relaxed);
+                            link.transport.send_datagram(&position_packet(own_id, position, &context), peer)
+                        },
+                        None => Ok(0),
+                    },
+                    None if link.server_addr.is_some() => link.send(&position_packet(0, position, &context)),
+                    None => Ok(0),
+                };
+                if let Err(e) = sent {
+                    debug!(target: NET, "Position send error: {}", e);
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        positional.polling.store(false, Ordering::SeqCst);
+        // Включили снова, пока поток завершался
+        if !is_needed(&positional) || positional.polling.swap(true, Ordering::SeqCst) {
+            break;
+        }
+    });
+}
@@ -0,0 +1,300 @@
+// Формат пакетов, общий для всех клиентов и сервера: заголовок управляющих
+// пакетов, их типы и голос в MEDIA. Модуль не зависит ни от звука, ни от
+// сокетов, поэтому его же собирают клиент для браузера (web/) и fuzz-цели
+// (fuzz/). Разбор не паникует ни на каких входных байтах.
+
+pub const SAMPLE_RATE: u32 = 48000;
+
+// Управляющие пакеты: CONTROL_MAGIC, версия протокола, тип, тело. Моно-Opus
+// никогда не начинается с 0x4E: в этом TOC-байте выставлен бит стерео.
+const CONTROL_MAGIC: [u8; 2] = [0x4E, 0x53];
+pub const CONTROL_HEADER_SIZE: usize = 4;
+// Диапазон версий протокола, которые понимает клиент
+pub const PROTOCOL_VERSION: u8 = 2;
+pub const MIN_PROTOCOL_VERSION: u8 = 2;
+
+pub mod control_types {
+    pub const HELLO: u8 = 0x01;
+    pub const HELLO_ACCEPT: u8 = 0x02;
+    pub const HELLO_REJECT: u8 = 0x03;
+    // Ответ сервера на HELLO неподдерживаемой версии: его min и max версии
+    pub const VERSION_MISMATCH: u8 = 0x04;
+    // Keep-alive: ID клиента (u32) + время отправки в мс (u64).
+    // Сервер возвращает его как есть: по нему считается RTT.
+    pub const KEEP_ALIVE: u8 = 0x05;
+    // Каналы: JOIN (имя и пароль), LEAVE, JOINED (имя, ответ сервера),
+    // запрос списка, сам список и отказ во входе (см. channels.rs)
+    pub const CHANNEL_JOIN: u8 = 0x06;
+    pub const CHANNEL_LEAVE: u8 = 0x07;
+    pub const CHANNEL_JOINED: u8 = 0x08;
+    pub const CHANNEL_LIST_REQUEST: u8 = 0x09;
+    pub const CHANNEL_LIST: u8 = 0x0A;
+    pub const CHANNEL_DENIED: u8 = 0x0B;
+    // Участники канала: запрос и список (ID, ник), см. users.rs
+    pub const ROSTER_REQUEST: u8 = 0x0C;
+    pub const ROSTER: u8 = 0x0D;
+    // Текстовое сообщение: ID участника, флаги, текст (см. text.rs)
+    pub const TEXT_MESSAGE: u8 = 0x0E;
+    // Клиент уходит: сервер (или собеседник в прямом звонке) убирает его
+    // сразу, не дожидаясь таймаута. Без тела.
+    pub const GOODBYE: u8 = 0x0F;
+    pub const P2P_CANDIDATES: u8 = 0x10;
+    pub const P2P_PUNCH: u8 = 0x11;
+    pub const P2P_PUNCH_ACK: u8 = 0x12;
+    // Положение в игре: ID участника, x, y, z, контекст (см. positional.rs)
+    pub const POSITION: u8 = 0x13;
+    // Ник отправителя без сервера: SSRC (u32) + ник (см. users.rs)
+    pub const IDENTITY: u8 = 0x14;
+    // Голос в multicast-группе: SSRC отправителя (u32) + пакет Opus
+    pub const MULTICAST_AUDIO: u8 = 0x20;
+    // Голос с заголовком: SSRC (u32), номер пакета (u32), пакет Opus
+    pub const MEDIA: u8 = 0x21;
+    // Записанный клип (voice_client_send_clip): формат MEDIA, но получатель
+    // знает, что это не живая речь. Только при features::CLIP.
+    pub const MEDIA_CLIP: u8 = 0x22;
+    // Шепот: голос только участникам из списка, а не всему каналу. Как
+    // MEDIA, но после номера пакета - число получателей (u8) и их ID (u32).
+    // Получатели в каждом пакете: потерянный пакет не оставит шепот
+    // включенным. Клиент шлет его только серверу с features::WHISPER, а
+    // сервер пересылает получателям обычным MEDIA.
+    pub const MEDIA_WHISPER: u8 = 0x23;
+    // Пакет-заполнитель для проверки MTU пути, получатели его игнорируют
+    pub const MTU_PROBE: u8 = 0x30;
+}
+
+pub fn control_header(kind: u8) -> [u8; CONTROL_HEADER_SIZE] {
+    [CONTROL_MAGIC[0], CONTROL_MAGIC[1], PROTOCOL_VERSION, kind]
+}
+
+pub fn control_packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(CONTROL_HEADER_SIZE + body.len());
+    packet.extend_from_slice(&control_header(kind));
+    packet.extend_from_slice(body);
+    packet
+}
+
+pub fn is_control_packet(data: &[u8]) -> bool {
+    data.len() >= CONTROL_HEADER_SIZE && data[..2] == CONTROL_MAGIC
+}
+
+// Разбирает управляющий пакет: (версия, тип, тело); None для голоса без заголовка
+pub fn parse_control_packet(data: &[u8]) -> Option<(u8, u8, &[u8])> {
+    if !is_control_packet(data) {
+        return None;
+    }
+    Some((control_version(data), control_type(data), &data[CONTROL_HEADER_SIZE..]))
+}
+
+// control_version и control_type - только после is_control_packet
+pub fn control_version(data: &[u8]) -> u8 {
+    data[2]
+}
+
+pub fn control_type(data: &[u8]) -> u8 {
+    data[3]
+}
+
+pub fn is_supported_version(version: u8) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+pub fn media_packet(ssrc: u32, seq: u32, opus: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(CONTROL_HEADER_SIZE + 8 + opus.len());
+    write_media_packet(&mut packet, ssrc, seq, opus);
+    packet
+}
+
+// media_packet в готовый буфер: на горячем пути клиент переиспользует буферы пакетов
+pub fn write_media_packet(out: &mut Vec<u8>, ssrc: u32, seq: u32, opus: &[u8]) {
+    out.clear();
+    out.extend_from_slice(&control_header(control_types::MEDIA));
+    out.extend_from_slice(&ssrc.to_be_bytes());
+    out.extend_from_slice(&seq.to_be_bytes());
+    out.extend_from_slice(opus);
+}
+
+// MEDIA и MEDIA_CLIP устроены одинаково
+pub fn is_media_type(kind: u8) -> bool {
+    kind == control_types::MEDIA || kind == control_types::MEDIA_CLIP
+}
+
+// Помечает готовый пакет MEDIA как клип
+pub fn mark_as_clip(packet: &mut [u8]) {
+    packet[3] = control_types::MEDIA_CLIP;
+}
+
+// Больше получателей в одном шепоте не бывает
+pub const MAX_WHISPER_TARGETS: usize = 16;
+
+// Как write_media_packet, но MEDIA_WHISPER для targets (не больше MAX_WHISPER_TARGETS)
+pub fn write_whisper_packet(out: &mut Vec<u8>, ssrc: u32, seq: u32, targets: &[u32], opus: &[u8]) {
+    let targets = &targets[..targets.len().min(MAX_WHISPER_TARGETS)];
+    out.clear();
+    out.extend_from_slice(&control_header(control_types::MEDIA_WHISPER));
+    out.extend_from_slice(&ssrc.to_be_bytes());
+    out.extend_from_slice(&seq.to_be_bytes());
+    out.push(targets.len() as u8);
+    for target in targets {
+        out.extend_from_slice(&target.to_be_bytes());
+    }
+    out.extend_from_slice(opus);
+}
+
+// Разбирает MEDIA_WHISPER: (SSRC, номер пакета, получатели, диапазон Opus)
+pub fn parse_whisper_packet(data: &[u8]) -> Option<(u32, u32, Vec<u32>, std::ops::Range<usize>)> {
+    let (ssrc, seq, rest) = parse_media_packet(data)?;
+    let count = *data.get(rest.start)? as usize;
+    if count > MAX_WHISPER_TARGETS {
+        return None;
+    }
+    let start = rest.start + 1;
+    let targets = data.get(start..start + count * 4)?;
+    let targets = targets.chunks_exact(4).map(|id| u32::from_be_bytes([id[0], id[1], id[2], id[3]])).collect();
+    Some((ssrc, seq, targets, start + count * 4..data.len()))
+}
+
+// Разбирает MEDIA или MEDIA_CLIP: (SSRC, номер пакета, диапазон Opus)
+pub fn parse_media_packet(data: &[u8]) -> Option<(u32, u32, std::ops::Range<usize>)> {
+    let header = data.get(CONTROL_HEADER_SIZE..CONTROL_HEADER_SIZE + 8)?;
+    let ssrc = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let seq = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    Some((ssrc, seq, CONTROL_HEADER_SIZE + 8..data.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_header_layout() {
+        assert_eq!(control_header(control_types::HELLO), [0x4E, 0x53, PROTOCOL_VERSION, control_types::HELLO]);
+    }
+
+    #[test]
+    fn control_packet_round_trip() {
+        let packet = control_packet(control_types::TEXT_MESSAGE, b"hello");
+        assert_eq!(packet.len(), CONTROL_HEADER_SIZE + 5);
+        assert!(is_control_packet(&packet));
+        assert_eq!(control_version(&packet), PROTOCOL_VERSION);
+        assert_eq!(control_type(&packet), control_types::TEXT_MESSAGE);
+        assert_eq!(parse_control_packet(&packet), Some((PROTOCOL_VERSION, control_types::TEXT_MESSAGE, &b"hello"[..])));
+    }
+
+    #[test]
+    fn empty_body_is_a_control_packet() {
+        let packet = control_packet(control_types::CHANNEL_LEAVE, &[]);
+        assert_eq!(parse_control_packet(&packet), Some((PROTOCOL_VERSION, control_types::CHANNEL_LEAVE, &[][..])));
+    }
+
+    #[test]
+    fn short_or_foreign_data_is_not_a_control_packet() {
+        for data in [&[][..], &[0x4E], &[0x4E, 0x53], &[0x4E, 0x53, PROTOCOL_VERSION], &[0x4E, 0x54, 2, 1], &[0x01]] {
+            assert!(!is_control_packet(data), "{:?}", data);
+            assert_eq!(parse_control_packet(data), None);
+        }
+    }
+
+    #[test]
+    fn mono_opus_never_looks_like_a_control_packet() {
+        // Бит 2 TOC-байта Opus - стерео; клиенты шлют только моно
+        for toc in (0..=u8::MAX).filter(|toc| toc & 0x04 == 0) {
+            assert!(!is_control_packet(&[toc, 0x53, PROTOCOL_VERSION, 0]), "TOC {:#04x}", toc);
+        }
+    }
+
+    #[test]
+    fn supported_versions() {
+        assert!(is_supported_version(PROTOCOL_VERSION));
+        assert!(is_supported_version(MIN_PROTOCOL_VERSION));
+        assert!(!is_supported_version(MIN_PROTOCOL_VERSION - 1));
+        assert!(!is_supported_version(PROTOCOL_VERSION + 1));
+    }
+
+    #[test]
+    fn media_packet_round_trip() {
+        let packet = media_packet(0xDEADBEEF, 42, &[1, 2, 3]);
+        assert_eq!(control_type(&packet), control_types::MEDIA);
+        let (ssrc, seq, opus) = parse_media_packet(&packet).unwrap();
+        assert_eq!((ssrc, seq), (0xDEADBEEF, 42));
+        assert_eq!(&packet[opus], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn media_packet_is_big_endian() {
+        let packet = media_packet(0x01020304, 0x05060708, &[]);
+        assert_eq!(&packet[CONTROL_HEADER_SIZE..], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn media_packet_with_empty_payload() {
+        let packet = media_packet(1, u32::MAX, &[]);
+        let (_, seq, opus) = parse_media_packet(&packet).unwrap();
+        assert_eq!(seq, u32::MAX);
+        assert!(opus.is_empty());
+    }
+
+    #[test]
+    fn write_media_packet_reuses_buffer() {
+        let mut buffer = media_packet(1, 1, &[0xFF; 100]);
+        let capacity = buffer.capacity();
+        write_media_packet(&mut buffer, 2, 3, &[4, 5]);
+        assert_eq!(buffer, media_packet(2, 3, &[4, 5]));
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn clip_packet_parses_like_media() {
+        let mut packet = media_packet(7, 8, &[9]);
+        mark_as_clip(&mut packet);
+        assert_eq!(control_type(&packet), control_types::MEDIA_CLIP);
+        assert!(is_media_type(control_type(&packet)));
+        assert!(!is_media_type(control_types::MULTICAST_AUDIO));
+        let (ssrc, seq, opus) = parse_media_packet(&packet).unwrap();
+        assert_eq!((ssrc, seq), (7, 8));
+        assert_eq!(&packet[opus], &[9]);
+    }
+
+    #[test]
+    fn whisper_packet_round_trip() {
+        let mut packet = Vec::new();
+        write_whisper_packet(&mut packet, 1, 2, &[7, 0x01020304], &[9, 9]);
+        assert_eq!(control_type(&packet), control_types::MEDIA_WHISPER);
+        assert!(!is_media_type(control_type(&packet)));
+        let (ssrc, seq, targets, opus) = parse_whisper_packet(&packet).unwrap();
+        assert_eq!((ssrc, seq), (1, 2));
+        assert_eq!(targets, vec![7, 0x01020304]);
+        assert_eq!(&packet[opus], &[9, 9]);
+    }
+
+    #[test]
+    fn whisper_targets_are_capped() {
+        let mut packet = Vec::new();
+        let targets: Vec<u32> = (0..MAX_WHISPER_TARGETS as u32 + 5).collect();
+        write_whisper_packet(&mut packet, 1, 2, &targets, &[3]);
+        let (_, _, parsed, opus) = parse_whisper_packet(&packet).unwrap();
+        assert_eq!(parsed, targets[..MAX_WHISPER_TARGETS]);
+        assert_eq!(&packet[opus], &[3]);
+
+        // Чужой пакет с лишними получателями не разбирается
+        packet[CONTROL_HEADER_SIZE + 8] = MAX_WHISPER_TARGETS as u8 + 1;
+        assert_eq!(parse_whisper_packet(&packet), None);
+    }
+
+    #[test]
+    fn truncated_whisper_packet_is_rejected() {
+        let mut packet = Vec::new();
+        write_whisper_packet(&mut packet, 1, 2, &[3, 4], &[]);
+        for len in 0..packet.len() {
+            assert_eq!(parse_whisper_packet(&packet[..len]), None, "length {}", len);
+        }
+        assert!(parse_whisper_packet(&packet).is_some());
+    }
+
+    #[test]
+    fn truncated_media_header_is_rejected() {
+        let packet = media_packet(1, 2, &[]);
+        for len in 0..packet.len() {
+            assert_eq!(parse_media_packet(&packet[..len]), None, "length {}", len);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// Задержка отпускания PTT: когда клавишу отпустили (или хост вызвал
+// voice_client_set_transmitting(false)), микрофон передает еще немного, и
+// последние слоги не обрезаются. Новое нажатие во время задержки просто
+// продолжает передачу. Проверка идет в callback захвата, поэтому без
+// блокировок: конец задержки хранится числом.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub(crate) struct ReleaseDelay {
+    delay: Duration,
+    epoch: Instant,
+    // До какого момента (мкс от epoch) передача еще идет; 0 - не идет
+    until_us: AtomicU64,
+}
+
+impl ReleaseDelay {
+    pub fn new(delay: Duration) -> Self {
+        ReleaseDelay { delay, epoch: Instant::now(), until_us: AtomicU64::new(0) }
+    }
+
+    fn now_us(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    // Передачу включили или выключили; was - что было до этого
+    pub fn on_change(&self, was: bool, transmitting: bool) {
+        if transmitting {
+            self.until_us.store(0, Ordering::SeqCst);
+        } else if was && !self.delay.is_zero() {
+            self.until_us.store(self.now_us() + self.delay.as_micros() as u64, Ordering::SeqCst);
+        }
+    }
+
+    // Клавишу уже отпустили, но задержка еще не кончилась
+    pub fn is_holding(&self) -> bool {
+        self.until_us.load(Ordering::SeqCst) > self.now_us()
+    }
+}
@@ -0,0 +1,161 @@
+// Обмен звуком с callback'ами устройств. Они работают в потоках реального
+// времени, и мьютекс, который в этот момент держит сетевой поток, дает
+// щелчки и пропуски (инверсия приоритетов). Поэтому callback'и только
+// пишут и читают SPSC-кольца rtrb без блокировок, а кодирование и
+// смешивание источников идут в своих потоках. Эти потоки завершаются сами,
+// когда поток звука удален и второй конец кольца брошен.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::{PlaybackMixer, SAMPLE_RATE};
+
+// Захват, который кодер еще не забрал: 200 мс
+const CAPTURE_RING_SAMPLES: usize = SAMPLE_RATE as usize / 5;
+// Сколько смешанного звука держать готовым для вывода: 20 мс
+const PLAYBACK_AHEAD_SAMPLES: usize = SAMPLE_RATE as usize / 50;
+const MIX_INTERVAL: Duration = Duration::from_millis(5);
+// Без сигнала от захвата кодер все равно просыпается: проверить, жив ли поток звука
+const ENCODER_WAKE_TIMEOUT: Duration = Duration::from_millis(50);
+// Звук кончился и пошел снова быстрее - это пропуск, а не пауза в речи
+const UNDERRUN_GAP: Duration = Duration::from_millis(100);
+
+// Сторона захвата для callback'а; на каждую порцию будит поток кодера
+pub struct CaptureWriter {
+    producer: Producer<f32>,
+    encoder: thread::Thread,
+}
+
+impl CaptureWriter {
+    pub fn write(&mut self, data: &[f32]) {
+        // Кольцо полно - кодер не успевает; лишнее выбрасываем, ждать нельзя
+        let count = data.len().min(self.producer.slots());
+        if let Ok(chunk) = self.producer.write_chunk_uninit(count) {
+            chunk.fill_from_iter(data.iter().copied());
+        }
+        self.encoder.unpark();
+    }
+
+    // Тишина вместо захвата: кодер идет в темпе устройства и без голоса
+    pub fn write_silence(&mut self, count: usize) {
+        let count = count.min(self.producer.slots());
+        if let Ok(chunk) = self.producer.write_chunk_uninit(count) {
+            chunk.fill_from_iter(std::iter::repeat(0.0));
+        }
+        self.encoder.unpark();
+    }
+}
+
+// Запускает поток кодера: on_samples получает захваченный звук в порядке записи
+pub fn spawn_encoder(mut on_samples: impl FnMut(&[f32]) + Send + 'static) -> CaptureWriter {
+    let (producer, mut consumer) = RingBuffer::<f32>::new(CAPTURE_RING_SAMPLES);
+    let encoder = thread::spawn(move || {
+        let mut samples = Vec::with_capacity(CAPTURE_RING_SAMPLES);
+        while !consumer.is_abandoned() {
+            thread::park_timeout(ENCODER_WAKE_TIMEOUT);
+            let Ok(chunk) = consumer.read_chunk(consumer.slots()) else {
+                continue;
+            };
+            let (first, second) = chunk.as_slices();
+            samples.clear();
+            samples.extend_from_slice(first);
+            samples.extend_from_slice(second);
+            chunk.commit_all();
+            if !samples.is_empty() {
+                on_samples(&samples);
+            }
+        }
+    });
+    CaptureWriter { producer, encoder: encoder.thread().clone() }
+}
+
+// Сторона воспроизведения для callback'а
+pub struct PlaybackReader {
+    consumer: Consumer<f32>,
+    // Только у вывода микшера
+    underruns: Option<Underruns>,
+}
+
+impl PlaybackReader {
+    // Недостающее до конца out заполняется тишиной
+    pub fn read(&mut self, out: &mut [f32]) {
+        let count = out.len().min(self.consumer.slots());
+        let (mixed, silence) = out.split_at_mut(count);
+        if let Ok(chunk) = self.consumer.read_chunk(count) {
+            let (first, second) = chunk.as_slices();
+            mixed[..first.len()].copy_from_slice(first);
+            mixed[first.len()..].copy_from_slice(second);
+            chunk.commit_all();
+        }
+        silence.fill(0.0);
+        if let Some(underruns) = &mut self.underruns {
+            underruns.on_read(count, out.len());
+        }
+    }
+}
+
+// Пропуски воспроизведения для статистики (см. stats.rs)
+struct Underruns {
+    count: Arc<AtomicU64>,
+    // Прошлому чтению звука хватило
+    playing: bool,
+    // Когда звук кончился посреди воспроизведения
+    starved: Option<Instant>,
+}
+
+impl Underruns {
+    fn on_read(&mut self, read: usize, wanted: usize) {
+        if read > 0 && self.starved.take().is_some_and(|at| at.elapsed() < UNDERRUN_GAP) {
+            self.count.fetch_add(1, Ordering::Relaxed);
+        }
+        if read < wanted && (read > 0 || self.playing) {
+            self.starved = Some(Instant::now());
+        }
+        self.playing = read == wanted;
+    }
+}
+
+// Кольцо, которое заполняет не поток микшера, а его хозяин (см. voice_output.rs)
+pub fn playback_ring(capacity: usize) -> (Producer<f32>, PlaybackReader) {
+    let (producer, consumer) = RingBuffer::<f32>::new(capacity);
+    (producer, PlaybackReader { consumer, underruns: None })
+}
+
+// Звук, готовый целиком (проверочный сигнал): кольцо заполняется сразу
+pub fn prefilled(samples: &[f32]) -> PlaybackReader {
+    let (mut producer, consumer) = RingBuffer::<f32>::new(samples.len().max(1));
+    if let Ok(chunk) = producer.write_chunk_uninit(samples.len()) {
+        chunk.fill_from_iter(samples.iter().copied());
+    }
+    PlaybackReader { consumer, underruns: None }
+}
+
+// Запускает поток, который смешивает очереди источников и держит готовыми
+// до PLAYBACK_AHEAD_SAMPLES. Тишину он не дописывает: новый голос не ждет
+// за ней в кольце. Пропуски считаются в underruns.
+pub fn spawn_mixer(mixer: Arc<Mutex<PlaybackMixer>>, underruns: Arc<AtomicU64>) -> PlaybackReader {
+    let (mut producer, consumer) = RingBuffer::<f32>::new(PLAYBACK_AHEAD_SAMPLES);
+    thread::spawn(move || {
+        let mut mixed = vec![0.0f32; PLAYBACK_AHEAD_SAMPLES];
+        while !producer.is_abandoned() {
+            let count = {
+                let mut mixer = match mixer.lock() {
+                    Ok(mixer) => mixer,
+                    Err(_) => return,
+                };
+                let count = producer.slots().min(mixer.buffered_samples());
+                mixer.mix_into(&mut mixed[..count]);
+                count
+            };
+            if let Ok(chunk) = producer.write_chunk_uninit(count) {
+                chunk.fill_from_iter(mixed[..count].iter().copied());
+            }
+            thread::sleep(MIX_INTERVAL);
+        }
+    });
+    let underruns = Underruns { count: underruns, playing: false, starved: None };
+    PlaybackReader { consumer, underruns: Some(underruns) }
+}
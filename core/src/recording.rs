@@ -0,0 +1,239 @@
+// Запись разговора: голоса собеседников без звуковых сигналов, а по
+// желанию и свой голос - пока он передается. Формат - по расширению файла.
+//
+// WAV (48 кГц, моно, 16 бит) - общий микс до общей громкости. Микшер и
+// кодер только складывают отсчеты в очереди, файл пишет свой поток по
+// часам: чего к сроку не пришло, дописывается тишиной, поэтому паузы в
+// разговоре остаются паузами и в записи. По запросу WAV пишется и
+// дорожками, по файлу на собеседника (см. multitrack.rs).
+//
+// .opus и .ogg - пакеты Opus как пришли, без перекодирования: файл
+// намного меньше, и долгий разговор не нагружает процессор. Смешать их без
+// декодирования нельзя, поэтому такая запись всегда идет дорожками; свой
+// голос - из пакетов, которые уходят в сеть.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::error::NsvcError;
+use crate::logging::AUDIO;
+use crate::multitrack::{self, Track, Tracks};
+use crate::{users, SourceKey, SAMPLE_RATE};
+
+const WRITE_INTERVAL: Duration = Duration::from_millis(100);
+// Отсчеты, пришедшие раньше срока; больше - часы разошлись, старое выбрасывается
+const MAX_QUEUED: usize = SAMPLE_RATE as usize * 2;
+const WAV_HEADER_BYTES: u32 = 44;
+
+// Очереди отсчетов до записи; mic - None, если свой голос не пишется
+struct Queues {
+    remote: VecDeque<f32>,
+    mic: Option<VecDeque<f32>>,
+}
+
+fn push_limited(queue: &mut VecDeque<f32>, samples: &[f32]) {
+    queue.extend(samples);
+    if queue.len() > MAX_QUEUED {
+        queue.drain(..queue.len() - MAX_QUEUED);
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+// Дорожки создаются с первым пакетом, поэтому каталог проверяется заранее
+pub(crate) fn check_dir(path: &Path) -> Result<(), NsvcError> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if dir.is_dir() {
+        return Ok(());
+    }
+    Err(NsvcError::Recording(io::Error::new(io::ErrorKind::NotFound, format!("no directory {}", dir.display()))))
+}
+
+pub(crate) struct Recording {
+    // Общий микс в WAV; None - запись не идет
+    queues: Arc<Mutex<Option<Queues>>>,
+    writer: Mutex<Option<Writer>>,
+    // Дорожки
+    tracks: Mutex<Option<Tracks>>,
+    // Ники для имен дорожек
+    roster: Arc<users::Roster>,
+}
+
+impl Recording {
+    pub fn new(roster: Arc<users::Roster>) -> Self {
+        Recording {
+            queues: Arc::default(),
+            writer: Mutex::new(None),
+            tracks: Mutex::new(None),
+            roster,
+        }
+    }
+
+    // separate - WAV дорожками; .opus и .ogg пишутся дорожками всегда.
+    // Прежняя запись, если была, завершается.
+    pub fn start(&self, path: &Path, include_mic: bool, separate: bool) -> Result<(), NsvcError> {
+        self.stop()?;
+        if separate || multitrack::is_ogg(path) {
+            check_dir(path)?;
+            let tracks = Tracks::new(path, include_mic, self.roster.clone());
+            info!(target: AUDIO, "Recording to {}", tracks.pattern());
+            *self.tracks.lock().unwrap() = Some(tracks);
+            return Ok(());
+        }
+        let wav = WavWriter::create(path).map_err(NsvcError::Recording)?;
+        *self.queues.lock().unwrap() =
+            Some(Queues { remote: VecDeque::new(), mic: include_mic.then(VecDeque::new) });
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let queues = self.queues.clone();
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || write_loop(wav, &queues, &stop))
+        };
+        *self.writer.lock().unwrap() = Some(Writer { path: path.to_path_buf(), stop, thread });
+        info!(target: AUDIO, "Recording to {}", path.display());
+        Ok(())
+    }
+
+    // Дописывает и закрывает файлы; без записи ничего не делает
+    pub fn stop(&self) -> Result<(), NsvcError> {
+        if let Some(tracks) = self.tracks.lock().unwrap().take() {
+            let saved = tracks.pattern();
+            tracks.finish().map_err(NsvcError::Recording)?;
+            info!(target: AUDIO, "Recording saved to {}", saved);
+            return Ok(());
+        }
+        let Some(writer) = self.writer.lock().unwrap().take() else {
+            return Ok(());
+        };
+        writer.stop.store(true, Ordering::SeqCst);
+        let result = writer.thread.join().unwrap_or_else(|_| Err(io::Error::other("recording thread panicked")));
+        *self.queues.lock().unwrap() = None;
+        result.map_err(NsvcError::Recording)?;
+        info!(target: AUDIO, "Recording saved to {}", writer.path.display());
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        if let Some(tracks) = self.tracks.lock().unwrap().as_ref() {
+            return tracks.error.is_none();
+        }
+        self.writer.lock().unwrap().as_ref().is_some_and(|writer| !writer.thread.is_finished())
+    }
+
+    // Пакет собеседника до декодирования
+    pub fn on_remote_packet(&self, source: SourceKey, opus: &[u8]) {
+        if let Some(tracks) = self.tracks.lock().unwrap().as_mut() {
+            tracks.write(Track::Remote(source), opus);
+        }
+    }
+
+    // Свой пакет, уходящий в сеть
+    pub fn on_mic_packet(&self, opus: &[u8]) {
+        if let Some(tracks) = self.tracks.lock().unwrap().as_mut().filter(|tracks| tracks.include_mic) {
+            tracks.write(Track::Mic, opus);
+        }
+    }
+
+    // Смешанные голоса из микшера
+    pub fn on_playback(&self, samples: &[f32]) {
+        if let Some(queues) = self.queues.lock().unwrap().as_mut() {
+            push_limited(&mut queues.remote, samples);
+        }
+    }
+
+    // Свой голос из кодера
+    pub fn on_capture(&self, samples: &[f32]) {
+        if let Some(mic) = self.queues.lock().unwrap().as_mut().and_then(|queues| queues.mic.as_mut()) {
+            push_limited(mic, samples);
+        }
+    }
+}
+
+fn write_loop(mut wav: WavWriter, queues: &Mutex<Option<Queues>>, stop: &AtomicBool) -> io::Result<()> {
+    let started = Instant::now();
+    let mut written = 0u64;
+    let mut pcm = Vec::new();
+    loop {
+        let stopping = stop.load(Ordering::SeqCst);
+        let due = (started.elapsed().as_secs_f64() * SAMPLE_RATE as f64) as u64;
+        pcm.clear();
+        if let Some(queues) = queues.lock().unwrap().as_mut() {
+            for _ in written..due {
+                let remote = queues.remote.pop_front().unwrap_or(0.0);
+                let mic = queues.mic.as_mut().and_then(VecDeque::pop_front).unwrap_or(0.0);
+                pcm.push(((remote + mic).clamp(-1.0, 1.0) * 32767.0) as i16);
+            }
+        }
+        written = due;
+        if let Err(e) = wav.write(&pcm) {
+            warn!(target: AUDIO, "Recording write error: {}", e);
+            return Err(e);
+        }
+        if stopping {
+            return wav.finish();
+        }
+        thread::sleep(WRITE_INTERVAL);
+    }
+}
+
+// PCM WAV; размеры в заголовке дописываются в finish()
+pub(crate) struct WavWriter {
+    file: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut wav = WavWriter { file: BufWriter::new(File::create(path)?), data_bytes: 0 };
+        wav.write_header()?;
+        Ok(wav)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let block_align = 2u16;
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(WAV_HEADER_BYTES - 8 + self.data_bytes).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&1u16.to_le_bytes())?; // моно
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&self.data_bytes.to_le_bytes())
+    }
+
+    pub fn write(&mut self, pcm: &[i16]) -> io::Result<()> {
+        // Больше 4 ГБ (около 12 часов) заголовок WAV не опишет
+        let bytes = pcm.len() as u32 * 2;
+        if self.data_bytes.checked_add(bytes).is_none_or(|total| total > u32::MAX - WAV_HEADER_BYTES) {
+            return Err(io::Error::other("WAV file size limit reached"));
+        }
+        for sample in pcm {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_bytes += bytes;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.flush()
+    }
+}
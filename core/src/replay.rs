@@ -0,0 +1,185 @@
+// Защита от повторов: скользящее окно номеров пакетов, как в IPsec/SRTP
+// (RFC 4303, 3.4.3). Пакет принимается, если его номер новее самого
+// старшего или попадает в окно и еще не встречался.
+//
+// Переподключившийся клиент сохраняет ID (а значит, и SSRC), но начинает
+// нумерацию с нового случайного номера - нередко позади старой. Поэтому
+// RESTART_RUN пакетов подряд, которые старше окна и идут друг за другом,
+// считаются новым потоком: окно начинается заново с них.
+
+// Ширина окна в пакетах: при 10 мс кадрах это 640 мс переупорядочивания
+pub const WINDOW_SIZE: u32 = 64;
+
+// Столько старых пакетов подряд - уже не повтор, а новая нумерация
+pub const RESTART_RUN: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Fresh,
+    // Номер уже был в окне
+    Duplicate,
+    // Номер старше окна, проверить нельзя
+    TooOld,
+    // Источник начал нумерацию заново; пакет принят, окно сброшено
+    Restarted,
+}
+
+pub struct ReplayWindow {
+    highest: Option<u32>,
+    // Бит i - пакет highest - i уже принят
+    seen: u64,
+    // Последний номер и длина текущей серии пакетов старше окна
+    stale_run: Option<(u32, u32)>,
+}
+
+impl ReplayWindow {
+    pub fn new() -> Self {
+        ReplayWindow { highest: None, seen: 0, stale_run: None }
+    }
+
+    pub fn highest(&self) -> Option<u32> {
+        self.highest
+    }
+
+    // Проверяет номер и, если пакет новый, отмечает его как принятый
+    pub fn check(&mut self, seq: u32) -> Verdict {
+        let highest = match self.highest {
+            Some(highest) => highest,
+            None => {
+                self.restart(seq);
+                return Verdict::Fresh;
+            },
+        };
+
+        // Разница с учетом переполнения счетчика
+        let ahead = seq.wrapping_sub(highest) as i32;
+        if ahead > 0 {
+            let shift = ahead as u32;
+            self.seen = if shift >= WINDOW_SIZE { 0 } else { self.seen << shift };
+            self.seen |= 1;
+            self.highest = Some(seq);
+            self.stale_run = None;
+            return Verdict::Fresh;
+        }
+
+        let behind = ahead.unsigned_abs();
+        if behind >= WINDOW_SIZE {
+            return self.on_stale(seq);
+        }
+        self.stale_run = None;
+        let bit = 1u64 << behind;
+        if self.seen & bit != 0 {
+            return Verdict::Duplicate;
+        }
+        self.seen |= bit;
+        Verdict::Fresh
+    }
+
+    // Серию продолжает пакет чуть новее предыдущего в ней: так идет живой
+    // поток с потерями, а не разрозненные повторы
+    fn on_stale(&mut self, seq: u32) -> Verdict {
+        let run = match self.stale_run {
+            Some((last, run)) if (1..WINDOW_SIZE).contains(&seq.wrapping_sub(last)) => run + 1,
+            _ => 1,
+        };
+        if run < RESTART_RUN {
+            self.stale_run = Some((seq, run));
+            return Verdict::TooOld;
+        }
+        self.restart(seq);
+        Verdict::Restarted
+    }
+
+    fn restart(&mut self, seq: u32) {
+        self.highest = Some(seq);
+        self.seen = 1;
+        self.stale_run = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_packets_are_fresh() {
+        let mut window = ReplayWindow::new();
+        for seq in 100..200 {
+            assert_eq!(window.check(seq), Verdict::Fresh);
+        }
+        assert_eq!(window.highest(), Some(199));
+    }
+
+    #[test]
+    fn duplicates_are_dropped() {
+        let mut window = ReplayWindow::new();
+        for seq in 0..10 {
+            window.check(seq);
+        }
+        assert_eq!(window.check(9), Verdict::Duplicate);
+        assert_eq!(window.check(3), Verdict::Duplicate);
+    }
+
+    #[test]
+    fn reordered_packets_inside_the_window_are_fresh_once() {
+        let mut window = ReplayWindow::new();
+        window.check(10);
+        window.check(12);
+        assert_eq!(window.check(11), Verdict::Fresh);
+        assert_eq!(window.check(11), Verdict::Duplicate);
+        assert_eq!(window.highest(), Some(12));
+    }
+
+    #[test]
+    fn packets_older_than_the_window_are_dropped() {
+        let mut window = ReplayWindow::new();
+        window.check(1000);
+        assert_eq!(window.check(1000 - WINDOW_SIZE), Verdict::TooOld);
+        assert_eq!(window.check(1000 - WINDOW_SIZE + 1), Verdict::Fresh);
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let mut window = ReplayWindow::new();
+        for seq in u32::MAX - 5..=u32::MAX {
+            assert_eq!(window.check(seq), Verdict::Fresh);
+        }
+        for seq in 0..5 {
+            assert_eq!(window.check(seq), Verdict::Fresh, "seq {}", seq);
+        }
+        assert_eq!(window.check(u32::MAX), Verdict::Duplicate);
+        assert_eq!(window.highest(), Some(4));
+    }
+
+    #[test]
+    fn restarted_sequence_is_accepted_after_a_run() {
+        let mut window = ReplayWindow::new();
+        for seq in 1_000_000..1_000_100 {
+            window.check(seq);
+        }
+        for seq in 5..5 + RESTART_RUN - 1 {
+            assert_eq!(window.check(seq), Verdict::TooOld);
+        }
+        assert_eq!(window.check(5 + RESTART_RUN - 1), Verdict::Restarted);
+        let fresh = (5 + RESTART_RUN..5000).filter(|&seq| window.check(seq) == Verdict::Fresh).count();
+        assert_eq!(fresh as u32, 5000 - 5 - RESTART_RUN);
+    }
+
+    #[test]
+    fn scattered_old_packets_do_not_reset_the_window() {
+        let mut window = ReplayWindow::new();
+        for seq in 1_000_000..1_000_100 {
+            window.check(seq);
+        }
+        for i in 0..100 {
+            assert_eq!(window.check(i * 1000), Verdict::TooOld);
+        }
+        // Пакет в окне прерывает серию
+        for seq in 10..10 + RESTART_RUN - 1 {
+            window.check(seq);
+        }
+        assert_eq!(window.check(1_000_099), Verdict::Duplicate);
+        assert_eq!(window.check(10 + RESTART_RUN), Verdict::TooOld);
+        assert_eq!(window.highest(), Some(1_000_099));
+    }
+}
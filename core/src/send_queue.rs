@@ -0,0 +1,128 @@
+// Очередь исходящего голоса: аудио-callback только кладет пакеты, а
+// отправляет их отдельный сетевой поток, так что полный буфер сокета не
+// задерживает захват. Если очередь переполнена, выбрасываются самые старые
+// пакеты - для голоса опоздавший кадр хуже потерянного.
+//
+// Отправленные пакеты возвращаются через recycle() и снова выдаются
+// buffer(), так что в установившемся режиме новых выделений памяти нет.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(not(feature = "tokio"))]
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::MAX_PACKET_SIZE;
+
+// 16 кадров по 10 мс: дольше копить нет смысла, собеседник их уже не ждет
+pub const MAX_QUEUED_PACKETS: usize = 16;
+// Минимальный промежуток между отправками: cpal отдает звук пачками,
+// и без паузы несколько кадров уходили бы одним всплеском
+pub const PACING_INTERVAL: Duration = Duration::from_millis(2);
+// Запас свободных буферов: очередь целиком и пакет в отправке
+const MAX_SPARE_BUFFERS: usize = MAX_QUEUED_PACKETS + 1;
+
+pub struct SendQueue {
+    packets: Mutex<VecDeque<Vec<u8>>>,
+    spare: Mutex<Vec<Vec<u8>>>,
+    #[cfg(not(feature = "tokio"))]
+    ready: Condvar,
+    // Для задачи отправки tokio вместо Condvar
+    #[cfg(feature = "tokio")]
+    ready_async: tokio::sync::Notify,
+    dropped: AtomicU64,
+}
+
+impl SendQueue {
+    pub fn new() -> Self {
+        SendQueue {
+            packets: Mutex::new(VecDeque::with_capacity(MAX_QUEUED_PACKETS)),
+            spare: Mutex::new(Vec::with_capacity(MAX_SPARE_BUFFERS)),
+            #[cfg(not(feature = "tokio"))]
+            ready: Condvar::new(),
+            #[cfg(feature = "tokio")]
+            ready_async: tokio::sync::Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    // Вызывается из аудио-callback: не блокируется дольше захвата мьютекса
+    pub fn push(&self, packet: Vec<u8>) {
+        let mut packets = match self.packets.lock() {
+            Ok(packets) => packets,
+            Err(_) => return,
+        };
+        while packets.len() >= MAX_QUEUED_PACKETS {
+            if let Some(old) = packets.pop_front() {
+                self.recycle(old);
+            }
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        packets.push_back(packet);
+        #[cfg(not(feature = "tokio"))]
+        self.ready.notify_one();
+        #[cfg(feature = "tokio")]
+        self.ready_async.notify_one();
+    }
+
+    // Следующий пакет или None, если за timeout ничего не пришло
+    #[cfg(not(feature = "tokio"))]
+    pub fn pop(&self, timeout: Duration) -> Option<Vec<u8>> {
+        let packets = self.packets.lock().ok()?;
+        let (mut packets, _) = self
+            .ready
+            .wait_timeout_while(packets, timeout, |packets| packets.is_empty())
+            .ok()?;
+        packets.pop_front()
+    }
+
+    // pop для задачи tokio: ждет, не занимая поток runtime
+    #[cfg(feature = "tokio")]
+    pub async fn pop_async(&self, timeout: Duration) -> Option<Vec<u8>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(packet) = self.packets.lock().ok()?.pop_front() {
+                return Some(packet);
+            }
+            // Пакет, положенный между проверкой и ожиданием, не теряется:
+            // notify_one без ожидающих оставляет разрешение
+            tokio::time::timeout_at(deadline, self.ready_async.notified()).await.ok()?;
+        }
+    }
+
+    // Пустой буфер под пакет: из отправленных, а новый - только пока запас не набрался
+    pub fn buffer(&self) -> Vec<u8> {
+        let spare = self.spare.lock().ok().and_then(|mut spare| spare.pop());
+        match spare {
+            Some(mut buffer) => {
+                buffer.clear();
+                buffer
+            },
+            None => Vec::with_capacity(MAX_PACKET_SIZE),
+        }
+    }
+
+    // Возвращает отправленный пакет в запас
+    pub fn recycle(&self, packet: Vec<u8>) {
+        if let Ok(mut spare) = self.spare.lock() {
+            if spare.len() < MAX_SPARE_BUFFERS {
+                spare.push(packet);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.lock().map_or(true, |packets| packets.is_empty())
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut packets) = self.packets.lock() {
+            packets.clear();
+        }
+    }
+
+    // Сколько пакетов выброшено из-за переполнения
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,69 @@
+// Звуковая панель: файл (см. audio_file.rs) уходит собеседникам через
+// кодер, как голос с микрофона - для звуков, объявлений и музыки в
+// разговоре. Файл идет в темпе захвата и передается и без нажатой клавиши
+// PTT, и при выключенном микрофоне: его запустили явно. Выключенный
+// микрофон при этом дает тишину вместо голоса, заглушенный клиент не
+// передает ничего (см. mute.rs). Новый файл прерывает прежний. Клип -
+// записанное сообщение - идет вместо голоса в пакетах MEDIA_CLIP.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+struct Playback {
+    samples: Vec<f32>,
+    position: usize,
+    // false - файл вместо голоса, true - поверх него
+    mix_with_mic: bool,
+    clip: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Soundboard {
+    // Для callback'а захвата: ему нельзя ждать мьютекс
+    playing: AtomicBool,
+    playback: Mutex<Option<Playback>>,
+}
+
+impl Soundboard {
+    pub fn play(&self, samples: Vec<f32>, mix_with_mic: bool) {
+        self.start(Playback { samples, position: 0, mix_with_mic, clip: false });
+    }
+
+    pub fn play_clip(&self, samples: Vec<f32>) {
+        self.start(Playback { samples, position: 0, mix_with_mic: false, clip: true });
+    }
+
+    fn start(&self, playback: Playback) {
+        *self.playback.lock().unwrap() = Some(playback);
+        self.playing.store(true, Ordering::SeqCst);
+    }
+
+    pub fn stop(&self) {
+        *self.playback.lock().unwrap() = None;
+        self.playing.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::SeqCst)
+    }
+
+    pub fn is_clip(&self) -> bool {
+        self.playback.lock().unwrap().as_ref().is_some_and(|playback| playback.clip)
+    }
+
+    // Подмешивает в захваченный звук очередную порцию файла
+    pub fn mix_into(&self, data: &mut [f32]) {
+        let mut guard = self.playback.lock().unwrap();
+        let Some(playback) = guard.as_mut() else {
+            return;
+        };
+        let chunk = &playback.samples[playback.position..];
+        for (out, &sample) in data.iter_mut().zip(chunk) {
+            *out = if playback.mix_with_mic { (*out + sample).clamp(-1.0, 1.0) } else { sample };
+        }
+        playback.position += data.len().min(chunk.len());
+        if playback.position == playback.samples.len() {
+            *guard = None;
+            self.playing.store(false, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// Статистика сессии для индикаторов качества у хоста. Счетчики лежат под
+// одним замком, поэтому снимок всегда согласован: потери не могут оказаться
+// посчитанными по пакетам, которых еще нет в числе принятых. Только
+// пропуски воспроизведения считает callback устройства, без замка.
+// Traffic - отдельно: весь трафик сокета, а не только голос.
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Снимок для voice_client_get_stats
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct VoiceClientStats {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    // Голосовые пакеты, включая дубликаты и опоздавшие
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    // По номерам пакетов MEDIA; пакеты без номеров не учитываются
+    pub packets_lost: u64,
+    pub loss_percent: f32,
+    // 0 - еще не измерен
+    pub rtt_ms: u32,
+    pub jitter_ms: f32,
+    // Текущий битрейт кодера, бит/с
+    pub bitrate: u32,
+    // Сколько звука ждет воспроизведения
+    pub buffer_ms: u32,
+    // Сколько раз голос прерывался: устройству не хватило звука
+    pub underruns: u64,
+    // Сглаженное время кодирования кадра, мкс
+    pub encode_us: f32,
+    // Весь трафик сессии в обе стороны: голос, служебные пакеты, STUN и
+    // TURN, с заголовками UDP и IP - столько сессия стоит на лимитном
+    // подключении
+    pub total_bytes_sent: u64,
+    pub total_bytes_received: u64,
+    // Тот же трафик за последние секунды, кбит/с
+    pub send_kbps: f32,
+    pub receive_kbps: f32,
+}
+
+#[derive(Default)]
+struct Counters {
+    packets_sent: u64,
+    bytes_sent: u64,
+    packets_received: u64,
+    bytes_received: u64,
+    // Сколько пакетов с номерами должно было прийти и сколько пришло
+    expected: u64,
+    fresh: u64,
+    jitter_ms: f32,
+    encode_us: f32,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    counters: Mutex<Counters>,
+    underruns: Arc<AtomicU64>,
+}
+
+impl Stats {
+    pub fn on_sent(&self, bytes: usize) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.packets_sent += 1;
+        counters.bytes_sent += bytes as u64;
+    }
+
+    pub fn on_received(&self, bytes: usize) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.packets_received += 1;
+        counters.bytes_received += bytes as u64;
+    }
+
+    // Новый пакет с номером: previous - старший номер источника до него
+    pub fn on_sequence(&self, previous: Option<u32>, seq: u32) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.fresh += 1;
+        counters.expected += match previous {
+            None => 1,
+            // Разница с учетом переполнения; опоздавший пакет уже учтен как ожидаемый
+            Some(highest) => (seq.wrapping_sub(highest) as i32).max(0) as u64,
+        };
+    }
+
+    pub fn set_jitter(&self, jitter: Duration) {
+        self.counters.lock().unwrap().jitter_ms = jitter.as_secs_f32() * 1000.0;
+    }
+
+    // Сглаживаем, как джиттер: один медленный кадр не делает погоды
+    pub fn on_encoded(&self, elapsed: Duration) {
+        let mut counters = self.counters.lock().unwrap();
+        let us = elapsed.as_secs_f32() * 1_000_000.0;
+        counters.encode_us = if counters.encode_us == 0.0 { us } else { (counters.encode_us * 15.0 + us) / 16.0 };
+    }
+
+    // Для callback'а вывода, см. realtime::spawn_mixer
+    pub fn underrun_counter(&self) -> Arc<AtomicU64> {
+        self.underruns.clone()
+    }
+
+    // Снимок счетчиков; RTT, битрейт и буфер дописывает вызывающий
+    pub fn snapshot(&self) -> VoiceClientStats {
+        let counters = self.counters.lock().unwrap();
+        let lost = counters.expected.saturating_sub(counters.fresh);
+        VoiceClientStats {
+            packets_sent: counters.packets_sent,
+            bytes_sent: counters.bytes_sent,
+            packets_received: counters.packets_received,
+            bytes_received: counters.bytes_received,
+            packets_lost: lost,
+            loss_percent: if counters.expected == 0 { 0.0 } else { lost as f32 * 100.0 / counters.expected as f32 },
+            jitter_ms: counters.jitter_ms,
+            underruns: self.underruns.load(Ordering::Relaxed),
+            encode_us: counters.encode_us,
+            ..VoiceClientStats::default()
+        }
+    }
+
+    pub fn reset(&self) {
+        *self.counters.lock().unwrap() = Counters::default();
+        self.underruns.store(0, Ordering::Relaxed);
+    }
+}
+
+// Заголовки IP и UDP, которых нет в размере датаграммы
+const IPV4_UDP_OVERHEAD: u64 = 28;
+const IPV6_UDP_OVERHEAD: u64 = 48;
+// За сколько считается текущая скорость
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+// Трафик сокета клиента; считает транспорт и прием
+#[derive(Default)]
+pub struct Traffic {
+    sent: Mutex<Direction>,
+    received: Mutex<Direction>,
+}
+
+#[derive(Default)]
+struct Direction {
+    total: u64,
+    // Начало текущего окна скорости и байты в нем
+    window_start: Option<Instant>,
+    window_bytes: u64,
+    // Скорость в прошлом окне
+    kbps: f32,
+}
+
+impl Direction {
+    fn add(&mut self, bytes: u64) {
+        let now = Instant::now();
+        self.total += bytes;
+        self.window_bytes += bytes;
+        let start = *self.window_start.get_or_insert(now);
+        let elapsed = now.duration_since(start);
+        if elapsed >= RATE_WINDOW {
+            self.kbps = kbps(self.window_bytes, elapsed);
+            self.window_start = Some(now);
+            self.window_bytes = 0;
+        }
+    }
+
+    // Когда пакеты перестали идти, скорость падает до нуля, а не застывает
+    fn kbps(&self) -> f32 {
+        match self.window_start {
+            Some(start) if start.elapsed() >= RATE_WINDOW * 2 => kbps(self.window_bytes, start.elapsed()),
+            Some(_) => self.kbps,
+            None => 0.0,
+        }
+    }
+}
+
+fn kbps(bytes: u64, elapsed: Duration) -> f32 {
+    bytes as f32 * 8.0 / elapsed.as_secs_f32() / 1000.0
+}
+
+fn wire_size(bytes: usize, addr: SocketAddr) -> u64 {
+    bytes as u64 + if addr.is_ipv4() { IPV4_UDP_OVERHEAD } else { IPV6_UDP_OVERHEAD }
+}
+
+impl Traffic {
+    pub fn on_sent(&self, bytes: usize, to: SocketAddr) {
+        self.sent.lock().unwrap().add(wire_size(bytes, to));
+    }
+
+    pub fn on_received(&self, bytes: usize, from: SocketAddr) {
+        self.received.lock().unwrap().add(wire_size(bytes, from));
+    }
+
+    // Дописывает трафик в снимок
+    pub fn fill(&self, snapshot: &mut VoiceClientStats) {
+        let sent = self.sent.lock().unwrap();
+        let received = self.received.lock().unwrap();
+        snapshot.total_bytes_sent = sent.total;
+        snapshot.total_bytes_received = received.total;
+        snapshot.send_kbps = sent.kbps();
+        snapshot.receive_kbps = received.kbps();
+    }
+
+    pub fn reset(&self) {
+        *self.sent.lock().unwrap() = Direction::default();
+        *self.received.lock().unwrap() = Direction::default();
+    }
+}
+
+// Джиттер источника: разброс интервалов между соседними пакетами
+// (IPDV, RFC 5481), сглаженный как в RTP (RFC 3550, 6.4.1)
+#[derive(Default)]
+pub struct Jitter {
+    last: Option<(u32, Instant)>,
+    last_interval: Option<Duration>,
+    value: Duration,
+}
+
+impl Jitter {
+    pub fn on_packet(&mut self, seq: u32) -> Duration {
+        let now = Instant::now();
+        let interval = match self.last {
+            Some((last_seq, at)) if seq == last_seq.wrapping_add(1) => Some(now.duration_since(at)),
+            _ => None,
+        };
+        if let (Some(interval), Some(last_interval)) = (interval, self.last_interval) {
+            let variation = interval.abs_diff(last_interval);
+            self.value = (self.value * 15 + variation) / 16;
+        }
+        self.last = Some((seq, now));
+        self.last_interval = interval;
+        self.value
+    }
+}
@@ -0,0 +1,186 @@
+// Минимальный STUN-клиент (RFC 5389): Binding Request и разбор
+// (XOR-)MAPPED-ADDRESS из ответа
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub const MAGIC_COOKIE: u32 = 0x2112_A442;
+pub const HEADER_SIZE: usize = 20;
+
+pub const BINDING_REQUEST: u16 = 0x0001;
+pub const BINDING_SUCCESS: u16 = 0x0101;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+
+pub type TransactionId = [u8; 12];
+
+pub fn new_transaction_id() -> TransactionId {
+    rand::random()
+}
+
+// Заголовок STUN: тип, длина атрибутов, magic cookie, transaction id
+pub fn write_header(buf: &mut Vec<u8>, message_type: u16, transaction_id: &TransactionId) {
+    buf.extend_from_slice(&message_type.to_be_bytes());
+    buf.extend_from_slice(&0u16.to_be_bytes());
+    buf.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    buf.extend_from_slice(transaction_id);
+}
+
+// Добавляет атрибут с выравниванием значения по 4 байта
+pub fn write_attribute(buf: &mut Vec<u8>, attr_type: u16, value: &[u8]) {
+    buf.extend_from_slice(&attr_type.to_be_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+    let padding = (4 - value.len() % 4) % 4;
+    buf.extend(std::iter::repeat_n(0u8, padding));
+}
+
+// Атрибут адреса в XOR-формате (XOR-PEER-ADDRESS и т.п.)
+pub fn write_xor_address(buf: &mut Vec<u8>, attr_type: u16, addr: SocketAddr, transaction_id: &TransactionId) {
+    let port = addr.port() ^ (MAGIC_COOKIE >> 16) as u16;
+    let mut value = vec![0u8];
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            value.push(FAMILY_IPV4);
+            value.extend_from_slice(&port.to_be_bytes());
+            for (octet, key) in ip.octets().iter().zip(MAGIC_COOKIE.to_be_bytes()) {
+                value.push(octet ^ key);
+            }
+        },
+        IpAddr::V6(ip) => {
+            value.push(FAMILY_IPV6);
+            value.extend_from_slice(&port.to_be_bytes());
+            let mut key = [0u8; 16];
+            key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+            key[4..].copy_from_slice(transaction_id);
+            for (octet, k) in ip.octets().iter().zip(key) {
+                value.push(octet ^ k);
+            }
+        },
+    }
+    write_attribute(buf, attr_type, &value);
+}
+
+// Обновляет поле длины после добавления атрибутов
+pub fn finish_message(buf: &mut [u8]) {
+    let len = (buf.len() - HEADER_SIZE) as u16;
+    buf[2..4].copy_from_slice(&len.to_be_bytes());
+}
+
+pub fn build_binding_request(transaction_id: &TransactionId) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_SIZE);
+    write_header(&mut buf, BINDING_REQUEST, transaction_id);
+    buf
+}
+
+// Первые два бита STUN-сообщения всегда нулевые, плюс фиксированный cookie
+pub fn is_stun_message(buf: &[u8]) -> bool {
+    buf.len() >= HEADER_SIZE
+        && buf[0] & 0xC0 == 0
+        && u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) == MAGIC_COOKIE
+}
+
+pub fn message_type(buf: &[u8]) -> u16 {
+    u16::from_be_bytes([buf[0], buf[1]])
+}
+
+pub fn transaction_id(buf: &[u8]) -> &[u8] {
+    &buf[8..20]
+}
+
+// Список атрибутов сообщения: (тип, значение)
+pub fn attributes(buf: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut result = Vec::new();
+    if buf.len() < HEADER_SIZE {
+        return result;
+    }
+
+    let declared_len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    let end = (HEADER_SIZE + declared_len).min(buf.len());
+    let mut pos = HEADER_SIZE;
+
+    while pos + 4 <= end {
+        let attr_type = u16::from_be_bytes([buf[pos], buf[pos + 1]]);
+        let attr_len = u16::from_be_bytes([buf[pos + 2], buf[pos + 3]]) as usize;
+        let value_start = pos + 4;
+        if value_start + attr_len > end {
+            break;
+        }
+        result.push((attr_type, &buf[value_start..value_start + attr_len]));
+        // Атрибуты выровнены по 4 байта
+        pos = value_start + ((attr_len + 3) & !3);
+    }
+
+    result
+}
+
+pub fn find_attribute(buf: &[u8], wanted: u16) -> Option<&[u8]> {
+    attributes(buf)
+        .into_iter()
+        .find(|(attr_type, _)| *attr_type == wanted)
+        .map(|(_, value)| value)
+}
+
+fn parse_address(value: &[u8], xor_with: Option<&[u8]>) -> Option<SocketAddr> {
+    if value.len() < 4 {
+        return None;
+    }
+
+    let family = value[1];
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    if xor_with.is_some() {
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+    }
+
+    let ip = match family {
+        FAMILY_IPV4 if value.len() >= 8 => {
+            let mut octets = [value[4], value[5], value[6], value[7]];
+            if xor_with.is_some() {
+                for (octet, key) in octets.iter_mut().zip(MAGIC_COOKIE.to_be_bytes()) {
+                    *octet ^= key;
+                }
+            }
+            IpAddr::V4(Ipv4Addr::from(octets))
+        },
+        FAMILY_IPV6 if value.len() >= 20 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&value[4..20]);
+            if let Some(transaction_id) = xor_with {
+                let mut key = [0u8; 16];
+                key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+                key[4..].copy_from_slice(transaction_id);
+                for (octet, k) in octets.iter_mut().zip(key) {
+                    *octet ^= k;
+                }
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        },
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, port))
+}
+
+// Читает адрес из атрибута (XOR-)MAPPED-ADDRESS-подобного формата
+pub fn parse_xor_address(buf: &[u8], value: &[u8]) -> Option<SocketAddr> {
+    parse_address(value, Some(transaction_id(buf)))
+}
+
+// Разбирает успешный Binding Response и возвращает внешний адрес
+pub fn parse_binding_response(buf: &[u8], expected_id: &TransactionId) -> Option<SocketAddr> {
+    if !is_stun_message(buf) || message_type(buf) != BINDING_SUCCESS {
+        return None;
+    }
+    if transaction_id(buf) != expected_id {
+        return None;
+    }
+
+    if let Some(value) = find_attribute(buf, ATTR_XOR_MAPPED_ADDRESS) {
+        return parse_xor_address(buf, value);
+    }
+
+    // Старые серверы (RFC 3489) присылают только MAPPED-ADDRESS
+    find_attribute(buf, ATTR_MAPPED_ADDRESS).and_then(|value| parse_address(value, None))
+}
@@ -0,0 +1,40 @@
+// Проверочный сигнал для выбора динамиков до разговора: ровный тон или
+// развертка по частотам, по которой слышно, что устройство верное и не
+// режет низы или верха. Генерируется локально, в сеть не уходит.
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use crate::{test_tones, SAMPLE_RATE};
+
+const AMPLITUDE: f32 = 0.25;
+const FADE_MS: usize = 10;
+const TONE_HZ: f32 = 440.0;
+// Развертка - по всей полосе голоса и чуть шире
+const SWEEP_FROM_HZ: f32 = 100.0;
+const SWEEP_TO_HZ: f32 = 10_000.0;
+
+// Отсчеты при SAMPLE_RATE; None - неизвестный вид из test_tones
+pub(crate) fn samples(kind: i32, duration: Duration) -> Option<Vec<f32>> {
+    let len = (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+    let seconds = duration.as_secs_f32();
+    // Фаза развертки с экспоненциальным ростом частоты: каждая октава
+    // звучит одинаково долго
+    let phase: Box<dyn Fn(f32) -> f32> = match kind {
+        test_tones::STEADY => Box::new(|t| TAU * TONE_HZ * t),
+        test_tones::SWEEP => {
+            let rate = (SWEEP_TO_HZ / SWEEP_FROM_HZ).ln() / seconds;
+            Box::new(move |t| TAU * SWEEP_FROM_HZ * ((rate * t).exp() - 1.0) / rate)
+        },
+        _ => return None,
+    };
+    let fade_len = SAMPLE_RATE as usize * FADE_MS / 1000;
+    Some(
+        (0..len)
+            .map(|i| {
+                // Края сглажены, иначе сигнал начинается и кончается щелчком
+                let fade = (i.min(len - 1 - i) as f32 / fade_len as f32).min(1.0);
+                phase(i as f32 / SAMPLE_RATE as f32).sin() * AMPLITUDE * fade
+            })
+            .collect(),
+    )
+}
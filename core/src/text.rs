@@ -0,0 +1,38 @@
+// Текстовые сообщения через тот же сокет, что и голос: ссылки и короткие
+// реплики без отдельного чата. Тело TEXT_MESSAGE: ID участника (u32), флаги
+// (u8) и текст в UTF-8. Клиент пишет в ID получателя (0 - весь канал),
+// сервер при пересылке - ID отправителя и флаг личного сообщения. Доставка,
+// как и у голоса, не гарантируется.
+use crate::{control_packet, control_type, control_types, CONTROL_HEADER_SIZE};
+
+// Влезает в датаграмму минимального размера вместе с заголовками
+pub const MAX_TEXT_LEN: usize = 500;
+// Сообщение только этому получателю, а не всему каналу
+pub const FLAG_PRIVATE: u8 = 0x01;
+
+// Текст: непустой и не длиннее MAX_TEXT_LEN байт
+pub fn is_valid_text(text: &str) -> bool {
+    !text.is_empty() && text.len() <= MAX_TEXT_LEN
+}
+
+pub fn text_packet(user_id: u32, flags: u8, text: &str) -> Vec<u8> {
+    let mut body = Vec::with_capacity(5 + text.len());
+    body.extend_from_slice(&user_id.to_be_bytes());
+    body.push(flags);
+    body.extend_from_slice(text.as_bytes());
+    control_packet(control_types::TEXT_MESSAGE, &body)
+}
+
+// (ID участника, флаги, текст); None для чужих и испорченных пакетов
+pub fn parse_text(packet: &[u8]) -> Option<(u32, u8, &str)> {
+    if control_type(packet) != control_types::TEXT_MESSAGE {
+        return None;
+    }
+    let body = packet.get(CONTROL_HEADER_SIZE..)?;
+    let id = body.get(..4)?;
+    let text = std::str::from_utf8(body.get(5..)?).ok()?;
+    if !is_valid_text(text) {
+        return None;
+    }
+    Some((u32::from_be_bytes([id[0], id[1], id[2], id[3]]), body[4], text))
+}
@@ -0,0 +1,77 @@
+// Транспорт датаграмм клиента. Выше него (протокол, P2P, TURN, звук) все
+// работает с датаграммами и адресами, поэтому TCP, WebSocket, QUIC или
+// loopback для тестов добавляются своей реализацией Transport.
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+
+use crate::stats::Traffic;
+
+// События соединения - у транспортов, где оно есть (TCP, WebSocket, QUIC);
+// у UDP их нет, поэтому пока варианты никто не создает
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum TransportEvent {
+    Connected(SocketAddr),
+    Disconnected(String),
+}
+
+pub trait Transport: Send + Sync {
+    fn send_datagram(&self, data: &[u8], to: SocketAddr) -> io::Result<usize>;
+
+    // Не блокируется: нет готовой датаграммы - WouldBlock
+    fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    // Другая сторона соединения; None, если транспорт шлет кому угодно
+    #[allow(dead_code)]
+    fn peer_addr(&self) -> Option<SocketAddr>;
+
+    // Следующее событие соединения, если есть
+    fn poll_event(&self) -> Option<TransportEvent> {
+        None
+    }
+
+    // Сокет под транспортом - для буферов ОС и приема через tokio
+    fn udp_socket(&self) -> Option<&UdpSocket> {
+        None
+    }
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    // Отправленное; принятое считает Receiver, в том числе мимо recv (tokio)
+    traffic: Arc<Traffic>,
+}
+
+impl UdpTransport {
+    pub fn new(socket: UdpSocket, traffic: Arc<Traffic>) -> Self {
+        UdpTransport { socket, traffic }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_datagram(&self, data: &[u8], to: SocketAddr) -> io::Result<usize> {
+        let sent = self.socket.send_to(data, to)?;
+        self.traffic.on_sent(sent, to);
+        Ok(sent)
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // Сокет клиента не подключается: через него идут и сервер, и STUN, и P2P
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        self.socket.peer_addr().ok()
+    }
+
+    fn udp_socket(&self) -> Option<&UdpSocket> {
+        Some(&self.socket)
+    }
+}
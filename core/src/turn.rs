@@ -0,0 +1,360 @@
+// TURN-клиент (RFC 5766): выделение relay-адреса на TURN-сервере и
+// пересылка медиа через ChannelData, когда прямой UDP до сервера не проходит
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use tracing::{info, warn};
+
+use crate::logging::NET;
+use crate::MAX_PACKET_SIZE;
+use crate::stun::{self, TransactionId};
+use crate::transport::Transport;
+
+const METHOD_ALLOCATE: u16 = 0x0003;
+const METHOD_REFRESH: u16 = 0x0004;
+const METHOD_CHANNEL_BIND: u16 = 0x0009;
+
+const CLASS_SUCCESS: u16 = 0x0100;
+const CLASS_ERROR: u16 = 0x0110;
+
+const ATTR_CHANNEL_NUMBER: u16 = 0x000C;
+const ATTR_LIFETIME: u16 = 0x000D;
+const ATTR_USERNAME: u16 = 0x0006;
+const ATTR_MESSAGE_INTEGRITY: u16 = 0x0008;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_XOR_PEER_ADDRESS: u16 = 0x0012;
+const ATTR_REALM: u16 = 0x0014;
+const ATTR_NONCE: u16 = 0x0015;
+const ATTR_XOR_RELAYED_ADDRESS: u16 = 0x0016;
+const ATTR_REQUESTED_TRANSPORT: u16 = 0x0019;
+
+const TRANSPORT_UDP: u8 = 17;
+const ERROR_UNAUTHORIZED: u16 = 401;
+const ERROR_STALE_NONCE: u16 = 438;
+
+// Каналы TURN - диапазон 0x4000..0x7FFF, нам нужен один (до голосового сервера)
+const RELAY_CHANNEL: u16 = 0x4000;
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(600);
+// Привязка канала живет 10 минут, обновляем заранее
+const CHANNEL_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(3);
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct TurnConfig {
+    pub server: SocketAddr,
+    pub username: String,
+    pub password: String,
+}
+
+// Данные long-term аутентификации, полученные из ответа 401
+#[derive(Clone)]
+struct Auth {
+    realm: String,
+    nonce: String,
+    key: [u8; 16],
+}
+
+struct Allocation {
+    peer: SocketAddr,
+    expires_at: Instant,
+    channel_bound_at: Instant,
+}
+
+pub struct TurnClient {
+    pub config: TurnConfig,
+    transport: Arc<dyn Transport>,
+    // Ожидаемая транзакция и ответ на нее, который кладет поток приема
+    pending: Mutex<Option<(TransactionId, Option<Vec<u8>>)>>,
+    auth: Mutex<Option<Auth>>,
+    allocation: Mutex<Option<Allocation>>,
+    allocating: AtomicBool,
+}
+
+fn long_term_key(username: &str, realm: &str, password: &str) -> [u8; 16] {
+    let mut hasher = Md5::new();
+    hasher.update(format!("{}:{}:{}", username, realm, password).as_bytes());
+    hasher.finalize().into()
+}
+
+fn error_code(buf: &[u8]) -> Option<u16> {
+    let value = stun::find_attribute(buf, ATTR_ERROR_CODE)?;
+    if value.len() < 4 {
+        return None;
+    }
+    Some((value[2] & 0x07) as u16 * 100 + value[3] as u16)
+}
+
+fn string_attribute(buf: &[u8], attr_type: u16) -> Option<String> {
+    stun::find_attribute(buf, attr_type).map(|v| String::from_utf8_lossy(v).into_owned())
+}
+
+// Проверяет, что буфер похож на ChannelData (первые два бита 01)
+pub fn is_channel_data(buf: &[u8]) -> bool {
+    buf.len() >= 4 && buf[0] & 0xC0 == 0x40
+}
+
+impl TurnClient {
+    pub fn new(config: TurnConfig, transport: Arc<dyn Transport>) -> Self {
+        TurnClient {
+            config,
+            transport,
+            pending: Mutex::new(None),
+            auth: Mutex::new(None),
+            allocation: Mutex::new(None),
+            allocating: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.allocation.lock().unwrap().is_some()
+    }
+
+    // Строит запрос; при наличии auth добавляет USERNAME/REALM/NONCE и MESSAGE-INTEGRITY
+    fn build_request(
+        &self,
+        method: u16,
+        transaction_id: &TransactionId,
+        auth: Option<&Auth>,
+        add_attributes: &dyn Fn(&mut Vec<u8>, &TransactionId),
+    ) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(128);
+        stun::write_header(&mut buf, method, transaction_id);
+        add_attributes(&mut buf, transaction_id);
+
+        if let Some(auth) = auth {
+            stun::write_attribute(&mut buf, ATTR_USERNAME, self.config.username.as_bytes());
+            stun::write_attribute(&mut buf, ATTR_REALM, auth.realm.as_bytes());
+            stun::write_attribute(&mut buf, ATTR_NONCE, auth.nonce.as_bytes());
+
+            // HMAC считается по сообщению с длиной, уже включающей сам MESSAGE-INTEGRITY
+            let len_with_integrity = (buf.len() - stun::HEADER_SIZE + 24) as u16;
+            buf[2..4].copy_from_slice(&len_with_integrity.to_be_bytes());
+            let mut mac = Hmac::<Sha1>::new_from_slice(&auth.key).expect("HMAC accepts any key size");
+            mac.update(&buf);
+            let integrity = mac.finalize().into_bytes();
+            stun::write_attribute(&mut buf, ATTR_MESSAGE_INTEGRITY, &integrity);
+        }
+
+        stun::finish_message(&mut buf);
+        buf
+    }
+
+    // Отправляет запрос и ждет ответ. На 401/438 обновляет realm/nonce и повторяет.
+    fn transact(&self, method: u16, add_attributes: &dyn Fn(&mut Vec<u8>, &TransactionId)) -> Result<Vec<u8>, String> {
+        for _ in 0..2 {
+            let transaction_id = stun::new_transaction_id();
+            let auth = self.auth.lock().unwrap().clone();
+            let request = self.build_request(method, &transaction_id, auth.as_ref(), add_attributes);
+            let response = self.send_and_wait(&request, transaction_id)?;
+
+            let response_type = stun::message_type(&response);
+            if response_type == method | CLASS_SUCCESS {
+                return Ok(response);
+            }
+            if response_type != method | CLASS_ERROR {
+                return Err(format!("unexpected TURN response type 0x{:04x}", response_type));
+            }
+
+            let code = error_code(&response).unwrap_or(0);
+            if code != ERROR_UNAUTHORIZED && code != ERROR_STALE_NONCE {
+                return Err(format!("TURN error {}", code));
+            }
+
+            let realm = string_attribute(&response, ATTR_REALM)
+                .or_else(|| auth.as_ref().map(|a| a.realm.clone()))
+                .ok_or("TURN 401 without REALM")?;
+            let nonce = string_attribute(&response, ATTR_NONCE).ok_or("TURN 401 without NONCE")?;
+            let key = long_term_key(&self.config.username, &realm, &self.config.password);
+            *self.auth.lock().unwrap() = Some(Auth { realm, nonce, key });
+        }
+
+        Err("TURN authentication failed".to_string())
+    }
+
+    fn send_and_wait(&self, request: &[u8], transaction_id: TransactionId) -> Result<Vec<u8>, String> {
+        *self.pending.lock().unwrap() = Some((transaction_id, None));
+
+        let started = Instant::now();
+        let mut last_send: Option<Instant> = None;
+        while started.elapsed() < TRANSACTION_TIMEOUT {
+            if last_send.is_none_or(|t| t.elapsed() >= RETRANSMIT_INTERVAL) {
+                if let Err(e) = self.transport.send_datagram(request, self.config.server) {
+                    warn!(target: NET, "TURN send error: {}", e);
+                }
+                last_send = Some(Instant::now());
+            }
+
+            thread::sleep(Duration::from_millis(5));
+
+            let mut pending = self.pending.lock().unwrap();
+            if let Some((_, Some(_))) = pending.as_ref() {
+                if let Some((_, response)) = pending.take() {
+                    return response.ok_or_else(|| "TURN response lost".to_string());
+                }
+            }
+        }
+
+        *self.pending.lock().unwrap() = None;
+        Err("TURN transaction timed out".to_string())
+    }
+
+    // Вызывается потоком приема для STUN-сообщений от TURN-сервера.
+    // Возвращает true, если сообщение было ответом на текущую транзакцию.
+    pub fn handle_stun_packet(&self, data: &[u8]) -> bool {
+        if !stun::is_stun_message(data) {
+            return false;
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        match pending.as_mut() {
+            Some((transaction_id, response)) if stun::transaction_id(data) == &transaction_id[..] => {
+                *response = Some(data.to_vec());
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // Выделяет relay и привязывает канал к адресу голосового сервера (блокирующий вызов)
+    pub fn allocate(&self, peer: SocketAddr) -> Result<SocketAddr, String> {
+        let response = self.transact(METHOD_ALLOCATE, &|buf, _| {
+            stun::write_attribute(buf, ATTR_REQUESTED_TRANSPORT, &[TRANSPORT_UDP, 0, 0, 0]);
+        })?;
+
+        let relayed_addr = stun::find_attribute(&response, ATTR_XOR_RELAYED_ADDRESS)
+            .and_then(|value| stun::parse_xor_address(&response, value))
+            .ok_or("Allocate response without XOR-RELAYED-ADDRESS")?;
+        let lifetime = stun::find_attribute(&response, ATTR_LIFETIME)
+            .filter(|v| v.len() == 4)
+            .map(|v| Duration::from_secs(u32::from_be_bytes([v[0], v[1], v[2], v[3]]) as u64))
+            .unwrap_or(DEFAULT_LIFETIME);
+
+        self.bind_channel(peer)?;
+
+        let now = Instant::now();
+        *self.allocation.lock().unwrap() = Some(Allocation {
+            peer,
+            expires_at: now + lifetime,
+            channel_bound_at: now,
+        });
+
+        Ok(relayed_addr)
+    }
+
+    // ChannelBind заодно создает permission для адреса сервера
+    fn bind_channel(&self, peer: SocketAddr) -> Result<(), String> {
+        self.transact(METHOD_CHANNEL_BIND, &|buf, transaction_id| {
+            stun::write_attribute(buf, ATTR_CHANNEL_NUMBER, &[(RELAY_CHANNEL >> 8) as u8, RELAY_CHANNEL as u8, 0, 0]);
+            stun::write_xor_address(buf, ATTR_XOR_PEER_ADDRESS, peer, transaction_id);
+        })?;
+        Ok(())
+    }
+
+    // Запускает выделение relay в отдельном потоке, если оно еще не идет
+    pub fn start_allocation(self: &Arc<Self>, peer: SocketAddr) {
+        if self.is_active() || self.allocating.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let turn = self.clone();
+        thread::spawn(move || {
+            warn!(target: NET, "Direct path failed, allocating TURN relay on {}", turn.config.server);
+            match turn.allocate(peer) {
+                Ok(relayed) => info!(target: NET, "TURN relay allocated: {}", relayed),
+                Err(e) => warn!(target: NET, "TURN allocation failed: {}", e),
+            }
+            turn.allocating.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // Продлевает allocation и привязку канала; вызывается периодически из keep-alive потока
+    pub fn maintain(&self) {
+        let (expires_at, channel_bound_at, peer) = match self.allocation.lock().unwrap().as_ref() {
+            Some(a) => (a.expires_at, a.channel_bound_at, a.peer),
+            None => return,
+        };
+
+        let now = Instant::now();
+        if expires_at.saturating_duration_since(now) < Duration::from_secs(60) {
+            let lifetime = DEFAULT_LIFETIME.as_secs() as u32;
+            match self.transact(METHOD_REFRESH, &|buf, _| {
+                stun::write_attribute(buf, ATTR_LIFETIME, &lifetime.to_be_bytes());
+            }) {
+                Ok(_) => {
+                    if let Some(a) = self.allocation.lock().unwrap().as_mut() {
+                        a.expires_at = Instant::now() + DEFAULT_LIFETIME;
+                    }
+                },
+                Err(e) => {
+                    warn!(target: NET, "TURN refresh failed, dropping relay: {}", e);
+                    *self.allocation.lock().unwrap() = None;
+                    return;
+                }
+            }
+        }
+
+        if now.duration_since(channel_bound_at) > CHANNEL_REFRESH_INTERVAL {
+            match self.bind_channel(peer) {
+                Ok(()) => {
+                    if let Some(a) = self.allocation.lock().unwrap().as_mut() {
+                        a.channel_bound_at = Instant::now();
+                    }
+                },
+                Err(e) => warn!(target: NET, "TURN channel refresh failed: {}", e),
+            }
+        }
+    }
+
+    // Освобождает allocation (Refresh с нулевым lifetime), ответ не ждем
+    pub fn release(&self) {
+        if self.allocation.lock().unwrap().take().is_none() {
+            return;
+        }
+
+        let auth = self.auth.lock().unwrap().clone();
+        let request = self.build_request(METHOD_REFRESH, &stun::new_transaction_id(), auth.as_ref(), &|buf, _| {
+            stun::write_attribute(buf, ATTR_LIFETIME, &0u32.to_be_bytes());
+        });
+        let _ = self.transport.send_datagram(&request, self.config.server);
+        info!(target: NET, "TURN relay released");
+    }
+
+    // Отправляет данные голосовому серверу через relay в ChannelData
+    pub fn send(&self, data: &[u8]) -> std::io::Result<usize> {
+        // ChannelData собирается на стеке: голос через relay идет без выделений памяти
+        let mut packet = [0u8; 4 + MAX_PACKET_SIZE + 3];
+        let Some(body) = packet.get_mut(4..4 + data.len()) else {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        };
+        body.copy_from_slice(data);
+        packet[..2].copy_from_slice(&RELAY_CHANNEL.to_be_bytes());
+        packet[2..4].copy_from_slice(&(data.len() as u16).to_be_bytes());
+        // По UDP выравнивание не обязательно, но часть серверов его ожидает
+        // (нули уже на месте)
+        let padded = (4 + data.len()).next_multiple_of(4);
+
+        self.transport.send_datagram(&packet[..padded], self.config.server).map(|_| data.len())
+    }
+
+    // Диапазон полезной нагрузки внутри ChannelData нашего канала
+    pub fn channel_data_payload(&self, data: &[u8]) -> Option<Range<usize>> {
+        if !is_channel_data(data) {
+            return None;
+        }
+
+        let channel = u16::from_be_bytes([data[0], data[1]]);
+        let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        if channel != RELAY_CHANNEL || 4 + len > data.len() {
+            return None;
+        }
+
+        Some(4..4 + len)
+    }
+}
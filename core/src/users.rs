@@ -0,0 +1,373 @@
+// Участники: сервер выдает каждому клиенту ID и рассылает участникам канала
+// список (ID, ник). Голос от сервера приходит в MEDIA с ID говорящего
+// вместо SSRC, поэтому клиент знает, кто говорит, и может задать громкость
+// каждого участника отдельно. Без сервера (LAN, multicast, прямой звонок)
+// список собирается из IDENTITY: каждый сам раз в IDENTITY_INTERVAL
+// напоминает соседям свой SSRC и ник.
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{control_packet, control_type, control_types, CONTROL_HEADER_SIZE};
+
+pub const MAX_NICKNAME_LEN: usize = 32;
+// Столько времени после последнего кадра с голосом участник считается говорящим
+pub const SPEAKING_TIMEOUT: Duration = Duration::from_millis(300);
+// Столько должен длиться громкий звук, чтобы участник начал считаться
+// говорящим: щелчок или кашель в один-два кадра индикатор не зажигают
+pub const SPEAKING_START_DELAY: Duration = Duration::from_millis(60);
+// Пороги RMS декодированного звука: громче первого - начало речи, тише
+// второго - пауза. Между ними ничего не меняется, поэтому шум в
+// непрерывной передаче не зажигает индикатор, а тихий конец слова не гасит.
+pub const SPEAKING_ON_LEVEL: f32 = 0.02;
+pub const SPEAKING_OFF_LEVEL: f32 = 0.008;
+// Не чаще этого просим список, увидев незнакомый ID
+const ROSTER_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+pub const MAX_USER_VOLUME: f32 = 2.0;
+pub const IDENTITY_INTERVAL: Duration = Duration::from_secs(5);
+// Сосед, не объявлявшийся столько, считается ушедшим
+const IDENTITY_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Ник: непустой, без управляющих символов, не длиннее MAX_NICKNAME_LEN байт
+pub fn is_valid_nickname(name: &str) -> bool {
+    !name.trim().is_empty() && name.len() <= MAX_NICKNAME_LEN && !name.chars().any(char::is_control)
+}
+
+pub fn roster_request_packet() -> Vec<u8> {
+    control_packet(control_types::ROSTER_REQUEST, &[])
+}
+
+// Список: число участников (u16), затем ID (u32), длина ника (u8) и ник.
+// Хвост, не влезающий в датаграмму, отбрасывается.
+pub fn roster_packet(users: &[(u32, String)], max_size: usize) -> Vec<u8> {
+    let mut entries = Vec::new();
+    let mut count: u16 = 0;
+    for (id, name) in users {
+        if CONTROL_HEADER_SIZE + 2 + entries.len() + 5 + name.len() > max_size {
+            break;
+        }
+        entries.extend_from_slice(&id.to_be_bytes());
+        entries.push(name.len() as u8);
+        entries.extend_from_slice(name.as_bytes());
+        count += 1;
+    }
+
+    let mut body = Vec::with_capacity(2 + entries.len());
+    body.extend_from_slice(&count.to_be_bytes());
+    body.extend_from_slice(&entries);
+    control_packet(control_types::ROSTER, &body)
+}
+
+pub fn parse_roster(packet: &[u8]) -> Option<Vec<(u32, String)>> {
+    let body = packet.get(CONTROL_HEADER_SIZE..)?;
+    let count = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let mut pos = 2;
+    let mut users = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let id = body.get(pos..pos + 4)?;
+        let id = u32::from_be_bytes([id[0], id[1], id[2], id[3]]);
+        let len = *body.get(pos + 4)? as usize;
+        let name = std::str::from_utf8(body.get(pos + 5..pos + 5 + len)?).ok()?;
+        users.push((id, name.to_string()));
+        pos += 5 + len;
+    }
+
+    Some(users)
+}
+
+pub fn identity_packet(ssrc: u32, name: &str) -> Vec<u8> {
+    let mut body = Vec::with_capacity(4 + name.len());
+    body.extend_from_slice(&ssrc.to_be_bytes());
+    body.extend_from_slice(name.as_bytes());
+    control_packet(control_types::IDENTITY, &body)
+}
+
+// (SSRC, ник); None для других пакетов и недопустимых ников
+pub fn parse_identity(packet: &[u8]) -> Option<(u32, String)> {
+    if control_type(packet) != control_types::IDENTITY {
+        return None;
+    }
+    let body = packet.get(CONTROL_HEADER_SIZE..)?;
+    let ssrc = u32::from_be_bytes(body.get(..4)?.try_into().ok()?);
+    let name = std::str::from_utf8(&body[4..]).ok()?;
+    is_valid_nickname(name).then(|| (ssrc, name.to_string()))
+}
+
+// Речь участника по громкости его кадров: паузы короче SPEAKING_TIMEOUT ее
+// не прерывают
+struct VoiceActivity {
+    // С какого кадра звук громче SPEAKING_ON_LEVEL; None - паузы не было
+    // с тех пор, как он стал тише SPEAKING_OFF_LEVEL
+    loud_since: Option<Instant>,
+    // Последний кадр не тише SPEAKING_OFF_LEVEL
+    last_voice: Instant,
+    speaking: bool,
+}
+
+impl VoiceActivity {
+    fn on_level(&mut self, level: f32, now: Instant) {
+        if self.speaking && now.duration_since(self.last_voice) >= SPEAKING_TIMEOUT {
+            self.speaking = false;
+        }
+        if level < SPEAKING_OFF_LEVEL {
+            self.loud_since = None;
+        } else {
+            self.last_voice = now;
+            if level >= SPEAKING_ON_LEVEL {
+                self.loud_since.get_or_insert(now);
+            }
+        }
+        if self.loud_since.is_some_and(|since| now.duration_since(since) >= SPEAKING_START_DELAY) {
+            self.speaking = true;
+        }
+    }
+
+    // Пакеты могли перестать приходить совсем
+    fn is_speaking(&self) -> bool {
+        self.speaking && self.last_voice.elapsed() < SPEAKING_TIMEOUT
+    }
+}
+
+// Изменение состава канала или активности участника
+pub enum Change {
+    Joined(u32, String),
+    // Был в канале до нас: первый список после подключения
+    Listed(u32, String),
+    Left(u32, String),
+    Speaking(u32, bool),
+}
+
+// Участники канала на стороне клиента
+#[derive(Default)]
+pub struct Roster {
+    // Наш ID от сервера (0 - еще не выдан)
+    own_id: AtomicU32,
+    names: Mutex<HashMap<u32, String>>,
+    // Громкость задается и для тех, кого еще нет в списке
+    volumes: Mutex<HashMap<u32, f32>>,
+    // Заглушенные у нас: их пакеты не декодируются. Как и громкость,
+    // переживают переподключение.
+    muted: Mutex<HashSet<u32>>,
+    // Ники заглушенных: сервер выдает ID заново при каждом входе, а ник
+    // узнается и в следующей сессии (VoiceClientConfig::muted_users)
+    muted_names: Mutex<HashSet<String>>,
+    voice: Mutex<HashMap<u32, VoiceActivity>>,
+    last_request: Mutex<Option<Instant>>,
+    // Кто говорил на прошлой проверке poll_speaking
+    speaking: Mutex<HashSet<u32>>,
+    // Изменения, еще не забранные take_changes
+    changes: Mutex<Vec<Change>>,
+    // Когда соседи без сервера последний раз объявляли ник
+    announced: Mutex<HashMap<u32, Instant>>,
+}
+
+impl Roster {
+    pub fn new(muted_names: Vec<String>) -> Self {
+        Roster { muted_names: Mutex::new(muted_names.into_iter().collect()), ..Roster::default() }
+    }
+
+    pub fn own_id(&self) -> u32 {
+        self.own_id.load(Ordering::Relaxed)
+    }
+
+    pub fn set_own_id(&self, id: u32) {
+        self.own_id.store(id, Ordering::Relaxed);
+    }
+
+    // Обрабатывает ROSTER. Возвращает true, если пакет наш.
+    pub fn on_packet(&self, packet: &[u8]) -> bool {
+        if control_type(packet) != control_types::ROSTER {
+            return false;
+        }
+        if let Some(users) = parse_roster(packet) {
+            let users: HashMap<u32, String> = users.into_iter().collect();
+            let mut names = self.names.lock().unwrap();
+            let mut changes = self.changes.lock().unwrap();
+            let own_id = self.own_id();
+            let first = names.is_empty();
+            for (id, name) in names.iter() {
+                if *id != own_id && !users.contains_key(id) {
+                    changes.push(Change::Left(*id, name.clone()));
+                }
+            }
+            for (id, name) in &users {
+                self.mute_by_name(*id, name);
+                if *id != own_id && !names.contains_key(id) {
+                    let name = name.clone();
+                    changes.push(if first { Change::Listed(*id, name) } else { Change::Joined(*id, name) });
+                }
+            }
+            *names = users;
+        }
+        true
+    }
+
+    // Сосед без сервера объявил свой ник (см. identity_packet)
+    pub fn on_identity(&self, id: u32, name: String) {
+        self.announced.lock().unwrap().insert(id, Instant::now());
+        self.mute_by_name(id, &name);
+        let mut names = self.names.lock().unwrap();
+        if names.insert(id, name.clone()).is_none() {
+            self.changes.lock().unwrap().push(Change::Joined(id, name));
+        }
+    }
+
+    // Убирает соседей, переставших объявлять ник
+    pub fn expire_identities(&self) {
+        let mut announced = self.announced.lock().unwrap();
+        let mut names = self.names.lock().unwrap();
+        let mut changes = self.changes.lock().unwrap();
+        announced.retain(|id, last| {
+            if last.elapsed() < IDENTITY_TIMEOUT {
+                return true;
+            }
+            if let Some(name) = names.remove(id) {
+                changes.push(Change::Left(*id, name));
+            }
+            false
+        });
+    }
+
+    // Отмечает, кто начал и кто перестал говорить с прошлого вызова
+    pub fn poll_speaking(&self) {
+        let now_speaking: HashSet<u32> = self
+            .voice
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, activity)| activity.is_speaking())
+            .map(|(id, _)| *id)
+            .collect();
+        let mut speaking = self.speaking.lock().unwrap();
+        let mut changes = self.changes.lock().unwrap();
+        for id in now_speaking.difference(&speaking) {
+            changes.push(Change::Speaking(*id, true));
+        }
+        for id in speaking.difference(&now_speaking) {
+            changes.push(Change::Speaking(*id, false));
+        }
+        *speaking = now_speaking;
+    }
+
+    pub fn take_changes(&self) -> Vec<Change> {
+        std::mem::take(&mut *self.changes.lock().unwrap())
+    }
+
+    pub fn name(&self, id: u32) -> Option<String> {
+        self.names.lock().unwrap().get(&id).cloned()
+    }
+
+    // Громкость (RMS) декодированного кадра участника, до его громкости у нас
+    pub fn on_level(&self, id: u32, level: f32) {
+        let now = Instant::now();
+        self.voice
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert(VoiceActivity { loud_since: None, last_voice: now, speaking: false })
+            .on_level(level, now);
+    }
+
+    // Пришел голос участника. Возвращает true, если участник незнаком и
+    // пора попросить у сервера свежий список.
+    pub fn on_voice(&self, id: u32) -> bool {
+        if self.names.lock().unwrap().contains_key(&id) {
+            return false;
+        }
+
+        let mut last_request = self.last_request.lock().unwrap();
+        if last_request.is_some_and(|t| t.elapsed() < ROSTER_REQUEST_INTERVAL) {
+            return false;
+        }
+        *last_request = Some(Instant::now());
+        true
+    }
+
+    pub fn volume(&self, id: u32) -> f32 {
+        self.volumes.lock().unwrap().get(&id).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_volume(&self, id: u32, volume: f32) {
+        self.volumes.lock().unwrap().insert(id, volume);
+    }
+
+    // Ник запоминается, если участник уже в списке
+    pub fn set_muted(&self, id: u32, muted: bool) {
+        if let Some(name) = self.name(id) {
+            let mut names = self.muted_names.lock().unwrap();
+            if muted {
+                names.insert(name);
+            } else {
+                names.remove(&name);
+            }
+        }
+        let mut set = self.muted.lock().unwrap();
+        if muted {
+            set.insert(id);
+        } else {
+            set.remove(&id);
+        }
+    }
+
+    pub fn is_muted(&self, id: u32) -> bool {
+        self.muted.lock().unwrap().contains(&id)
+    }
+
+    // Заменяет ники заглушенных; кто из канала попал в список или выпал из
+    // него, тот заглушен или слышен сразу
+    pub fn set_muted_names(&self, names: Vec<String>) {
+        let names: HashSet<String> = names.into_iter().collect();
+        let users = self.names.lock().unwrap().clone();
+        let previous = std::mem::replace(&mut *self.muted_names.lock().unwrap(), names.clone());
+        let mut muted = self.muted.lock().unwrap();
+        for (id, name) in users {
+            if names.contains(&name) {
+                muted.insert(id);
+            } else if previous.contains(&name) {
+                muted.remove(&id);
+            }
+        }
+    }
+
+    // Ники заглушенных по алфавиту, чтобы хост сохранил их до следующей сессии
+    pub fn muted_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.muted_names.lock().unwrap().iter().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn mute_by_name(&self, id: u32, name: &str) {
+        if self.muted_names.lock().unwrap().contains(name) {
+            self.muted.lock().unwrap().insert(id);
+        }
+    }
+
+    pub fn is_speaking(&self, id: u32) -> bool {
+        self.voice.lock().unwrap().get(&id).is_some_and(VoiceActivity::is_speaking)
+    }
+
+    // (ID, ник, говорит ли сейчас), по возрастанию ID
+    pub fn users(&self) -> Vec<(u32, String, bool)> {
+        let mut users: Vec<(u32, String, bool)> = self
+            .names
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, name)| (*id, name.clone(), self.is_speaking(*id)))
+            .collect();
+        users.sort_by_key(|(id, _, _)| *id);
+        users
+    }
+
+    // После переподключения список устарел
+    pub fn reset(&self) {
+        self.own_id.store(0, Ordering::Relaxed);
+        self.names.lock().unwrap().clear();
+        self.voice.lock().unwrap().clear();
+        self.speaking.lock().unwrap().clear();
+        self.changes.lock().unwrap().clear();
+        self.announced.lock().unwrap().clear();
+    }
+}
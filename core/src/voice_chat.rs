@@ -0,0 +1,4035 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::net::{UdpSocket, SocketAddr, ToSocketAddrs, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, Ordering, AtomicU32, AtomicI32, AtomicU64, AtomicUsize};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, info, trace, warn};
+use logging::{AUDIO, CLIENT, CODEC, NET};
+use audio::AudioStream;
+use opus::{Encoder, Decoder, Channels, Application, Bitrate};
+
+mod afk;
+#[cfg(target_os = "android")]
+mod android;
+pub mod audio;
+mod audio_file;
+mod c_ptr;
+pub mod channels;
+mod clip;
+pub mod client;
+pub mod config;
+mod cues;
+pub mod handshake;
+mod direct;
+pub mod error;
+pub mod events;
+mod handles;
+mod lan;
+pub mod logging;
+mod loopback;
+mod metrics;
+mod mic_check;
+mod multitrack;
+mod mumble_link;
+mod mute;
+mod network;
+pub mod ogg;
+pub mod overlay;
+mod p2p;
+pub mod positional;
+mod ptt_release;
+mod protocol;
+mod realtime;
+mod recording;
+mod replay;
+mod send_queue;
+mod soundboard;
+pub mod stats;
+mod stun;
+mod test_tone;
+pub mod text;
+mod transport;
+mod turn;
+pub mod users;
+mod voice_output;
+pub mod websocket;
+mod whisper;
+
+pub use error::NsvcError;
+pub use protocol::*;
+
+const CHANNELS: Channels = Channels::Mono;
+const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(60);
+// Как часто keep-alive поток проверяет таймауты, TURN и P2P
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_PACKET_SIZE: usize = 4000;
+const MAX_DECODED_FRAME: usize = 5760; // 120 мс при 48 кГц - максимум для одного пакета Opus
+const SILENCE_PACKET: [u8; 1] = [0x01]; // Специальный пакет для обозначения тишины
+const SERVER_TIMEOUT: Duration = Duration::from_secs(5); // Сколько ждем ответа сервера до Reconnecting
+const CONNECTION_FAIL_TIMEOUT: Duration = Duration::from_secs(30); // Сколько ждем до Failed
+const DEFAULT_SERVER_SILENCE_TIMEOUT: Duration = Duration::from_secs(60); // Тишина сервера, после которой клиент отключается
+pub const MAX_OUTPUT_VOLUME: f32 = 2.0;
+const SEND_DRAIN_TIMEOUT: Duration = Duration::from_millis(200); // Сколько при остановке ждем отправки накопленного голоса
+const STUN_RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1200; // Проходит без IP-фрагментации почти на любом пути
+const MIN_DATAGRAM_SIZE: usize = 576;
+const MULTICAST_HEADER_SIZE: usize = CONTROL_HEADER_SIZE + 4; // Заголовок и SSRC отправителя
+const MEDIA_FRAMING_OVERHEAD: usize = 24; // Запас под обертки поверх Opus (заголовок MEDIA, SSRC, ChannelData TURN)
+const MTU_PROBE_WAIT: Duration = Duration::from_millis(300);
+const EVENTS_CHECK_INTERVAL: Duration = Duration::from_millis(50); // Как часто проверяем, кто начал или перестал говорить
+// Буферы сокета по умолчанию: стандартных 8-64 КБ на некоторых ОС не
+// хватает на всплески Opus, и пакеты теряются до того, как мы их прочитали
+const DEFAULT_SOCKET_BUFFER_SIZE: usize = 256 * 1024;
+const MAX_SOCKET_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+const MAX_TEST_TONE_SECONDS: u32 = 30;
+const MAX_MIC_CHECK_SECONDS: u32 = 10;
+// Устройство вывода без клиента закрывается с запасом после конца звука
+const LOCAL_OUTPUT_CLOSE_DELAY: Duration = Duration::from_millis(300);
+
+#[repr(C)]
+pub struct VoiceClient {
+    is_transmitting: Arc<AtomicBool>,
+    transmit_mode: Arc<AtomicI32>,
+    // Пик громкости микрофона с прошлого опроса (биты f32)
+    input_level: Arc<AtomicU32>,
+    // То же для воспроизведения
+    output_level: Arc<AtomicU32>,
+    // Захват без передачи для проверки микрофона
+    preview_stream: Mutex<Option<AudioStream>>,
+    // Вывод без запущенного клиента (проверочный сигнал, проверка
+    // микрофона) и момент, когда звук кончится
+    local_output: Arc<Mutex<Option<(Instant, AudioStream)>>>,
+    // Запись проверки микрофона и момент ее конца, см. mic_check.rs
+    mic_check_stream: Mutex<Option<(Instant, AudioStream)>>,
+    link: Arc<ServerLink>,
+    running: Arc<AtomicBool>,
+    input_stream: Mutex<Option<AudioStream>>,
+    output_stream: Mutex<Option<AudioStream>>,
+    encoder: Arc<Mutex<Encoder>>,
+    playback_buffer: Arc<Mutex<PlaybackMixer>>,
+    bitrate: Arc<AtomicU32>,
+    // Новые поля для DTX:
+    last_silence_packet: Arc<Mutex<Instant>>,
+    was_speaking: Arc<AtomicBool>,
+    connection: Arc<ConnectionTracker>,
+    stun: Arc<StunState>,
+    handshake: Arc<handshake::Handshake>,
+    errors: Arc<ErrorReporter>,
+    events: Arc<events::EventSink>,
+    // Потоки (задачи) сети текущего запуска, см. network::Workers
+    workers: Arc<network::Workers>,
+    stats: Arc<stats::Stats>,
+    send_queue: Arc<send_queue::SendQueue>,
+    // Размер кадра согласуется с сервером при рукопожатии
+    frame_size: Arc<AtomicUsize>,
+    // Возможности, которые запрашиваем в HELLO
+    features: AtomicU32,
+    vad: config::Vad,
+    // Устройства из настроек; None - по умолчанию
+    input_device: Mutex<Option<String>>,
+    output_device: Mutex<Option<String>>,
+    keep_alive_interval_ms: Arc<AtomicU64>,
+    channels: Arc<channels::ChannelState>,
+    // Ключ и пароль сервера, отправляются в HELLO
+    credentials: Mutex<handshake::Credentials>,
+    nickname: Arc<Mutex<String>>,
+    roster: Arc<users::Roster>,
+    // Устройства cpal или MockAudio (см. audio.rs)
+    audio: Arc<dyn audio::AudioBackend>,
+    // Устройства, общие с другими клиентами (voice_client_share_audio)
+    audio_group: Mutex<Option<Arc<audio::SharedAudio>>>,
+    // Mute и deafen, см. mute.rs
+    mute: Arc<mute::MuteState>,
+    // Передача после отпускания клавиши, см. ptt_release.rs
+    release: Arc<ptt_release::ReleaseDelay>,
+    // Шепот выбранным участникам, см. whisper.rs
+    whisper: Arc<whisper::Whisper>,
+    // Выключение забытого открытого микрофона, см. afk.rs
+    afk: Arc<afk::AfkGuard>,
+    // Звуковые сигналы и уведомления, см. cues.rs
+    cues: Arc<cues::CuePlayer>,
+    // Запись разговора, см. recording.rs
+    recording: Arc<recording::Recording>,
+    // Последние секунды разговора, см. clip.rs
+    clip: Arc<clip::ClipBuffer>,
+    // Файл, передаваемый вместо микрофона или поверх него, см. soundboard.rs
+    soundboard: Arc<soundboard::Soundboard>,
+    // Положение в игре по Mumble Link, см. positional.rs
+    positional: Arc<positional::Positional>,
+    // Сокет "кто говорит" для оверлеев, см. overlay.rs
+    overlay: Arc<overlay::Overlay>,
+    // Метрики для Prometheus, см. metrics.rs
+    metrics: metrics::Metrics,
+    // Голоса на отдельное устройство для трансляции, см. voice_output.rs
+    voice_output: Arc<voice_output::VoiceOutput>,
+}
+
+// Клиенты, выданные хосту (см. handles.rs)
+static CLIENTS: handles::Registry<VoiceClient> = handles::Registry::new();
+
+// Коды ошибок
+pub mod error_codes {
+    pub const SUCCESS: i32 = 0;
+    pub const NULL_POINTER: i32 = -1;
+    pub const INVALID_IP: i32 = -2;
+    pub const SOCKET_BIND_FAILED: i32 = -3;
+    pub const INVALID_SERVER_ADDR: i32 = -4;
+    pub const SOCKET_CONNECT_FAILED: i32 = -5;
+    pub const NO_INPUT_DEVICE: i32 = -6;
+    pub const NO_OUTPUT_DEVICE: i32 = -7;
+    pub const ENCODER_INIT_FAILED: i32 = -8;
+    pub const INPUT_STREAM_FAILED: i32 = -9;
+    pub const OUTPUT_STREAM_FAILED: i32 = -10;
+    pub const INVALID_AUDIO_PARAM: i32 = -11;
+    pub const NOT_RUNNING: i32 = -12;
+    pub const UNSUPPORTED_SAMPLE_FORMAT: i32 = -13;
+    pub const STUN_FAILED: i32 = -14;
+    pub const BUFFER_TOO_SMALL: i32 = -15;
+    pub const PROTOCOL_MISMATCH: i32 = -16;
+    pub const SOCKET_OPTION_FAILED: i32 = -17;
+    pub const ACCESS_DENIED: i32 = -18;
+    pub const LOG_FILE_FAILED: i32 = -19;
+    pub const RECORDING_FAILED: i32 = -20;
+    pub const AUDIO_FILE_FAILED: i32 = -21;
+    pub const MUMBLE_LINK_FAILED: i32 = -22;
+
+    use std::ffi::CStr;
+
+    // Описание кода для хоста; неизвестные коды - "unknown error"
+    pub fn description(code: i32) -> &'static CStr {
+        match code {
+            SUCCESS => c"success",
+            NULL_POINTER => c"null pointer argument",
+            INVALID_IP => c"invalid IP address",
+            SOCKET_BIND_FAILED => c"failed to bind socket",
+            INVALID_SERVER_ADDR => c"invalid or unresolvable server address",
+            SOCKET_CONNECT_FAILED => c"failed to connect socket",
+            NO_INPUT_DEVICE => c"no audio input device",
+            NO_OUTPUT_DEVICE => c"no audio output device",
+            ENCODER_INIT_FAILED => c"failed to create Opus encoder",
+            INPUT_STREAM_FAILED => c"failed to open audio input stream",
+            OUTPUT_STREAM_FAILED => c"failed to open audio output stream",
+            INVALID_AUDIO_PARAM => c"invalid parameter",
+            NOT_RUNNING => c"client is not running",
+            UNSUPPORTED_SAMPLE_FORMAT => c"audio device has no supported sample format",
+            STUN_FAILED => c"STUN request failed",
+            BUFFER_TOO_SMALL => c"buffer too small",
+            PROTOCOL_MISMATCH => c"server speaks an incompatible protocol version",
+            SOCKET_OPTION_FAILED => c"failed to set socket option",
+            ACCESS_DENIED => c"access denied",
+            LOG_FILE_FAILED => c"failed to open log file",
+            RECORDING_FAILED => c"failed to write recording",
+            AUDIO_FILE_FAILED => c"failed to read audio file",
+            MUMBLE_LINK_FAILED => c"failed to open Mumble Link shared memory",
+            _ => c"unknown error",
+        }
+    }
+}
+
+// Последняя ошибка FFI-вызова в этом потоке: код и подробности. Ошибки
+// других потоков, в том числе внутренних потоков клиента, ее не затирают.
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(i32, String)>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(code: i32, message: &str) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((code, message.to_string())));
+}
+
+// Граница FFI для внутренних ошибок: журнал, last error и код из error_codes
+fn report_error(error: &NsvcError) -> i32 {
+    fail(error.code(), &error.to_string())
+}
+
+// Описатель нового клиента или NULL с записанной ошибкой
+fn handle_or_null(result: Result<*mut c_void, NsvcError>) -> *mut c_void {
+    result.unwrap_or_else(|e| {
+        report_error(&e);
+        std::ptr::null_mut()
+    })
+}
+
+// Пишет ошибку в лог и запоминает ее для voice_client_last_error_message;
+// возвращает код, чтобы FFI-функция могла сразу его вернуть
+fn fail(code: i32, message: &str) -> i32 {
+    logging::init();
+    error!(target: CLIENT, "{}", message);
+    set_last_error(code, message);
+    code
+}
+
+// Время для keep-alive: миллисекунды Unix
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+// Путь до голосового сервера: напрямую по UDP или через TURN relay.
+// Голос может идти и напрямую собеседнику, если включен P2P.
+// В LAN-режиме сервера нет, голос рассылается найденным через mDNS соседям
+// или в multicast-группу. При прямом звонке сервера тоже нет, голос идет
+// единственному собеседнику.
+struct ServerLink {
+    transport: Arc<dyn transport::Transport>,
+    server_addr: Option<SocketAddr>,
+    turn: Mutex<Option<Arc<turn::TurnClient>>>,
+    p2p: Arc<p2p::P2pSession>,
+    lan_name: Option<String>,
+    lan: Mutex<Option<Arc<lan::LanSession>>>,
+    multicast: Option<MulticastGroup>,
+    direct: Option<direct::DirectCall>,
+    // Заданный предел размера датаграммы и предел, найденный проверкой MTU (0 - неизвестен)
+    max_datagram: AtomicUsize,
+    path_mtu_limit: AtomicUsize,
+    probe_mtu: AtomicBool,
+    // SSRC и счетчик наших пакетов MEDIA; начальные значения случайны,
+    // чтобы после перезапуска клиента получатели не приняли поток за повтор.
+    // После рукопожатия SSRC заменяется на выданный сервером ID участника.
+    media_ssrc: AtomicU32,
+    media_seq: AtomicU32,
+    // Токен сессии от сервера (0 - нет): серверу MEDIA уходит с ним вместо SSRC
+    session_token: AtomicU32,
+    // Весь трафик сокета, см. stats::Traffic
+    traffic: Arc<stats::Traffic>,
+}
+
+struct MulticastGroup {
+    group: SocketAddr,
+    // Идентификатор отправителя: по нему слушатели разделяют потоки
+    // и отбрасывают собственные пакеты, вернувшиеся через loopback
+    ssrc: u32,
+}
+
+// Источник звука: адрес отправителя и SSRC (0 для пакетов без SSRC)
+type SourceKey = (SocketAddr, u32);
+
+// Декодер удаленного источника, время его последнего пакета
+// и окно номеров для отбрасывания повторов
+struct RemoteSource {
+    decoder: Decoder,
+    last_packet: Instant,
+    replay: replay::ReplayWindow,
+    duplicates_dropped: u64,
+    replays_dropped: u64,
+    jitter: stats::Jitter,
+    // Последний пакет клипа от источника: по нему видно начало нового клипа
+    last_clip_packet: Option<Instant>,
+}
+
+const SOURCE_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+// Пакеты клипа после такого перерыва - уже следующий клип. Паузы, которые
+// детектор голоса вырезал внутри клипа, обычно короче.
+const CLIP_GAP: Duration = Duration::from_secs(2);
+
+impl ServerLink {
+    fn new(socket: UdpSocket, server_addr: Option<SocketAddr>) -> Self {
+        let traffic = Arc::new(stats::Traffic::default());
+        ServerLink {
+            transport: Arc::new(transport::UdpTransport::new(socket, traffic.clone())),
+            server_addr,
+            turn: Mutex::new(None),
+            p2p: Arc::new(p2p::P2pSession::new()),
+            lan_name: None,
+            lan: Mutex::new(None),
+            multicast: None,
+            direct: None,
+            max_datagram: AtomicUsize::new(DEFAULT_MAX_DATAGRAM_SIZE),
+            path_mtu_limit: AtomicUsize::new(0),
+            probe_mtu: AtomicBool::new(false),
+            media_ssrc: AtomicU32::new(rand::random()),
+            media_seq: AtomicU32::new(rand::random()),
+            session_token: AtomicU32::new(0),
+            traffic,
+        }
+    }
+    
+    // Keep-alive с ID клиента (он же SSRC) и временем отправки
+    fn keep_alive_packet(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(12);
+        body.extend_from_slice(&self.media_ssrc.load(Ordering::Relaxed).to_be_bytes());
+        body.extend_from_slice(&unix_millis().to_be_bytes());
+        control_packet(control_types::KEEP_ALIVE, &body)
+    }
+    
+    // RTT по вернувшемуся от сервера собственному keep-alive
+    fn keep_alive_rtt(&self, packet: &[u8]) -> Option<Duration> {
+        let body = packet.get(CONTROL_HEADER_SIZE..CONTROL_HEADER_SIZE + 12)?;
+        let client_id = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+        if client_id != self.media_ssrc.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut sent = [0u8; 8];
+        sent.copy_from_slice(&body[4..12]);
+        let elapsed = unix_millis().checked_sub(u64::from_be_bytes(sent))?;
+        Some(Duration::from_millis(elapsed))
+    }
+    
+    // Оборачивает пакет Opus в MEDIA со следующим номером
+    fn write_media_packet(&self, out: &mut Vec<u8>, opus: &[u8]) {
+        let seq = self.media_seq.fetch_add(1, Ordering::Relaxed);
+        write_media_packet(out, self.media_ssrc.load(Ordering::Relaxed), seq, opus);
+    }
+
+    // То же шепотом для targets, см. whisper.rs
+    fn write_whisper_packet(&self, out: &mut Vec<u8>, targets: &[u32], opus: &[u8]) {
+        let seq = self.media_seq.fetch_add(1, Ordering::Relaxed);
+        write_whisper_packet(out, self.media_ssrc.load(Ordering::Relaxed), seq, targets, opus);
+    }
+
+    fn max_datagram(&self) -> usize {
+        let configured = self.max_datagram.load(Ordering::Relaxed);
+        match self.path_mtu_limit.load(Ordering::Relaxed) {
+            0 => configured,
+            limit => configured.min(limit),
+        }
+    }
+
+    // Сколько байт Opus влезает в одну датаграмму вместе с обертками
+    fn max_payload(&self) -> usize {
+        self.max_datagram().saturating_sub(MEDIA_FRAMING_OVERHEAD).min(MAX_PACKET_SIZE)
+    }
+
+
+    fn lan(&self) -> Option<Arc<lan::LanSession>> {
+        self.lan.lock().unwrap().clone()
+    }
+
+    fn turn(&self) -> Option<Arc<turn::TurnClient>> {
+        self.turn.lock().unwrap().clone()
+    }
+
+    fn is_turn_server(&self, addr: SocketAddr) -> bool {
+        self.turn().is_some_and(|turn| turn.config.server == addr)
+    }
+
+    fn send(&self, data: &[u8]) -> std::io::Result<usize> {
+        match (self.turn(), self.server_addr) {
+            (Some(turn), _) if turn.is_active() => turn.send(data),
+            (_, Some(server_addr)) => self.transport.send_datagram(data, server_addr),
+            (_, None) => Err(std::io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    // SSRC и диапазон Opus-пакета внутри multicast-пакета; None для чужих
+    // пакетов и для собственных, вернувшихся через loopback
+    fn multicast_payload(&self, data: &[u8]) -> Option<(u32, std::ops::Range<usize>)> {
+        let multicast = self.multicast.as_ref()?;
+        if !is_control_packet(data) || control_type(data) != control_types::MULTICAST_AUDIO || data.len() < CONTROL_HEADER_SIZE + 4 {
+            return None;
+        }
+        
+        let ssrc_bytes = &data[CONTROL_HEADER_SIZE..CONTROL_HEADER_SIZE + 4];
+        let ssrc = u32::from_be_bytes([ssrc_bytes[0], ssrc_bytes[1], ssrc_bytes[2], ssrc_bytes[3]]);
+        if ssrc == multicast.ssrc {
+            return None;
+        }
+        Some((ssrc, CONTROL_HEADER_SIZE + 4..data.len()))
+    }
+
+    // Прощание серверу или собеседнику в прямом звонке. Соседи в LAN узнают
+    // об уходе по mDNS, а в multicast-группе уходить не от кого.
+    fn send_goodbye(&self) {
+        let packet = control_packet(control_types::GOODBYE, &[]);
+        let result = match (self.server_addr, self.direct.as_ref().and_then(|direct| direct.peer())) {
+            (Some(_), _) => self.send(&packet),
+            (None, Some(peer)) => self.transport.send_datagram(&packet, peer),
+            (None, None) => return,
+        };
+        if let Err(e) = result {
+            warn!(target: NET, "Goodbye send error: {}", e);
+        }
+    }
+    
+    // Keep-alive идет серверу, а в LAN-режиме - всем соседям
+    fn send_keep_alive(&self, data: &[u8]) -> std::io::Result<usize> {
+        if self.server_addr.is_none() {
+            return self.send_media(data);
+        }
+        self.send(data)
+    }
+
+    // Голосовые пакеты: всем соседям в LAN, собеседнику при прямом звонке,
+    // напрямую пиру, если P2P-путь установлен, иначе на сервер
+    fn send_media(&self, data: &[u8]) -> std::io::Result<usize> {
+        // Слишком большие пакеты фрагментируются на уровне IP и часто теряются целиком
+        if data.len() > self.max_payload() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("packet of {} bytes exceeds max datagram size", data.len()),
+            ));
+        }
+        
+        // Обертки собираются на стеке: поток отправки не выделяет память на пакет
+        let mut scratch = [0u8; MULTICAST_HEADER_SIZE + MAX_PACKET_SIZE];
+        
+        if let Some(multicast) = &self.multicast {
+            scratch[..CONTROL_HEADER_SIZE].copy_from_slice(&control_header(control_types::MULTICAST_AUDIO));
+            scratch[CONTROL_HEADER_SIZE..MULTICAST_HEADER_SIZE].copy_from_slice(&multicast.ssrc.to_be_bytes());
+            scratch[MULTICAST_HEADER_SIZE..MULTICAST_HEADER_SIZE + data.len()].copy_from_slice(data);
+            let packet = &scratch[..MULTICAST_HEADER_SIZE + data.len()];
+            return self.transport.send_datagram(packet, multicast.group).map(|_| data.len());
+        }
+        
+        // Ошибка одного соседа не лишает пакета остальных
+        if let Some(lan) = self.lan() {
+            for peer in lan.peers() {
+                if let Err(e) = self.transport.send_datagram(data, peer) {
+                    let errors = lan.on_send_error(peer);
+                    if errors % 100 == 1 {
+                        warn!(target: NET, "Send error to {}: {}, {} errors so far", peer, e, errors);
+                    }
+                }
+            }
+            return Ok(data.len());
+        }
+        
+        if let Some(direct) = &self.direct {
+            // Пока никто не позвонил, слать некому
+            return match direct.peer() {
+                Some(peer) => self.transport.send_datagram(data, peer),
+                None => Ok(data.len()),
+            };
+        }
+        
+        // Адресатов шепота выбирает сервер, пиру он идет через него
+        let whisper = is_control_packet(data) && control_type(data) == control_types::MEDIA_WHISPER;
+        match self.p2p.peer().filter(|_| !whisper) {
+            Some(peer) => self.transport.send_datagram(data, peer),
+            None => match self.with_session_token(data, &mut scratch) {
+                Some(packet) => self.send(packet),
+                None => self.send(data),
+            },
+        }
+    }
+
+    // MEDIA для сервера: SSRC заменяется токеном сессии. Собеседники по P2P
+    // токен не видят, им голос идет с ID участника. Копия собирается в buf.
+    fn with_session_token<'a>(&self, data: &[u8], buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        let token = self.session_token.load(Ordering::Relaxed);
+        let media = is_control_packet(data)
+            && (is_media_type(control_type(data)) || control_type(data) == control_types::MEDIA_WHISPER);
+        if token == 0 || !media {
+            return None;
+        }
+        let packet = buf.get_mut(..data.len())?;
+        packet.copy_from_slice(data);
+        packet.get_mut(CONTROL_HEADER_SIZE..CONTROL_HEADER_SIZE + 4)?.copy_from_slice(&token.to_be_bytes());
+        Some(packet)
+    }
+
+    // Диапазон полезной нагрузки, если пакет пришел от сервера (напрямую или через relay)
+    fn server_payload(&self, from: SocketAddr, data: &[u8]) -> Option<std::ops::Range<usize>> {
+        if Some(from) == self.server_addr {
+            return Some(0..data.len());
+        }
+        
+        let turn = self.turn()?;
+        if from != turn.config.server {
+            return None;
+        }
+        if turn.handle_stun_packet(data) {
+            return None;
+        }
+        turn.channel_data_payload(data)
+    }
+}
+
+// Очереди воспроизведения по источникам (сервер, P2P-пир, соседи в LAN).
+// Смешивает их поток микшера (см. realtime.rs).
+struct PlaybackMixer {
+    sources: HashMap<SourceKey, VecDeque<f32>>,
+    // Предел очереди одного источника в отсчетах
+    capacity: usize,
+    // Общая громкость воспроизведения, см. voice_client_set_output_volume
+    volume: f32,
+    // Заглушен: слышны только сигналы cues.rs
+    deafened: bool,
+    // Получают голоса без сигналов и до громкости
+    recording: Arc<recording::Recording>,
+    voice_output: Arc<voice_output::VoiceOutput>,
+}
+
+impl PlaybackMixer {
+    fn new(
+        capacity: usize,
+        recording: Arc<recording::Recording>,
+        voice_output: Arc<voice_output::VoiceOutput>,
+    ) -> Self {
+        PlaybackMixer {
+            sources: HashMap::new(),
+            capacity,
+            volume: 1.0,
+            deafened: false,
+            recording,
+            voice_output,
+        }
+    }
+    
+    fn push(&mut self, source: SourceKey, samples: &[f32]) {
+        let queue = self.sources
+            .entry(source)
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+        queue.extend(samples);
+        
+        // Поддержка размера буфера
+        while queue.len() > self.capacity {
+            queue.pop_front();
+        }
+    }
+    
+    // Сигнал идет без ограничения очереди: короткий буфер его бы обрезал
+    fn push_cue(&mut self, samples: Vec<f32>) {
+        self.sources.insert(cues::CUE_SOURCE, samples.into());
+    }
+    
+    fn mix_into(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let deafened = self.deafened;
+        for (source, queue) in self.sources.iter_mut() {
+            if *source == cues::CUE_SOURCE {
+                continue;
+            }
+            // Голоса заглушенному выбрасываются в том же темпе, а не копятся
+            if deafened {
+                queue.drain(..out.len().min(queue.len()));
+                continue;
+            }
+            for sample in out.iter_mut() {
+                match queue.pop_front() {
+                    Some(s) => *sample += s,
+                    None => break,
+                }
+            }
+        }
+        // Сигналы в запись и отдельный вывод голосов не попадают
+        self.recording.on_playback(out);
+        self.voice_output.on_playback(out);
+        if let Some(queue) = self.sources.get_mut(&cues::CUE_SOURCE) {
+            for sample in out.iter_mut() {
+                match queue.pop_front() {
+                    Some(s) => *sample += s,
+                    None => break,
+                }
+            }
+        }
+        for sample in out.iter_mut() {
+            *sample = (*sample * self.volume).clamp(-1.0, 1.0);
+        }
+        self.sources.retain(|_, queue| !queue.is_empty());
+    }
+    
+    // Глубина буфера - по самому заполненному источнику
+    fn buffered_samples(&self) -> usize {
+        self.sources.values().map(|q| q.len()).max().unwrap_or(0)
+    }
+}
+
+// Состояния соединения с сервером
+pub mod connection_states {
+    pub const DISCONNECTED: i32 = 0;
+    pub const CONNECTING: i32 = 1;
+    pub const CONNECTED: i32 = 2;
+    pub const RECONNECTING: i32 = 3;
+    pub const FAILED: i32 = 4;
+}
+
+// Когда микрофон передает голос
+pub mod transmit_modes {
+    // Только пока хост держит передачу (voice_client_set_transmitting)
+    pub const PTT: i32 = 0;
+    // Сам, когда слышит голос; тишина не передается
+    pub const VOICE_ACTIVATION: i32 = 1;
+    // Всегда, каждый кадр, без детектора голоса
+    pub const CONTINUOUS: i32 = 2;
+    // Как PTT, но клавишу не нужно держать: нажатие включает передачу,
+    // следующее - выключает (см. voice_client_talk_key)
+    pub const TOGGLE: i32 = 3;
+}
+
+// Проверочные сигналы voice_client_play_test_tone
+pub mod test_tones {
+    // Ровный тон 440 Гц
+    pub const STEADY: i32 = 0;
+    // Развертка 100 Гц - 10 кГц: слышно, не режет ли устройство низы или верха
+    pub const SWEEP: i32 = 1;
+}
+
+// Уведомления в динамиках (cues.rs) и voice_client_set_notification_sound
+pub mod notification_sounds {
+    // Кто-то вошел в канал или вышел; кто был в канале до нас, не звучит
+    pub const USER_JOINED: i32 = 0;
+    pub const USER_LEFT: i32 = 1;
+    // Соединение с сервером установлено, потеряно и ищется снова, оборвано
+    pub const CONNECTED: i32 = 2;
+    pub const RECONNECTING: i32 = 3;
+    pub const DISCONNECTED: i32 = 4;
+}
+
+// Как громкость собеседника падает с расстоянием (voice_client_set_rolloff)
+pub mod rolloff_models {
+    // Линейно от полной на min_distance до тишины на max_distance
+    pub const LINEAR: i32 = 0;
+    // Обратно расстоянию: min_distance / расстояние, дальше max_distance - тишина
+    pub const INVERSE: i32 = 1;
+}
+
+// Callback смены состояния: (новое состояние, userdata).
+// Вызывается из сетевых потоков клиента, а не из потока хоста.
+pub type ConnectionStateCallback = extern "C" fn(state: i32, userdata: *mut c_void);
+
+// Callback ошибок: (код из error_codes, текст, userdata). Текст действителен
+// только на время вызова. Вызывается из сетевых потоков клиента.
+pub type ErrorCallback = extern "C" fn(code: i32, message: *const c_char, userdata: *mut c_void);
+
+// Асинхронные ошибки, которые нельзя вернуть кодом из FFI-функции
+struct ErrorReporter {
+    callback: Mutex<Option<(ErrorCallback, usize)>>,
+    events: Arc<events::EventSink>,
+}
+
+impl ErrorReporter {
+    fn new(events: Arc<events::EventSink>) -> Self {
+        ErrorReporter {
+            callback: Mutex::new(None),
+            events,
+        }
+    }
+
+    fn report(&self, code: i32, message: &str) {
+        error!(target: CLIENT, "Error {}: {}", code, message);
+
+        self.events.call_host(|| {
+            let callback = *self.callback.lock().unwrap();
+            if let Some((func, userdata)) = callback {
+                let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+                func(code, message.as_ptr(), userdata as *mut c_void);
+            }
+        });
+        self.events.emit(events::event_types::ERROR, 0, code, message);
+    }
+}
+
+pub fn connection_state_name(state: i32) -> &'static str {
+    match state {
+        connection_states::DISCONNECTED => "Disconnected",
+        connection_states::CONNECTING => "Connecting",
+        connection_states::CONNECTED => "Connected",
+        connection_states::RECONNECTING => "Reconnecting",
+        connection_states::FAILED => "Failed",
+        _ => "Unknown",
+    }
+}
+
+// Отслеживание состояния соединения: текущее состояние, время последнего
+// пакета от сервера и зарегистрированный callback
+struct ConnectionTracker {
+    state: AtomicI32,
+    // userdata хранится как usize, чтобы структура оставалась Send + Sync
+    callback: Mutex<Option<(ConnectionStateCallback, usize)>>,
+    last_server_packet: Mutex<Instant>,
+    // Момент перехода в Connecting/Reconnecting, от него считается CONNECTION_FAIL_TIMEOUT
+    waiting_since: Mutex<Instant>,
+    // Последний RTT по эху keep-alive
+    rtt: Mutex<Option<Duration>>,
+    // Через сколько мс молчания сервера клиент отключается (0 - никогда)
+    silence_timeout_ms: AtomicU64,
+    events: Arc<events::EventSink>,
+    cues: Arc<cues::CuePlayer>,
+}
+
+impl ConnectionTracker {
+    fn new(events: Arc<events::EventSink>, cues: Arc<cues::CuePlayer>) -> Self {
+        ConnectionTracker {
+            state: AtomicI32::new(connection_states::DISCONNECTED),
+            callback: Mutex::new(None),
+            last_server_packet: Mutex::new(Instant::now()),
+            waiting_since: Mutex::new(Instant::now()),
+            rtt: Mutex::new(None),
+            silence_timeout_ms: AtomicU64::new(DEFAULT_SERVER_SILENCE_TIMEOUT.as_millis() as u64),
+            events,
+            cues,
+        }
+    }
+
+    fn state(&self) -> i32 {
+        self.state.load(Ordering::SeqCst)
+    }
+
+    fn transition(&self, new_state: i32) {
+        let old_state = self.state.swap(new_state, Ordering::SeqCst);
+        if old_state == new_state {
+            return;
+        }
+
+        if new_state == connection_states::CONNECTING || new_state == connection_states::RECONNECTING {
+            *self.waiting_since.lock().unwrap() = Instant::now();
+        }
+        // Молчание сервера отсчитываем от старта, а не от прошлой сессии
+        if new_state == connection_states::CONNECTING {
+            *self.last_server_packet.lock().unwrap() = Instant::now();
+        }
+
+        info!(
+            target: NET,
+            "Connection state: {} -> {}",
+            connection_state_name(old_state),
+            connection_state_name(new_state)
+        );
+
+        self.events.call_host(|| {
+            let callback = *self.callback.lock().unwrap();
+            if let Some((func, userdata)) = callback {
+                func(new_state, userdata as *mut c_void);
+            }
+        });
+        
+        match new_state {
+            connection_states::CONNECTED => {
+                self.events.emit(events::event_types::CONNECTED, 0, new_state, "");
+                self.cues.play(cues::Cue::Notification(notification_sounds::CONNECTED));
+            },
+            connection_states::RECONNECTING => {
+                self.cues.play(cues::Cue::Notification(notification_sounds::RECONNECTING));
+            },
+            // Остановка хостом не звучит: клиент к этому моменту уже не запущен
+            connection_states::DISCONNECTED | connection_states::FAILED => {
+                self.events.emit(events::event_types::DISCONNECTED, 0, new_state, connection_state_name(new_state));
+                self.cues.play(cues::Cue::Notification(notification_sounds::DISCONNECTED));
+            },
+            _ => {},
+        }
+    }
+
+    fn rtt(&self) -> Option<Duration> {
+        *self.rtt.lock().unwrap()
+    }
+
+    fn set_rtt(&self, rtt: Duration) {
+        *self.rtt.lock().unwrap() = Some(rtt);
+    }
+
+    // Любой пакет от сервера (включая эхо keep-alive) подтверждает, что связь есть
+    fn on_server_packet(&self) {
+        *self.last_server_packet.lock().unwrap() = Instant::now();
+        if self.state() != connection_states::CONNECTED {
+            self.transition(connection_states::CONNECTED);
+        }
+    }
+
+    // Сколько мы уже ждем ответа сервера (None, если соединение есть или клиент остановлен)
+    fn waiting_for(&self) -> Option<Duration> {
+        match self.state() {
+            connection_states::CONNECTING | connection_states::RECONNECTING | connection_states::FAILED => {
+                Some(self.waiting_since.lock().unwrap().elapsed())
+            },
+            _ => None,
+        }
+    }
+
+    // Сервер молчит дольше silence_timeout - пора отключаться
+    fn server_silence_exceeded(&self) -> bool {
+        let timeout = self.silence_timeout_ms.load(Ordering::Relaxed);
+        if timeout == 0 || matches!(self.state(), connection_states::CONNECTED | connection_states::DISCONNECTED) {
+            return false;
+        }
+        self.last_server_packet.lock().unwrap().elapsed() > Duration::from_millis(timeout)
+    }
+
+    // Периодическая проверка из keep-alive потока
+    fn check_timeouts(&self) {
+        let now = Instant::now();
+        match self.state() {
+            connection_states::CONNECTED => {
+                let last_packet = *self.last_server_packet.lock().unwrap();
+                if now.duration_since(last_packet) > SERVER_TIMEOUT {
+                    self.transition(connection_states::RECONNECTING);
+                }
+            },
+            connection_states::CONNECTING | connection_states::RECONNECTING => {
+                let waiting_since = *self.waiting_since.lock().unwrap();
+                if now.duration_since(waiting_since) > CONNECTION_FAIL_TIMEOUT {
+                    self.transition(connection_states::FAILED);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+// Состояние STUN-обнаружения внешнего адреса. Ответ может прийти как в
+// поток приема (если клиент запущен), так и в сам запрос discover.
+struct StunState {
+    pending: Mutex<Option<(SocketAddr, stun::TransactionId)>>,
+    public_addr: Mutex<Option<SocketAddr>>,
+    // Последний результат (STUN-сервер, внешний адрес) для сравнения при следующем запросе
+    last_result: Mutex<Option<(SocketAddr, SocketAddr)>>,
+}
+
+impl StunState {
+    fn new() -> Self {
+        StunState {
+            pending: Mutex::new(None),
+            public_addr: Mutex::new(None),
+            last_result: Mutex::new(None),
+        }
+    }
+
+    // Возвращает true, если пакет был ответом на текущий STUN-запрос
+    fn handle_packet(&self, from: SocketAddr, data: &[u8]) -> bool {
+        if !stun::is_stun_message(data) {
+            return false;
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        let (stun_server, transaction_id) = match *pending {
+            Some(p) if p.0 == from => p,
+            _ => return false,
+        };
+
+        let mapped = match stun::parse_binding_response(data, &transaction_id) {
+            Some(addr) => addr,
+            None => return false,
+        };
+        *pending = None;
+
+        let mut last_result = self.last_result.lock().unwrap();
+        if let Some((prev_server, prev_mapped)) = *last_result {
+            // Разные внешние порты для разных STUN-серверов - признак симметричного NAT
+            if prev_server != stun_server && prev_mapped != mapped {
+                info!(
+                    target: NET,
+                    "Symmetric NAT suspected: {} reported {}, {} reported {}",
+                    prev_server, prev_mapped, stun_server, mapped
+                );
+            }
+        }
+        *last_result = Some((stun_server, mapped));
+        *self.public_addr.lock().unwrap() = Some(mapped);
+
+        info!(target: NET, "STUN: public address is {} (via {})", mapped, stun_server);
+        true
+    }
+}
+
+fn resolve_addr(host: &str, port: u16) -> Option<SocketAddr> {
+    let addrs: Vec<SocketAddr> = (host, port).to_socket_addrs().ok()?.collect();
+    // Сокет биндится на IPv4, поэтому предпочитаем IPv4-адреса
+    addrs.iter().find(|a| a.is_ipv4()).or(addrs.first()).copied()
+}
+
+// Узнает MTU пути до сервера: отправляет пакет максимального размера с
+// запретом фрагментации и читает MTU, известный ядру (с учетом ICMP
+// "Fragmentation Needed"). Возвращает допустимый размер UDP-данных.
+#[cfg(target_os = "linux")]
+fn probe_path_mtu(server_addr: SocketAddr) -> Option<usize> {
+    use std::os::unix::io::AsRawFd;
+    
+    let (bind_addr, level, discover_opt, discover_do, mtu_opt, headers) = if server_addr.is_ipv4() {
+        ("0.0.0.0:0", libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO, libc::IP_MTU, 28)
+    } else {
+        ("[::]:0", libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DO, libc::IPV6_MTU, 48)
+    };
+    
+    let probe = UdpSocket::bind(bind_addr).ok()?;
+    probe.connect(server_addr).ok()?;
+    let fd = probe.as_raw_fd();
+    
+    let value: libc::c_int = discover_do;
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            discover_opt,
+            &value as *const libc::c_int as *const c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res != 0 {
+        return None;
+    }
+    
+    // Пакет размером с Ethernet MTU: при меньшем MTU ядро вернет EMSGSIZE
+    // сразу или узнает новый MTU из ICMP
+    let mut packet = control_packet(control_types::MTU_PROBE, &[]);
+    packet.resize(1500 - headers, 0);
+    let _ = probe.send(&packet);
+    thread::sleep(MTU_PROBE_WAIT);
+    let _ = probe.send(&packet);
+    
+    let mut mtu: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(fd, level, mtu_opt, &mut mtu as *mut libc::c_int as *mut c_void, &mut len)
+    };
+    if res != 0 || mtu <= headers as libc::c_int {
+        return None;
+    }
+    
+    Some(mtu as usize - headers)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe_path_mtu(_server_addr: SocketAddr) -> Option<usize> {
+    None
+}
+
+// Записывает строку в буфер хоста как C-строку
+fn write_c_string(value: &str, buf: *mut c_char, buf_len: usize) -> i32 {
+    if buf.is_null() {
+        return fail(error_codes::NULL_POINTER, "output buffer is null!");
+    }
+    
+    // Хосты с растущим буфером получают это на каждой пробе, поэтому без журнала
+    let bytes = value.as_bytes();
+    if bytes.len() + 1 > buf_len {
+        let message = format!("buffer of {} bytes is too small, {} needed", buf_len, bytes.len() + 1);
+        set_last_error(error_codes::BUFFER_TOO_SMALL, &message);
+        return error_codes::BUFFER_TOO_SMALL;
+    }
+    
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, bytes.len());
+        *buf.add(bytes.len()) = 0;
+    }
+    
+    error_codes::SUCCESS
+}
+
+// Уровни сообщений журнала
+pub mod log_levels {
+    pub const TRACE: i32 = -1;
+    pub const DEBUG: i32 = 0;
+    pub const INFO: i32 = 1;
+    pub const WARNING: i32 = 2;
+    pub const ERROR: i32 = 3;
+}
+
+// Callback журнала: (уровень из log_levels, строка, userdata). Строка
+// действительна только на время вызова. Вызывается из любых потоков клиента.
+pub type LogCallback = extern "C" fn(level: i32, message: *const c_char, userdata: *mut c_void);
+
+// Запоминает пик громкости. У неотрицательных f32 порядок битов совпадает
+// с порядком чисел, поэтому максимум можно брать атомарно по битам.
+fn update_level(level: &AtomicU32, data: &[f32]) {
+    let peak = data.iter().fold(0.0f32, |peak, &sample| peak.max(sample.abs())).min(1.0);
+    level.fetch_max(peak.to_bits(), Ordering::Relaxed);
+}
+
+// Функция для обнаружения тишины
+fn is_silent_frame(data: &[f32], threshold: f32) -> bool {
+    !data.iter().any(|&sample| sample.abs() > threshold)
+}
+
+// Слышит ли детектор тишину в кадре при режиме передачи mode
+fn detects_silence(mode: i32, vad: &config::Vad, frame: &[f32]) -> bool {
+    match mode {
+        transmit_modes::CONTINUOUS => false,
+        // Голосовой активации детектор нужен, даже если он выключен в настройках
+        transmit_modes::VOICE_ACTIVATION => is_silent_frame(frame, vad.threshold),
+        _ => vad.enabled && is_silent_frame(frame, vad.threshold),
+    }
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_new(server_ip: *const c_char, server_port: u16) -> *mut c_void {
+    let ip_str = unsafe { CStr::from_ptr(server_ip).to_str().unwrap_or_default() };
+    handle_or_null(new_server_client(ip_str, server_port, config::Settings::default()))
+}
+
+// Заполняет структуру значениями по умолчанию (см. config.rs)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_config_init(config: *mut config::VoiceClientConfig) -> i32 {
+    if !c_ptr::set(config, config::VoiceClientConfig::default()) {
+        return fail(error_codes::NULL_POINTER, "voice_client_config_init: config is null!");
+    }
+    error_codes::SUCCESS
+}
+
+// Клиент для сервера со всеми настройками сразу. Структура читается только
+// во время вызова, строки из нее копируются.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_new_with_config(config: *const config::VoiceClientConfig) -> *mut c_void {
+    let Some(config) = c_ptr::get(config) else {
+        fail(error_codes::NULL_POINTER, "voice_client_new_with_config: config is null!");
+        return std::ptr::null_mut();
+    };
+    let Some(host) = c_ptr::string(config.server_host) else {
+        fail(error_codes::NULL_POINTER, "voice_client_new_with_config: server host is null!");
+        return std::ptr::null_mut();
+    };
+    
+    let settings = match config::Settings::from_config(config) {
+        Ok(settings) => settings,
+        Err(e) => {
+            report_error(&NsvcError::InvalidParam(format!("client config: {}", e)));
+            return std::ptr::null_mut();
+        }
+    };
+    
+    handle_or_null(new_server_client(host.to_str().unwrap_or_default(), config.server_port, settings))
+}
+
+// Конструкторы клиентов для FFI и для client::ClientBuilder: описатель из CLIENTS
+pub(crate) fn new_server_client(host: &str, port: u16, settings: config::Settings) -> Result<*mut c_void, NsvcError> {
+    logging::init();
+    create_client(server_link(host, port)?, settings)
+}
+
+fn server_link(ip_str: &str, server_port: u16) -> Result<ServerLink, NsvcError> {
+    if ip_str.is_empty() {
+        return Err(NsvcError::InvalidIp("empty host".to_string()));
+    }
+    
+    let server_addr_str = format!("{}:{}", ip_str, server_port);
+    
+    info!(target: CLIENT, "Creating client for server: {}", server_addr_str);
+    
+    // Сокет не подключается к серверу через connect(): тот же сокет используется
+    // для STUN-запросов, чтобы узнать внешний адрес именно этого порта
+    let server_addr = match resolve_addr(ip_str, server_port) {
+        Some(addr) => {
+            info!(target: NET, "Server address resolved to: {}", addr);
+            addr
+        },
+        None => {
+            return Err(NsvcError::Resolve(server_addr_str));
+        }
+    };
+    
+    let bind_addr = if server_addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    match UdpSocket::bind(bind_addr) {
+        Ok(socket) => Ok(ServerLink::new(socket, Some(server_addr))),
+        Err(e) => Err(NsvcError::SocketBind(e)),
+    }
+}
+
+// LAN-режим без сервера: соседи находятся через mDNS, голос идет напрямую.
+// port = 0 - любой свободный порт.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_new_lan(port: u16, name: *const c_char) -> *mut c_void {
+    let name = c_ptr::string(name).unwrap_or_default().to_string_lossy().into_owned();
+    handle_or_null(new_lan_client(port, &name, config::Settings::default()))
+}
+
+pub(crate) fn new_lan_client(port: u16, name: &str, settings: config::Settings) -> Result<*mut c_void, NsvcError> {
+    logging::init();
+    // Имя сервиса mDNS - только простые символы
+    let name: String = name.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+    let name = if name.is_empty() { "nsvc".to_string() } else { name };
+    
+    info!(target: CLIENT, "Creating LAN client '{}' on port {}", name, port);
+    
+    let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(NsvcError::SocketBind)?;
+    
+    let mut link = ServerLink::new(socket, None);
+    link.lan_name = Some(name);
+    create_client(link, settings)
+}
+
+// Прямой звонок без сервера. peer_ip = NULL - ждать звонка на local_port,
+// иначе звонить на peer_ip:peer_port. local_port = 0 - любой свободный порт.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_new_direct(peer_ip: *const c_char, peer_port: u16, local_port: u16) -> *mut c_void {
+    let peer = c_ptr::string(peer_ip).map(|ip| (ip.to_str().unwrap_or_default(), peer_port));
+    handle_or_null(new_direct_client(peer, local_port, config::Settings::default()))
+}
+
+pub(crate) fn new_direct_client(
+    peer: Option<(&str, u16)>,
+    local_port: u16,
+    settings: config::Settings,
+) -> Result<*mut c_void, NsvcError> {
+    logging::init();
+    let call = match peer {
+        None => {
+            info!(target: CLIENT, "Creating direct client listening on port {}", local_port);
+            direct::DirectCall::listen()
+        },
+        Some((ip_str, peer_port)) => match resolve_addr(ip_str, peer_port) {
+            Some(addr) => {
+                info!(target: CLIENT, "Creating direct client calling {}", addr);
+                direct::DirectCall::call(addr)
+            },
+            None => {
+                return Err(NsvcError::Resolve(format!("{}:{}", ip_str, peer_port)));
+            }
+        },
+    };
+    
+    let bind_addr = match call.peer() {
+        Some(peer) if peer.is_ipv6() => SocketAddr::from((Ipv6Addr::UNSPECIFIED, local_port)),
+        _ => SocketAddr::from((Ipv4Addr::UNSPECIFIED, local_port)),
+    };
+    let socket = UdpSocket::bind(bind_addr).map_err(NsvcError::SocketBind)?;
+    
+    let mut link = ServerLink::new(socket, None);
+    link.direct = Some(call);
+    create_client(link, settings)
+}
+
+// Multicast-режим для LAN: голос уходит в группу, слушать может кто угодно
+// в той же сети без сервера. Потоки разных отправителей различаются по SSRC.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_new_multicast(group_ip: *const c_char, port: u16) -> *mut c_void {
+    if group_ip.is_null() {
+        fail(error_codes::NULL_POINTER, "voice_client_new_multicast: group is null!");
+        return std::ptr::null_mut();
+    }
+    
+    let group_str = c_ptr::string(group_ip).unwrap_or_default().to_str().unwrap_or_default();
+    let group: Ipv4Addr = match group_str.parse() {
+        Ok(ip) => ip,
+        Err(_) => {
+            fail(error_codes::INVALID_IP, &format!("Invalid multicast group: {}", group_str));
+            return std::ptr::null_mut();
+        }
+    };
+    handle_or_null(new_multicast_client(group, port, config::Settings::default()))
+}
+
+pub(crate) fn new_multicast_client(group: Ipv4Addr, port: u16, settings: config::Settings) -> Result<*mut c_void, NsvcError> {
+    logging::init();
+    if !group.is_multicast() {
+        return Err(NsvcError::InvalidIp(format!("{} is not a multicast address", group)));
+    }
+    
+    info!(target: CLIENT, "Creating multicast client for group {}:{}", group, port);
+    
+    let socket = bind_multicast_socket(group, port).map_err(NsvcError::SocketBind)?;
+    
+    let mut link = ServerLink::new(socket, None);
+    link.multicast = Some(MulticastGroup {
+        group: SocketAddr::new(group.into(), port),
+        ssrc: rand::random(),
+    });
+    create_client(link, settings)
+}
+
+// SO_REUSEADDR нужен, чтобы несколько клиентов на одной машине слушали один порт
+fn bind_multicast_socket(group: Ipv4Addr, port: u16) -> std::io::Result<UdpSocket> {
+    let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port).into())?;
+    
+    let socket: UdpSocket = socket.into();
+    socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+    // Loopback оставляем, чтобы слышать клиентов на этой же машине; свои пакеты отсекаются по SSRC
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_multicast_ttl_v4(1)?;
+    Ok(socket)
+}
+
+// Задает SO_SNDBUF/SO_RCVBUF; 0 - оставить как есть. ОС может округлить
+// или урезать значение, поэтому в лог пишем то, что получилось.
+fn set_socket_buffers(socket: &UdpSocket, send_size: usize, recv_size: usize) -> std::io::Result<()> {
+    let sock = socket2::SockRef::from(socket);
+    if send_size != 0 {
+        sock.set_send_buffer_size(send_size)?;
+    }
+    if recv_size != 0 {
+        sock.set_recv_buffer_size(recv_size)?;
+    }
+    
+    info!(
+        target: NET,
+        "Socket buffers: send {} bytes, receive {} bytes",
+        sock.send_buffer_size()?,
+        sock.recv_buffer_size()?
+    );
+    Ok(())
+}
+
+fn create_client(link: ServerLink, settings: config::Settings) -> Result<*mut c_void, NsvcError> {
+    if let Some(socket) = link.transport.udp_socket() {
+        if let Err(e) = socket.set_nonblocking(true) {
+            return Err(NsvcError::SocketOption(e));
+        }
+        
+        // Не критично: с буферами ОС клиент работает, просто теряет больше
+        if let Err(e) = set_socket_buffers(socket, DEFAULT_SOCKET_BUFFER_SIZE, DEFAULT_SOCKET_BUFFER_SIZE) {
+            warn!(target: NET, "Failed to set socket buffer sizes: {}", e);
+        }
+    }
+    
+    match link.transport.local_addr() {
+        Ok(addr) => info!(target: NET, "Socket local address: {}", addr),
+        Err(e) => warn!(target: NET, "Failed to get local address: {}", e),
+    }
+    
+    let mut encoder = match Encoder::new(SAMPLE_RATE, CHANNELS, Application::Audio) {
+        Ok(enc) => enc,
+        Err(e) => {
+            return Err(NsvcError::Encoder(e));
+        }
+    };
+    
+    // Установка VBR для качественной передачи голоса
+    if let Err(e) = encoder.set_bitrate(Bitrate::Bits(settings.bitrate as i32)) {
+        warn!(target: CODEC, "Failed to set bitrate: {:?}", e);
+    }
+    if let Err(e) = encoder.set_vbr(true) {
+        warn!(target: CODEC, "Failed to set VBR: {:?}", e);
+    }
+    
+    let events = Arc::new(events::EventSink::default());
+    let roster = Arc::new(users::Roster::new(settings.muted_users.clone()));
+    let recording = Arc::new(recording::Recording::new(roster.clone()));
+    let voice_output = Arc::new(voice_output::VoiceOutput::default());
+    let playback_buffer = PlaybackMixer::new(settings.buffer_samples, recording.clone(), voice_output.clone());
+    let playback_buffer = Arc::new(Mutex::new(playback_buffer));
+    let running = Arc::new(AtomicBool::new(false));
+    let cue_settings = cues::CueSettings {
+        mute: settings.cues,
+        transmit: settings.transmit_cues,
+        notifications: settings.notification_cues,
+        volume: settings.cue_volume,
+    };
+    let cues = Arc::new(cues::CuePlayer::new(cue_settings, playback_buffer.clone(), running.clone()));
+    let client = VoiceClient {
+        is_transmitting: Arc::new(AtomicBool::new(false)),
+        transmit_mode: Arc::new(AtomicI32::new(settings.transmit_mode)),
+        input_level: Arc::new(AtomicU32::new(0)),
+        output_level: Arc::new(AtomicU32::new(0)),
+        preview_stream: Mutex::new(None),
+        local_output: Arc::default(),
+        mic_check_stream: Mutex::new(None),
+        link: Arc::new(link),
+        running,
+        input_stream: Mutex::new(None),
+        output_stream: Mutex::new(None),
+        encoder: Arc::new(Mutex::new(encoder)),
+        playback_buffer,
+        bitrate: Arc::new(AtomicU32::new(settings.bitrate)),
+        // Инициализация DTX полей:
+        last_silence_packet: Arc::new(Mutex::new(Instant::now())),
+        was_speaking: Arc::new(AtomicBool::new(false)),
+        connection: Arc::new(ConnectionTracker::new(events.clone(), cues.clone())),
+        stun: Arc::new(StunState::new()),
+        handshake: Arc::new(handshake::Handshake::new()),
+        errors: Arc::new(ErrorReporter::new(events.clone())),
+        events,
+        workers: Arc::new(network::Workers::default()),
+        stats: Arc::new(stats::Stats::default()),
+        send_queue: Arc::new(send_queue::SendQueue::new()),
+        frame_size: Arc::new(AtomicUsize::new(settings.frame_size)),
+        features: AtomicU32::new(settings.features),
+        vad: settings.vad,
+        input_device: Mutex::new(settings.input_device),
+        output_device: Mutex::new(settings.output_device),
+        channels: Arc::new(channels::ChannelState::default()),
+        credentials: Mutex::new(handshake::Credentials::default()),
+        nickname: Arc::new(Mutex::new(String::new())),
+        roster: roster.clone(),
+        audio: settings.audio,
+        audio_group: Mutex::new(None),
+        mute: Arc::new(mute::MuteState::default()),
+        release: Arc::new(ptt_release::ReleaseDelay::new(settings.ptt_release)),
+        whisper: Arc::new(whisper::Whisper::new(settings.ptt_release)),
+        afk: Arc::new(afk::AfkGuard::new(settings.afk_minutes)),
+        cues,
+        recording,
+        clip: Arc::new(clip::ClipBuffer::new(settings.clip_length, roster)),
+        soundboard: Arc::default(),
+        positional: Arc::default(),
+        overlay: Arc::default(),
+        metrics: metrics::Metrics::default(),
+        voice_output,
+        keep_alive_interval_ms: Arc::new(AtomicU64::new(DEFAULT_KEEP_ALIVE_INTERVAL.as_millis() as u64)),
+    };
+    
+    Ok(CLIENTS.insert(client))
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_start: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_start: invalid client handle");
+    };
+    
+    match start_client(&client) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// Самопроверка: голос с микрофона через кодер сразу в динамики, без сети.
+// Настройки устройств, битрейта, кадра и режима передачи - те же, что для
+// разговора; в PTT слышно, только пока передача включена. Выход - stop.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_loopback(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_loopback: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_loopback: invalid client handle");
+    };
+    
+    match start_loopback(&client) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// То же без ожидания: устройства и потоки поднимаются в отдельном потоке,
+// функция сразу возвращает SUCCESS, а итог приходит событием STARTED
+// (code - SUCCESS или код ошибки). Состояние CONNECTING - сразу.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_async(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_async: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_async: invalid client handle");
+    };
+    
+    begin_start(&client);
+    thread::spawn(move || {
+        let result = start_streams_and_threads(&client);
+        finish_start(&client, &result);
+        let (code, text) = match result {
+            // Хост остановил клиента, пока тот запускался: потоки клиента уже
+            // завершаются сами, а звук, открытый после остановки, закрываем
+            Ok(()) => {
+                if !client.running.load(Ordering::SeqCst) {
+                    *client.input_stream.lock().unwrap() = None;
+                    *client.output_stream.lock().unwrap() = None;
+                    client.voice_output.close();
+                }
+                (error_codes::SUCCESS, String::new())
+            },
+            Err(e) => (report_error(&e), e.to_string()),
+        };
+        client.events.emit(events::event_types::STARTED, 0, code, &text);
+    });
+    
+    error_codes::SUCCESS
+}
+
+// Синхронный запуск для voice_client_start и client::Client
+pub(crate) fn start_client(client: &VoiceClient) -> Result<(), NsvcError> {
+    begin_start(client);
+    let result = start_streams_and_threads(client);
+    finish_start(client, &result);
+    result
+}
+
+// Самопроверка без сети (см. loopback.rs): свой голос сразу в динамики
+// через кодер и буфер воспроизведения. Остановка - как обычно, stop.
+pub(crate) fn start_loopback(client: &VoiceClient) -> Result<(), NsvcError> {
+    *client.preview_stream.lock().unwrap() = None;
+    *client.local_output.lock().unwrap() = None;
+    *client.mic_check_stream.lock().unwrap() = None;
+    client.running.store(true, Ordering::SeqCst);
+    info!(target: CLIENT, "Starting voice client in loopback mode");
+    
+    let result = start_loopback_streams(client);
+    if result.is_err() {
+        client.running.store(false, Ordering::SeqCst);
+    }
+    result
+}
+
+fn start_loopback_streams(client: &VoiceClient) -> Result<(), NsvcError> {
+    // Договариваться не с кем: MEDIA с номерами, как без сервера
+    client.handshake.set_outcome(handshake::Outcome::Accepted(handshake::SessionParams {
+        sample_rate: SAMPLE_RATE,
+        channels: 1,
+        frame_size: client.frame_size.load(Ordering::Relaxed) as u16,
+        bitrate: client.bitrate.load(Ordering::Relaxed),
+        features: client.features.load(Ordering::SeqCst),
+    }));
+    
+    *client.input_stream.lock().unwrap() = Some(open_input_stream(client)?);
+    *client.output_stream.lock().unwrap() = Some(open_output_stream(client)?);
+    open_voice_output(client);
+    
+    client.send_queue.clear();
+    client.stats.reset();
+    network::spawn_loopback(client)
+}
+
+fn begin_start(client: &VoiceClient) {
+    // Микрофон нужен основному потоку; уровень дальше считает он
+    *client.preview_stream.lock().unwrap() = None;
+    *client.local_output.lock().unwrap() = None;
+    *client.mic_check_stream.lock().unwrap() = None;
+    client.running.store(true, Ordering::SeqCst);
+    info!(target: CLIENT, "Starting voice client");
+    client.connection.transition(connection_states::CONNECTING);
+}
+
+fn finish_start(client: &VoiceClient, result: &Result<(), NsvcError>) {
+    if result.is_err() {
+        client.running.store(false, Ordering::SeqCst);
+        client.connection.transition(connection_states::FAILED);
+    }
+}
+
+// Открывает микрофон из настроек клиента и запускает захват. Поток только
+// кодирует кадры и ставит их в очередь отправки, поэтому его можно
+// пересоздать на ходу, не трогая сокет и кодер.
+fn open_input_stream(client: &VoiceClient) -> Result<AudioStream, NsvcError> {
+    let link_tx = client.link.clone();
+    let is_transmitting = client.is_transmitting.clone();
+    let transmit_mode = client.transmit_mode.clone();
+    let mute = client.mute.clone();
+    let release = client.release.clone();
+    let whisper = client.whisper.clone();
+    let whisper_enc = client.whisper.clone();
+    let transmit_mode_enc = client.transmit_mode.clone();
+    let running = client.running.clone();
+    let encoder = client.encoder.clone();
+    let bitrate = client.bitrate.clone();
+    // Новые поля для DTX:
+    let last_silence_packet = client.last_silence_packet.clone();
+    let was_speaking = client.was_speaking.clone();
+    let handshake_tx = client.handshake.clone();
+    let handshake_enc = client.handshake.clone();
+    let send_queue_tx = client.send_queue.clone();
+    let frame_size = client.frame_size.clone();
+    let events_in = client.events.clone();
+    let vad = client.vad;
+    let input_level = client.input_level.clone();
+    let recording = client.recording.clone();
+    let clip = client.clip.clone();
+    let soundboard_enc = client.soundboard.clone();
+    let soundboard_in = client.soundboard.clone();
+    let stats_enc = client.stats.clone();
+    let mute_enc = client.mute.clone();
+    let afk = client.afk.clone();
+    let events_enc = client.events.clone();
+    let cues_enc = client.cues.clone();
+
+    // Кодирование - в потоке кодера: callback только пишет в кольцо (см. realtime.rs)
+    // Буферы кадра переиспользуются: в установившемся режиме кодер не выделяет память
+    let mut acc = Vec::new();
+    let mut frame = Vec::new();
+    let mut pcm = Vec::new();
+    let mut with_file = Vec::new();
+    // Сколько отсчетов подряд детектор слышит тишину
+    let mut silent_run = 0usize;
+    let mut afk_detector = afk::Detector::default();
+    let mut capture = realtime::spawn_encoder(move |data: &[f32]| {
+        let mode = transmit_mode_enc.load(Ordering::Relaxed);
+        // Помечаем клипом кадры всей порции, в которой он еще звучал
+        let sending_clip = soundboard_enc.is_playing() && soundboard_enc.is_clip();
+        let data = if soundboard_enc.is_playing() {
+            with_file.clear();
+            with_file.extend_from_slice(data);
+            soundboard_enc.mix_into(&mut with_file);
+            &with_file[..]
+        } else {
+            data
+        };
+        recording.on_capture(data);
+        acc.extend_from_slice(data);
+        
+        // Process full frames
+        let frame_size = frame_size.load(Ordering::Relaxed);
+        while acc.len() >= frame_size {
+            frame.clear();
+            frame.extend(acc.drain(0..frame_size));
+            
+            // Проверяем, есть ли голос в фрейме
+            let detected_silence = detects_silence(mode, &vad, &frame);
+            // После голоса тишина передается еще hangover, потом замолкаем
+            silent_run = if detected_silence { silent_run + frame_size } else { 0 };
+            let is_silent = detected_silence && silent_run > vad.hangover_samples;
+            let current_time = Instant::now();
+
+            // Файл звуковой панели у заглушенного - не открытый микрофон
+            let open_mic = matches!(mode, transmit_modes::VOICE_ACTIVATION | transmit_modes::CONTINUOUS);
+            if !open_mic || mute_enc.mic_blocked() {
+                afk_detector.reset();
+            } else if afk_detector.on_frame(&frame, afk.minutes()) && mute_enc.set_muted(true) {
+                warn!(target: AUDIO, "No speech for {} min with the microphone open, muting", afk.minutes());
+                events_enc.emit(events::event_types::MUTE_CHANGED, 0, 1, "");
+                events_enc.emit(events::event_types::AFK_MUTED, 0, afk.minutes() as i32, "");
+                cues_enc.play(cues::Cue::Mute(true));
+                continue;
+            }
+            
+            if !is_silent {
+                // Есть голос - отправляем голосовой пакет
+                was_speaking.store(true, Ordering::Relaxed);
+                *last_silence_packet.lock().unwrap() = current_time; // Сбрасываем таймер тишины
+                
+                // Конвертируем в PCM
+                pcm.clear();
+                pcm.extend(frame.iter()
+                    .map(|&s| {
+                        let scaled = s * 32767.0;
+                        if scaled > 32767.0 {
+                            32767
+                        } else if scaled < -32768.0 {
+                            -32768
+                        } else {
+                            scaled as i16
+                        }
+                    }));
+                
+                let mut encoder_guard = match encoder.lock() {
+                    Ok(enc) => enc,
+                    Err(_) => return,
+                };
+                
+                // Применяем текущий битрейт
+                let current_bitrate = bitrate.load(Ordering::Relaxed) as i32;
+                if let Err(e) = encoder_guard.set_bitrate(Bitrate::Bits(current_bitrate)) {
+                    warn!(target: CODEC, "Failed to update bitrate: {:?}", e);
+                }
+                
+                // Opus подстраивает размер кадра под размер выходного буфера
+                let mut encoded = [0u8; MAX_PACKET_SIZE];
+                let whispering = whisper_enc.is_sending();
+                let mut max_payload = link_tx.max_payload();
+                if whispering {
+                    max_payload = max_payload.saturating_sub(whisper_enc.overhead());
+                }
+                let encode_start = Instant::now();
+                let encoded_len = encoder_guard.encode(&pcm, &mut encoded[..max_payload]);
+                stats_enc.on_encoded(encode_start.elapsed());
+                match encoded_len {
+                    Ok(len) => {
+                        if len > 0 {
+                            recording.on_mic_packet(&encoded[..len]);
+                            clip.on_packet(multitrack::Track::Mic, &encoded[..len]);
+                            let mut packet = send_queue_tx.buffer();
+                            if whispering {
+                                // Без сервера, который разошлет шепот адресатам, кадр не уходит никому
+                                let targets = whisper_enc.targets();
+                                let routed = link_tx.server_addr.is_some()
+                                    && handshake_enc.has_feature(handshake::features::WHISPER);
+                                if !routed || targets.is_empty() {
+                                    continue;
+                                }
+                                link_tx.write_whisper_packet(&mut packet, &targets, &encoded[..len]);
+                            } else if handshake_enc.has_feature(handshake::features::SEQUENCE) {
+                                link_tx.write_media_packet(&mut packet, &encoded[..len]);
+                                if sending_clip && handshake_enc.has_feature(handshake::features::CLIP) {
+                                    mark_as_clip(&mut packet);
+                                }
+                            } else {
+                                packet.extend_from_slice(&encoded[..len]);
+                            }
+                            send_queue_tx.push(packet);
+                        }
+                    },
+                    Err(e) => {
+                        warn!(target: CODEC, "Encoding error: {:?}", e);
+                    }
+                }
+            } else {
+                // Тишина - отправляем пакет тишины только при переходе или с интервалом
+                let was_speaking_now = was_speaking.load(Ordering::Relaxed);
+                let last_silence = *last_silence_packet.lock().unwrap();
+                
+                // Если только что закончили говорить или прошло достаточно времени
+                if was_speaking_now || current_time.duration_since(last_silence) > vad.silence_interval {
+                    was_speaking.store(false, Ordering::Relaxed);
+                    *last_silence_packet.lock().unwrap() = current_time;
+                    
+                    // Отправляем специальный пакет тишины
+                    let mut packet = send_queue_tx.buffer();
+                    packet.extend_from_slice(&SILENCE_PACKET);
+                    send_queue_tx.push(packet);
+                }
+            }
+        }
+    });
+    
+    // Audio input thread
+    let running1 = running;
+    let on_data = Box::new(move |data: &[f32]| {
+        if !running1.load(Ordering::SeqCst) {
+            return;
+        }
+        
+        update_level(&input_level, data);
+        
+        let mode = transmit_mode.load(Ordering::Relaxed);
+        let key_released = matches!(mode, transmit_modes::PTT | transmit_modes::TOGGLE)
+            && !is_transmitting.load(Ordering::SeqCst)
+            && !release.is_holding()
+            && !whisper.is_holding();
+        let mic_open = !key_released && !mute.mic_blocked();
+        // Файл звуковой панели идет и без голоса, но не у заглушенного
+        let file_only = !mic_open && soundboard_in.is_playing() && !mute.is_deafened();
+        if !mic_open && !file_only {
+            return;
+        }
+        
+        // До ответа сервера на HELLO формат потока еще не согласован
+        if !handshake_tx.may_stream() {
+            return;
+        }
+        
+        if mic_open {
+            capture.write(data);
+        } else {
+            capture.write_silence(data.len());
+        }
+    });
+    // Микрофон отключили: хост может перезапустить клиент с другим устройством
+    let on_lost = Box::new(move |reason: String| {
+        events_in.emit(events::event_types::DEVICE_CHANGED, 0, events::DEVICE_INPUT, &reason);
+    });
+    let device = client.input_device.lock().unwrap().clone();
+    let (input_stream, name) = audio_backend(client).open_input(device.as_deref(), on_data, on_lost)?;
+    
+    client.events.emit(events::event_types::DEVICE_CHANGED, 0, events::DEVICE_INPUT, &name);
+    Ok(input_stream)
+}
+
+// Открывает устройство вывода из настроек клиента; очереди воспроизведения
+// остаются в клиенте и переживают смену устройства
+fn open_output_stream(client: &VoiceClient) -> Result<AudioStream, NsvcError> {
+    let running2 = client.running.clone();
+    let output_level = client.output_level.clone();
+    // Смешивает источники поток микшера, callback только читает кольцо
+    let mut playback = realtime::spawn_mixer(client.playback_buffer.clone(), client.stats.underrun_counter());
+    let on_data = Box::new(move |data: &mut [f32]| {
+        // Остановленный клиент (в том числе по тишине сервера, когда поток
+        // еще открыт) играет тишину, а не то, что было в буфере устройства
+        if !running2.load(Ordering::SeqCst) {
+            data.fill(0.0);
+            return;
+        }
+        
+        playback.read(data);
+        update_level(&output_level, data);
+    });
+    let events_out = client.events.clone();
+    let on_lost = Box::new(move |reason: String| {
+        events_out.emit(events::event_types::DEVICE_CHANGED, 0, events::DEVICE_OUTPUT, &reason);
+    });
+    let device = client.output_device.lock().unwrap().clone();
+    let (output_stream, name) = audio_backend(client).open_output(device.as_deref(), on_data, on_lost)?;
+    
+    client.events.emit(events::event_types::DEVICE_CHANGED, 0, events::DEVICE_OUTPUT, &name);
+    Ok(output_stream)
+}
+
+// Микрофон и динамики клиента: общие с группой, если он в ней. Отдельный
+// вывод голосов (voice_output.rs) всегда свой.
+fn audio_backend(client: &VoiceClient) -> Arc<dyn audio::AudioBackend> {
+    match client.audio_group.lock().unwrap().as_ref() {
+        Some(group) => group.clone(),
+        None => client.audio.clone(),
+    }
+}
+
+// Без отдельного вывода голосов звонок все равно идет: устройство могли
+// удалить с прошлого раза
+fn open_voice_output(client: &VoiceClient) {
+    if let Err(e) = voice_output::open(client) {
+        warn!(target: AUDIO, "Failed to open the voice output: {}", e);
+    }
+}
+
+fn start_streams_and_threads(client: &VoiceClient) -> Result<(), NsvcError> {
+    if let Some(name) = &client.link.lan_name {
+        let port = client.link.transport.local_addr().map(|a| a.port()).unwrap_or(0);
+        match lan::LanSession::start(name, port) {
+            Ok(session) => *client.link.lan.lock().unwrap() = Some(session),
+            Err(e) => return Err(NsvcError::Discovery(e)),
+        }
+    }
+    
+    if client.link.probe_mtu.load(Ordering::SeqCst) {
+        if let Some(server_addr) = client.link.server_addr {
+            let link_mtu = client.link.clone();
+            thread::spawn(move || {
+                match probe_path_mtu(server_addr) {
+                    Some(limit) => {
+                        info!(target: NET, "Path MTU probe: max datagram {} bytes", limit);
+                        link_mtu.path_mtu_limit.store(limit.max(MIN_DATAGRAM_SIZE), Ordering::Relaxed);
+                    },
+                    None => warn!(target: NET, "Path MTU probe unavailable, using configured datagram size"),
+                }
+            });
+        }
+    }
+    
+    *client.input_stream.lock().unwrap() = Some(open_input_stream(client)?);
+    *client.output_stream.lock().unwrap() = Some(open_output_stream(client)?);
+    open_voice_output(client);
+    
+    client.send_queue.clear();
+    client.channels.reset();
+    client.roster.reset();
+    client.stats.reset();
+    client.link.traffic.reset();
+    network::spawn(client)?;
+    if client.positional.is_enabled() {
+        positional::spawn(client);
+    }
+    
+    // Handshake thread
+    let requested = handshake::SessionParams {
+        sample_rate: SAMPLE_RATE,
+        channels: 1,
+        frame_size: client.frame_size.load(Ordering::Relaxed) as u16,
+        bitrate: client.bitrate.load(Ordering::Relaxed),
+        features: client.features.load(Ordering::SeqCst),
+    };
+    if client.link.server_addr.is_some() {
+        client.handshake.set_outcome(handshake::Outcome::Pending);
+        // Токен прошлой сессии новый сервер не примет
+        client.link.session_token.store(0, Ordering::Relaxed);
+        let link_hs = client.link.clone();
+        let handshake_hs = client.handshake.clone();
+        let running_hs = client.running.clone();
+        let connection_hs = client.connection.clone();
+        let encoder_hs = client.encoder.clone();
+        let frame_size_hs = client.frame_size.clone();
+        let bitrate_hs = client.bitrate.clone();
+        let errors_hs = client.errors.clone();
+        let credentials = client.credentials.lock().unwrap().clone();
+        let nickname = client.nickname.lock().unwrap().clone();
+        let roster_hs = client.roster.clone();
+        thread::spawn(move || {
+            run_handshake(&link_hs, &handshake_hs, &running_hs, requested, &credentials, &nickname);
+            apply_handshake_outcome(&handshake_hs, &connection_hs, &errors_hs, &encoder_hs, &frame_size_hs, &bitrate_hs);
+            
+            // Дальше наш голос несет ID участника: им нас узнают и собеседники по P2P
+            let user_id = handshake_hs.user_id();
+            if user_id != 0 && matches!(handshake_hs.outcome(), handshake::Outcome::Accepted(_)) {
+                link_hs.media_ssrc.store(user_id, Ordering::Relaxed);
+                link_hs.session_token.store(handshake_hs.session_token(), Ordering::Relaxed);
+                roster_hs.set_own_id(user_id);
+                info!(target: NET, "Server assigned user id {}", user_id);
+            }
+        });
+    } else {
+        // Без сервера (LAN, multicast) договариваться не с кем: все соседи -
+        // такие же клиенты, поэтому сразу шлем MEDIA с номерами пакетов.
+        // Именно в Wi-Fi сетях датаграммы чаще всего приходят дважды.
+        client.handshake.set_outcome(handshake::Outcome::Accepted(requested));
+    }
+    
+    info!(target: CLIENT, "Voice client fully started");
+    Ok(())
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop(client: *mut c_void) {
+    if client.is_null() {
+        warn!(target: CLIENT, "voice_client_stop: client is null!");
+        return;
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_stop: invalid client handle");
+        return;
+    };
+    stop_client(&client);
+}
+
+fn stop_client(client: &VoiceClient) {
+    info!(target: CLIENT, "Stopping voice client");
+    
+    // Сначала замолкает микрофон: сказанное до остановки успевает уйти,
+    // а затем прощание - сервер видит уход, а не обрыв посреди фразы
+    *client.input_stream.lock().unwrap() = None;
+    if client.running.load(Ordering::SeqCst) {
+        let deadline = Instant::now() + SEND_DRAIN_TIMEOUT;
+        while !client.send_queue.is_empty() && Instant::now() < deadline {
+            thread::sleep(send_queue::PACING_INTERVAL);
+        }
+        client.link.send_goodbye();
+    }
+    
+    client.running.store(false, Ordering::SeqCst);
+    // Циклы сети замечают остановку за миллисекунды. Остановку из callback'а
+    // не ждем: ее поток может быть одним из них.
+    if !client.events.in_host_call() {
+        client.workers.wait();
+    }
+    
+    *client.output_stream.lock().unwrap() = None;
+    client.voice_output.close();
+    *client.preview_stream.lock().unwrap() = None;
+    *client.local_output.lock().unwrap() = None;
+    *client.mic_check_stream.lock().unwrap() = None;
+    client.soundboard.stop();
+    if let Err(e) = client.recording.stop() {
+        warn!(target: AUDIO, "{}", e);
+    }
+    
+    if let Some(turn) = client.link.turn() {
+        turn.release();
+    }
+    client.link.p2p.reset("P2P session closed");
+    if let Some(lan) = client.link.lan.lock().unwrap().take() {
+        lan.shutdown();
+    }
+    
+    client.connection.transition(connection_states::DISCONNECTED);
+    
+    info!(target: CLIENT, "Voice client stopped");
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_transmitting(client: *mut c_void, transmitting: bool) {
+    if client.is_null() {
+        warn!(target: AUDIO, "voice_client_set_transmitting: client is null!");
+        return;
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_set_transmitting: invalid client handle");
+        return;
+    };
+    let was = client.is_transmitting.swap(transmitting, Ordering::SeqCst);
+    on_transmit_change(&client, was, transmitting);
+    
+    info!(target: AUDIO, "Transmitting: {}", transmitting);
+}
+
+// Передачу включили или выключили клавишей или хостом. Сигнал - только там,
+// где от этого зависит микрофон: в PTT и TOGGLE, и не при mute.
+fn on_transmit_change(client: &VoiceClient, was: bool, transmitting: bool) {
+    client.release.on_change(was, transmitting);
+    let mode = client.transmit_mode.load(Ordering::Relaxed);
+    if was != transmitting && matches!(mode, transmit_modes::PTT | transmit_modes::TOGGLE) && !client.mute.mic_blocked() {
+        client.cues.play(cues::Cue::Transmit(transmitting));
+    }
+}
+
+// Клавиша разговора нажата или отпущена. Что это значит, решает режим:
+// в PTT передача идет, пока клавиша нажата, в TOGGLE каждое нажатие
+// переключает передачу, а отпускание ничего не меняет. В остальных режимах
+// клавиша не действует. Хост сообщает о клавише, не зная режима, поэтому
+// режим можно сменить в настройках без изменений в коде хоста.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_talk_key(client: *mut c_void, pressed: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_talk_key: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_talk_key: invalid client handle");
+    };
+    let (was, transmitting) = match client.transmit_mode.load(Ordering::Relaxed) {
+        transmit_modes::PTT => (client.is_transmitting.swap(pressed, Ordering::SeqCst), pressed),
+        transmit_modes::TOGGLE if pressed => {
+            let was = client.is_transmitting.fetch_xor(true, Ordering::SeqCst);
+            (was, !was)
+        },
+        _ => return error_codes::SUCCESS,
+    };
+    on_transmit_change(&client, was, transmitting);
+    
+    info!(target: AUDIO, "Transmitting: {}", transmitting);
+    error_codes::SUCCESS
+}
+
+// Кому шепот (см. whisper.rs): count ID участников из ids, не больше
+// MAX_WHISPER_TARGETS. count = 0 очищает список, и шепот не слышит никто.
+// Список можно менять и на ходу, в том числе пока клавиша шепота нажата.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_whisper_targets(client: *mut c_void, ids: *const u32, count: usize) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_whisper_targets: client is null!");
+    }
+    if count > MAX_WHISPER_TARGETS {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_whisper_targets: too many targets");
+    }
+    let Some(targets) = c_ptr::slice(ids, count) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_whisper_targets: ids is null!");
+    };
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_whisper_targets: invalid client handle");
+    };
+    client.whisper.set_targets(targets);
+    info!(target: AUDIO, "Whisper targets: {:?}", targets);
+    error_codes::SUCCESS
+}
+
+// Клавиша шепота нажата или отпущена - вторая клавиша разговора: пока она
+// нажата, микрофон передает в любом режиме, но голос слышат только
+// участники из voice_client_set_whisper_targets.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_whisper_key(client: *mut c_void, pressed: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_whisper_key: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_whisper_key: invalid client handle");
+    };
+    let was = client.whisper.set_pressed(pressed);
+    if was == pressed {
+        return error_codes::SUCCESS;
+    }
+    if !client.mute.mic_blocked() {
+        client.cues.play(cues::Cue::Transmit(pressed));
+    }
+    let accepted = matches!(client.handshake.outcome(), handshake::Outcome::Accepted(_));
+    if pressed && accepted && !client.handshake.has_feature(handshake::features::WHISPER) {
+        warn!(target: AUDIO, "Server does not route whispers, nobody will hear this one");
+    }
+    
+    info!(target: AUDIO, "Whispering: {}", pressed);
+    error_codes::SUCCESS
+}
+
+// Меняет микрофон; name = NULL или "" - устройство по умолчанию. У
+// запущенного клиента пересоздается только поток захвата, соединение и
+// кодер остаются. Когда новый поток заработал, приходит DEVICE_CHANGED.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_input_device(client: *mut c_void, name: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_input_device: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_input_device: invalid client handle");
+    };
+    *client.input_device.lock().unwrap() = config::device_name(name);
+    
+    // Замок потока держим до конца, чтобы не разойтись с voice_client_stop
+    let mut stream = client.input_stream.lock().unwrap();
+    if !client.running.load(Ordering::SeqCst) {
+        return error_codes::SUCCESS;
+    }
+    // Старый поток закрываем первым: не все драйверы дают открыть устройство дважды
+    *stream = None;
+    match open_input_stream(&client) {
+        Ok(new_stream) => {
+            *stream = Some(new_stream);
+            error_codes::SUCCESS
+        },
+        Err(e) => report_error(&e),
+    }
+}
+
+// То же для устройства вывода; очереди воспроизведения сохраняются
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_output_device(client: *mut c_void, name: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_output_device: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_output_device: invalid client handle");
+    };
+    *client.output_device.lock().unwrap() = config::device_name(name);
+    
+    let mut stream = client.output_stream.lock().unwrap();
+    if !client.running.load(Ordering::SeqCst) {
+        return error_codes::SUCCESS;
+    }
+    *stream = None;
+    match open_output_stream(&client) {
+        Ok(new_stream) => {
+            *stream = Some(new_stream);
+            error_codes::SUCCESS
+        },
+        Err(e) => report_error(&e),
+    }
+}
+
+// Голоса собеседников еще и на устройство name (см. voice_output.rs) -
+// например, виртуальное, которое захватывает OBS. NULL или пустая строка
+// выключают отдельный вывод. У запущенного клиента устройство открывается
+// сразу, иначе - при запуске.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_voice_output_device(client: *mut c_void, name: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_voice_output_device: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_voice_output_device: invalid client handle");
+    };
+    client.voice_output.set_device(config::device_name(name));
+    
+    // Под замком вывода, как у voice_client_set_output_device: остановка
+    // дождется и закроет и этот поток
+    let _output = client.output_stream.lock().unwrap();
+    if !client.running.load(Ordering::SeqCst) {
+        client.voice_output.close();
+        return error_codes::SUCCESS;
+    }
+    match voice_output::open(&client) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// Один микрофон и одни динамики на несколько клиентов, например чтобы
+// слушать два сервера или канала сразу. Каждый клиент держит свою сессию,
+// а голоса всех звучат вместе; куда говорить, хост выбирает mute и режимом
+// передачи каждого клиента. Клиент подключается к группе other (создается,
+// если other ни с кем не делит устройства); NULL выводит его из группы.
+// Устройство открывается по настройкам первого запущенного в группе. Оба
+// клиента должны быть остановлены.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_share_audio(client: *mut c_void, other: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_share_audio: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_share_audio: invalid client handle");
+    };
+    if client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_share_audio: client is running");
+    }
+    if other.is_null() {
+        *client.audio_group.lock().unwrap() = None;
+        return error_codes::SUCCESS;
+    }
+    let Some(other) = CLIENTS.get(other) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_share_audio: invalid other client handle");
+    };
+    if other.running.load(Ordering::SeqCst) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_share_audio: other client is running");
+    }
+    
+    let group = other
+        .audio_group
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| Arc::new(audio::SharedAudio::new(other.audio.clone())))
+        .clone();
+    *client.audio_group.lock().unwrap() = Some(group);
+    error_codes::SUCCESS
+}
+
+// Пересоздает оба аудиопотока с устройствами из настроек: после выхода из
+// спящего режима или сбоя драйвера. Сокет, кодер, сессия с сервером и
+// очереди воспроизведения остаются.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_restart_audio(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_restart_audio: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_restart_audio: invalid client handle");
+    };
+    
+    let mut input = client.input_stream.lock().unwrap();
+    let mut output = client.output_stream.lock().unwrap();
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, "voice_client_restart_audio: client is not running");
+    }
+    info!(target: AUDIO, "Restarting audio streams");
+    
+    *input = None;
+    *output = None;
+    *input = match open_input_stream(&client) {
+        Ok(stream) => Some(stream),
+        Err(e) => return report_error(&e),
+    };
+    *output = match open_output_stream(&client) {
+        Ok(stream) => Some(stream),
+        Err(e) => return report_error(&e),
+    };
+    open_voice_output(&client);
+    
+    info!(target: AUDIO, "Audio streams restarted");
+    error_codes::SUCCESS
+}
+
+// Проверка микрофона до подключения: захват без кодирования и передачи,
+// только уровень для voice_client_get_preview_level. У запущенного клиента
+// уровень и так считается, и вызов ничего не делает.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_mic_preview(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_mic_preview: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_mic_preview: invalid client handle");
+    };
+    
+    let mut preview = client.preview_stream.lock().unwrap();
+    if client.running.load(Ordering::SeqCst) || preview.is_some() {
+        return error_codes::SUCCESS;
+    }
+    
+    let input_level = client.input_level.clone();
+    let on_data = Box::new(move |data: &[f32]| update_level(&input_level, data));
+    let device = client.input_device.lock().unwrap().clone();
+    let (stream, name) = match audio_backend(&client).open_input(device.as_deref(), on_data, Box::new(|_| {})) {
+        Ok(opened) => opened,
+        Err(e) => return report_error(&e),
+    };
+    
+    info!(target: AUDIO, "Microphone preview started on {:?}", name);
+    *preview = Some(stream);
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_mic_preview(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_mic_preview: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_mic_preview: invalid client handle");
+    };
+    if client.preview_stream.lock().unwrap().take().is_some() {
+        info!(target: AUDIO, "Microphone preview stopped");
+    }
+    error_codes::SUCCESS
+}
+
+// Пиковая громкость микрофона (0..1) с прошлого вызова. Работает и во время
+// проверки микрофона (и записью), и у запущенного клиента, даже без передачи.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_preview_level(client: *mut c_void, level: *mut f32) -> i32 {
+    if client.is_null() || level.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_preview_level: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_preview_level: invalid client handle");
+    };
+    if !client.running.load(Ordering::SeqCst)
+        && client.preview_stream.lock().unwrap().is_none()
+        && client.mic_check_stream.lock().unwrap().is_none()
+    {
+        return fail(error_codes::NOT_RUNNING, "voice_client_get_preview_level: no capture is running");
+    }
+    
+    c_ptr::set(level, f32::from_bits(client.input_level.swap(0, Ordering::Relaxed)));
+    error_codes::SUCCESS
+}
+
+// Пиковая громкость воспроизведения (0..1) с прошлого вызова, после общей
+// громкости и громкости участников; только у запущенного клиента
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_output_level(client: *mut c_void, level: *mut f32) -> i32 {
+    if client.is_null() || level.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_output_level: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_output_level: invalid client handle");
+    };
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, "voice_client_get_output_level: client is not running");
+    }
+    
+    c_ptr::set(level, f32::from_bits(client.output_level.swap(0, Ordering::Relaxed)));
+    error_codes::SUCCESS
+}
+
+// Проверочный сигнал из test_tones длиной seconds (1-30) на устройстве
+// вывода из настроек, с общей громкостью - чтобы проверить динамики до
+// разговора. Сеть не нужна. У запущенного клиента сигнал идет поверх
+// разговора, как звуковые сигналы; иначе устройство открывается только на
+// время сигнала. Новый сигнал прерывает прежний.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_play_test_tone(client: *mut c_void, kind: i32, seconds: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_play_test_tone: client is null!");
+    }
+    if !(1..=MAX_TEST_TONE_SECONDS).contains(&seconds) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_play_test_tone: seconds out of range");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_play_test_tone: invalid client handle");
+    };
+    let duration = Duration::from_secs(seconds as u64);
+    let Some(samples) = test_tone::samples(kind, duration) else {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_play_test_tone: unknown tone");
+    };
+    if client.running.load(Ordering::SeqCst) {
+        client.playback_buffer.lock().unwrap().push_cue(samples);
+        return error_codes::SUCCESS;
+    }
+    match play_local(&client, samples) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// Звук на устройстве вывода без запущенного клиента, с общей громкостью.
+// Он целиком уже в кольце, поток микшера не нужен; устройство закрывает
+// поток, который ждет конца звука. Новый звук прерывает прежний.
+pub(crate) fn play_local(client: &VoiceClient, mut samples: Vec<f32>) -> Result<(), NsvcError> {
+    let duration = Duration::from_secs_f64(samples.len() as f64 / SAMPLE_RATE as f64);
+    let volume = client.playback_buffer.lock().unwrap().volume;
+    for sample in samples.iter_mut() {
+        *sample = (*sample * volume).clamp(-1.0, 1.0);
+    }
+    let mut playback = realtime::prefilled(&samples);
+    let on_data = Box::new(move |data: &mut [f32]| playback.read(data));
+    let device = client.output_device.lock().unwrap().clone();
+    let mut slot = client.local_output.lock().unwrap();
+    // Прежний звук закрывается до открытия устройства заново
+    *slot = None;
+    let (stream, name) = audio_backend(client).open_output(device.as_deref(), on_data, Box::new(|_| {}))?;
+    info!(target: AUDIO, "Playing on {:?}", name);
+    let end = Instant::now() + duration;
+    *slot = Some((end, stream));
+    
+    let local_output = client.local_output.clone();
+    thread::spawn(move || {
+        thread::sleep(duration + LOCAL_OUTPUT_CLOSE_DELAY);
+        let mut slot = local_output.lock().unwrap();
+        // Более поздний звук закроет свой поток
+        if slot.as_ref().is_some_and(|(stream_end, _)| *stream_end <= end) {
+            *slot = None;
+        }
+    });
+    Ok(())
+}
+
+// Обрывает проверочный сигнал
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_test_tone(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_test_tone: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_test_tone: invalid client handle");
+    };
+    *client.local_output.lock().unwrap() = None;
+    if client.running.load(Ordering::SeqCst) {
+        client.playback_buffer.lock().unwrap().push_cue(Vec::new());
+    }
+    error_codes::SUCCESS
+}
+
+// Свой звук уведомления kind (notification_sounds) из файла WAV или
+// Ogg/Opus, до 5 секунд; NULL или пустая строка возвращают тон. Громкость -
+// как у сигналов (cue_volume), выключаются они вместе с
+// notification_cues_enabled.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_notification_sound(client: *mut c_void, kind: i32, path: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_notification_sound: client is null!");
+    }
+    if !cues::is_notification(kind) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_notification_sound: unknown notification");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_notification_sound: invalid client handle");
+    };
+    let path = if path.is_null() {
+        ""
+    } else {
+        let Some(Ok(path)) = c_ptr::string(path).map(CStr::to_str) else {
+            return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_notification_sound: invalid path");
+        };
+        path
+    };
+    if path.is_empty() {
+        client.cues.set_sound(kind, None);
+        return error_codes::SUCCESS;
+    }
+    match cues::load_notification(Path::new(path)) {
+        Ok(samples) => {
+            client.cues.set_sound(kind, Some(samples));
+            error_codes::SUCCESS
+        },
+        Err(e) => report_error(&NsvcError::AudioFile(e)),
+    }
+}
+
+// Проверка микрофона записью (см. mic_check.rs): seconds (1-10) с
+// микрофона, затем они же в динамиках - через детектор голоса и Opus с
+// текущими настройками, как их услышат другие. Вызов сразу возвращается;
+// запись идет seconds, потом столько же проигрывается. Пока идет запись,
+// уровень отдает voice_client_get_preview_level. Только у незапущенного
+// клиента: в разговоре микрофон занят.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_mic_check(client: *mut c_void, seconds: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_mic_check: client is null!");
+    }
+    if !(1..=MAX_MIC_CHECK_SECONDS).contains(&seconds) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_mic_check: seconds out of range");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_mic_check: invalid client handle");
+    };
+    if client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_mic_check: client is running");
+    }
+    match mic_check::start(client, Duration::from_secs(seconds as u64)) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// Обрывает проверку микрофона записью - и запись, и проигрывание
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_mic_check(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_mic_check: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_mic_check: invalid client handle");
+    };
+    *client.mic_check_stream.lock().unwrap() = None;
+    if !client.running.load(Ordering::SeqCst) {
+        *client.local_output.lock().unwrap() = None;
+    }
+    error_codes::SUCCESS
+}
+
+// Режим передачи из transmit_modes; действует сразу
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_transmit_mode(client: *mut c_void, mode: i32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_transmit_mode: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_transmit_mode: invalid client handle");
+    };
+    if !config::is_valid_transmit_mode(mode) {
+        return report_error(&NsvcError::InvalidParam(format!("unknown transmit mode {}", mode)));
+    }
+    
+    client.transmit_mode.store(mode, Ordering::Relaxed);
+    info!(target: AUDIO, "Transmit mode set to {}", mode);
+    error_codes::SUCCESS
+}
+
+// Останавливает и освобождает клиента. После возврата callback'и хоста
+// больше не вызываются, и их userdata можно освобождать.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_free(client: *mut c_void) {
+    if client.is_null() {
+        warn!(target: CLIENT, "voice_client_free: client is null!");
+        return;
+    }
+    
+    // Удаляем из таблицы сразу: повторный free или вызов со старым
+    // описателем получит ошибку. Память освободит последний вызов, который
+    // еще держит клиента.
+    let Some(client) = CLIENTS.remove(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_free: invalid client handle");
+        return;
+    };
+    info!(target: CLIENT, "Freeing voice client");
+    stop_client(&client);
+    // После free хост освобождает userdata, поэтому ни одного вызова больше:
+    // ни из потоков, которые еще завершаются, ни из тех, что уже внутри callback
+    *client.errors.callback.lock().unwrap() = None;
+    *client.connection.callback.lock().unwrap() = None;
+    client.events.close();
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_bitrate(client: *mut c_void, bitrate: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_bitrate: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_bitrate: invalid client handle");
+    };
+    
+    if bitrate < 6000 || bitrate > 510000 {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_bitrate: {} bps is outside 6000..=510000", bitrate),
+        );
+    }
+    
+    client.bitrate.store(bitrate, Ordering::Relaxed);
+    info!(target: CODEC, "Bitrate set to {} bps", bitrate);
+    
+    if client.running.load(Ordering::SeqCst) {
+        if let Ok(mut encoder) = client.encoder.lock() {
+            if let Err(e) = encoder.set_bitrate(Bitrate::Bits(bitrate as i32)) {
+                warn!(target: CODEC, "Failed to set bitrate: {:?}", e);
+            }
+        }
+    }
+    
+    error_codes::SUCCESS
+}
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_error_callback(
+    client: *mut c_void,
+    callback: Option<ErrorCallback>,
+    userdata: *mut c_void,
+) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_error_callback: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_error_callback: invalid client handle");
+    };
+    *client.errors.callback.lock().unwrap() = callback.map(|func| (func, userdata as usize));
+    
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_state_callback(
+    client: *mut c_void,
+    callback: Option<ConnectionStateCallback>,
+    userdata: *mut c_void,
+) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_state_callback: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_state_callback: invalid client handle");
+    };
+    *client.connection.callback.lock().unwrap() = callback.map(|func| (func, userdata as usize));
+    
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_connection_state(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_connection_state: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_connection_state: invalid client handle");
+    };
+    client.connection.state()
+}
+
+// Запущен ли клиент (между voice_client_start и voice_client_stop)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_running(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_is_running: invalid client handle");
+        return false;
+    };
+    client.running.load(Ordering::SeqCst)
+}
+
+// Включена ли передача голоса
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_transmitting(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_is_transmitting: invalid client handle");
+        return false;
+    };
+    client.is_transmitting.load(Ordering::SeqCst)
+}
+
+// Состояние соединения CONNECTED (см. voice_client_get_connection_state)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_connected(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_is_connected: invalid client handle");
+        return false;
+    };
+    client.connection.state() == connection_states::CONNECTED
+}
+
+// Сколько звука сейчас ждет воспроизведения, мс; отрицательное - код ошибки
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_buffer_ms(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_buffer_ms: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_buffer_ms: invalid client handle");
+    };
+    buffered_ms(&client) as i32
+}
+
+fn buffered_ms(client: &VoiceClient) -> u32 {
+    let buffered = client.playback_buffer.lock().unwrap().buffered_samples();
+    (buffered as u64 * 1000 / SAMPLE_RATE as u64) as u32
+}
+
+// Шлет HELLO, пока сервер не ответит или не истечет HANDSHAKE_TIMEOUT
+fn run_handshake(
+    link: &ServerLink,
+    handshake: &handshake::Handshake,
+    running: &AtomicBool,
+    requested: handshake::SessionParams,
+    credentials: &handshake::Credentials,
+    nickname: &str,
+) {
+    info!(
+        target: NET,
+        "Handshake: protocol v{}-v{}, {} Hz, frame {} samples, {} bps, features 0x{:x}",
+        MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, requested.sample_rate, requested.frame_size, requested.bitrate, requested.features
+    );
+    
+    let hello = handshake::hello_packet(&requested, credentials, nickname);
+    let started = Instant::now();
+    let mut last_send: Option<Instant> = None;
+    
+    while running.load(Ordering::SeqCst)
+        && handshake.outcome() == handshake::Outcome::Pending
+        && started.elapsed() < handshake::HANDSHAKE_TIMEOUT
+    {
+        if last_send.is_none_or(|t| t.elapsed() >= handshake::HELLO_INTERVAL) {
+            if let Err(e) = link.send(&hello) {
+                warn!(target: NET, "Hello send error: {}", e);
+            }
+            last_send = Some(Instant::now());
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    
+    if handshake.outcome() == handshake::Outcome::Pending {
+        warn!(target: NET, "Server did not answer hello, assuming legacy protocol (48 kHz mono, 10 ms Opus)");
+        handshake.set_outcome(handshake::Outcome::Legacy);
+    }
+}
+
+// Применяет параметры, согласованные с сервером
+fn apply_handshake_outcome(
+    handshake: &handshake::Handshake,
+    connection: &ConnectionTracker,
+    errors: &ErrorReporter,
+    encoder: &Mutex<Encoder>,
+    frame_size: &AtomicUsize,
+    bitrate: &AtomicU32,
+) {
+    match handshake.outcome() {
+        handshake::Outcome::Accepted(params) => {
+            if let Err(reason) = handshake::validate(&params, SAMPLE_RATE, 1) {
+                warn!(target: NET, "Handshake failed: {}", reason);
+                handshake.set_outcome(handshake::Outcome::Rejected(reason));
+                connection.transition(connection_states::FAILED);
+                return;
+            }
+            
+            frame_size.store(params.frame_size as usize, Ordering::Relaxed);
+            // Сервер может ограничить битрейт; 0 - оставить наш
+            if params.bitrate != 0 {
+                bitrate.store(params.bitrate, Ordering::Relaxed);
+            }
+            
+            let fec = params.features & handshake::features::FEC != 0;
+            if let Ok(mut encoder) = encoder.lock() {
+                if let Err(e) = encoder.set_inband_fec(fec) {
+                    warn!(target: CODEC, "Failed to set FEC: {:?}", e);
+                }
+                if fec {
+                    if let Err(e) = encoder.set_packet_loss_perc(10) {
+                        warn!(target: CODEC, "Failed to set expected packet loss: {:?}", e);
+                    }
+                }
+            }
+            
+            info!(
+                target: NET,
+                "Handshake accepted: frame {} samples, bitrate {} bps, features 0x{:x}",
+                params.frame_size, bitrate.load(Ordering::Relaxed), params.features
+            );
+        },
+        handshake::Outcome::Rejected(reason) => {
+            warn!(target: NET, "Server rejected hello: {}", reason);
+            if reason.starts_with(handshake::ACCESS_DENIED_PREFIX) {
+                errors.report(error_codes::ACCESS_DENIED, &reason);
+            }
+            connection.transition(connection_states::FAILED);
+        },
+        handshake::Outcome::VersionMismatch { server_min, server_max } => {
+            errors.report(
+                error_codes::PROTOCOL_MISMATCH,
+                &format!(
+                    "protocol mismatch: server supports v{}-v{}, client supports v{}-v{}",
+                    server_min, server_max, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+                ),
+            );
+            connection.transition(connection_states::FAILED);
+        },
+        _ => {},
+    }
+}
+
+// Рассылает собеседнику через сервер наши адреса: локальный и внешний (из STUN)
+fn emit_roster_change(events: &events::EventSink, roster: &users::Roster, change: users::Change) {
+    match change {
+        users::Change::Joined(id, name) | users::Change::Listed(id, name) => {
+            events.emit(events::event_types::USER_JOINED, id, 0, &name)
+        },
+        users::Change::Left(id, name) => events.emit(events::event_types::USER_LEFT, id, 0, &name),
+        users::Change::Speaking(id, speaking) => {
+            let event = if speaking { events::event_types::SPEAKING_STARTED } else { events::event_types::SPEAKING_STOPPED };
+            events.emit(event, id, 0, &roster.name(id).unwrap_or_default());
+        },
+    }
+}
+
+fn announce_p2p_candidates(link: &ServerLink, stun: &StunState) {
+    let server_addr = match link.server_addr {
+        Some(addr) => addr,
+        None => return,
+    };
+    
+    let mut candidates = Vec::with_capacity(2);
+    if let Some(local) = p2p::local_candidate(&*link.transport, server_addr) {
+        candidates.push(local);
+    }
+    if let Some(public) = *stun.public_addr.lock().unwrap() {
+        if !candidates.contains(&public) {
+            candidates.push(public);
+        }
+    }
+    
+    let packet = link.p2p.candidates_packet(&candidates);
+    if let Err(e) = link.send(&packet) {
+        warn!(target: NET, "P2P candidates send error: {}", e);
+    }
+}
+
+// Выполняет STUN-запрос с сокета клиента и ждет ответ не дольше timeout.
+// Если клиент запущен, ответ перехватывает поток приема.
+fn discover_public_address(client: &VoiceClient, stun_addr: SocketAddr, timeout: Duration) -> Option<SocketAddr> {
+    let transaction_id = stun::new_transaction_id();
+    let request = stun::build_binding_request(&transaction_id);
+    *client.stun.pending.lock().unwrap() = Some((stun_addr, transaction_id));
+    
+    let started = Instant::now();
+    let mut last_send: Option<Instant> = None;
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    
+    while started.elapsed() < timeout {
+        if last_send.is_none_or(|t| t.elapsed() >= STUN_RETRANSMIT_INTERVAL) {
+            if let Err(e) = client.link.transport.send_datagram(&request, stun_addr) {
+                warn!(target: NET, "STUN send error: {}", e);
+            }
+            last_send = Some(Instant::now());
+        }
+        
+        if client.running.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(5));
+        } else {
+            match client.link.transport.recv(&mut buf) {
+                Ok((size, from)) => {
+                    client.stun.handle_packet(from, &buf[..size]);
+                },
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(5));
+                },
+                Err(e) => {
+                    warn!(target: NET, "STUN receive error: {}", e);
+                    thread::sleep(Duration::from_millis(5));
+                }
+            }
+        }
+        
+        // Ответ обработан - запрос снят
+        if client.stun.pending.lock().unwrap().is_none() {
+            return *client.stun.public_addr.lock().unwrap();
+        }
+    }
+    
+    *client.stun.pending.lock().unwrap() = None;
+    warn!(target: NET, "STUN request to {} timed out", stun_addr);
+    None
+}
+
+// Блокирует вызывающий поток до timeout_ms
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stun_discover(
+    client: *mut c_void,
+    stun_host: *const c_char,
+    stun_port: u16,
+    timeout_ms: u32,
+) -> i32 {
+    if client.is_null() || stun_host.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stun_discover: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stun_discover: invalid client handle");
+    };
+    let host = c_ptr::string(stun_host).unwrap_or_default().to_str().unwrap_or_default();
+    
+    let stun_addr = match resolve_addr(host, stun_port) {
+        Some(addr) => addr,
+        None => {
+            return report_error(&NsvcError::Resolve(format!("{}:{}", host, stun_port)));
+        }
+    };
+    
+    match discover_public_address(&client, stun_addr, Duration::from_millis(timeout_ms as u64)) {
+        Some(_) => error_codes::SUCCESS,
+        None => fail(error_codes::STUN_FAILED, "voice_client_stun_discover: no answer from the STUN server"),
+    }
+}
+
+// Внешний адрес в виде "ip:port", последний результат voice_client_stun_discover
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_public_address(client: *mut c_void, buf: *mut c_char, buf_len: usize) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_public_address: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_public_address: invalid client handle");
+    };
+    let public_addr = *client.stun.public_addr.lock().unwrap();
+    match public_addr {
+        Some(addr) => write_c_string(&addr.to_string(), buf, buf_len),
+        None => fail(error_codes::STUN_FAILED, "voice_client_get_public_address: public address is not known yet"),
+    }
+}
+
+// Настраивает TURN-сервер, через который пойдет медиа, если прямой UDP до
+// голосового сервера не отвечает. Пустой host отключает fallback.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_turn_server(
+    client: *mut c_void,
+    host: *const c_char,
+    port: u16,
+    username: *const c_char,
+    password: *const c_char,
+) -> i32 {
+    if client.is_null() || host.is_null() || username.is_null() || password.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_turn_server: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_turn_server: invalid client handle");
+    };
+    let host = c_ptr::string(host).unwrap_or_default().to_str().unwrap_or_default();
+    let username = c_ptr::string(username).unwrap_or_default().to_string_lossy().into_owned();
+    let password = c_ptr::string(password).unwrap_or_default().to_string_lossy().into_owned();
+    
+    if let Some(old) = client.link.turn.lock().unwrap().take() {
+        old.release();
+    }
+    
+    if host.is_empty() {
+        info!(target: NET, "TURN fallback disabled");
+        return error_codes::SUCCESS;
+    }
+    
+    let server = match resolve_addr(host, port) {
+        Some(addr) => addr,
+        None => {
+            return report_error(&NsvcError::Resolve(format!("{}:{}", host, port)));
+        }
+    };
+    
+    let config = turn::TurnConfig { server, username, password };
+    *client.link.turn.lock().unwrap() = Some(Arc::new(turn::TurnClient::new(config, client.link.transport.clone())));
+    info!(target: NET, "TURN fallback configured: {}", server);
+    
+    error_codes::SUCCESS
+}
+
+// Включает P2P-режим для разговора двоих. Для внешнего кандидата стоит
+// заранее вызвать voice_client_stun_discover.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_p2p_enabled(client: *mut c_void, enabled: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_p2p_enabled: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_p2p_enabled: invalid client handle");
+    };
+    client.link.p2p.set_enabled(enabled);
+    info!(target: NET, "P2P mode: {}", enabled);
+    
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_p2p_active(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        fail(error_codes::NULL_POINTER, "voice_client_is_p2p_active: invalid client handle");
+        return false;
+    };
+    client.link.p2p.peer().is_some()
+}
+
+// Максимальный размер UDP-датаграммы (по умолчанию 1200 байт)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_max_datagram_size(client: *mut c_void, size: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_max_datagram_size: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_max_datagram_size: invalid client handle");
+    };
+    
+    let size = size as usize;
+    if !(MIN_DATAGRAM_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!(
+                "voice_client_set_max_datagram_size: {} is outside {}..={}",
+                size, MIN_DATAGRAM_SIZE, MAX_PACKET_SIZE
+            ),
+        );
+    }
+    
+    client.link.max_datagram.store(size, Ordering::Relaxed);
+    info!(target: NET, "Max datagram size set to {} bytes", size);
+    
+    error_codes::SUCCESS
+}
+
+// Сколько мс молчания сервера терпеть до отключения; 0 - не отключаться
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_server_timeout(client: *mut c_void, timeout_ms: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_server_timeout: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_server_timeout: invalid client handle");
+    };
+    
+    // Раньше SERVER_TIMEOUT отключаться нельзя: это обычная потеря пакетов
+    if timeout_ms != 0 && Duration::from_millis(timeout_ms as u64) < SERVER_TIMEOUT {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_server_timeout: {} ms is below {} ms", timeout_ms, SERVER_TIMEOUT.as_millis()),
+        );
+    }
+    
+    client.connection.silence_timeout_ms.store(timeout_ms as u64, Ordering::Relaxed);
+    info!(target: NET, "Server silence timeout set to {} ms", timeout_ms);
+    
+    error_codes::SUCCESS
+}
+
+// Интервал keep-alive в миллисекундах (100..=60000)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_keep_alive_interval(client: *mut c_void, interval_ms: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_keep_alive_interval: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_keep_alive_interval: invalid client handle");
+    };
+    
+    let interval = Duration::from_millis(interval_ms as u64);
+    if !(MIN_KEEP_ALIVE_INTERVAL..=MAX_KEEP_ALIVE_INTERVAL).contains(&interval) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_keep_alive_interval: {} ms is out of range", interval_ms),
+        );
+    }
+    
+    client.keep_alive_interval_ms.store(interval_ms as u64, Ordering::Relaxed);
+    info!(target: NET, "Keep-alive interval set to {} ms", interval_ms);
+    
+    error_codes::SUCCESS
+}
+
+// Размеры буферов сокета в байтах (SO_SNDBUF/SO_RCVBUF); 0 - не менять
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_socket_buffer_sizes(client: *mut c_void, send_size: u32, recv_size: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_socket_buffer_sizes: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_socket_buffer_sizes: invalid client handle");
+    };
+    
+    let (send_size, recv_size) = (send_size as usize, recv_size as usize);
+    if send_size > MAX_SOCKET_BUFFER_SIZE || recv_size > MAX_SOCKET_BUFFER_SIZE {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_socket_buffer_sizes: sizes above {} bytes", MAX_SOCKET_BUFFER_SIZE),
+        );
+    }
+    
+    // У транспортов без сокета UDP настраивать нечего
+    let Some(socket) = client.link.transport.udp_socket() else {
+        return fail(
+            error_codes::SOCKET_OPTION_FAILED,
+            "voice_client_set_socket_buffer_sizes: transport has no UDP socket",
+        );
+    };
+    match set_socket_buffers(socket, send_size, recv_size) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => fail(error_codes::SOCKET_OPTION_FAILED, &format!("Failed to set socket buffer sizes: {}", e)),
+    }
+}
+
+// Проверять MTU пути до сервера при старте клиента
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_mtu_probe(client: *mut c_void, enabled: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_mtu_probe: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_mtu_probe: invalid client handle");
+    };
+    client.link.probe_mtu.store(enabled, Ordering::SeqCst);
+    
+    error_codes::SUCCESS
+}
+
+// Запрашивать у сервера FEC (in-band forward error correction Opus).
+// Применяется при следующем рукопожатии.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_fec_enabled(client: *mut c_void, enabled: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_fec_enabled: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_fec_enabled: invalid client handle");
+    };
+    if enabled {
+        client.features.fetch_or(handshake::features::FEC, Ordering::SeqCst);
+    } else {
+        client.features.fetch_and(!handshake::features::FEC, Ordering::SeqCst);
+    }
+    
+    error_codes::SUCCESS
+}
+
+// Переходит в канал с указанным именем. Если клиент запущен, запрос уходит
+// сразу и повторяется, пока сервер не подтвердит.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_join_channel(client: *mut c_void, name: *const c_char) -> i32 {
+    voice_client_join_channel_with_password(client, name, std::ptr::null())
+}
+
+// То же для канала с паролем. Если канала еще нет, сервер создаст его
+// с этим паролем. password может быть NULL.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_join_channel_with_password(
+    client: *mut c_void,
+    name: *const c_char,
+    password: *const c_char,
+) -> i32 {
+    if client.is_null() || name.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_join_channel: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_join_channel_with_password: invalid client handle");
+    };
+    let name = c_ptr::string(name).unwrap_or_default().to_string_lossy().into_owned();
+    let password = c_ptr::string(password).unwrap_or_default().to_string_lossy().into_owned();
+    if !channels::is_valid_name(&name) || !channels::is_valid_password(&password) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            "voice_client_join_channel_with_password: invalid channel name or password",
+        );
+    }
+    
+    info!(target: NET, "Joining channel '{}'", name);
+    client.channels.set_wanted(Some((name, password)));
+    if client.running.load(Ordering::SeqCst) {
+        if let Some(join) = client.channels.pending_join() {
+            if let Err(e) = client.link.send(&join) {
+                warn!(target: NET, "Channel join send error: {}", e);
+            }
+        }
+    }
+    
+    error_codes::SUCCESS
+}
+
+// Возвращается в канал по умолчанию
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_leave_channel(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_leave_channel: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_leave_channel: invalid client handle");
+    };
+    client.channels.set_wanted(None);
+    client.channels.reset();
+    
+    if client.running.load(Ordering::SeqCst) {
+        if let Err(e) = client.link.send(&channels::leave_packet()) {
+            warn!(target: NET, "Channel leave send error: {}", e);
+        }
+    }
+    
+    error_codes::SUCCESS
+}
+
+// Просит у сервера список каналов; ответ доступен через voice_client_get_channel_list
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_request_channel_list(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_request_channel_list: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_request_channel_list: invalid client handle");
+    };
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, "voice_client_request_channel_list: client is not running");
+    }
+    
+    match client.link.send(&channels::list_request_packet()) {
+        Ok(_) => error_codes::SUCCESS,
+        Err(e) => fail(error_codes::SOCKET_CONNECT_FAILED, &format!("Channel list request error: {}", e)),
+    }
+}
+
+// Текстовое сообщение участнику to_user или всему каналу (to_user = 0),
+// UTF-8 не длиннее text::MAX_TEXT_LEN байт. При прямом звонке сообщение
+// уходит собеседнику, to_user не важен. Входящие приходят событием
+// TEXT_MESSAGE.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_send_text(client: *mut c_void, to_user: u32, text: *const c_char) -> i32 {
+    if client.is_null() || text.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_send_text: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_send_text: invalid client handle");
+    };
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, "voice_client_send_text: client is not running");
+    }
+    let text = match c_ptr::string(text).map(CStr::to_str) {
+        Some(Ok(text)) if text::is_valid_text(text) => text,
+        _ => return report_error(&NsvcError::InvalidParam("text message".to_string())),
+    };
+    
+    let sent = match &client.link.direct {
+        Some(direct) => match direct.peer() {
+            Some(peer) => client.link.transport.send_datagram(&text::text_packet(0, text::FLAG_PRIVATE, text), peer),
+            None => Err(std::io::ErrorKind::NotConnected.into()),
+        },
+        None => client.link.send(&text::text_packet(to_user, 0, text)),
+    };
+    match sent {
+        Ok(_) => error_codes::SUCCESS,
+        Err(e) => report_error(&NsvcError::Send(e)),
+    }
+}
+
+// Последний полученный список каналов: по строке "имя\tучастники" на канал
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_channel_list(client: *mut c_void, buf: *mut c_char, buf_len: usize) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_channel_list: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_channel_list: invalid client handle");
+    };
+    let list: String = client.channels
+        .list()
+        .iter()
+        .map(|(name, users)| format!("{}\t{}\n", name, users))
+        .collect();
+    write_c_string(&list, buf, buf_len)
+}
+
+// Канал, в котором сервер нас подтвердил (пустая строка - канал по умолчанию)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_current_channel(client: *mut c_void, buf: *mut c_char, buf_len: usize) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_current_channel: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_current_channel: invalid client handle");
+    };
+    write_c_string(&client.channels.current().unwrap_or_default(), buf, buf_len)
+}
+
+// Ключ клиента (по нему сервер применяет списки доступа) и пароль сервера.
+// NULL - не передавать. Применяется при следующем подключении.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_credentials(client: *mut c_void, key: *const c_char, password: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_credentials: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_credentials: invalid client handle");
+    };
+    let read = |ptr: *const c_char| c_ptr::string(ptr).unwrap_or_default().to_string_lossy().into_owned();
+    let credentials = handshake::Credentials {
+        key: read(key),
+        password: read(password),
+    };
+    if !credentials.is_valid() {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_credentials: invalid key or password");
+    }
+    
+    *client.credentials.lock().unwrap() = credentials;
+    error_codes::SUCCESS
+}
+
+// Ник, который сервер покажет остальным участникам. Применяется при следующем
+// подключении; без сервера соседи увидят его со следующим IDENTITY.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_nickname(client: *mut c_void, nickname: *const c_char) -> i32 {
+    if client.is_null() || nickname.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_nickname: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_nickname: invalid client handle");
+    };
+    let nickname = c_ptr::string(nickname).unwrap_or_default().to_string_lossy().into_owned();
+    if !users::is_valid_nickname(&nickname) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_nickname: invalid nickname {:?}", nickname),
+        );
+    }
+    
+    *client.nickname.lock().unwrap() = nickname;
+    error_codes::SUCCESS
+}
+
+// ID участника, выданный сервером (0 - еще не выдан или сервер старый)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_user_id(client: *mut c_void, user_id: *mut u32) -> i32 {
+    if client.is_null() || user_id.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_user_id: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_user_id: invalid client handle");
+    };
+    c_ptr::set(user_id, client.roster.own_id());
+    error_codes::SUCCESS
+}
+
+// Участники текущего канала строками "id\tник\tговорит(0/1)\n"
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_users(client: *mut c_void, buf: *mut c_char, buf_len: usize) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_users: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_users: invalid client handle");
+    };
+    let list: String = client.roster
+        .users()
+        .iter()
+        .map(|(id, name, speaking)| format!("{}\t{}\t{}\n", id, name, *speaking as u8))
+        .collect();
+    write_c_string(&list, buf, buf_len)
+}
+
+// Громкость участника: 0.0 - не слышать, 1.0 - как есть, до 2.0
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_user_volume(client: *mut c_void, user_id: u32, volume: f32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_user_volume: client is null!");
+    }
+    if !(0.0..=users::MAX_USER_VOLUME).contains(&volume) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_user_volume: {} is outside 0.0..={}", volume, users::MAX_USER_VOLUME),
+        );
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_user_volume: invalid client handle");
+    };
+    client.roster.set_volume(user_id, volume);
+    error_codes::SUCCESS
+}
+
+// Заглушить участника только у себя, что бы ни разрешал сервер: его пакеты
+// отбрасываются до декодирования, поэтому его нет ни в звуке, ни в записи,
+// ни в индикаторе речи. Действует и на тех, кого еще нет в списке; ник
+// участника из списка попадает в voice_client_get_muted_users.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_user_muted(client: *mut c_void, user_id: u32, muted: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_user_muted: client is null!");
+    }
+
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_user_muted: invalid client handle");
+    };
+    client.roster.set_muted(user_id, muted);
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_user_muted(client: *mut c_void, user_id: u32) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    CLIENTS.get(client).is_some_and(|client| client.roster.is_muted(user_id))
+}
+
+// Заменяет ники заглушенных у себя, по одному в строке, как
+// VoiceClientConfig::muted_users - для клиентов, созданных без VoiceClientConfig
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_muted_users(client: *mut c_void, nicknames: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_muted_users: client is null!");
+    }
+
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_muted_users: invalid client handle");
+    };
+    match config::nickname_list(nicknames) {
+        Ok(names) => {
+            client.roster.set_muted_names(names);
+            error_codes::SUCCESS
+        },
+        Err(e) => fail(error_codes::INVALID_AUDIO_PARAM, &format!("voice_client_set_muted_users: {}", e)),
+    }
+}
+
+// Ники заглушенных у себя строками "ник\n" - для VoiceClientConfig::muted_users
+// или voice_client_set_muted_users следующей сессии
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_muted_users(client: *mut c_void, buf: *mut c_char, buf_len: usize) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_muted_users: client is null!");
+    }
+
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_muted_users: invalid client handle");
+    };
+    let list: String = client.roster.muted_names().iter().map(|name| format!("{}\n", name)).collect();
+    write_c_string(&list, buf, buf_len)
+}
+
+// Микрофон выключен (mute) независимо от режима передачи и voice_client_set_transmitting.
+// Смена состояния - событием MUTE_CHANGED и, если сигналы включены, звуком.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_muted(client: *mut c_void, muted: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_muted: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_muted: invalid client handle");
+    };
+    if client.mute.set_muted(muted) {
+        info!(target: AUDIO, "Muted: {}", muted);
+        client.events.emit(events::event_types::MUTE_CHANGED, 0, muted as i32, "");
+        client.cues.play(cues::Cue::Mute(muted));
+    }
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_muted(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    CLIENTS.get(client).is_some_and(|client| client.mute.is_muted())
+}
+
+// Звук заглушен (deafen): голоса собеседников не воспроизводятся, и
+// микрофон тоже молчит. Прежнее состояние mute не меняется и действует
+// снова после снятия. Смена - событием DEAFEN_CHANGED и звуком.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_deafened(client: *mut c_void, deafened: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_deafened: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_deafened: invalid client handle");
+    };
+    if client.mute.set_deafened(deafened) {
+        client.playback_buffer.lock().unwrap().deafened = deafened;
+        info!(target: AUDIO, "Deafened: {}", deafened);
+        client.events.emit(events::event_types::DEAFEN_CHANGED, 0, deafened as i32, "");
+        client.cues.play(cues::Cue::Deafen(deafened));
+    }
+    error_codes::SUCCESS
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_deafened(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    CLIENTS.get(client).is_some_and(|client| client.mute.is_deafened())
+}
+
+// Запись разговора по пути path: голоса собеседников, а с include_mic - и
+// свой голос, пока он передается. Звуковые сигналы не записываются.
+// Путь на .opus или .ogg - пакеты Opus без перекодирования, по дорожке на
+// собеседника: <путь без расширения>_<ID>_<ник>.opus и _mic.opus для своего
+// голоса. Иначе - общий микс в WAV (48 кГц, моно, 16 бит); дорожками WAV
+// пишет voice_client_start_recording_tracks.
+// Идущая запись сначала завершается. Запись останавливается
+// voice_client_stop_recording или с клиентом.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_recording(client: *mut c_void, path: *const c_char, include_mic: bool) -> i32 {
+    start_recording("voice_client_start_recording", client, path, include_mic, false)
+}
+
+// Запись по дорожкам в любом формате: у каждого собеседника и у своего
+// голоса свой файл <путь без расширения>_<ID>_<ник>.<расширение>, чтобы
+// голоса можно было править по отдельности. Дорожки начинаются вместе с
+// записью и кончаются вместе, паузы в них - тишина. В WAV пакеты
+// декодируются, в .opus и .ogg пишутся как есть.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_recording_tracks(client: *mut c_void, path: *const c_char, include_mic: bool) -> i32 {
+    start_recording("voice_client_start_recording_tracks", client, path, include_mic, true)
+}
+
+fn start_recording(name: &str, client: *mut c_void, path: *const c_char, include_mic: bool, separate: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, &format!("{}: client is null!", name));
+    }
+    if path.is_null() {
+        return fail(error_codes::NULL_POINTER, &format!("{}: path is null!", name));
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, &format!("{}: invalid client handle", name));
+    };
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, &format!("{}: client is not running", name));
+    }
+    let path = match c_ptr::string(path).map(CStr::to_str) {
+        Some(Ok(path)) if !path.is_empty() => path,
+        _ => return fail(error_codes::INVALID_AUDIO_PARAM, &format!("{}: invalid path", name)),
+    };
+    match client.recording.start(Path::new(path), include_mic, separate) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// Дописывает и закрывает файл записи; без записи ничего не делает
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_recording(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_recording: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_recording: invalid client handle");
+    };
+    match client.recording.stop() {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// false и после ошибки записи (например, кончилось место на диске)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_recording(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    CLIENTS.get(client).is_some_and(|client| client.recording.is_active())
+}
+
+// Сохраняет последние clip_seconds (см. VoiceClientConfig и
+// voice_client_set_clip_length) разговора в path; формат - как у voice_client_start_recording, с include_mic - со
+// своим голосом. Работает и после остановки клиента: буфер остается до
+// voice_client_free.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_save_clip(client: *mut c_void, path: *const c_char, include_mic: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_save_clip: client is null!");
+    }
+    if path.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_save_clip: path is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_save_clip: invalid client handle");
+    };
+    let path = match c_ptr::string(path).map(CStr::to_str) {
+        Some(Ok(path)) if !path.is_empty() => path,
+        _ => return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_save_clip: invalid path"),
+    };
+    match client.clip.save(Path::new(path), include_mic) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&e),
+    }
+}
+
+// Защита от забытого открытого микрофона на ходу, минуты (до 1440, см.
+// afk.rs); 0 выключает
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_afk_timeout(client: *mut c_void, minutes: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_afk_timeout: client is null!");
+    }
+    if minutes > config::MAX_AFK_MINUTES {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_afk_timeout: minutes out of range");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_afk_timeout: invalid client handle");
+    };
+    client.afk.set_minutes(minutes);
+    error_codes::SUCCESS
+}
+
+// Длина буфера повтора на ходу, секунды (до 600); 0 выключает буфер и
+// забывает накопленное
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_clip_length(client: *mut c_void, seconds: u32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_clip_length: client is null!");
+    }
+    if seconds > config::MAX_CLIP_SECONDS {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_clip_length: seconds out of range");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_clip_length: invalid client handle");
+    };
+    client.clip.set_length(Duration::from_secs(seconds as u64));
+    error_codes::SUCCESS
+}
+
+// Передает собеседникам звуковой файл (WAV или Ogg/Opus, частота любая)
+// как голос с микрофона: с mix_with_mic - поверх голоса, иначе вместо него.
+// Файл идет и без нажатой клавиши PTT, и при выключенном микрофоне; новый
+// файл прерывает прежний. Файл читается целиком до возврата, не длиннее
+// 10 минут.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_play_file_to_mic(client: *mut c_void, path: *const c_char, mix_with_mic: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_play_file_to_mic: client is null!");
+    }
+    if path.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_play_file_to_mic: path is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_play_file_to_mic: invalid client handle");
+    };
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, "voice_client_play_file_to_mic: client is not running");
+    }
+    let path = match c_ptr::string(path).map(CStr::to_str) {
+        Some(Ok(path)) if !path.is_empty() => path,
+        _ => return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_play_file_to_mic: invalid path"),
+    };
+    match audio_file::load(Path::new(path)) {
+        Ok(samples) => {
+            info!(target: AUDIO, "Playing {} to mic ({:.1} s)", path, samples.len() as f32 / SAMPLE_RATE as f32);
+            client.soundboard.play(samples, mix_with_mic);
+            error_codes::SUCCESS
+        },
+        Err(e) => report_error(&NsvcError::AudioFile(e)),
+    }
+}
+
+// Передает каналу записанное сообщение из файла: кодируется и идет в темпе
+// речи, как голос, но в пакетах MEDIA_CLIP - получатели с features::CLIP
+// получают о нем событие CLIP_STARTED. Клип звучит вместо микрофона, в
+// остальном как voice_client_play_file_to_mic; обрывается
+// voice_client_stop_file_to_mic.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_send_clip(client: *mut c_void, path: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_send_clip: client is null!");
+    }
+    if path.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_send_clip: path is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_send_clip: invalid client handle");
+    };
+    if !client.running.load(Ordering::SeqCst) {
+        return fail(error_codes::NOT_RUNNING, "voice_client_send_clip: client is not running");
+    }
+    let path = match c_ptr::string(path).map(CStr::to_str) {
+        Some(Ok(path)) if !path.is_empty() => path,
+        _ => return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_send_clip: invalid path"),
+    };
+    match audio_file::load(Path::new(path)) {
+        Ok(samples) => {
+            info!(target: AUDIO, "Sending clip {} ({:.1} s)", path, samples.len() as f32 / SAMPLE_RATE as f32);
+            client.soundboard.play_clip(samples);
+            error_codes::SUCCESS
+        },
+        Err(e) => report_error(&NsvcError::AudioFile(e)),
+    }
+}
+
+// Обрывает файл или клип, запущенный voice_client_play_file_to_mic или
+// voice_client_send_clip
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_file_to_mic(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_file_to_mic: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_file_to_mic: invalid client handle");
+    };
+    client.soundboard.stop();
+    error_codes::SUCCESS
+}
+
+// Идет ли еще файл звуковой панели
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_playing_file(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    CLIENTS.get(client).is_some_and(|client| client.soundboard.is_playing())
+}
+
+// Позиционный звук по Mumble Link: положение игрока из игры рассылается
+// каналу, и собеседники, далекие в игре, звучат тише (в одном контексте -
+// та же игра и сервер). Без игры, поддерживающей Mumble Link, громкость не
+// меняется. Только Linux; включенный переживает перезапуск клиента.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_positional_audio(client: *mut c_void, enabled: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_positional_audio: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_positional_audio: invalid client handle");
+    };
+    if !enabled {
+        client.positional.disable();
+        return error_codes::SUCCESS;
+    }
+    if let Err(e) = client.positional.enable() {
+        return report_error(&NsvcError::MumbleLink(e));
+    }
+    info!(target: CLIENT, "Positional audio enabled");
+    if client.running.load(Ordering::SeqCst) {
+        positional::spawn(&client);
+    }
+    error_codes::SUCCESS
+}
+
+// Работает ли позиционный звук: игра сообщает положение через Mumble Link
+// или задала его voice_client_set_listener_position
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_is_positional_audio_active(client: *mut c_void) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    CLIENTS.get(client).is_some_and(|client| client.positional.is_active())
+}
+
+// Положение слушателя (камеры игрока) для позиционного звука без Mumble
+// Link: игра сама сообщает, где игрок и где остальные, в любых единицах, в
+// которых задано затухание (voice_client_set_rolloff). Собеседник без
+// положения звучит с полной громкостью. С включенным Mumble Link положение
+// слушателя приходит из него.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_listener_position(client: *mut c_void, x: f32, y: f32, z: f32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_listener_position: client is null!");
+    }
+    if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_listener_position: invalid position");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_listener_position: invalid client handle");
+    };
+    client.positional.set_listener([x, y, z]);
+    error_codes::SUCCESS
+}
+
+// Положение участника user_id (ID из событий и voice_client_get_users)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_user_position(client: *mut c_void, user_id: u32, x: f32, y: f32, z: f32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_user_position: client is null!");
+    }
+    if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_user_position: invalid position");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_user_position: invalid client handle");
+    };
+    client.positional.set_remote(user_id, [x, y, z]);
+    error_codes::SUCCESS
+}
+
+// Забывает все положения: собеседники снова звучат с полной громкостью
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_clear_positions(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_clear_positions: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_clear_positions: invalid client handle");
+    };
+    client.positional.clear();
+    error_codes::SUCCESS
+}
+
+// Затухание с расстоянием: модель из rolloff_models, ближе min_distance -
+// полная громкость, дальше max_distance - тишина. По умолчанию LINEAR,
+// 2 и 50 (метры Mumble Link).
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_rolloff(client: *mut c_void, model: i32, min_distance: f32, max_distance: f32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_rolloff: client is null!");
+    }
+    let rolloff = positional::Rolloff { model, min_distance, max_distance };
+    if !rolloff.is_valid() {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_rolloff: invalid model or distances");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_rolloff: invalid client handle");
+    };
+    client.positional.set_rolloff(rolloff);
+    error_codes::SUCCESS
+}
+
+// Открывает Unix-сокет path (NULL - $XDG_RUNTIME_DIR/nsvc-overlay.sock), в
+// котором оверлеи получают, кто в канале и кто говорит: JSON по строке на
+// событие (см. overlay.rs). Работает и до voice_client_start; сокет
+// закрывается voice_client_stop_overlay_socket или при освобождении клиента.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_overlay_socket(client: *mut c_void, path: *const c_char) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_overlay_socket: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_overlay_socket: invalid client handle");
+    };
+    let path = if path.is_null() {
+        overlay::default_path()
+    } else {
+        match c_ptr::string(path).map(CStr::to_str) {
+            Some(Ok(path)) if !path.is_empty() => PathBuf::from(path),
+            _ => return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_start_overlay_socket: invalid path"),
+        }
+    };
+    match client.overlay.start(path, client.roster.clone()) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&NsvcError::SocketBind(e)),
+    }
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_overlay_socket(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_overlay_socket: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_overlay_socket: invalid client handle");
+    };
+    client.overlay.stop();
+    error_codes::SUCCESS
+}
+
+// Громкость всего воспроизведения, поверх громкости участников: 0.0 - тишина,
+// 1.0 - как есть, до MAX_OUTPUT_VOLUME
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_output_volume(client: *mut c_void, volume: f32) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_output_volume: client is null!");
+    }
+    if !(0.0..=MAX_OUTPUT_VOLUME).contains(&volume) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_output_volume: {} is outside 0.0..={}", volume, MAX_OUTPUT_VOLUME),
+        );
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_output_volume: invalid client handle");
+    };
+    client.playback_buffer.lock().unwrap().volume = volume;
+    error_codes::SUCCESS
+}
+
+// Текст кода ошибки из error_codes. Строка статическая, освобождать не нужно.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_error_string(code: i32) -> *const c_char {
+    error_codes::description(code).as_ptr()
+}
+
+// Версия библиотеки для отчетов об ошибках: версия crate, коммит, версии
+// протокола и возможности, которые клиент умеет запрашивать. Строка
+// статическая, освобождать не нужно.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_version() -> *const c_char {
+    static VERSION: OnceLock<CString> = OnceLock::new();
+    VERSION
+        .get_or_init(|| {
+            let feature_names = [
+                (handshake::features::DTX, "dtx"),
+                (handshake::features::FEC, "fec"),
+                (handshake::features::ENCRYPTION, "encryption"),
+                (handshake::features::SEQUENCE, "sequence"),
+                (handshake::features::SESSION_TOKEN, "session_token"),
+                (handshake::features::CLIP, "clip"),
+                (handshake::features::WHISPER, "whisper"),
+            ];
+            let features: Vec<&str> = feature_names
+                .iter()
+                .filter(|(flag, _)| config::SUPPORTED_FEATURES & flag != 0)
+                .map(|(_, name)| *name)
+                .collect();
+            let version = format!(
+                "NSVC {} ({}, {}-{}), protocol {}-{}, features: {}",
+                env!("CARGO_PKG_VERSION"),
+                env!("NSVC_GIT_HASH"),
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION,
+                features.join(" "),
+            );
+            CString::new(version).unwrap_or_default()
+        })
+        .as_ptr()
+}
+
+// Подробности последней ошибки FFI-вызова в вызывающем потоке. Пустая
+// строка, если ошибок еще не было. Асинхронные ошибки приходят только в
+// callback ошибок и событие ERROR.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_last_error_message(buf: *mut c_char, buf_len: usize) -> i32 {
+    let message = LAST_ERROR.with(|last| last.borrow().as_ref().map(|(_, message)| message.clone()));
+    write_c_string(&message.unwrap_or_default(), buf, buf_len)
+}
+
+// Перенаправляет журнал всех клиентов в callback хоста вместо stderr и
+// файла журнала; сообщения ниже level отбрасываются. NULL возвращает
+// запись в файл.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_log_callback(callback: Option<LogCallback>, userdata: *mut c_void, level: i32) -> i32 {
+    if !(log_levels::TRACE..=log_levels::ERROR).contains(&level) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_log_callback: invalid log level {}", level),
+        );
+    }
+    
+    *logging::LOG_CALLBACK.lock().unwrap() = callback.map(|func| (func, userdata as usize, level));
+    error_codes::SUCCESS
+}
+
+// Минимальный уровень журнала из log_levels для всех клиентов: ниже него
+// сообщения не попадают ни в callback, ни в stderr и файл. Сведения о
+// каждом пакете пишутся с уровнем TRACE и по умолчанию отброшены.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_log_level(level: i32) -> i32 {
+    if !(log_levels::TRACE..=log_levels::ERROR).contains(&level) {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_log_level: invalid log level {}", level),
+        );
+    }
+    
+    logging::LOG_LEVEL.store(level, Ordering::Relaxed);
+    error_codes::SUCCESS
+}
+
+// Файл журнала, пока не задан callback (по умолчанию voice_client.log в
+// рабочем каталоге). Файл дописывается до предела ротации (см.
+// voice_client_set_log_rotation); NULL отключает запись в файл.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_log_file(path: *const c_char) -> i32 {
+    if path.is_null() {
+        *logging::LOG_FILE.lock().unwrap() = None;
+        return error_codes::SUCCESS;
+    }
+    if cfg!(not(feature = "file-log")) {
+        return fail(error_codes::LOG_FILE_FAILED, "voice_client_set_log_file: built without the file-log feature");
+    }
+    let path = match c_ptr::string(path).map(CStr::to_str) {
+        Some(Ok(path)) if !path.is_empty() => path.to_string(),
+        _ => return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_set_log_file: invalid path"),
+    };
+    
+    // Проверяем сразу, чтобы хост узнал об ошибке, а не терял журнал молча
+    if let Err(e) = std::fs::OpenOptions::new().append(true).create(true).open(&path) {
+        return fail(error_codes::LOG_FILE_FAILED, &format!("Failed to open log file {}: {}", path, e));
+    }
+    *logging::LOG_FILE.lock().unwrap() = Some(Cow::Owned(path));
+    error_codes::SUCCESS
+}
+
+// Ротация файла журнала: файл, доросший до max_bytes, становится <путь>.1,
+// прежние сдвигаются до <путь>.<keep_files>, более старые удаляются.
+// max_bytes 0 отключает ротацию; keep_files 0 - старый файл просто удаляется.
+// По умолчанию 10 МБ и 3 файла.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_log_rotation(max_bytes: u64, keep_files: u32) -> i32 {
+    if keep_files > logging::MAX_LOG_KEEP_FILES {
+        return fail(
+            error_codes::INVALID_AUDIO_PARAM,
+            &format!("voice_client_set_log_rotation: keep_files must be at most {}", logging::MAX_LOG_KEEP_FILES),
+        );
+    }
+    
+    logging::LOG_MAX_BYTES.store(max_bytes, Ordering::Relaxed);
+    logging::LOG_KEEP_FILES.store(keep_files, Ordering::Relaxed);
+    error_codes::SUCCESS
+}
+
+// Callback событий клиента (см. events::event_types); NULL отключает
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_event_callback(
+    client: *mut c_void,
+    callback: Option<events::EventCallback>,
+    userdata: *mut c_void,
+) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_event_callback: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_event_callback: invalid client handle");
+    };
+    *client.events.callback.lock().unwrap() = callback.map(|func| (func, userdata as usize));
+    
+    error_codes::SUCCESS
+}
+
+// Режим очереди событий: вместо вызова callback из потоков клиента события
+// копятся, пока хост не вызовет voice_client_poll_events в своем потоке
+// (например, раз за кадр). Callback состояния, ошибок и журнала это не
+// меняет; их события CONNECTED, DISCONNECTED и ERROR приходят и в очередь.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_set_event_queue(client: *mut c_void, enabled: bool) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_event_queue: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_set_event_queue: invalid client handle");
+    };
+    client.events.set_queued(enabled);
+    error_codes::SUCCESS
+}
+
+// Вызывает callback событий для накопленных событий в потоке вызывающего.
+// Возвращает их число или отрицательный код ошибки.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_poll_events(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_poll_events: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_poll_events: invalid client handle");
+    };
+    client.events.poll() as i32
+}
+
+// Статистика сессии одним снимком (см. stats::VoiceClientStats)
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_get_stats(client: *mut c_void, out: *mut stats::VoiceClientStats) -> i32 {
+    if client.is_null() || out.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_stats: null argument!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_get_stats: invalid client handle");
+    };
+    c_ptr::set(out, stats_snapshot(&client));
+    error_codes::SUCCESS
+}
+
+fn stats_snapshot(client: &VoiceClient) -> stats::VoiceClientStats {
+    let mut snapshot = client.stats.snapshot();
+    snapshot.rtt_ms = client.connection.rtt().map_or(0, |rtt| rtt.as_millis().max(1) as u32);
+    snapshot.bitrate = client.bitrate.load(Ordering::Relaxed);
+    snapshot.buffer_ms = buffered_ms(client);
+    client.link.traffic.fill(&mut snapshot);
+    snapshot
+}
+
+// Метрики клиента для Prometheus на http://127.0.0.1:port/metrics (см.
+// metrics.rs). Только в сборке с feature "metrics", иначе
+// SOCKET_BIND_FAILED. Работает и до voice_client_start; порт закрывается
+// voice_client_stop_metrics или при освобождении клиента.
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_start_metrics(client: *mut c_void, port: u16) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_metrics: client is null!");
+    }
+    if port == 0 {
+        return fail(error_codes::INVALID_AUDIO_PARAM, "voice_client_start_metrics: port must not be 0");
+    }
+    
+    let Some(instance) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_start_metrics: invalid client handle");
+    };
+    // По описателю, а не по ссылке: клиент владеет сервером метрик
+    let handle = client as usize;
+    let scrape = Box::new(move || {
+        let client = CLIENTS.get(handle as *mut c_void)?;
+        Some(metrics::render(&stats_snapshot(&client), client.connection.state()))
+    });
+    match instance.metrics.start(port, scrape) {
+        Ok(()) => error_codes::SUCCESS,
+        Err(e) => report_error(&NsvcError::SocketBind(e)),
+    }
+}
+
+#[cfg_attr(feature = "ffi", no_mangle)]
+pub extern "C" fn voice_client_stop_metrics(client: *mut c_void) -> i32 {
+    if client.is_null() {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_metrics: client is null!");
+    }
+    
+    let Some(client) = CLIENTS.get(client) else {
+        return fail(error_codes::NULL_POINTER, "voice_client_stop_metrics: invalid client handle");
+    };
+    client.metrics.stop();
+    error_codes::SUCCESS
+}
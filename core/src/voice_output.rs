@@ -0,0 +1,78 @@
+// Отдельный вывод голосов собеседников - для OBS и других программ
+// трансляции. Голоса идут еще и на второе устройство, обычно виртуальное
+// (null-sink PulseAudio или PipeWire, VB-Cable на Windows, BlackHole на
+// macOS), без сигналов, проверочного тона и звуковой панели и до общей
+// громкости. Программа трансляции захватывает это устройство отдельным
+// источником и сводит или приглушает собеседников независимо от звука
+// игры. Создает устройство система, клиент только выбирает его по имени.
+// Заглушенный (deafen) клиент не выводит голоса и сюда.
+use std::sync::Mutex;
+
+use rtrb::Producer;
+use tracing::info;
+
+use crate::audio::AudioStream;
+use crate::error::NsvcError;
+use crate::logging::AUDIO;
+use crate::{events, realtime, VoiceClient, SAMPLE_RATE};
+
+// Запас на разницу часов двух устройств: 100 мс, лишнее выбрасывается
+const RING_SAMPLES: usize = SAMPLE_RATE as usize / 10;
+
+#[derive(Default)]
+pub(crate) struct VoiceOutput {
+    // Имя устройства; None - вывод выключен
+    device: Mutex<Option<String>>,
+    // Кольцо к callback'у устройства; None - поток не открыт
+    producer: Mutex<Option<Producer<f32>>>,
+    stream: Mutex<Option<AudioStream>>,
+}
+
+impl VoiceOutput {
+    // Откроется с запуском клиента или сразу через open
+    pub fn set_device(&self, device: Option<String>) {
+        *self.device.lock().unwrap() = device;
+    }
+
+    // Голоса из потока микшера; устройство не успевает - лишнее выбрасываем
+    pub fn on_playback(&self, samples: &[f32]) {
+        let mut producer = self.producer.lock().unwrap();
+        let Some(producer) = producer.as_mut() else {
+            return;
+        };
+        let count = samples.len().min(producer.slots());
+        if let Ok(chunk) = producer.write_chunk_uninit(count) {
+            chunk.fill_from_iter(samples.iter().copied());
+        }
+    }
+
+    pub fn close(&self) {
+        *self.stream.lock().unwrap() = None;
+        *self.producer.lock().unwrap() = None;
+    }
+}
+
+// Открывает устройство из set_device заново; без устройства только закрывает
+pub(crate) fn open(client: &VoiceClient) -> Result<(), NsvcError> {
+    let output = &client.voice_output;
+    let mut stream = output.stream.lock().unwrap();
+    *stream = None;
+    *output.producer.lock().unwrap() = None;
+    let Some(device) = output.device.lock().unwrap().clone() else {
+        return Ok(());
+    };
+
+    let (producer, mut playback) = realtime::playback_ring(RING_SAMPLES);
+    let on_data = Box::new(move |data: &mut [f32]| playback.read(data));
+    let events_out = client.events.clone();
+    let on_lost = Box::new(move |reason: String| {
+        events_out.emit(events::event_types::DEVICE_CHANGED, 0, events::DEVICE_VOICE_OUTPUT, &reason);
+    });
+    let (new_stream, name) = client.audio.open_output(Some(&device), on_data, on_lost)?;
+    *output.producer.lock().unwrap() = Some(producer);
+    *stream = Some(new_stream);
+
+    info!(target: AUDIO, "Voice output on {:?}", name);
+    client.events.emit(events::event_types::DEVICE_CHANGED, 0, events::DEVICE_VOICE_OUTPUT, &name);
+    Ok(())
+}
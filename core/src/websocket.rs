@@ -0,0 +1,143 @@
+// WebSocket по RFC 6455 со стороны сервера: ответ на HTTP Upgrade и кадры.
+// Общий для сервера (браузерные клиенты, server/src/websocket.rs) и
+// терминального клиента (события для страниц, cli/src/http.rs); чтение
+// HTTP-запроса у каждого свое. Расширения и дробление сообщений не
+// поддерживаются: кадр клиента читается целиком, свои шлются одним кадром.
+use std::io::{self, Read, Write};
+
+use sha1::{Digest, Sha1};
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub mod opcodes {
+    pub const CONTINUATION: u8 = 0x0;
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xA;
+}
+
+// Коды закрытия
+pub mod close_codes {
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const UNSUPPORTED: u16 = 1003;
+    pub const TOO_BIG: u16 = 1009;
+}
+
+pub struct Frame {
+    // Последний фрагмент сообщения
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(opcode: u8, payload: Vec<u8>) -> Self {
+        Frame { fin: true, opcode, payload }
+    }
+
+    pub fn close(code: u16) -> Self {
+        Frame::new(opcodes::CLOSE, code.to_be_bytes().to_vec())
+    }
+}
+
+// Ключ клиента из заголовков запроса Upgrade (имена в нижнем регистре);
+// None - это не запрос WebSocket версии 13
+pub fn upgrade_key(headers: &[(String, String)]) -> Option<&str> {
+    let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    let is_upgrade = header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    header("sec-websocket-key").filter(|_| is_upgrade && header("sec-websocket-version") == Some("13"))
+}
+
+// Ответ на Upgrade: 101 с ключом или 426, если key - None
+pub fn respond(stream: &mut impl Write, key: Option<&str>) -> io::Result<()> {
+    match key {
+        Some(key) => write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        ),
+        None => write!(
+            stream,
+            "HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+    }
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&Sha1::digest(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+// Кадр клиента. InvalidData - данные длиннее max_len, InvalidInput -
+// незамаскированный кадр.
+pub fn read_frame(stream: &mut impl Read, max_len: usize) -> io::Result<Frame> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0F;
+    if header[1] & 0x80 == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "unmasked client frame"));
+    }
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        },
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        },
+        len => len as u64,
+    };
+    if len > max_len as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes", len)));
+    }
+
+    let mut mask = [0u8; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Frame { fin, opcode, payload })
+}
+
+// Кадры сервера не маскируются
+pub fn write_frame(stream: &mut impl Write, frame: &Frame) -> io::Result<()> {
+    let mut data = Vec::with_capacity(frame.payload.len() + 10);
+    data.push(if frame.fin { 0x80 } else { 0 } | frame.opcode);
+    match frame.payload.len() {
+        len if len < 126 => data.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            data.push(126);
+            data.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        len => {
+            data.push(127);
+            data.extend_from_slice(&(len as u64).to_be_bytes());
+        },
+    }
+    data.extend_from_slice(&frame.payload);
+    stream.write_all(&data)
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,82 @@
+// Шепот: пока хост держит клавишу шепота (voice_client_whisper_key),
+// голос слышат только выбранные участники (voice_client_set_whisper_targets),
+// а не весь канал. Клавиша включает микрофон в любом режиме передачи, как
+// PTT, и так же отпускается с задержкой. Адресатов выбирает сервер по
+// MEDIA_WHISPER, поэтому шепот идет только через сервер с features::WHISPER;
+// без него кадры шепота не отправляются вовсе - иначе их услышал бы весь канал.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::MAX_WHISPER_TARGETS;
+
+// Кадры, захваченные до отпускания, кодер получает чуть позже: столько
+// после конца задержки они еще уходят шепотом, а не всему каналу
+const ENCODER_TAIL: Duration = Duration::from_millis(100);
+
+pub(crate) struct Whisper {
+    targets: Mutex<Vec<u32>>,
+    pressed: AtomicBool,
+    delay: Duration,
+    epoch: Instant,
+    // Когда клавишу отпустили (мкс от epoch); 0 - не отпускали
+    released_us: AtomicU64,
+}
+
+impl Whisper {
+    pub fn new(delay: Duration) -> Self {
+        Whisper {
+            targets: Mutex::new(Vec::new()),
+            pressed: AtomicBool::new(false),
+            delay,
+            epoch: Instant::now(),
+            released_us: AtomicU64::new(0),
+        }
+    }
+
+    // Лишние сверх MAX_WHISPER_TARGETS отбрасываются
+    pub fn set_targets(&self, targets: &[u32]) {
+        let targets = &targets[..targets.len().min(MAX_WHISPER_TARGETS)];
+        *self.targets.lock().unwrap() = targets.to_vec();
+    }
+
+    pub fn targets(&self) -> MutexGuard<'_, Vec<u32>> {
+        self.targets.lock().unwrap()
+    }
+
+    // Клавиша нажата или отпущена; возвращает, была ли она нажата
+    pub fn set_pressed(&self, pressed: bool) -> bool {
+        let was = self.pressed.swap(pressed, Ordering::SeqCst);
+        if was && !pressed {
+            self.released_us.store(self.now_us().max(1), Ordering::SeqCst);
+        }
+        was
+    }
+
+    fn now_us(&self) -> u64 {
+        self.epoch.elapsed().as_micros() as u64
+    }
+
+    fn within(&self, after_release: Duration) -> bool {
+        if self.pressed.load(Ordering::SeqCst) {
+            return true;
+        }
+        let released = self.released_us.load(Ordering::SeqCst);
+        released != 0 && self.now_us() < released + after_release.as_micros() as u64
+    }
+
+    // Держит ли шепот микрофон открытым: клавиша нажата или задержка не кончилась
+    pub fn is_holding(&self) -> bool {
+        self.within(self.delay)
+    }
+
+    // Идут ли кадры кодера шепотом; дольше is_holding на ENCODER_TAIL
+    pub fn is_sending(&self) -> bool {
+        self.within(self.delay + ENCODER_TAIL)
+    }
+
+    // Сколько байт шепот добавляет к пакету MEDIA
+    pub fn overhead(&self) -> usize {
+        1 + 4 * self.targets().len()
+    }
+}
@@ -0,0 +1,86 @@
+// Путь кодирование -> сеть -> декодирование без звуковой карты: два
+// клиента прямого звонка на 127.0.0.1 с MockAudio говорят друг другу тоном,
+// и у каждого должен прозвучать тон собеседника примерно той же длины.
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nsvc_core::audio::MockAudio;
+use nsvc_core::client::{Client, ClientBuilder, TransmitMode};
+use nsvc_core::SAMPLE_RATE;
+
+// Длины разные, чтобы звук не засчитался не той стороне
+const CALLER_SECONDS: f32 = 1.0;
+const CALLEE_SECONDS: f32 = 0.5;
+// Тише этого - тишина: тон идет с амплитудой 0.5, Opus его не обнуляет
+const SILENCE_LEVEL: f32 = 0.05;
+// Порты двух сторон; на CI-машине их никто не занимает
+const CALLER_PORT: u16 = 47611;
+const CALLEE_PORT: u16 = 47612;
+
+fn tone(seconds: f32) -> Vec<f32> {
+    let samples = (SAMPLE_RATE as f32 * seconds) as usize;
+    (0..samples).map(|i| 0.5 * (2.0 * PI * 440.0 * i as f32 / SAMPLE_RATE as f32).sin()).collect()
+}
+
+// Сколько отсчетов громче SILENCE_LEVEL, по кускам в 10 мс: так считается
+// длина звука, а не число пиков синусоиды
+fn audible_samples(samples: &[f32]) -> usize {
+    samples
+        .chunks(SAMPLE_RATE as usize / 100)
+        .filter(|chunk| chunk.iter().any(|s| s.abs() > SILENCE_LEVEL))
+        .map(<[f32]>::len)
+        .sum()
+}
+
+// Клиент прямого звонка с MockAudio без звука: тон подается, когда обе
+// стороны уже слушают, иначе первые пакеты уйдут в закрытый порт
+fn call(peer_port: u16, local_port: u16, audio: &Arc<MockAudio>) -> Client {
+    let client = ClientBuilder::call("127.0.0.1", peer_port, local_port)
+        .audio_backend(audio.clone())
+        .transmit_mode(TransmitMode::Continuous)
+        // Сигналы тоже громче порога тишины
+        .cues(false)
+        .transmit_cues(false)
+        .notification_cues(false)
+        .build()
+        .expect("build");
+    client.start().expect("start");
+    client
+}
+
+fn assert_heard(audio: &MockAudio, seconds: f32) {
+    let expected = (SAMPLE_RATE as f32 * seconds) as usize;
+    let heard = audible_samples(&audio.played());
+    assert!(
+        heard > expected * 8 / 10 && heard < expected * 13 / 10,
+        "heard {} audible samples, expected about {}",
+        heard,
+        expected
+    );
+}
+
+#[test]
+fn tone_reaches_the_other_side() {
+    let caller_audio = Arc::new(MockAudio::new(Vec::new()));
+    let callee_audio = Arc::new(MockAudio::new(Vec::new()));
+    let callee = call(CALLER_PORT, CALLEE_PORT, &callee_audio);
+    let caller = call(CALLEE_PORT, CALLER_PORT, &caller_audio);
+
+    thread::sleep(Duration::from_millis(200));
+    caller_audio.feed(&tone(CALLER_SECONDS));
+    callee_audio.feed(&tone(CALLEE_SECONDS));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !caller_audio.input_finished() || !callee_audio.input_finished() {
+        assert!(Instant::now() < deadline, "capture never finished");
+        thread::sleep(Duration::from_millis(10));
+    }
+    // Хвост: буфер воспроизведения и последние пакеты
+    thread::sleep(Duration::from_millis(700));
+    caller.stop();
+    callee.stop();
+
+    assert_heard(&callee_audio, CALLER_SECONDS);
+    assert_heard(&caller_audio, CALLEE_SECONDS);
+}
@@ -0,0 +1,137 @@
+// Генерация include/nsvc.h из C API ядра. Функции, структуры и
+// типы callback описывает cbindgen; группы констант (коды ошибок, состояния,
+// уровни журнала, типы событий, флаги возможностей, проверочные сигналы, модели затухания) выписываем сами: в C у них нет модулей, и
+// одинаковые имена из разных групп (CONNECTED, ERROR) затерли бы друг друга.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HEADER_PATH: &str = "../include/nsvc.h";
+const CORE_SOURCES: &str = "../core/src";
+
+// Файл ядра, модуль (None - верхний уровень файла) и префикс имен в C
+const CONSTANT_GROUPS: &[(&str, Option<&str>, &str)] = &[
+    ("voice_chat.rs", Some("error_codes"), "NSVC_"),
+    ("voice_chat.rs", Some("connection_states"), "NSVC_STATE_"),
+    ("voice_chat.rs", Some("transmit_modes"), "NSVC_TRANSMIT_"),
+    ("voice_chat.rs", Some("log_levels"), "NSVC_LOG_"),
+    ("voice_chat.rs", Some("test_tones"), "NSVC_TONE_"),
+    ("voice_chat.rs", Some("rolloff_models"), "NSVC_ROLLOFF_"),
+    ("voice_chat.rs", Some("notification_sounds"), "NSVC_NOTIFICATION_"),
+    ("events.rs", Some("event_types"), "NSVC_EVENT_"),
+    ("events.rs", None, "NSVC_"),
+    ("handshake.rs", Some("features"), "NSVC_FEATURE_"),
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed={}", CORE_SOURCES);
+
+    // Заголовок - удобство для встраивающих, сборку библиотеки он не ломает
+    if let Err(e) = generate() {
+        println!("cargo:warning=Failed to generate {}: {}", HEADER_PATH, e);
+    }
+}
+
+fn generate() -> Result<(), String> {
+    let mut defines = String::new();
+    for (file, module, prefix) in CONSTANT_GROUPS {
+        let source = fs::read_to_string(Path::new(CORE_SOURCES).join(file)).map_err(|e| format!("{}: {}", file, e))?;
+        for (name, value) in read_constants(&source, *module) {
+            // Выражения вроде 1 << 2 - в скобках, как принято в C
+            let value = if value.contains(' ') { format!("({})", value) } else { value };
+            defines.push_str(&format!("#define {}{} {}\n", prefix, name, value));
+        }
+        defines.push('\n');
+    }
+
+    let config = cbindgen::Config::from_file("cbindgen.toml").map_err(|e| e.to_string())?;
+    let sources = exported_sources().map_err(|e| e.to_string())?;
+    let bindings = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(sources.join("voice_chat.rs"))
+        .with_after_include(format!("\n{}", defines.trim_end()))
+        .generate()
+        .map_err(|e| e.to_string())?;
+    let mut output = Vec::new();
+    bindings.write(&mut output);
+    let header = unwrap_optional_callbacks(&String::from_utf8_lossy(&output));
+
+    // Не трогаем файл без изменений, чтобы не пересобирать зависимые C-проекты
+    if fs::read_to_string(HEADER_PATH).ok().as_deref() != Some(header.as_str()) {
+        if let Some(dir) = Path::new(HEADER_PATH).parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(HEADER_PATH, header).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Экспорт в исходниках включается feature "ffi" через cfg_attr, а cbindgen
+// видит только #[no_mangle]. Поэтому он читает копию src в OUT_DIR, где
+// атрибут записан прямо.
+fn exported_sources() -> std::io::Result<PathBuf> {
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").ok_or(std::io::ErrorKind::NotFound)?).join("header-src");
+    copy_sources(Path::new(CORE_SOURCES), &out_dir)?;
+    Ok(out_dir)
+}
+
+fn copy_sources(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        let target = to.join(path.file_name().unwrap_or_default());
+        if path.is_dir() {
+            copy_sources(&path, &target)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            let source = fs::read_to_string(&path)?;
+            fs::write(target, source.replace("#[cfg_attr(feature = \"ffi\", no_mangle)]", "#[no_mangle]"))?;
+        }
+    }
+    Ok(())
+}
+
+// Публичные целочисленные константы модуля: строки вида
+// `pub const NAME: i32 = VALUE;` (или u32) до закрывающей скобки модуля
+fn read_constants(source: &str, module: Option<&str>) -> Vec<(String, String)> {
+    let mut inside = module.is_none();
+    let mut constants = Vec::new();
+    for line in source.lines() {
+        match module {
+            Some(module) if line.starts_with(&format!("pub mod {} {{", module)) => {
+                inside = true;
+                continue;
+            },
+            Some(_) if inside && line.starts_with('}') => break,
+            // На верхнем уровне файла вложенные модули не смотрим
+            None if line.starts_with("pub mod ") => inside = false,
+            None if line.starts_with('}') => inside = true,
+            _ => {},
+        }
+        if !inside {
+            continue;
+        }
+        let constant = line
+            .trim()
+            .strip_prefix("pub const ")
+            .and_then(|rest| rest.split_once(": i32 = ").or_else(|| rest.split_once(": u32 = ")))
+            .and_then(|(name, value)| Some((name, value.strip_suffix(';')?)));
+        if let Some((name, value)) = constant {
+            constants.push((name.to_string(), value.to_string()));
+        }
+    }
+    constants
+}
+
+// cbindgen не раскрывает Option<псевдоним указателя на функцию> и оставляет
+// его в сигнатурах как есть; в C это тот же указатель, где NULL - отсутствие
+// callback
+fn unwrap_optional_callbacks(header: &str) -> String {
+    let mut result = header.to_string();
+    while let Some(start) = result.find("Option<") {
+        let Some(end) = result[start..].find('>') else { break };
+        let name = result[start + "Option<".len()..start + end].to_string();
+        result.replace_range(start..=start + end, &name);
+    }
+    result
+}
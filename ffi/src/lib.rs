@@ -0,0 +1,4 @@
+// Библиотека для C, C++, Android и всех, кто грузит ее динамически. Функции
+// voice_client_* и JNI экспортирует ядро с feature "ffi"; здесь ядро только
+// подключается, чтобы его символы попали в cdylib.
+pub use nsvc_core::*;
@@ -0,0 +1,20 @@
+// Собранный MEDIA разбирается в те же SSRC, номер и Opus
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../core/src/protocol.rs"]
+mod protocol;
+
+use protocol::*;
+
+fuzz_target!(|input: (u32, u32, &[u8])| {
+    let (ssrc, seq, opus) = input;
+    let packet = media_packet(ssrc, seq, opus);
+    assert_eq!(parse_control_packet(&packet).map(|(_, kind, _)| kind), Some(control_types::MEDIA));
+
+    let (parsed_ssrc, parsed_seq, range) = parse_media_packet(&packet).expect("own MEDIA packet must parse");
+    assert_eq!((parsed_ssrc, parsed_seq), (ssrc, seq));
+    assert_eq!(&packet[range], opus);
+});
@@ -0,0 +1,41 @@
+// Любая датаграмма из сети: разбор не паникует, а разобранный пакет
+// собирается обратно в те же байты
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../core/src/protocol.rs"]
+mod protocol;
+
+use protocol::*;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((version, kind, body)) = parse_control_packet(data) else {
+        return;
+    };
+    if version == PROTOCOL_VERSION {
+        assert_eq!(control_packet(kind, body), data);
+    }
+
+    if kind == control_types::MEDIA {
+        if let Some((ssrc, seq, opus)) = parse_media_packet(data) {
+            assert!(opus.start <= opus.end && opus.end == data.len());
+            if version == PROTOCOL_VERSION {
+                assert_eq!(media_packet(ssrc, seq, &data[opus]), data);
+            }
+        }
+    }
+
+    if kind == control_types::MEDIA_WHISPER {
+        if let Some((ssrc, seq, targets, opus)) = parse_whisper_packet(data) {
+            assert!(targets.len() <= MAX_WHISPER_TARGETS);
+            assert!(opus.start <= opus.end && opus.end == data.len());
+            if version == PROTOCOL_VERSION {
+                let mut packet = Vec::new();
+                write_whisper_packet(&mut packet, ssrc, seq, &targets, &data[opus]);
+                assert_eq!(packet, data);
+            }
+        }
+    }
+});
@@ -0,0 +1,377 @@
+/* Generated from the NSVC sources by build.rs; do not edit by hand. */
+
+#ifndef NSVC_H
+#define NSVC_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define NSVC_SUCCESS 0
+#define NSVC_NULL_POINTER -1
+#define NSVC_INVALID_IP -2
+#define NSVC_SOCKET_BIND_FAILED -3
+#define NSVC_INVALID_SERVER_ADDR -4
+#define NSVC_SOCKET_CONNECT_FAILED -5
+#define NSVC_NO_INPUT_DEVICE -6
+#define NSVC_NO_OUTPUT_DEVICE -7
+#define NSVC_ENCODER_INIT_FAILED -8
+#define NSVC_INPUT_STREAM_FAILED -9
+#define NSVC_OUTPUT_STREAM_FAILED -10
+#define NSVC_INVALID_AUDIO_PARAM -11
+#define NSVC_NOT_RUNNING -12
+#define NSVC_UNSUPPORTED_SAMPLE_FORMAT -13
+#define NSVC_STUN_FAILED -14
+#define NSVC_BUFFER_TOO_SMALL -15
+#define NSVC_PROTOCOL_MISMATCH -16
+#define NSVC_SOCKET_OPTION_FAILED -17
+#define NSVC_ACCESS_DENIED -18
+#define NSVC_LOG_FILE_FAILED -19
+#define NSVC_RECORDING_FAILED -20
+#define NSVC_AUDIO_FILE_FAILED -21
+#define NSVC_MUMBLE_LINK_FAILED -22
+
+#define NSVC_STATE_DISCONNECTED 0
+#define NSVC_STATE_CONNECTING 1
+#define NSVC_STATE_CONNECTED 2
+#define NSVC_STATE_RECONNECTING 3
+#define NSVC_STATE_FAILED 4
+
+#define NSVC_TRANSMIT_PTT 0
+#define NSVC_TRANSMIT_VOICE_ACTIVATION 1
+#define NSVC_TRANSMIT_CONTINUOUS 2
+#define NSVC_TRANSMIT_TOGGLE 3
+
+#define NSVC_LOG_TRACE -1
+#define NSVC_LOG_DEBUG 0
+#define NSVC_LOG_INFO 1
+#define NSVC_LOG_WARNING 2
+#define NSVC_LOG_ERROR 3
+
+#define NSVC_TONE_STEADY 0
+#define NSVC_TONE_SWEEP 1
+
+#define NSVC_ROLLOFF_LINEAR 0
+#define NSVC_ROLLOFF_INVERSE 1
+
+#define NSVC_NOTIFICATION_USER_JOINED 0
+#define NSVC_NOTIFICATION_USER_LEFT 1
+#define NSVC_NOTIFICATION_CONNECTED 2
+#define NSVC_NOTIFICATION_RECONNECTING 3
+#define NSVC_NOTIFICATION_DISCONNECTED 4
+
+#define NSVC_EVENT_CONNECTED 1
+#define NSVC_EVENT_DISCONNECTED 2
+#define NSVC_EVENT_DEVICE_CHANGED 3
+#define NSVC_EVENT_USER_JOINED 4
+#define NSVC_EVENT_USER_LEFT 5
+#define NSVC_EVENT_SPEAKING_STARTED 6
+#define NSVC_EVENT_SPEAKING_STOPPED 7
+#define NSVC_EVENT_ERROR 8
+#define NSVC_EVENT_TEXT_MESSAGE 9
+#define NSVC_EVENT_STARTED 10
+#define NSVC_EVENT_MUTE_CHANGED 11
+#define NSVC_EVENT_DEAFEN_CHANGED 12
+#define NSVC_EVENT_CLIP_STARTED 13
+#define NSVC_EVENT_AFK_MUTED 14
+
+#define NSVC_DEVICE_INPUT 0
+#define NSVC_DEVICE_OUTPUT 1
+#define NSVC_DEVICE_VOICE_OUTPUT 2
+
+#define NSVC_FEATURE_DTX (1 << 0)
+#define NSVC_FEATURE_FEC (1 << 1)
+#define NSVC_FEATURE_ENCRYPTION (1 << 2)
+#define NSVC_FEATURE_SEQUENCE (1 << 3)
+#define NSVC_FEATURE_SESSION_TOKEN (1 << 4)
+#define NSVC_FEATURE_CLIP (1 << 5)
+#define NSVC_FEATURE_WHISPER (1 << 6)
+
+typedef struct VoiceClientConfig {
+  const char *server_host;
+  uint16_t server_port;
+  uint32_t bitrate;
+  uint32_t frame_size;
+  bool vad_enabled;
+  float vad_threshold;
+  uint32_t vad_silence_interval_ms;
+  const char *input_device;
+  const char *output_device;
+  uint32_t buffer_ms;
+  uint32_t features;
+  int32_t transmit_mode;
+  uint32_t vad_hangover_ms;
+  bool cues_enabled;
+  uint32_t ptt_release_ms;
+  bool transmit_cues_enabled;
+  float cue_volume;
+  uint32_t clip_seconds;
+  bool notification_cues_enabled;
+  uint32_t afk_minutes;
+  const char *muted_users;
+} VoiceClientConfig;
+
+typedef void (*ErrorCallback)(int32_t code, const char *message, void *userdata);
+
+typedef void (*ConnectionStateCallback)(int32_t state, void *userdata);
+
+typedef void (*LogCallback)(int32_t level, const char *message, void *userdata);
+
+typedef void (*EventCallback)(int32_t event,
+                              uint32_t user_id,
+                              int32_t code,
+                              const char *text,
+                              void *userdata);
+
+typedef struct VoiceClientStats {
+  uint64_t packets_sent;
+  uint64_t bytes_sent;
+  uint64_t packets_received;
+  uint64_t bytes_received;
+  uint64_t packets_lost;
+  float loss_percent;
+  uint32_t rtt_ms;
+  float jitter_ms;
+  uint32_t bitrate;
+  uint32_t buffer_ms;
+  uint64_t underruns;
+  float encode_us;
+  uint64_t total_bytes_sent;
+  uint64_t total_bytes_received;
+  float send_kbps;
+  float receive_kbps;
+} VoiceClientStats;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+void *voice_client_new(const char *server_ip, uint16_t server_port);
+
+int32_t voice_client_config_init(struct VoiceClientConfig *config);
+
+void *voice_client_new_with_config(const struct VoiceClientConfig *config);
+
+void *voice_client_new_lan(uint16_t port, const char *name);
+
+void *voice_client_new_direct(const char *peer_ip, uint16_t peer_port, uint16_t local_port);
+
+void *voice_client_new_multicast(const char *group_ip, uint16_t port);
+
+int32_t voice_client_start(void *client);
+
+int32_t voice_client_start_loopback(void *client);
+
+int32_t voice_client_start_async(void *client);
+
+void voice_client_stop(void *client);
+
+void voice_client_set_transmitting(void *client, bool transmitting);
+
+int32_t voice_client_talk_key(void *client, bool pressed);
+
+int32_t voice_client_set_whisper_targets(void *client, const uint32_t *ids, size_t count);
+
+int32_t voice_client_whisper_key(void *client, bool pressed);
+
+int32_t voice_client_set_input_device(void *client, const char *name);
+
+int32_t voice_client_set_output_device(void *client, const char *name);
+
+int32_t voice_client_set_voice_output_device(void *client, const char *name);
+
+int32_t voice_client_share_audio(void *client, void *other);
+
+int32_t voice_client_restart_audio(void *client);
+
+int32_t voice_client_start_mic_preview(void *client);
+
+int32_t voice_client_stop_mic_preview(void *client);
+
+int32_t voice_client_get_preview_level(void *client, float *level);
+
+int32_t voice_client_get_output_level(void *client, float *level);
+
+int32_t voice_client_play_test_tone(void *client, int32_t kind, uint32_t seconds);
+
+int32_t voice_client_stop_test_tone(void *client);
+
+int32_t voice_client_set_notification_sound(void *client, int32_t kind, const char *path);
+
+int32_t voice_client_mic_check(void *client, uint32_t seconds);
+
+int32_t voice_client_stop_mic_check(void *client);
+
+int32_t voice_client_set_transmit_mode(void *client, int32_t mode);
+
+void voice_client_free(void *client);
+
+int32_t voice_client_set_bitrate(void *client, uint32_t bitrate);
+
+int32_t voice_client_set_error_callback(void *client,
+                                        ErrorCallback callback,
+                                        void *userdata);
+
+int32_t voice_client_set_state_callback(void *client,
+                                        ConnectionStateCallback callback,
+                                        void *userdata);
+
+int32_t voice_client_get_connection_state(void *client);
+
+bool voice_client_is_running(void *client);
+
+bool voice_client_is_transmitting(void *client);
+
+bool voice_client_is_connected(void *client);
+
+int32_t voice_client_get_buffer_ms(void *client);
+
+int32_t voice_client_stun_discover(void *client,
+                                   const char *stun_host,
+                                   uint16_t stun_port,
+                                   uint32_t timeout_ms);
+
+int32_t voice_client_get_public_address(void *client, char *buf, size_t buf_len);
+
+int32_t voice_client_set_turn_server(void *client,
+                                     const char *host,
+                                     uint16_t port,
+                                     const char *username,
+                                     const char *password);
+
+int32_t voice_client_set_p2p_enabled(void *client, bool enabled);
+
+bool voice_client_is_p2p_active(void *client);
+
+int32_t voice_client_set_max_datagram_size(void *client, uint32_t size);
+
+int32_t voice_client_set_server_timeout(void *client, uint32_t timeout_ms);
+
+int32_t voice_client_set_keep_alive_interval(void *client, uint32_t interval_ms);
+
+int32_t voice_client_set_socket_buffer_sizes(void *client, uint32_t send_size, uint32_t recv_size);
+
+int32_t voice_client_set_mtu_probe(void *client, bool enabled);
+
+int32_t voice_client_set_fec_enabled(void *client, bool enabled);
+
+int32_t voice_client_join_channel(void *client, const char *name);
+
+int32_t voice_client_join_channel_with_password(void *client,
+                                                const char *name,
+                                                const char *password);
+
+int32_t voice_client_leave_channel(void *client);
+
+int32_t voice_client_request_channel_list(void *client);
+
+int32_t voice_client_send_text(void *client, uint32_t to_user, const char *text);
+
+int32_t voice_client_get_channel_list(void *client, char *buf, size_t buf_len);
+
+int32_t voice_client_get_current_channel(void *client, char *buf, size_t buf_len);
+
+int32_t voice_client_set_credentials(void *client, const char *key, const char *password);
+
+int32_t voice_client_set_nickname(void *client, const char *nickname);
+
+int32_t voice_client_get_user_id(void *client, uint32_t *user_id);
+
+int32_t voice_client_get_users(void *client, char *buf, size_t buf_len);
+
+int32_t voice_client_set_user_volume(void *client, uint32_t user_id, float volume);
+
+int32_t voice_client_set_user_muted(void *client, uint32_t user_id, bool muted);
+
+bool voice_client_is_user_muted(void *client, uint32_t user_id);
+
+int32_t voice_client_set_muted_users(void *client, const char *nicknames);
+
+int32_t voice_client_get_muted_users(void *client, char *buf, size_t buf_len);
+
+int32_t voice_client_set_muted(void *client, bool muted);
+
+bool voice_client_is_muted(void *client);
+
+int32_t voice_client_set_deafened(void *client, bool deafened);
+
+bool voice_client_is_deafened(void *client);
+
+int32_t voice_client_start_recording(void *client, const char *path, bool include_mic);
+
+int32_t voice_client_start_recording_tracks(void *client, const char *path, bool include_mic);
+
+int32_t voice_client_stop_recording(void *client);
+
+bool voice_client_is_recording(void *client);
+
+int32_t voice_client_save_clip(void *client, const char *path, bool include_mic);
+
+int32_t voice_client_set_afk_timeout(void *client, uint32_t minutes);
+
+int32_t voice_client_set_clip_length(void *client, uint32_t seconds);
+
+int32_t voice_client_play_file_to_mic(void *client, const char *path, bool mix_with_mic);
+
+int32_t voice_client_send_clip(void *client, const char *path);
+
+int32_t voice_client_stop_file_to_mic(void *client);
+
+bool voice_client_is_playing_file(void *client);
+
+int32_t voice_client_set_positional_audio(void *client, bool enabled);
+
+bool voice_client_is_positional_audio_active(void *client);
+
+int32_t voice_client_set_listener_position(void *client, float x, float y, float z);
+
+int32_t voice_client_set_user_position(void *client, uint32_t user_id, float x, float y, float z);
+
+int32_t voice_client_clear_positions(void *client);
+
+int32_t voice_client_set_rolloff(void *client,
+                                 int32_t model,
+                                 float min_distance,
+                                 float max_distance);
+
+int32_t voice_client_start_overlay_socket(void *client, const char *path);
+
+int32_t voice_client_stop_overlay_socket(void *client);
+
+int32_t voice_client_set_output_volume(void *client, float volume);
+
+const char *voice_client_error_string(int32_t code);
+
+const char *voice_client_version(void);
+
+int32_t voice_client_last_error_message(char *buf, size_t buf_len);
+
+int32_t voice_client_set_log_callback(LogCallback callback, void *userdata, int32_t level);
+
+int32_t voice_client_set_log_level(int32_t level);
+
+int32_t voice_client_set_log_file(const char *path);
+
+int32_t voice_client_set_log_rotation(uint64_t max_bytes, uint32_t keep_files);
+
+int32_t voice_client_set_event_callback(void *client,
+                                        EventCallback callback,
+                                        void *userdata);
+
+int32_t voice_client_set_event_queue(void *client, bool enabled);
+
+int32_t voice_client_poll_events(void *client);
+
+int32_t voice_client_get_stats(void *client, struct VoiceClientStats *out);
+
+int32_t voice_client_start_metrics(void *client, uint16_t port);
+
+int32_t voice_client_stop_metrics(void *client);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NSVC_H */
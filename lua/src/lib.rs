@@ -0,0 +1,449 @@
+// Модуль Lua поверх FFI библиотеки для аддонов игр: вместо своего ffi.cdef
+// в каждом аддоне - require("nsvc"). Работает и в LuaJIT, и в Lua 5.1/5.4
+// (см. features в Cargo.toml).
+//
+//   local nsvc = require("nsvc")
+//   local client = nsvc.connect("example.org", 40000, { nickname = "Player", channel = "raid" })
+//   client:on_event(function(event, user_id, code, text)
+//       if event == nsvc.EVENT_USER_JOINED then print(text .. " joined") end
+//   end)
+//   -- в OnUpdate или таймере аддона:
+//   client:poll()
+//   -- по кнопке разговора:
+//   client:set_transmitting(true)
+//
+// События приходят из потоков клиента, а Lua однопоточна, поэтому они
+// копятся в очереди и вызываются в poll() из потока аддона. Ошибки FFI
+// становятся ошибками Lua.
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
+
+use mlua::prelude::*;
+use mlua::{AnyUserData, RegistryKey, UserData, UserDataMethods};
+use nsvc_core::{
+    connection_states, error_codes, events, notification_sounds, rolloff_models, test_tones, transmit_modes,
+    voice_client_clear_positions, voice_client_free, voice_client_get_connection_state, voice_client_get_muted_users,
+    voice_client_get_user_id, voice_client_is_connected, voice_client_is_deafened, voice_client_is_muted,
+    voice_client_is_playing_file, voice_client_is_positional_audio_active, voice_client_is_recording,
+    voice_client_is_transmitting, voice_client_is_user_muted, voice_client_join_channel_with_password,
+    voice_client_leave_channel, voice_client_new, voice_client_play_file_to_mic, voice_client_play_test_tone,
+    voice_client_save_clip, voice_client_send_clip, voice_client_send_text, voice_client_set_afk_timeout,
+    voice_client_set_clip_length, voice_client_set_credentials, voice_client_set_deafened,
+    voice_client_set_event_callback, voice_client_set_listener_position, voice_client_set_muted,
+    voice_client_set_muted_users, voice_client_set_nickname, voice_client_set_notification_sound,
+    voice_client_set_positional_audio, voice_client_set_rolloff, voice_client_set_transmit_mode,
+    voice_client_set_transmitting, voice_client_set_user_muted, voice_client_set_user_position,
+    voice_client_set_user_volume, voice_client_set_voice_output_device, voice_client_set_whisper_targets,
+    voice_client_share_audio, voice_client_start, voice_client_start_overlay_socket, voice_client_start_recording,
+    voice_client_start_recording_tracks, voice_client_stop, voice_client_stop_file_to_mic,
+    voice_client_stop_overlay_socket, voice_client_stop_recording, voice_client_stop_test_tone, voice_client_talk_key,
+    voice_client_whisper_key,
+};
+
+// Если аддон не вызывает poll(), старые события выбрасываются
+const MAX_QUEUED_EVENTS: usize = 1024;
+
+struct Event {
+    kind: i32,
+    user_id: u32,
+    code: i32,
+    text: String,
+}
+
+type EventQueue = Mutex<VecDeque<Event>>;
+
+// Вызывается из сетевых и аудиопотоков клиента: только ставит событие в очередь
+extern "C" fn on_event(event: i32, user_id: u32, code: i32, text: *const c_char, userdata: *mut c_void) {
+    let queue = unsafe { &*(userdata as *const EventQueue) };
+    let text = unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned();
+    let mut queue = queue.lock().unwrap();
+    if queue.len() >= MAX_QUEUED_EVENTS {
+        queue.pop_front();
+    }
+    queue.push_back(Event { kind: event, user_id, code, text });
+}
+
+// Код ошибки FFI в ошибку Lua
+fn check(code: i32) -> LuaResult<()> {
+    if code == error_codes::SUCCESS {
+        return Ok(());
+    }
+    let description = error_codes::description(code).to_string_lossy();
+    Err(LuaError::RuntimeError(format!("{} ({})", description, code)))
+}
+
+fn c_string(value: &str) -> LuaResult<CString> {
+    CString::new(value).map_err(|_| LuaError::RuntimeError("string contains a NUL byte".to_string()))
+}
+
+struct Client {
+    handle: usize,
+    // Очередь живет, пока жив клиент: на нее указывает userdata callback
+    queue: Box<EventQueue>,
+    callback: Option<RegistryKey>,
+}
+
+impl Client {
+    fn handle(&self) -> LuaResult<*mut c_void> {
+        if self.handle == 0 {
+            return Err(LuaError::RuntimeError("client is closed".to_string()));
+        }
+        Ok(self.handle as *mut c_void)
+    }
+
+    fn close(&mut self) {
+        if self.handle != 0 {
+            voice_client_free(self.handle as *mut c_void);
+            self.handle = 0;
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl UserData for Client {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("stop", |_, this, ()| {
+            voice_client_stop(this.handle()?);
+            Ok(())
+        });
+
+        // Освобождает клиента; дальше любой вызов - ошибка
+        methods.add_method_mut("close", |lua, this, ()| {
+            this.close();
+            if let Some(key) = this.callback.take() {
+                lua.remove_registry_value(key)?;
+            }
+            Ok(())
+        });
+
+        // callback(event, user_id, code, text), см. nsvc.EVENT_*; nil отключает
+        methods.add_method_mut("on_event", |lua, this, callback: Option<LuaFunction>| {
+            if let Some(key) = this.callback.take() {
+                lua.remove_registry_value(key)?;
+            }
+            this.callback = callback.map(|f| lua.create_registry_value(f)).transpose()?;
+            Ok(())
+        });
+
+        methods.add_method("set_transmitting", |_, this, transmitting: bool| {
+            voice_client_set_transmitting(this.handle()?, transmitting);
+            Ok(())
+        });
+
+        methods.add_method("is_transmitting", |_, this, ()| Ok(voice_client_is_transmitting(this.handle()?)));
+
+        // Клавиша разговора: в PTT держится, в TOGGLE переключает
+        methods.add_method("talk_key", |_, this, pressed: bool| check(voice_client_talk_key(this.handle()?, pressed)));
+
+        // Шепот: таблица ID адресатов (пустая - никому); пока whisper_key
+        // нажата, голос слышат только они
+        methods.add_method("set_whisper_targets", |_, this, user_ids: Vec<u32>| {
+            check(voice_client_set_whisper_targets(this.handle()?, user_ids.as_ptr(), user_ids.len()))
+        });
+        methods.add_method("whisper_key", |_, this, pressed: bool| {
+            check(voice_client_whisper_key(this.handle()?, pressed))
+        });
+
+        methods.add_method("set_muted", |_, this, muted: bool| check(voice_client_set_muted(this.handle()?, muted)));
+        methods.add_method("is_muted", |_, this, ()| Ok(voice_client_is_muted(this.handle()?)));
+
+        // Заглушенный клиент и не слышит, и не передает
+        methods.add_method("set_deafened", |_, this, deafened: bool| {
+            check(voice_client_set_deafened(this.handle()?, deafened))
+        });
+        methods.add_method("is_deafened", |_, this, ()| Ok(voice_client_is_deafened(this.handle()?)));
+
+        // Запись разговора в WAV; include_mic - вместе со своим голосом
+        methods.add_method("start_recording", |_, this, (path, include_mic): (String, Option<bool>)| {
+            let path = c_string(&path)?;
+            check(voice_client_start_recording(this.handle()?, path.as_ptr(), include_mic.unwrap_or(false)))
+        });
+        // По файлу на собеседника: <path без расширения>_<ID>_<ник>.wav
+        methods.add_method("start_recording_tracks", |_, this, (path, include_mic): (String, Option<bool>)| {
+            let path = c_string(&path)?;
+            check(voice_client_start_recording_tracks(this.handle()?, path.as_ptr(), include_mic.unwrap_or(false)))
+        });
+        methods.add_method("stop_recording", |_, this, ()| check(voice_client_stop_recording(this.handle()?)));
+        methods.add_method("is_recording", |_, this, ()| Ok(voice_client_is_recording(this.handle()?)));
+
+        // Буфер повтора в секундах; 0 - выключен
+        methods.add_method("set_clip_length", |_, this, seconds: u32| {
+            check(voice_client_set_clip_length(this.handle()?, seconds))
+        });
+        // Выключать микрофон после стольких минут без речи; 0 - не выключать
+        methods.add_method("set_afk_timeout", |_, this, minutes: u32| {
+            check(voice_client_set_afk_timeout(this.handle()?, minutes))
+        });
+        methods.add_method("save_clip", |_, this, (path, include_mic): (String, Option<bool>)| {
+            let path = c_string(&path)?;
+            check(voice_client_save_clip(this.handle()?, path.as_ptr(), include_mic.unwrap_or(false)))
+        });
+
+        // Звуковой файл собеседникам как голос: mix_with_mic - поверх голоса
+        methods.add_method("play_file_to_mic", |_, this, (path, mix_with_mic): (String, Option<bool>)| {
+            let path = c_string(&path)?;
+            check(voice_client_play_file_to_mic(this.handle()?, path.as_ptr(), mix_with_mic.unwrap_or(false)))
+        });
+        // Записанное сообщение каналу вместо голоса; остановка - stop_file_to_mic
+        methods.add_method("send_clip", |_, this, path: String| {
+            let path = c_string(&path)?;
+            check(voice_client_send_clip(this.handle()?, path.as_ptr()))
+        });
+        methods.add_method("stop_file_to_mic", |_, this, ()| check(voice_client_stop_file_to_mic(this.handle()?)));
+        methods.add_method("is_playing_file", |_, this, ()| Ok(voice_client_is_playing_file(this.handle()?)));
+
+        // Громкость собеседников по расстоянию в игре (Mumble Link, Linux)
+        methods.add_method("set_positional_audio", |_, this, enabled: bool| {
+            check(voice_client_set_positional_audio(this.handle()?, enabled))
+        });
+        methods.add_method("is_positional_audio_active", |_, this, ()| {
+            Ok(voice_client_is_positional_audio_active(this.handle()?))
+        });
+        // Положения от самой игры: слушатель и участники по ID
+        methods.add_method("set_listener_position", |_, this, (x, y, z): (f32, f32, f32)| {
+            check(voice_client_set_listener_position(this.handle()?, x, y, z))
+        });
+        methods.add_method("set_user_position", |_, this, (user_id, x, y, z): (u32, f32, f32, f32)| {
+            check(voice_client_set_user_position(this.handle()?, user_id, x, y, z))
+        });
+        methods.add_method("clear_positions", |_, this, ()| check(voice_client_clear_positions(this.handle()?)));
+        // nsvc.ROLLOFF_LINEAR или nsvc.ROLLOFF_INVERSE, ближняя и дальняя границы
+        methods.add_method("set_rolloff", |_, this, (model, min_distance, max_distance): (i32, f32, f32)| {
+            check(voice_client_set_rolloff(this.handle()?, model, min_distance, max_distance))
+        });
+
+        // Кто говорит - JSON-строками в Unix-сокете для оверлеев; без пути -
+        // $XDG_RUNTIME_DIR/nsvc-overlay.sock
+        methods.add_method("start_overlay_socket", |_, this, path: Option<String>| {
+            let path = path.as_deref().map(c_string).transpose()?;
+            let path = path.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+            check(voice_client_start_overlay_socket(this.handle()?, path))
+        });
+        methods.add_method("stop_overlay_socket", |_, this, ()| {
+            check(voice_client_stop_overlay_socket(this.handle()?))
+        });
+        // Голоса собеседников еще и на это устройство (для OBS); nil выключает
+        methods.add_method("set_voice_output_device", |_, this, name: Option<String>| {
+            let name = c_string(name.as_deref().unwrap_or(""))?;
+            check(voice_client_set_voice_output_device(this.handle()?, name.as_ptr()))
+        });
+
+        // Общие с другим клиентом микрофон и динамики; nil - снова свои.
+        // Оба клиента должны быть остановлены
+        methods.add_method("share_audio", |_, this, other: Option<AnyUserData>| {
+            let other = match other {
+                Some(other) => other.borrow::<Client>()?.handle()?,
+                None => std::ptr::null_mut(),
+            };
+            check(voice_client_share_audio(this.handle()?, other))
+        });
+
+        // nsvc.NOTIFICATION_*, файл WAV или Ogg/Opus; nil - снова тон
+        methods.add_method("set_notification_sound", |_, this, (kind, path): (i32, Option<String>)| {
+            let path = c_string(path.as_deref().unwrap_or(""))?;
+            check(voice_client_set_notification_sound(this.handle()?, kind, path.as_ptr()))
+        });
+
+        // nsvc.TONE_STEADY или nsvc.TONE_SWEEP, 1-30 секунд
+        methods.add_method("play_test_tone", |_, this, (kind, seconds): (i32, u32)| {
+            check(voice_client_play_test_tone(this.handle()?, kind, seconds))
+        });
+        methods.add_method("stop_test_tone", |_, this, ()| check(voice_client_stop_test_tone(this.handle()?)));
+
+        // nsvc.TRANSMIT_*
+        methods.add_method("set_transmit_mode", |_, this, mode: i32| {
+            check(voice_client_set_transmit_mode(this.handle()?, mode))
+        });
+
+        // Громкость участника: 1.0 - как есть
+        methods.add_method("set_user_volume", |_, this, (user_id, volume): (u32, f32)| {
+            check(voice_client_set_user_volume(this.handle()?, user_id, volume))
+        });
+
+        // Заглушить участника только у себя
+        methods.add_method("set_user_muted", |_, this, (user_id, muted): (u32, bool)| {
+            check(voice_client_set_user_muted(this.handle()?, user_id, muted))
+        });
+
+        methods.add_method("is_user_muted", |_, this, user_id: u32| {
+            Ok(voice_client_is_user_muted(this.handle()?, user_id))
+        });
+
+        // Ники заглушенных у себя: сохранить и передать в connect
+        // (muted_users) в следующей сессии, ID участников при этом меняются
+        methods.add_method("muted_users", |_, this, ()| muted_users(this.handle()?));
+
+        methods.add_method("set_muted_users", |_, this, nicknames: Vec<String>| {
+            set_muted_users(this.handle()?, &nicknames)
+        });
+
+        methods.add_method("join_channel", |_, this, (name, password): (String, Option<String>)| {
+            let name = c_string(&name)?;
+            let password = password.as_deref().map(c_string).transpose()?;
+            let password = password.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+            check(voice_client_join_channel_with_password(this.handle()?, name.as_ptr(), password))
+        });
+
+        methods.add_method("leave_channel", |_, this, ()| check(voice_client_leave_channel(this.handle()?)));
+
+        // to_user 0 - всему каналу
+        methods.add_method("send_text", |_, this, (to_user, text): (u32, String)| {
+            let text = c_string(&text)?;
+            check(voice_client_send_text(this.handle()?, to_user, text.as_ptr()))
+        });
+
+        methods.add_method("set_nickname", |_, this, nickname: String| {
+            let nickname = c_string(&nickname)?;
+            check(voice_client_set_nickname(this.handle()?, nickname.as_ptr()))
+        });
+
+        methods.add_method("get_user_id", |_, this, ()| {
+            let mut user_id = 0;
+            check(voice_client_get_user_id(this.handle()?, &mut user_id))?;
+            Ok(user_id)
+        });
+
+        // nsvc.STATE_*
+        methods.add_method("get_connection_state", |_, this, ()| {
+            Ok(voice_client_get_connection_state(this.handle()?))
+        });
+
+        methods.add_method("is_connected", |_, this, ()| Ok(voice_client_is_connected(this.handle()?)));
+
+        // Вызывает callback для накопленных событий; возвращает их число.
+        // Клиент не заимствуется на время вызовов: callback может звать его методы.
+        methods.add_function("poll", |lua, client: AnyUserData| {
+            let (events, callback) = {
+                let this = client.borrow::<Client>()?;
+                let events: Vec<Event> = this.queue.lock().unwrap().drain(..).collect();
+                let callback = match &this.callback {
+                    Some(key) => Some(lua.registry_value::<LuaFunction>(key)?),
+                    None => None,
+                };
+                (events, callback)
+            };
+            let count = events.len();
+            if let Some(callback) = callback {
+                for event in events {
+                    callback.call::<_, ()>((event.kind, event.user_id, event.code, event.text))?;
+                }
+            }
+            Ok(count)
+        });
+    }
+}
+
+fn muted_users(handle: *mut c_void) -> LuaResult<Vec<String>> {
+    let mut buf = vec![0 as c_char; 256];
+    loop {
+        match voice_client_get_muted_users(handle, buf.as_mut_ptr(), buf.len()) {
+            error_codes::BUFFER_TOO_SMALL => buf.resize(buf.len() * 2, 0),
+            code => {
+                check(code)?;
+                break;
+            },
+        }
+    }
+    let list = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+    Ok(list.lines().map(str::to_string).collect())
+}
+
+fn set_muted_users(handle: *mut c_void, nicknames: &[String]) -> LuaResult<()> {
+    let nicknames = c_string(&nicknames.join("\n"))?;
+    check(voice_client_set_muted_users(handle, nicknames.as_ptr()))
+}
+
+// nsvc.connect(host, port, options): создает и запускает клиента.
+// options (необязательно): nickname, key, password, muted_users (список
+// ников, см. muted_users у клиента) - до подключения;
+// channel, channel_password - канал, в который войти.
+fn connect(_: &Lua, (host, port, options): (String, u16, Option<LuaTable>)) -> LuaResult<Client> {
+    let host = c_string(&host)?;
+    let handle = voice_client_new(host.as_ptr(), port);
+    if handle.is_null() {
+        return Err(LuaError::RuntimeError("failed to create voice client".to_string()));
+    }
+    let client = Client { handle: handle as usize, queue: Box::new(Mutex::new(VecDeque::new())), callback: None };
+    let userdata = &*client.queue as *const EventQueue as *mut c_void;
+    check(voice_client_set_event_callback(handle, Some(on_event), userdata))?;
+
+    if let Some(options) = options {
+        if let Some(nickname) = options.get::<_, Option<String>>("nickname")? {
+            let nickname = c_string(&nickname)?;
+            check(voice_client_set_nickname(handle, nickname.as_ptr()))?;
+        }
+        if let Some(nicknames) = options.get::<_, Option<Vec<String>>>("muted_users")? {
+            set_muted_users(handle, &nicknames)?;
+        }
+        let key = options.get::<_, Option<String>>("key")?.as_deref().map(c_string).transpose()?;
+        let password = options.get::<_, Option<String>>("password")?.as_deref().map(c_string).transpose()?;
+        if key.is_some() || password.is_some() {
+            check(voice_client_set_credentials(
+                handle,
+                key.as_ref().map_or(std::ptr::null(), |k| k.as_ptr()),
+                password.as_ref().map_or(std::ptr::null(), |p| p.as_ptr()),
+            ))?;
+        }
+        if let Some(channel) = options.get::<_, Option<String>>("channel")? {
+            let channel = c_string(&channel)?;
+            let password = options.get::<_, Option<String>>("channel_password")?.as_deref().map(c_string).transpose()?;
+            let password = password.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+            check(voice_client_join_channel_with_password(handle, channel.as_ptr(), password))?;
+        }
+    }
+
+    check(voice_client_start(handle))?;
+    Ok(client)
+}
+
+#[mlua::lua_module]
+fn nsvc(lua: &Lua) -> LuaResult<LuaTable<'_>> {
+    let exports = lua.create_table()?;
+    exports.set("connect", lua.create_function(connect)?)?;
+
+    exports.set("TRANSMIT_PTT", transmit_modes::PTT)?;
+    exports.set("TRANSMIT_VOICE_ACTIVATION", transmit_modes::VOICE_ACTIVATION)?;
+    exports.set("TRANSMIT_CONTINUOUS", transmit_modes::CONTINUOUS)?;
+    exports.set("TRANSMIT_TOGGLE", transmit_modes::TOGGLE)?;
+
+    exports.set("TONE_STEADY", test_tones::STEADY)?;
+    exports.set("TONE_SWEEP", test_tones::SWEEP)?;
+
+    exports.set("ROLLOFF_LINEAR", rolloff_models::LINEAR)?;
+    exports.set("ROLLOFF_INVERSE", rolloff_models::INVERSE)?;
+
+    exports.set("NOTIFICATION_USER_JOINED", notification_sounds::USER_JOINED)?;
+    exports.set("NOTIFICATION_USER_LEFT", notification_sounds::USER_LEFT)?;
+    exports.set("NOTIFICATION_CONNECTED", notification_sounds::CONNECTED)?;
+    exports.set("NOTIFICATION_RECONNECTING", notification_sounds::RECONNECTING)?;
+    exports.set("NOTIFICATION_DISCONNECTED", notification_sounds::DISCONNECTED)?;
+
+    exports.set("STATE_DISCONNECTED", connection_states::DISCONNECTED)?;
+    exports.set("STATE_CONNECTING", connection_states::CONNECTING)?;
+    exports.set("STATE_CONNECTED", connection_states::CONNECTED)?;
+    exports.set("STATE_RECONNECTING", connection_states::RECONNECTING)?;
+    exports.set("STATE_FAILED", connection_states::FAILED)?;
+
+    exports.set("EVENT_CONNECTED", events::event_types::CONNECTED)?;
+    exports.set("EVENT_DISCONNECTED", events::event_types::DISCONNECTED)?;
+    exports.set("EVENT_DEVICE_CHANGED", events::event_types::DEVICE_CHANGED)?;
+    exports.set("EVENT_USER_JOINED", events::event_types::USER_JOINED)?;
+    exports.set("EVENT_USER_LEFT", events::event_types::USER_LEFT)?;
+    exports.set("EVENT_SPEAKING_STARTED", events::event_types::SPEAKING_STARTED)?;
+    exports.set("EVENT_SPEAKING_STOPPED", events::event_types::SPEAKING_STOPPED)?;
+    exports.set("EVENT_ERROR", events::event_types::ERROR)?;
+    exports.set("EVENT_TEXT_MESSAGE", events::event_types::TEXT_MESSAGE)?;
+    exports.set("EVENT_STARTED", events::event_types::STARTED)?;
+    exports.set("EVENT_MUTE_CHANGED", events::event_types::MUTE_CHANGED)?;
+    exports.set("EVENT_DEAFEN_CHANGED", events::event_types::DEAFEN_CHANGED)?;
+    exports.set("EVENT_CLIP_STARTED", events::event_types::CLIP_STARTED)?;
+    exports.set("EVENT_AFK_MUTED", events::event_types::AFK_MUTED)?;
+    Ok(exports)
+}
@@ -0,0 +1,542 @@
+// Модуль Node.js поверх FFI библиотеки: класс VoiceClient для Electron-
+// оверлеев и лаунчеров. Ошибки FFI превращаются в исключения JS, события
+// клиента приходят из его потоков и через ThreadsafeFunction попадают в
+// цикл событий JS.
+#![deny(clippy::all)]
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::JsFunction;
+use napi_derive::napi;
+use nsvc_core::{
+    error_codes, events, notification_sounds, rolloff_models, test_tones, transmit_modes, voice_client_clear_positions,
+    voice_client_free, voice_client_get_connection_state, voice_client_get_muted_users, voice_client_get_preview_level,
+    voice_client_get_user_id, voice_client_is_connected, voice_client_is_deafened, voice_client_is_muted,
+    voice_client_is_playing_file, voice_client_is_positional_audio_active, voice_client_is_recording,
+    voice_client_is_transmitting, voice_client_is_user_muted, voice_client_join_channel_with_password,
+    voice_client_last_error_message, voice_client_leave_channel, voice_client_mic_check, voice_client_new,
+    voice_client_play_file_to_mic, voice_client_play_test_tone, voice_client_restart_audio, voice_client_save_clip,
+    voice_client_send_clip, voice_client_send_text, voice_client_set_afk_timeout, voice_client_set_clip_length,
+    voice_client_set_deafened, voice_client_set_event_callback, voice_client_set_input_device,
+    voice_client_set_listener_position, voice_client_set_muted, voice_client_set_muted_users, voice_client_set_nickname,
+    voice_client_set_notification_sound, voice_client_set_output_device, voice_client_set_positional_audio,
+    voice_client_set_rolloff, voice_client_set_transmit_mode, voice_client_set_transmitting,
+    voice_client_set_user_muted, voice_client_set_user_position, voice_client_set_user_volume,
+    voice_client_set_voice_output_device, voice_client_set_whisper_targets, voice_client_share_audio,
+    voice_client_start, voice_client_start_async, voice_client_start_mic_preview, voice_client_start_overlay_socket,
+    voice_client_start_recording, voice_client_start_recording_tracks, voice_client_stop, voice_client_stop_file_to_mic,
+    voice_client_stop_mic_check, voice_client_stop_mic_preview, voice_client_stop_overlay_socket,
+    voice_client_stop_recording, voice_client_stop_test_tone, voice_client_talk_key, voice_client_whisper_key,
+};
+
+type EventFunction = ThreadsafeFunction<VoiceEvent, ErrorStrategy::Fatal>;
+
+// Поля как у callback событий, см. events::event_types
+#[napi(object)]
+pub struct VoiceEvent {
+    pub kind: i32,
+    pub user_id: u32,
+    pub code: i32,
+    pub text: String,
+}
+
+#[napi]
+pub const TRANSMIT_PTT: i32 = transmit_modes::PTT;
+#[napi]
+pub const TRANSMIT_VOICE_ACTIVATION: i32 = transmit_modes::VOICE_ACTIVATION;
+#[napi]
+pub const TRANSMIT_CONTINUOUS: i32 = transmit_modes::CONTINUOUS;
+#[napi]
+pub const TRANSMIT_TOGGLE: i32 = transmit_modes::TOGGLE;
+
+#[napi]
+pub const TONE_STEADY: i32 = test_tones::STEADY;
+#[napi]
+pub const TONE_SWEEP: i32 = test_tones::SWEEP;
+
+#[napi]
+pub const ROLLOFF_LINEAR: i32 = rolloff_models::LINEAR;
+#[napi]
+pub const ROLLOFF_INVERSE: i32 = rolloff_models::INVERSE;
+
+#[napi]
+pub const NOTIFICATION_USER_JOINED: i32 = notification_sounds::USER_JOINED;
+#[napi]
+pub const NOTIFICATION_USER_LEFT: i32 = notification_sounds::USER_LEFT;
+#[napi]
+pub const NOTIFICATION_CONNECTED: i32 = notification_sounds::CONNECTED;
+#[napi]
+pub const NOTIFICATION_RECONNECTING: i32 = notification_sounds::RECONNECTING;
+#[napi]
+pub const NOTIFICATION_DISCONNECTED: i32 = notification_sounds::DISCONNECTED;
+
+#[napi]
+pub const EVENT_CONNECTED: i32 = events::event_types::CONNECTED;
+#[napi]
+pub const EVENT_DISCONNECTED: i32 = events::event_types::DISCONNECTED;
+#[napi]
+pub const EVENT_DEVICE_CHANGED: i32 = events::event_types::DEVICE_CHANGED;
+#[napi]
+pub const EVENT_USER_JOINED: i32 = events::event_types::USER_JOINED;
+#[napi]
+pub const EVENT_USER_LEFT: i32 = events::event_types::USER_LEFT;
+#[napi]
+pub const EVENT_SPEAKING_STARTED: i32 = events::event_types::SPEAKING_STARTED;
+#[napi]
+pub const EVENT_SPEAKING_STOPPED: i32 = events::event_types::SPEAKING_STOPPED;
+#[napi]
+pub const EVENT_ERROR: i32 = events::event_types::ERROR;
+#[napi]
+pub const EVENT_TEXT_MESSAGE: i32 = events::event_types::TEXT_MESSAGE;
+#[napi]
+pub const EVENT_STARTED: i32 = events::event_types::STARTED;
+#[napi]
+pub const EVENT_MUTE_CHANGED: i32 = events::event_types::MUTE_CHANGED;
+#[napi]
+pub const EVENT_DEAFEN_CHANGED: i32 = events::event_types::DEAFEN_CHANGED;
+#[napi]
+pub const EVENT_CLIP_STARTED: i32 = events::event_types::CLIP_STARTED;
+#[napi]
+pub const EVENT_AFK_MUTED: i32 = events::event_types::AFK_MUTED;
+
+// Код ошибки FFI в исключение вместе с подробностями из
+// voice_client_last_error_message: их пишет каждый неудачный вызов в этом потоке.
+fn check(code: i32) -> Result<()> {
+    if code == error_codes::SUCCESS {
+        return Ok(());
+    }
+    let description = error_codes::description(code).to_string_lossy();
+    let mut buf = [0 as c_char; 1024];
+    if voice_client_last_error_message(buf.as_mut_ptr(), buf.len()) == error_codes::SUCCESS {
+        let details = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+        if !details.is_empty() {
+            return Err(Error::new(Status::GenericFailure, format!("{} ({}): {}", description, code, details)));
+        }
+    }
+    Err(Error::new(Status::GenericFailure, format!("{} ({})", description, code)))
+}
+
+fn c_string(value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| Error::new(Status::InvalidArg, "string contains a NUL byte".to_string()))
+}
+
+// Вызывается из сетевых и аудиопотоков клиента: только ставит событие в
+// очередь цикла событий JS
+extern "C" fn on_event(event: i32, user_id: u32, code: i32, text: *const c_char, userdata: *mut c_void) {
+    let function = unsafe { &*(userdata as *const EventFunction) };
+    let text = unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned();
+    function.call(
+        VoiceEvent { kind: event, user_id, code, text },
+        ThreadsafeFunctionCallMode::NonBlocking,
+    );
+}
+
+#[napi]
+pub struct VoiceClient {
+    handle: usize,
+    // Прежние функции живут до free: поток клиента мог уже взять старый
+    // callback, когда его заменили
+    events: Vec<Box<EventFunction>>,
+}
+
+#[napi]
+impl VoiceClient {
+    #[napi(constructor)]
+    pub fn new(host: String, port: u32) -> Result<Self> {
+        let port = u16::try_from(port).map_err(|_| Error::new(Status::InvalidArg, format!("invalid port {}", port)))?;
+        let host = c_string(&host)?;
+        let handle = voice_client_new(host.as_ptr(), port);
+        if handle.is_null() {
+            return Err(Error::new(Status::GenericFailure, "failed to create voice client".to_string()));
+        }
+        Ok(VoiceClient { handle: handle as usize, events: Vec::new() })
+    }
+
+    fn handle(&self) -> *mut c_void {
+        self.handle as *mut c_void
+    }
+
+    #[napi]
+    pub fn start(&self) -> Result<()> {
+        check(voice_client_start(self.handle()))
+    }
+
+    // Не ждет устройств: итог приходит событием EVENT_STARTED
+    #[napi]
+    pub fn start_async(&self) -> Result<()> {
+        check(voice_client_start_async(self.handle()))
+    }
+
+    #[napi]
+    pub fn stop(&self) {
+        voice_client_stop(self.handle())
+    }
+
+    // Освобождает клиента; дальше любой вызов бросает исключение
+    #[napi]
+    pub fn close(&mut self) {
+        if self.handle != 0 {
+            voice_client_free(self.handle());
+            self.handle = 0;
+            self.events.clear();
+        }
+    }
+
+    // Каждое событие клиента - вызов callback(event) в цикле событий JS.
+    // Процесс не ждет событий: при закрытии окна клиент не держит Node.
+    #[napi(ts_args_type = "callback: (event: VoiceEvent) => void")]
+    pub fn on_event(&mut self, env: Env, callback: JsFunction) -> Result<()> {
+        let mut function: EventFunction =
+            callback.create_threadsafe_function(0, |ctx: ThreadSafeCallContext<VoiceEvent>| Ok(vec![ctx.value]))?;
+        function.unref(&env)?;
+        let function = Box::new(function);
+        let userdata = &*function as *const EventFunction as *mut c_void;
+        check(voice_client_set_event_callback(self.handle(), Some(on_event), userdata))?;
+        self.events.push(function);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn set_transmitting(&self, transmitting: bool) {
+        voice_client_set_transmitting(self.handle(), transmitting)
+    }
+
+    // Клавиша разговора: в PTT держится, в TOGGLE переключает
+    #[napi]
+    pub fn talk_key(&self, pressed: bool) -> Result<()> {
+        check(voice_client_talk_key(self.handle(), pressed))
+    }
+
+    // ID участников, которым идет шепот; пустой массив - никому
+    #[napi]
+    pub fn set_whisper_targets(&self, user_ids: Vec<u32>) -> Result<()> {
+        check(voice_client_set_whisper_targets(self.handle(), user_ids.as_ptr(), user_ids.len()))
+    }
+
+    // Клавиша шепота: пока нажата, голос слышат только адресаты
+    #[napi]
+    pub fn whisper_key(&self, pressed: bool) -> Result<()> {
+        check(voice_client_whisper_key(self.handle(), pressed))
+    }
+
+    #[napi]
+    pub fn set_muted(&self, muted: bool) -> Result<()> {
+        check(voice_client_set_muted(self.handle(), muted))
+    }
+
+    #[napi]
+    pub fn is_muted(&self) -> bool {
+        voice_client_is_muted(self.handle())
+    }
+
+    // Заглушенный клиент и не слышит, и не передает
+    #[napi]
+    pub fn set_deafened(&self, deafened: bool) -> Result<()> {
+        check(voice_client_set_deafened(self.handle(), deafened))
+    }
+
+    #[napi]
+    pub fn is_deafened(&self) -> bool {
+        voice_client_is_deafened(self.handle())
+    }
+
+    // Запись разговора в WAV; includeMic - вместе со своим голосом
+    #[napi]
+    pub fn start_recording(&self, path: String, include_mic: bool) -> Result<()> {
+        let path = c_string(&path)?;
+        check(voice_client_start_recording(self.handle(), path.as_ptr(), include_mic))
+    }
+
+    // По файлу на собеседника: <path без расширения>_<ID>_<ник>.wav
+    #[napi]
+    pub fn start_recording_tracks(&self, path: String, include_mic: bool) -> Result<()> {
+        let path = c_string(&path)?;
+        check(voice_client_start_recording_tracks(self.handle(), path.as_ptr(), include_mic))
+    }
+
+    #[napi]
+    pub fn stop_recording(&self) -> Result<()> {
+        check(voice_client_stop_recording(self.handle()))
+    }
+
+    #[napi]
+    pub fn is_recording(&self) -> bool {
+        voice_client_is_recording(self.handle())
+    }
+
+    // Буфер повтора в секундах; 0 - выключен
+    #[napi]
+    pub fn set_clip_length(&self, seconds: u32) -> Result<()> {
+        check(voice_client_set_clip_length(self.handle(), seconds))
+    }
+
+    // Выключать микрофон после стольких минут без речи; 0 - не выключать
+    #[napi]
+    pub fn set_afk_timeout(&self, minutes: u32) -> Result<()> {
+        check(voice_client_set_afk_timeout(self.handle(), minutes))
+    }
+
+    // Последние секунды разговора в файл
+    #[napi]
+    pub fn save_clip(&self, path: String, include_mic: bool) -> Result<()> {
+        let path = c_string(&path)?;
+        check(voice_client_save_clip(self.handle(), path.as_ptr(), include_mic))
+    }
+
+    // Звуковой файл собеседникам как голос: mixWithMic - поверх голоса
+    #[napi]
+    pub fn play_file_to_mic(&self, path: String, mix_with_mic: bool) -> Result<()> {
+        let path = c_string(&path)?;
+        check(voice_client_play_file_to_mic(self.handle(), path.as_ptr(), mix_with_mic))
+    }
+
+    // Записанное сообщение каналу вместо голоса; остановка - stopFileToMic
+    #[napi]
+    pub fn send_clip(&self, path: String) -> Result<()> {
+        let path = c_string(&path)?;
+        check(voice_client_send_clip(self.handle(), path.as_ptr()))
+    }
+
+    #[napi]
+    pub fn stop_file_to_mic(&self) -> Result<()> {
+        check(voice_client_stop_file_to_mic(self.handle()))
+    }
+
+    #[napi]
+    pub fn is_playing_file(&self) -> bool {
+        voice_client_is_playing_file(self.handle())
+    }
+
+    // Громкость собеседников по расстоянию в игре (Mumble Link, Linux)
+    #[napi]
+    pub fn set_positional_audio(&self, enabled: bool) -> Result<()> {
+        check(voice_client_set_positional_audio(self.handle(), enabled))
+    }
+
+    #[napi]
+    pub fn is_positional_audio_active(&self) -> bool {
+        voice_client_is_positional_audio_active(self.handle())
+    }
+
+    // Положения от самой игры: слушатель и участники по ID
+    #[napi]
+    pub fn set_listener_position(&self, x: f64, y: f64, z: f64) -> Result<()> {
+        check(voice_client_set_listener_position(self.handle(), x as f32, y as f32, z as f32))
+    }
+
+    #[napi]
+    pub fn set_user_position(&self, user_id: u32, x: f64, y: f64, z: f64) -> Result<()> {
+        check(voice_client_set_user_position(self.handle(), user_id, x as f32, y as f32, z as f32))
+    }
+
+    #[napi]
+    pub fn clear_positions(&self) -> Result<()> {
+        check(voice_client_clear_positions(self.handle()))
+    }
+
+    // ROLLOFF_LINEAR или ROLLOFF_INVERSE; ближе minDistance - полная громкость,
+    // дальше maxDistance - тишина
+    #[napi]
+    pub fn set_rolloff(&self, model: i32, min_distance: f64, max_distance: f64) -> Result<()> {
+        check(voice_client_set_rolloff(self.handle(), model, min_distance as f32, max_distance as f32))
+    }
+
+    // Кто говорит - JSON-строками в Unix-сокете для оверлеев; без пути -
+    // $XDG_RUNTIME_DIR/nsvc-overlay.sock
+    #[napi]
+    pub fn start_overlay_socket(&self, path: Option<String>) -> Result<()> {
+        let path = path.as_deref().map(c_string).transpose()?;
+        let path = path.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+        check(voice_client_start_overlay_socket(self.handle(), path))
+    }
+
+    #[napi]
+    pub fn stop_overlay_socket(&self) -> Result<()> {
+        check(voice_client_stop_overlay_socket(self.handle()))
+    }
+
+    // Свой звук уведомления NOTIFICATION_* из файла WAV или Ogg/Opus;
+    // без пути - снова тон
+    #[napi]
+    pub fn set_notification_sound(&self, kind: i32, path: Option<String>) -> Result<()> {
+        let path = c_string(path.as_deref().unwrap_or(""))?;
+        check(voice_client_set_notification_sound(self.handle(), kind, path.as_ptr()))
+    }
+
+    // Проверка динамиков до разговора: TONE_STEADY или TONE_SWEEP, 1-30 секунд
+    #[napi]
+    pub fn play_test_tone(&self, kind: i32, seconds: u32) -> Result<()> {
+        check(voice_client_play_test_tone(self.handle(), kind, seconds))
+    }
+
+    #[napi]
+    pub fn stop_test_tone(&self) -> Result<()> {
+        check(voice_client_stop_test_tone(self.handle()))
+    }
+
+    #[napi]
+    pub fn set_transmit_mode(&self, mode: i32) -> Result<()> {
+        check(voice_client_set_transmit_mode(self.handle(), mode))
+    }
+
+    // null - устройство по умолчанию
+    #[napi]
+    pub fn set_input_device(&self, name: Option<String>) -> Result<()> {
+        let name = c_string(name.as_deref().unwrap_or(""))?;
+        check(voice_client_set_input_device(self.handle(), name.as_ptr()))
+    }
+
+    #[napi]
+    pub fn set_output_device(&self, name: Option<String>) -> Result<()> {
+        let name = c_string(name.as_deref().unwrap_or(""))?;
+        check(voice_client_set_output_device(self.handle(), name.as_ptr()))
+    }
+
+    // Голоса собеседников еще и на это устройство (виртуальное для OBS);
+    // null выключает
+    #[napi]
+    pub fn set_voice_output_device(&self, name: Option<String>) -> Result<()> {
+        let name = c_string(name.as_deref().unwrap_or(""))?;
+        check(voice_client_set_voice_output_device(self.handle(), name.as_ptr()))
+    }
+
+    // Общие с other микрофон и динамики; null - снова свои. Оба клиента
+    // должны быть остановлены
+    #[napi]
+    pub fn share_audio(&self, other: Option<ClassInstance<VoiceClient>>) -> Result<()> {
+        let other = other.as_deref().map_or(std::ptr::null_mut(), VoiceClient::handle);
+        check(voice_client_share_audio(self.handle(), other))
+    }
+
+    #[napi]
+    pub fn restart_audio(&self) -> Result<()> {
+        check(voice_client_restart_audio(self.handle()))
+    }
+
+    #[napi]
+    pub fn start_mic_preview(&self) -> Result<()> {
+        check(voice_client_start_mic_preview(self.handle()))
+    }
+
+    #[napi]
+    pub fn stop_mic_preview(&self) -> Result<()> {
+        check(voice_client_stop_mic_preview(self.handle()))
+    }
+
+    // Запись с микрофона на 1-10 секунд и ее проигрывание, как ее услышат другие
+    #[napi]
+    pub fn mic_check(&self, seconds: u32) -> Result<()> {
+        check(voice_client_mic_check(self.handle(), seconds))
+    }
+
+    #[napi]
+    pub fn stop_mic_check(&self) -> Result<()> {
+        check(voice_client_stop_mic_check(self.handle()))
+    }
+
+    // Уровень микрофона 0..1 для индикатора
+    #[napi]
+    pub fn get_preview_level(&self) -> Result<f64> {
+        let mut level = 0.0f32;
+        check(voice_client_get_preview_level(self.handle(), &mut level))?;
+        Ok(level as f64)
+    }
+
+    #[napi]
+    pub fn join_channel(&self, name: String, password: Option<String>) -> Result<()> {
+        let name = c_string(&name)?;
+        let password = password.as_deref().map(c_string).transpose()?;
+        let password = password.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+        check(voice_client_join_channel_with_password(self.handle(), name.as_ptr(), password))
+    }
+
+    #[napi]
+    pub fn leave_channel(&self) -> Result<()> {
+        check(voice_client_leave_channel(self.handle()))
+    }
+
+    // toUser 0 - всему каналу
+    #[napi]
+    pub fn send_text(&self, to_user: u32, text: String) -> Result<()> {
+        let text = c_string(&text)?;
+        check(voice_client_send_text(self.handle(), to_user, text.as_ptr()))
+    }
+
+    #[napi]
+    pub fn set_nickname(&self, nickname: String) -> Result<()> {
+        let nickname = c_string(&nickname)?;
+        check(voice_client_set_nickname(self.handle(), nickname.as_ptr()))
+    }
+
+    #[napi]
+    pub fn set_user_volume(&self, user_id: u32, volume: f64) -> Result<()> {
+        check(voice_client_set_user_volume(self.handle(), user_id, volume as f32))
+    }
+
+    #[napi]
+    pub fn set_user_muted(&self, user_id: u32, muted: bool) -> Result<()> {
+        check(voice_client_set_user_muted(self.handle(), user_id, muted))
+    }
+
+    #[napi]
+    pub fn is_user_muted(&self, user_id: u32) -> bool {
+        voice_client_is_user_muted(self.handle(), user_id)
+    }
+
+    // Ники заглушенных у себя: сохранить и вернуть через set_muted_users в
+    // следующей сессии, ID участников при этом меняются
+    #[napi]
+    pub fn muted_users(&self) -> Result<Vec<String>> {
+        let mut buf = vec![0 as c_char; 256];
+        loop {
+            match voice_client_get_muted_users(self.handle(), buf.as_mut_ptr(), buf.len()) {
+                error_codes::BUFFER_TOO_SMALL => buf.resize(buf.len() * 2, 0),
+                code => {
+                    check(code)?;
+                    break;
+                },
+            }
+        }
+        let list = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+        Ok(list.lines().map(str::to_string).collect())
+    }
+
+    #[napi]
+    pub fn set_muted_users(&self, nicknames: Vec<String>) -> Result<()> {
+        let nicknames = c_string(&nicknames.join("\n"))?;
+        check(voice_client_set_muted_users(self.handle(), nicknames.as_ptr()))
+    }
+
+    #[napi]
+    pub fn get_user_id(&self) -> Result<u32> {
+        let mut user_id = 0;
+        check(voice_client_get_user_id(self.handle(), &mut user_id))?;
+        Ok(user_id)
+    }
+
+    // Состояние из connection_states
+    #[napi]
+    pub fn get_connection_state(&self) -> i32 {
+        voice_client_get_connection_state(self.handle())
+    }
+
+    #[napi]
+    pub fn is_connected(&self) -> bool {
+        voice_client_is_connected(self.handle())
+    }
+
+    #[napi]
+    pub fn is_transmitting(&self) -> bool {
+        voice_client_is_transmitting(self.handle())
+    }
+}
+
+// Сборщик мусора JS освобождает клиента, если close() не вызвали
+impl Drop for VoiceClient {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,47 @@
+// Доступ к серверу: общий пароль и списки ключей. Ключ клиент передает
+// в HELLO вместе с паролем. Запрещенные ключи не пускаются никогда; если
+// список разрешенных не пуст, пускаются только ключи из него.
+use std::collections::HashSet;
+
+use nsvc_core::handshake::{Credentials, ACCESS_DENIED_PREFIX};
+
+#[derive(Default)]
+pub struct AccessPolicy {
+    pub password: Option<String>,
+    pub allow: HashSet<String>,
+    pub deny: HashSet<String>,
+}
+
+impl AccessPolicy {
+    // Старые клиенты без HELLO не могут предъявить ни пароль, ни ключ
+    pub fn admits_anonymous(&self) -> bool {
+        self.password.is_none() && self.allow.is_empty()
+    }
+
+    // Err - причина отказа для REJECT
+    pub fn check(&self, credentials: &Credentials) -> Result<(), String> {
+        if self.deny.contains(&credentials.key) {
+            return Err(format!("{}: key is banned", ACCESS_DENIED_PREFIX));
+        }
+        if !self.allow.is_empty() && !self.allow.contains(&credentials.key) {
+            return Err(format!("{}: key is not on the allow list", ACCESS_DENIED_PREFIX));
+        }
+        if let Some(password) = &self.password {
+            if !password_matches(password, &credentials.password) {
+                return Err(format!("{}: wrong server password", ACCESS_DENIED_PREFIX));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Сравнение за время, не зависящее от места первого расхождения,
+// чтобы пароль нельзя было подобрать по задержке ответа
+pub fn password_matches(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    let mut diff = (expected.len() != given.len()) as u8;
+    for (i, byte) in expected.iter().enumerate() {
+        diff |= byte ^ given.get(i).copied().unwrap_or(0);
+    }
+    diff == 0
+}
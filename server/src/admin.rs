@@ -0,0 +1,180 @@
+// Управление сервером по TCP. Текстовый протокол, по команде в строке:
+// первой строкой "auth ТОКЕН", дальше list, kick, mute, unmute, move,
+// record start КАНАЛ [mix|tracks|both] и record stop КАНАЛ.
+// Ответ - строки результата и завершающая "ok" или "error: причина".
+// Соединения обслуживаются в своих потоках, сами команды выполняет
+// основной цикл сервера: состояние клиентов живет только там.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::access::password_matches;
+use crate::log_message;
+use crate::recording;
+
+pub const DEFAULT_ADMIN_ADDR: &str = "127.0.0.1:40001";
+// Основной цикл может быть занят; дольше ответа не ждем
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+// Строка auth должна прийти сразу, дальше сессия может простаивать
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const MAX_LINE_LEN: usize = 1024;
+
+pub enum Command {
+    List,
+    Kick(SocketAddr),
+    Mute(SocketAddr, bool),
+    Move(SocketAddr, String),
+    StartRecording(String, recording::Kind),
+    StopRecording(String),
+}
+
+pub struct Request {
+    pub command: Command,
+    // Строки ответа; Err - текст ошибки
+    pub reply: Sender<Result<Vec<String>, String>>,
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let mut addr = || -> Result<SocketAddr, String> {
+        words
+            .next()
+            .ok_or("missing client address")?
+            .parse()
+            .map_err(|_| "invalid client address".to_string())
+    };
+
+    let command = match name {
+        "list" => Command::List,
+        "kick" => Command::Kick(addr()?),
+        "mute" => Command::Mute(addr()?, true),
+        "unmute" => Command::Mute(addr()?, false),
+        "move" => {
+            let addr = addr()?;
+            let channel = words.next().ok_or("missing channel name")?;
+            Command::Move(addr, channel.to_string())
+        },
+        "record" => {
+            let action = words.next().ok_or("missing record action")?;
+            let channel = words.next().ok_or("missing channel name")?.to_string();
+            match action {
+                "start" => {
+                    let kind = words.next().unwrap_or("mix");
+                    Command::StartRecording(channel, recording::Kind::parse(kind).ok_or("unknown recording kind")?)
+                },
+                "stop" => Command::StopRecording(channel),
+                _ => return Err(format!("unknown record action '{}'", action)),
+            }
+        },
+        _ => return Err(format!("unknown command '{}'", name)),
+    };
+    Ok(command)
+}
+
+// Запускает прием соединений; команды приходят в возвращаемый Receiver
+pub fn spawn(addr: &str, token: String) -> std::io::Result<Receiver<Request>> {
+    let listener = TcpListener::bind(addr)?;
+    let (requests_tx, requests_rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let requests = requests_tx.clone();
+                    let token = token.clone();
+                    thread::spawn(move || {
+                        let peer = stream.peer_addr().ok();
+                        if let Err(e) = serve(stream, &token, &requests) {
+                            log_message(&format!("Admin connection {:?} error: {}", peer, e));
+                        }
+                    });
+                },
+                Err(e) => log_message(&format!("Admin accept error: {}", e)),
+            }
+        }
+    });
+
+    Ok(requests_rx)
+}
+
+// Строка без перевода строки; None - соединение закрыто. Читается не
+// больше MAX_LINE_LEN, чтобы бесконечная строка не съела память.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.take(MAX_LINE_LEN as u64 + 1).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    Ok(Some(line))
+}
+
+fn serve(stream: TcpStream, token: &str, requests: &Sender<Request>) -> std::io::Result<()> {
+    let peer = stream.peer_addr()?;
+    stream.set_read_timeout(Some(AUTH_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let authorized = match read_line(&mut reader)? {
+        Some(line) => line
+            .strip_prefix("auth ")
+            .is_some_and(|given| password_matches(token, given.trim())),
+        None => return Ok(()),
+    };
+    if !authorized {
+        log_message(&format!("Admin authentication failed from {}", peer));
+        writeln!(writer, "error: authentication failed")?;
+        return Ok(());
+    }
+    log_message(&format!("Admin connected from {}", peer));
+    reader.get_ref().set_read_timeout(Some(IDLE_TIMEOUT))?;
+    writeln!(writer, "ok")?;
+
+    loop {
+        let line = match read_line(&mut reader) {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                // Остаток длинной строки не дочитать, соединение закрываем
+                writeln!(writer, "error: {}", e)?;
+                return Err(e);
+            },
+            Err(e) => return Err(e),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = match parse_command(&line) {
+            Ok(command) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if requests.send(Request { command, reply: reply_tx }).is_err() {
+                    break;
+                }
+                reply_rx
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| Err("server did not answer".to_string()))
+            },
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(output) => {
+                for line in output {
+                    writeln!(writer, "{}", line)?;
+                }
+                writeln!(writer, "ok")?;
+            },
+            Err(e) => writeln!(writer, "error: {}", e)?,
+        }
+    }
+
+    log_message(&format!("Admin disconnected: {}", peer));
+    Ok(())
+}
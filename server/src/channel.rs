@@ -0,0 +1,88 @@
+// Канал сервера: участники слышат только друг друга. У каждого канала свое
+// состояние режима пересылки (микшер MCU или выбор говорящих SFU).
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+use crate::recording::Recording;
+use crate::{access, mcu, sfu};
+
+#[derive(Clone, Copy)]
+pub enum ModeKind {
+    Relay,
+    Mcu,
+    Sfu { max_speakers: usize },
+}
+
+impl ModeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModeKind::Relay => "relay",
+            ModeKind::Mcu => "mcu",
+            ModeKind::Sfu { .. } => "sfu",
+        }
+    }
+}
+
+pub enum Mode {
+    Relay,
+    Mcu(mcu::Mixer),
+    Sfu(sfu::SpeakerSelector),
+}
+
+impl Mode {
+    fn new(kind: ModeKind) -> Self {
+        match kind {
+            ModeKind::Relay => Mode::Relay,
+            ModeKind::Mcu => Mode::Mcu(mcu::Mixer::new()),
+            ModeKind::Sfu { max_speakers } => Mode::Sfu(sfu::SpeakerSelector::new(max_speakers)),
+        }
+    }
+}
+
+pub struct Channel {
+    pub members: HashSet<SocketAddr>,
+    pub mode: Mode,
+    // Постоянные каналы (канал по умолчанию и заданные при запуске) не
+    // удаляются, когда из них все выходят
+    pub persistent: bool,
+    // Пароль для входа; у временного канала его задает создатель
+    pub password: Option<String>,
+    // Идущая запись канала (включается через управление)
+    pub recording: Option<Recording>,
+}
+
+impl Channel {
+    pub fn new(kind: ModeKind, persistent: bool, password: Option<String>) -> Self {
+        Channel {
+            members: HashSet::new(),
+            mode: Mode::new(kind),
+            persistent,
+            password,
+            recording: None,
+        }
+    }
+
+    pub fn admits(&self, password: &str) -> bool {
+        self.password.as_deref().is_none_or(|expected| access::password_matches(expected, password))
+    }
+
+    // bitrate - битрейт нисходящего потока MCU для участника
+    pub fn add(&mut self, addr: SocketAddr, bitrate: u32) {
+        self.members.insert(addr);
+        if let Mode::Mcu(mixer) = &mut self.mode {
+            mixer.add(addr, bitrate);
+        }
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        self.members.remove(addr);
+        match &mut self.mode {
+            Mode::Mcu(mixer) => mixer.remove(addr),
+            Mode::Sfu(selector) => selector.remove(addr),
+            Mode::Relay => {},
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.remove(addr);
+        }
+    }
+}
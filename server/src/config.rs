@@ -0,0 +1,244 @@
+// Настройки сервера. Читаются из TOML-файла (--config), ключи командной
+// строки поверх него. Пример:
+//
+//   address = "0.0.0.0"
+//   port = 40000
+//   max_clients = 100
+//   max_clients_per_ip = 4
+//   packet_rate = 500
+//   mode = "sfu"
+//
+//   [auth]
+//   password = "secret"
+//   deny = ["troll-key"]
+//
+//   [[channels]]
+//   name = "staff"
+//   password = "staff-only"
+//
+//   [admin]
+//   token = "admin-secret"
+//
+//   [websocket]
+//   listen = "0.0.0.0:40080"
+//
+//   [log]
+//   file = "nsvc-server.log"
+use std::collections::HashSet;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use nsvc_core::channels::{self, DEFAULT_CHANNEL};
+
+use crate::access::AccessPolicy;
+use crate::channel::ModeKind;
+use crate::{admin, limits, sfu};
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub address: String,
+    pub port: u16,
+    // 0 - без ограничения
+    pub max_clients: usize,
+    pub max_clients_per_ip: usize,
+    // Пакетов в секунду от одного адреса, лишние отбрасываются
+    pub packet_rate: u32,
+    pub mode: String,
+    pub speakers: usize,
+    // Клиент, от которого столько секунд ничего не приходило, считается ушедшим
+    pub client_timeout_secs: u64,
+    pub auth: AuthConfig,
+    pub channels: Vec<ChannelConfig>,
+    pub admin: Option<AdminConfig>,
+    pub metrics: Option<MetricsConfig>,
+    // Прием клиентов из браузера
+    pub websocket: Option<WebSocketConfig>,
+    pub recording: Option<RecordingConfig>,
+    pub log: LogConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub password: Option<String>,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    pub name: String,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    #[serde(default = "default_admin_listen")]
+    pub listen: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub listen: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketConfig {
+    pub listen: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordingConfig {
+    pub dir: PathBuf,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // Дублировать журнал в файл (дописывается)
+    pub file: Option<PathBuf>,
+}
+
+fn default_admin_listen() -> String {
+    admin::DEFAULT_ADMIN_ADDR.to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: "0.0.0.0".to_string(),
+            port: 40000,
+            max_clients: 0,
+            max_clients_per_ip: 0,
+            packet_rate: limits::DEFAULT_PACKETS_PER_SECOND,
+            mode: "relay".to_string(),
+            speakers: sfu::DEFAULT_MAX_SPEAKERS,
+            client_timeout_secs: 15,
+            auth: AuthConfig::default(),
+            channels: Vec::new(),
+            admin: None,
+            metrics: None,
+            websocket: None,
+            recording: None,
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Адрес приема: "адрес:порт"
+    pub fn listen_addr(&self) -> String {
+        if self.address.contains(':') {
+            // IPv6
+            format!("[{}]:{}", self.address.trim_matches(['[', ']']), self.port)
+        } else {
+            format!("{}:{}", self.address, self.port)
+        }
+    }
+
+    // Разбирает "адрес:порт" из командной строки
+    pub fn set_listen_addr(&mut self, addr: &str) -> Result<(), String> {
+        let (address, port) = addr.rsplit_once(':').ok_or("listen address must be address:port")?;
+        self.port = port.parse().map_err(|_| format!("invalid port '{}'", port))?;
+        self.address = address.trim_matches(['[', ']']).to_string();
+        Ok(())
+    }
+
+    pub fn mode_kind(&self) -> Result<ModeKind, String> {
+        match self.mode.as_str() {
+            "relay" => Ok(ModeKind::Relay),
+            "mcu" => Ok(ModeKind::Mcu),
+            "sfu" => Ok(ModeKind::Sfu {
+                max_speakers: self.speakers,
+            }),
+            other => Err(format!("unknown mode '{}' (expected relay, mcu or sfu)", other)),
+        }
+    }
+
+    pub fn access_policy(&self) -> AccessPolicy {
+        AccessPolicy {
+            password: self.auth.password.clone(),
+            allow: self.auth.allow.iter().cloned().collect(),
+            deny: self.auth.deny.iter().cloned().collect(),
+        }
+    }
+
+    // Проверяет настройки целиком; сообщает обо всех ошибках сразу
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if !self.listen_addr().to_socket_addrs().is_ok_and(|mut addrs| addrs.next().is_some()) {
+            errors.push(format!("invalid listen address '{}'", self.listen_addr()));
+        }
+        if let Err(e) = self.mode_kind() {
+            errors.push(e);
+        }
+        if self.speakers == 0 {
+            errors.push("speakers must be at least 1".to_string());
+        }
+        if self.max_clients != 0 && self.max_clients_per_ip > self.max_clients {
+            errors.push("max_clients_per_ip must not exceed max_clients".to_string());
+        }
+        if self.client_timeout_secs == 0 {
+            errors.push("client_timeout_secs must be at least 1".to_string());
+        }
+        if self.auth.password.as_deref() == Some("") {
+            errors.push("auth.password must not be empty (remove it to disable)".to_string());
+        }
+        if let Some(key) = self.auth.allow.iter().find(|key| self.auth.deny.contains(key)) {
+            errors.push(format!("key '{}' is both allowed and denied", key));
+        }
+
+        let mut names = HashSet::new();
+        for channel in &self.channels {
+            if !channels::is_valid_name(&channel.name) {
+                errors.push(format!("invalid channel name '{}'", channel.name));
+            } else if channel.name == DEFAULT_CHANNEL && channel.password.is_some() {
+                errors.push(format!("default channel '{}' cannot have a password", DEFAULT_CHANNEL));
+            } else if !names.insert(channel.name.as_str()) {
+                errors.push(format!("channel '{}' is defined twice", channel.name));
+            }
+            if channel.password.as_deref().is_some_and(|p| !channels::is_valid_password(p)) {
+                errors.push(format!("invalid password for channel '{}'", channel.name));
+            }
+        }
+
+        if let Some(admin) = &self.admin {
+            if admin.token.is_empty() {
+                errors.push("admin.token must not be empty".to_string());
+            }
+            if admin.listen.parse::<SocketAddr>().is_err() {
+                errors.push(format!("invalid admin.listen '{}'", admin.listen));
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            if metrics.listen.parse::<SocketAddr>().is_err() {
+                errors.push(format!("invalid metrics.listen '{}'", metrics.listen));
+            }
+        }
+        if let Some(websocket) = &self.websocket {
+            if websocket.listen.parse::<SocketAddr>().is_err() {
+                errors.push(format!("invalid websocket.listen '{}'", websocket.listen));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// Ограничение частоты пакетов ведрами жетонов. У принятого клиента ведро
+// свое, по адресу с портом: иначе клиенты за одним NAT или все WebSocket-
+// клиенты за обратным прокси делили бы одну норму. Незнакомые источники
+// делят ведро на IP: порт в ключ не входит, иначе флуд с перебором портов
+// получал бы по ведру на пакет.
+// Ведро пополняется с заданной скоростью и вмещает секунду трафика, так
+// что короткие всплески (повторы HELLO, догоняющий после паузы буфер)
+// проходят, а непрерывный поток сверх нормы отбрасывается.
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+
+// Кадры по 2.5 мс дают 400 пакетов в секунду, плюс keep-alive и управление
+pub const DEFAULT_PACKETS_PER_SECOND: u32 = 500;
+// Сколько IP незнакомых источников помнить: при флуде с подменой IP новые
+// адреса сверх этого отбрасываются, пока take_dropped не забудет
+// наполнившиеся ведра. Принятых клиентов ограничивает --max-clients.
+const MAX_BUCKETS: usize = 65536;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+    Client(SocketAddr),
+    Unknown(IpAddr),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Client(addr) => write!(f, "{}", addr),
+            Source::Unknown(ip) => write!(f, "{}", ip),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // Пакеты, отброшенные с последнего сообщения в журнал
+    dropped: u64,
+}
+
+pub struct RateLimiter {
+    // 0 - без ограничения
+    rate: u32,
+    buckets: HashMap<Source, Bucket>,
+    // Сколько ведер у незнакомых источников
+    unknown: usize,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32) -> Self {
+        RateLimiter {
+            rate,
+            buckets: HashMap::new(),
+            unknown: 0,
+        }
+    }
+
+    // Забирает жетон на пакет; false - пакет надо отбросить. admitted -
+    // from уже принят сервером как клиент.
+    pub fn allow(&mut self, from: SocketAddr, admitted: bool) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        let source = if admitted { Source::Client(from) } else { Source::Unknown(from.ip()) };
+        if !self.buckets.contains_key(&source) && !admitted {
+            if self.unknown >= MAX_BUCKETS {
+                return false;
+            }
+            self.unknown += 1;
+        }
+        let rate = self.rate as f64;
+        let now = Instant::now();
+        let bucket = self.buckets.entry(source).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+            dropped: 0,
+        });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(rate);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            bucket.dropped += 1;
+            false
+        }
+    }
+
+    // Раз в секунду: источники, которые превысили норму, с числом
+    // отброшенных пакетов. Заодно забывает тех, чьи ведра успели наполниться.
+    pub fn take_dropped(&mut self) -> Vec<(Source, u64)> {
+        let rate = self.rate as f64;
+        let mut dropped = Vec::new();
+        self.buckets.retain(|source, bucket| {
+            if bucket.dropped > 0 {
+                dropped.push((*source, bucket.dropped));
+                bucket.dropped = 0;
+            }
+            bucket.tokens + bucket.updated.elapsed().as_secs_f64() * rate < rate
+        });
+        self.unknown = self.buckets.keys().filter(|source| matches!(source, Source::Unknown(_))).count();
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn addr(ip: [u8; 4], port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::from(ip), port))
+    }
+
+    // Сколько пакетов подряд пропустит ведро
+    fn burst(limiter: &mut RateLimiter, from: SocketAddr, admitted: bool) -> u32 {
+        (0..1000).take_while(|_| limiter.allow(from, admitted)).count() as u32
+    }
+
+    #[test]
+    fn zero_rate_allows_everything() {
+        let mut limiter = RateLimiter::new(0);
+        assert_eq!(burst(&mut limiter, addr([10, 0, 0, 1], 1), false), 1000);
+    }
+
+    #[test]
+    fn bucket_holds_one_second_of_traffic() {
+        let mut limiter = RateLimiter::new(50);
+        assert_eq!(burst(&mut limiter, addr([10, 0, 0, 1], 1), true), 50);
+        let dropped = limiter.take_dropped();
+        assert_eq!(dropped, vec![(Source::Client(addr([10, 0, 0, 1], 1)), 1)]);
+    }
+
+    #[test]
+    fn admitted_clients_behind_one_nat_get_own_buckets() {
+        let mut limiter = RateLimiter::new(50);
+        assert_eq!(burst(&mut limiter, addr([10, 0, 0, 1], 1000), true), 50);
+        assert_eq!(burst(&mut limiter, addr([10, 0, 0, 1], 1001), true), 50);
+    }
+
+    #[test]
+    fn unknown_sources_share_a_bucket_per_ip() {
+        let mut limiter = RateLimiter::new(50);
+        let sent = (0..100u16).filter(|port| limiter.allow(addr([10, 0, 0, 1], *port), false)).count();
+        assert_eq!(sent, 50);
+        // Принятый клиент с того же IP этой нормой не задет
+        assert_eq!(burst(&mut limiter, addr([10, 0, 0, 1], 7), true), 50);
+    }
+
+    #[test]
+    fn unknown_sources_are_capped() {
+        let mut limiter = RateLimiter::new(50);
+        for i in 0..MAX_BUCKETS as u32 {
+            assert!(limiter.allow(SocketAddr::from((Ipv4Addr::from(i), 1)), false));
+        }
+        assert!(!limiter.allow(addr([250, 0, 0, 1], 1), false));
+        // Уже известные IP и принятые клиенты проходят
+        assert!(limiter.allow(SocketAddr::from((Ipv4Addr::from(5), 2)), false));
+        assert!(limiter.allow(addr([250, 0, 0, 1], 1), true));
+    }
+
+    #[test]
+    fn full_buckets_are_forgotten() {
+        let mut limiter = RateLimiter::new(50);
+        for i in 0..MAX_BUCKETS as u32 {
+            limiter.allow(SocketAddr::from((Ipv4Addr::from(i), 1)), false);
+        }
+        // Ведра с одним потраченным жетоном наполняются за 20 мс
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(limiter.take_dropped().is_empty());
+        assert!(limiter.allow(addr([250, 0, 0, 1], 1), false));
+    }
+}
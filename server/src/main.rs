@@ -0,0 +1,1292 @@
+// NSVC server: UDP-ретранслятор голоса. Клиенты различаются по адресу и
+// разбиты по каналам; голосовой пакет рассылается остальным участникам
+// канала отправителя. В режиме MCU сервер сам смешивает голоса и шлет
+// каждому один поток, в режиме SFU пересылает только самых активных.
+//
+// Запуск: nsvc-server [--config FILE] [--check-config] [--mode relay|mcu|sfu] [--speakers N]
+//                    [--channel NAME[=PASSWORD]]... [--password PASSWORD] [--allow KEY]... [--deny KEY]...
+//                    [--max-clients N] [--max-per-ip N] [--packet-rate N]
+//                    [--admin-token TOKEN] [--admin ADDR] [--record-dir DIR] [--metrics ADDR]
+//                    [--websocket ADDR] [адрес:порт]
+// (по умолчанию relay на 0.0.0.0:40000; настройки файла описаны в config.rs,
+// ключи командной строки их переопределяют. --channel добавляет постоянный канал,
+// --admin-token включает управление по TCP на 127.0.0.1:40001, см. admin.rs,
+// --record-dir разрешает запись каналов по команде управления,
+// --metrics отдает метрики Prometheus по HTTP на ADDR/metrics,
+// --websocket принимает клиентов из браузера по WebSocket на ADDR (см. websocket.rs),
+// --packet-rate ограничивает пакеты в секунду с одного адреса (0 - без ограничения),
+// --check-config только проверяет настройки)
+mod access;
+mod admin;
+mod channel;
+mod config;
+mod limits;
+mod mcu;
+mod metrics;
+mod recording;
+mod sfu;
+mod validation;
+mod websocket;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use nsvc_core::channels::{self, DEFAULT_CHANNEL};
+use nsvc_core::handshake::{self, features, SessionParams, ACCESS_DENIED_PREFIX};
+use nsvc_core::{positional, text, users};
+use nsvc_core::{control_type, media_packet, control_types, control_version, is_control_packet, is_media_type, is_supported_version, mark_as_clip, parse_media_packet, parse_whisper_packet, SAMPLE_RATE};
+
+use access::AccessPolicy;
+use channel::{Channel, Mode, ModeKind};
+use config::Config;
+use limits::RateLimiter;
+use validation::MAX_PACKET_SIZE;
+
+// Ответы сервера (список каналов) не должны фрагментироваться
+const MAX_REPLY_SIZE: usize = 1200;
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+// С включенным управлением команды обрабатываются не реже этого
+const ADMIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Выгнанный администратором клиент столько времени не может вернуться
+const KICK_BAN: Duration = Duration::from_secs(60);
+// Возможности, которые сервер соглашается включить
+const SUPPORTED_FEATURES: u32 =
+    features::DTX | features::FEC | features::SEQUENCE | features::SESSION_TOKEN | features::CLIP | features::WHISPER;
+// Однобайтовый keep-alive старых клиентов; сервер возвращает его отправителю
+const LEGACY_KEEP_ALIVE: u8 = 0x00;
+
+// Битрейт нисходящего потока MCU для старых клиентов без рукопожатия
+const LEGACY_BITRATE: u32 = 64000;
+
+struct Client {
+    last_seen: Instant,
+    // Параметры из рукопожатия; None - старый клиент без HELLO
+    params: Option<SessionParams>,
+    channel: String,
+    // Ключ из HELLO (пустой у старых клиентов)
+    key: String,
+    // ID участника: им сервер подписывает пересылаемый голос
+    user_id: u32,
+    nickname: String,
+    // Заглушен администратором: голос клиента никому не пересылается
+    muted: bool,
+    connected_at: Instant,
+    packets: u64,
+    bytes: u64,
+    // Номера пакетов MEDIA от клиента: по ним считаются потери
+    media: Option<SequenceStats>,
+    // Номер следующего пакета MEDIA, который сервер сам шлет клиенту (MCU)
+    downstream_seq: u32,
+    // Номер для голоса старого клиента, который сервер оборачивает в MEDIA
+    relay_seq: u32,
+    // Токен, который клиент обязан слать в MEDIA вместо SSRC; None - не согласован
+    session_token: Option<u32>,
+}
+
+impl Client {
+    // Понимает ли клиент пакеты MEDIA с заголовком
+    fn accepts_media_header(&self) -> bool {
+        self.params.is_some_and(|params| params.features & features::SEQUENCE != 0)
+    }
+
+    // Отличает ли клиент клипы от живой речи
+    fn accepts_clips(&self) -> bool {
+        self.params.is_some_and(|params| params.features & features::CLIP != 0)
+    }
+
+    fn new(params: Option<SessionParams>, key: String, user_id: u32, nickname: String) -> Self {
+        Client {
+            last_seen: Instant::now(),
+            params,
+            channel: DEFAULT_CHANNEL.to_string(),
+            key,
+            user_id,
+            nickname,
+            muted: false,
+            connected_at: Instant::now(),
+            packets: 0,
+            bytes: 0,
+            media: None,
+            downstream_seq: rand::random(),
+            relay_seq: rand::random(),
+            session_token: None,
+        }
+    }
+
+    fn bitrate(&self) -> u32 {
+        self.params.map_or(LEGACY_BITRATE, |params| params.bitrate)
+    }
+
+    fn on_media_seq(&mut self, seq: u32) {
+        match &mut self.media {
+            Some(stats) => {
+                stats.received += 1;
+                // Сравнение с учетом переполнения счетчика
+                if (seq.wrapping_sub(stats.highest) as i32) > 0 {
+                    stats.highest = seq;
+                }
+            },
+            None => {
+                self.media = Some(SequenceStats {
+                    first: seq,
+                    highest: seq,
+                    received: 1,
+                })
+            },
+        }
+    }
+}
+
+struct SequenceStats {
+    first: u32,
+    highest: u32,
+    received: u64,
+}
+
+impl SequenceStats {
+    // Доля потерянных пакетов с начала сессии
+    fn loss(&self) -> f64 {
+        let expected = self.highest.wrapping_sub(self.first) as u64 + 1;
+        expected.saturating_sub(self.received) as f64 / expected as f64
+    }
+}
+
+// Счетчики трафика с момента запуска
+#[derive(Clone, Copy, Default)]
+struct Traffic {
+    packets_received: u64,
+    bytes_received: u64,
+    packets_relayed: u64,
+    bytes_relayed: u64,
+    // Отброшено ограничителем частоты
+    packets_dropped: u64,
+    // Отброшено проверкой: мусор, обрезанные пакеты, чужой токен сессии
+    packets_invalid: u64,
+}
+
+// Отброшенные с прошлой сводки в журнале пакеты
+#[derive(Default)]
+struct InvalidPackets {
+    count: u64,
+    sources: HashSet<SocketAddr>,
+    last: Option<(SocketAddr, &'static str)>,
+}
+
+struct Server {
+    socket: UdpSocket,
+    clients: HashMap<SocketAddr, Client>,
+    channels: HashMap<String, Channel>,
+    // Режим, с которым создаются новые каналы
+    mode: ModeKind,
+    access: AccessPolicy,
+    admin: Option<Receiver<admin::Request>>,
+    // Выгнанные адреса и время окончания запрета
+    banned: HashMap<SocketAddr, Instant>,
+    // Каталог записей; None - запись выключена
+    record_dir: Option<PathBuf>,
+    // 0 - без ограничения
+    max_clients: usize,
+    max_clients_per_ip: usize,
+    limiter: RateLimiter,
+    invalid: InvalidPackets,
+    client_timeout: Duration,
+    traffic: Traffic,
+    started: Instant,
+    // Текст метрик для HTTP-потока; None - метрики выключены
+    metrics: Option<metrics::Snapshot>,
+    // Клиенты WebSocket; None - прием из браузера выключен
+    websocket: Option<websocket::Bridge>,
+    // Счетчики на момент прошлого обновления метрик, для пакетов в секунду
+    last_metrics: (Instant, Traffic),
+    // SSRC смешанного потока MCU
+    ssrc: u32,
+    next_user_id: u32,
+    // Клиент с ключом после переподключения получает прежний ID
+    user_ids: HashMap<String, u32>,
+}
+
+// Токен сессии; 0 означает "нет токена" и не выдается
+fn new_session_token() -> u32 {
+    rand::random::<u32>().max(1)
+}
+
+// Файл журнала из настроек; пишется вместе с выводом в консоль
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+fn log_message(message: &str) {
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
+    println!("[{}] {}", now, message);
+    if let Some(file) = LOG_FILE.get() {
+        let _ = writeln!(file.lock().unwrap(), "[{}] {}", now, message);
+    }
+}
+
+impl Server {
+    fn new(
+        socket: UdpSocket,
+        config: &Config,
+        mode: ModeKind,
+        admin: Option<Receiver<admin::Request>>,
+        metrics: Option<metrics::Snapshot>,
+        websocket: Option<websocket::Bridge>,
+    ) -> Self {
+        let mut channels = HashMap::new();
+        channels.insert(DEFAULT_CHANNEL.to_string(), Channel::new(mode, true, None));
+        for channel in &config.channels {
+            channels.insert(channel.name.clone(), Channel::new(mode, true, channel.password.clone()));
+        }
+
+        Server {
+            socket,
+            clients: HashMap::new(),
+            channels,
+            mode,
+            access: config.access_policy(),
+            admin,
+            banned: HashMap::new(),
+            record_dir: config.recording.as_ref().map(|recording| recording.dir.clone()),
+            max_clients: config.max_clients,
+            max_clients_per_ip: config.max_clients_per_ip,
+            limiter: RateLimiter::new(config.packet_rate),
+            invalid: InvalidPackets::default(),
+            client_timeout: Duration::from_secs(config.client_timeout_secs),
+            traffic: Traffic::default(),
+            started: Instant::now(),
+            metrics,
+            websocket,
+            last_metrics: (Instant::now(), Traffic::default()),
+            ssrc: rand::random(),
+            next_user_id: 1,
+            user_ids: HashMap::new(),
+        }
+    }
+
+    fn run(&mut self) -> std::io::Result<()> {
+        let mut buf = [0u8; MAX_PACKET_SIZE + 1];
+        let mut last_prune = Instant::now();
+        let mut next_mix = Instant::now() + mcu::MIX_INTERVAL;
+        let idle_timeout = if self.admin.is_some() { ADMIN_POLL_INTERVAL } else { RECV_TIMEOUT };
+
+        loop {
+            // В режиме MCU и при записи микса просыпаемся к каждому такту смешивания
+            let mixing = matches!(self.mode, ModeKind::Mcu)
+                || self.channels.values().any(|c| c.recording.as_ref().is_some_and(|r| r.has_mix()));
+            let timeout = if mixing {
+                next_mix.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))
+            } else {
+                idle_timeout
+            };
+            self.socket.set_read_timeout(Some(timeout))?;
+
+            match self.socket.recv_from(&mut buf) {
+                // Будильник потоков WebSocket или чужая датаграмма с адресом их клиента
+                Ok((_, from)) if self.websocket.as_ref().is_some_and(|ws| ws.owns(from)) => {},
+                Ok((received, from)) => self.on_packet(from, &buf[..received]),
+                Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {},
+                // На Windows сюда приходит ICMP port unreachable от ушедшего клиента
+                Err(e) => log_message(&format!("Receive error: {}", e)),
+            }
+
+            if mixing && Instant::now() >= next_mix {
+                self.mix();
+                next_mix += mcu::MIX_INTERVAL;
+                // Отстали больше чем на такт (сервер был занят) - не догоняем пачкой
+                if Instant::now() > next_mix {
+                    next_mix = Instant::now() + mcu::MIX_INTERVAL;
+                }
+            }
+
+            self.poll_admin();
+            self.poll_websocket();
+
+            if last_prune.elapsed() >= RECV_TIMEOUT {
+                self.prune();
+                self.update_metrics();
+                last_prune = Instant::now();
+            }
+        }
+    }
+
+    fn on_packet(&mut self, from: SocketAddr, data: &[u8]) {
+        self.traffic.packets_received += 1;
+        self.traffic.bytes_received += data.len() as u64;
+        if self.banned.contains_key(&from) {
+            return;
+        }
+        if !self.limiter.allow(from, self.clients.contains_key(&from)) {
+            self.traffic.packets_dropped += 1;
+            return;
+        }
+        if let Err(reason) = validation::check(data) {
+            self.reject_packet(from, reason);
+            return;
+        }
+
+        if is_control_packet(data) {
+            if control_type(data) == control_types::HELLO {
+                self.on_hello(from, data);
+                return;
+            }
+            // Пакеты незнакомых клиентов и чужих версий не пересылаем
+            if !self.touch(from, data.len()) || !is_supported_version(control_version(data)) {
+                return;
+            }
+            match control_type(data) {
+                control_types::KEEP_ALIVE => self.send(data, from),
+                control_types::MEDIA | control_types::MEDIA_CLIP | control_types::P2P_CANDIDATES => self.relay(from, data),
+                control_types::MEDIA_WHISPER => self.relay_whisper(from, data),
+                control_types::CHANNEL_JOIN => match channels::parse_join(data) {
+                    Some((name, password)) => self.join(from, &name, &password),
+                    None => log_message(&format!("Invalid channel join from {}", from)),
+                },
+                control_types::CHANNEL_LEAVE => self.join(from, DEFAULT_CHANNEL, ""),
+                control_types::CHANNEL_LIST_REQUEST => self.send_channel_list(from),
+                control_types::ROSTER_REQUEST => self.send_roster(from),
+                control_types::TEXT_MESSAGE => self.relay_text(from, data),
+                control_types::POSITION => self.relay_position(from, data),
+                control_types::GOODBYE => self.on_goodbye(from),
+                _ => {},
+            }
+            return;
+        }
+
+        // Старые клиенты начинают сессию без HELLO: первым keep-alive или голосом.
+        // На закрытый сервер их не пускаем: предъявить пароль они не могут.
+        if !self.clients.contains_key(&from) {
+            if !self.access.admits_anonymous() || self.is_full() || self.is_full_for(from.ip()) {
+                return;
+            }
+            log_message(&format!("Legacy client joined: {} ({} online)", from, self.clients.len() + 1));
+            let client = self.new_client(None, String::new(), String::new());
+            self.add_client(from, client);
+        }
+        self.touch(from, data.len());
+
+        if data.len() == 1 && data[0] == LEGACY_KEEP_ALIVE {
+            self.send(data, from);
+        } else {
+            self.relay(from, data);
+        }
+    }
+
+    // Учитывает отброшенный пакет; в журнал попадает сводкой раз в секунду
+    fn reject_packet(&mut self, from: SocketAddr, reason: &'static str) {
+        self.traffic.packets_invalid += 1;
+        self.invalid.count += 1;
+        self.invalid.sources.insert(from);
+        self.invalid.last = Some((from, reason));
+    }
+
+    // Обновляет время последнего пакета и счетчики; false для незнакомого адреса
+    fn touch(&mut self, from: SocketAddr, bytes: usize) -> bool {
+        match self.clients.get_mut(&from) {
+            Some(client) => {
+                client.last_seen = Instant::now();
+                client.packets += 1;
+                client.bytes += bytes as u64;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.max_clients != 0 && self.clients.len() >= self.max_clients
+    }
+
+    // Исчерпан ли лимит клиентов с одного IP-адреса
+    fn is_full_for(&self, ip: IpAddr) -> bool {
+        self.max_clients_per_ip != 0
+            && self.clients.keys().filter(|addr| addr.ip() == ip).count() >= self.max_clients_per_ip
+    }
+
+    // Новый клиент с ID участника; негодный или пустой ник заменяется на "userID"
+    fn new_client(&mut self, params: Option<SessionParams>, key: String, nickname: String) -> Client {
+        let known = if key.is_empty() { None } else { self.user_ids.get(&key).copied() };
+        let user_id = match known {
+            Some(id) => id,
+            None => {
+                let id = self.next_user_id;
+                self.next_user_id = self.next_user_id.wrapping_add(1).max(1);
+                if !key.is_empty() {
+                    self.user_ids.insert(key.clone(), id);
+                }
+                id
+            },
+        };
+        let nickname = if users::is_valid_nickname(&nickname) { nickname } else { format!("user{}", user_id) };
+        Client::new(params, key, user_id, nickname)
+    }
+
+    fn add_client(&mut self, addr: SocketAddr, client: Client) {
+        let name = client.channel.clone();
+        if let Some(channel) = self.channels.get_mut(&name) {
+            channel.add(addr, client.bitrate());
+        }
+        self.clients.insert(addr, client);
+        self.broadcast_roster(&name);
+    }
+
+    fn roster_packet(&self, name: &str) -> Option<Vec<u8>> {
+        let channel = self.channels.get(name)?;
+        let mut roster: Vec<(u32, String)> = channel
+            .members
+            .iter()
+            .filter_map(|addr| self.clients.get(addr))
+            .map(|client| (client.user_id, client.nickname.clone()))
+            .collect();
+        roster.sort();
+        Some(users::roster_packet(&roster, MAX_REPLY_SIZE))
+    }
+
+    // Список участников канала тому, кто его запросил
+    fn send_roster(&self, to: SocketAddr) {
+        let packet = self.clients.get(&to).and_then(|client| self.roster_packet(&client.channel));
+        if let Some(packet) = packet {
+            self.send(&packet, to);
+        }
+    }
+
+    // Новый список участников всем в канале, кто его понимает
+    fn broadcast_roster(&self, name: &str) {
+        let (packet, channel) = match (self.roster_packet(name), self.channels.get(name)) {
+            (Some(packet), Some(channel)) => (packet, channel),
+            _ => return,
+        };
+        for addr in &channel.members {
+            if self.clients.get(addr).is_some_and(|client| client.accepts_media_header()) {
+                self.send(&packet, *addr);
+            }
+        }
+    }
+
+    // Текст всему каналу отправителя или одному участнику в нем. Получатель
+    // видит ID отправителя; содержимое в журнал не пишем.
+    fn relay_text(&self, from: SocketAddr, data: &[u8]) {
+        let (to, text) = match text::parse_text(data) {
+            Some((to, _, text)) => (to, text),
+            None => {
+                log_message(&format!("Invalid text message from {}", from));
+                return;
+            },
+        };
+        let (sender, channel) = match self.clients.get(&from) {
+            Some(sender) => match self.channels.get(&sender.channel) {
+                Some(channel) => (sender, channel),
+                None => return,
+            },
+            None => return,
+        };
+
+        let flags = if to == 0 { 0 } else { text::FLAG_PRIVATE };
+        let packet = text::text_packet(sender.user_id, flags, text);
+        for addr in &channel.members {
+            if *addr == from {
+                continue;
+            }
+            let receives = self
+                .clients
+                .get(addr)
+                .is_some_and(|client| client.accepts_media_header() && (to == 0 || client.user_id == to));
+            if receives {
+                self.send(&packet, *addr);
+            }
+        }
+    }
+
+    // Положение в игре (Mumble Link) остальным участникам канала: по нему
+    // они ослабляют голос отправителя с расстоянием
+    fn relay_position(&self, from: SocketAddr, data: &[u8]) {
+        let Some((_, position, context)) = positional::parse_position(data) else {
+            log_message(&format!("Invalid position from {}", from));
+            return;
+        };
+        let Some(sender) = self.clients.get(&from) else {
+            return;
+        };
+        let Some(channel) = self.channels.get(&sender.channel) else {
+            return;
+        };
+
+        let packet = positional::position_packet(sender.user_id, position, context);
+        for addr in &channel.members {
+            if *addr != from && self.clients.get(addr).is_some_and(|client| client.accepts_media_header()) {
+                self.send(&packet, *addr);
+            }
+        }
+    }
+
+    // Убирает участника из канала; опустевший временный канал закрывается
+    fn leave_channel(&mut self, addr: &SocketAddr, name: &str) {
+        let closed = match self.channels.get_mut(name) {
+            Some(channel) => {
+                channel.remove(addr);
+                channel.members.is_empty() && !channel.persistent
+            },
+            None => false,
+        };
+        if closed {
+            self.channels.remove(name);
+            log_message(&format!("Channel '{}' closed", name));
+        } else {
+            self.broadcast_roster(name);
+        }
+    }
+
+    // Переводит клиента в канал (создает его при необходимости) и подтверждает.
+    // Новый канал получает пароль того, кто его создал.
+    fn join(&mut self, addr: SocketAddr, name: &str, password: &str) {
+        let old = match self.clients.get(&addr) {
+            Some(client) => client.channel.clone(),
+            None => return,
+        };
+
+        if old != name {
+            if self.channels.get(name).is_some_and(|channel| !channel.admits(password)) {
+                log_message(&format!("{} denied access to channel '{}'", addr, name));
+                self.send(&channels::denied_packet(name, "wrong channel password"), addr);
+                return;
+            }
+            self.move_client(addr, name, (!password.is_empty()).then(|| password.to_string()));
+        }
+
+        // JOIN повторяется, пока не дойдет ответ: отвечаем на каждый
+        self.send(&channels::joined_packet(name), addr);
+    }
+
+    // Переносит клиента без проверки пароля; password - для нового канала
+    fn move_client(&mut self, addr: SocketAddr, name: &str, password: Option<String>) {
+        let (old, bitrate) = match self.clients.get(&addr) {
+            Some(client) => (client.channel.clone(), client.bitrate()),
+            None => return,
+        };
+        if old == name {
+            return;
+        }
+
+        self.leave_channel(&addr, &old);
+        let mode = self.mode;
+        let channel = self.channels.entry(name.to_string()).or_insert_with(|| {
+            log_message(&format!("Channel '{}' created", name));
+            Channel::new(mode, false, password)
+        });
+        channel.add(addr, bitrate);
+        if let Some(client) = self.clients.get_mut(&addr) {
+            client.channel = name.to_string();
+        }
+        self.broadcast_roster(name);
+        log_message(&format!("{} moved from '{}' to '{}'", addr, old, name));
+    }
+
+    fn send_channel_list(&self, to: SocketAddr) {
+        let mut list: Vec<(String, u16)> = self
+            .channels
+            .iter()
+            .map(|(name, channel)| (name.clone(), channel.members.len().min(u16::MAX as usize) as u16))
+            .collect();
+        list.sort();
+        self.send(&channels::list_packet(&list, MAX_REPLY_SIZE), to);
+    }
+
+    fn on_hello(&mut self, from: SocketAddr, data: &[u8]) {
+        let hello = match handshake::parse_hello(data) {
+            Some(hello) => hello,
+            None => {
+                log_message(&format!("Malformed hello from {}", from));
+                return;
+            }
+        };
+        let (min_version, max_version, requested, credentials) =
+            (hello.min_version, hello.max_version, hello.params, hello.credentials);
+
+        if !(min_version..=max_version).any(is_supported_version) {
+            log_message(&format!(
+                "Client {} speaks protocol v{}-v{}, rejecting",
+                from, min_version, max_version
+            ));
+            self.send(&handshake::version_mismatch_packet(), from);
+            return;
+        }
+
+        if !self.clients.contains_key(&from) && self.is_full() {
+            log_message(&format!("Rejecting {}: server is full", from));
+            self.send(&handshake::reject_packet("server is full"), from);
+            return;
+        }
+        if !self.clients.contains_key(&from) && self.is_full_for(from.ip()) {
+            log_message(&format!("Rejecting {}: too many connections from {}", from, from.ip()));
+            self.send(&handshake::reject_packet("too many connections from your address"), from);
+            return;
+        }
+
+        if let Err(reason) = self.access.check(&credentials) {
+            log_message(&format!("Rejecting {} (key '{}'): {}", from, credentials.key, reason));
+            self.send(&handshake::reject_packet(&reason), from);
+            return;
+        }
+
+        if let Err(reason) = handshake::validate(&requested, SAMPLE_RATE, 1) {
+            log_message(&format!("Rejecting {}: {}", from, reason));
+            self.send(&handshake::reject_packet(&reason), from);
+            return;
+        }
+
+        let accepted = SessionParams {
+            features: requested.features & SUPPORTED_FEATURES,
+            ..requested
+        };
+        // HELLO повторяется, пока не дойдет ACCEPT: отвечаем на каждый.
+        // Список участников уходит после ACCEPT: до него клиент ID не знает.
+        match self.clients.get_mut(&from) {
+            Some(client) => {
+                client.last_seen = Instant::now();
+                client.params = Some(accepted);
+                // Повторный HELLO получает тот же токен: первый ACCEPT мог потеряться
+                let token = match accepted.features & features::SESSION_TOKEN {
+                    0 => None,
+                    _ => Some(*client.session_token.get_or_insert_with(new_session_token)),
+                };
+                client.session_token = token;
+                let packet = handshake::accept_packet(&accepted, client.user_id, token.unwrap_or(0));
+                self.send(&packet, from);
+            },
+            None => {
+                let mut client = self.new_client(Some(accepted), credentials.key, hello.nickname);
+                if accepted.features & features::SESSION_TOKEN != 0 {
+                    client.session_token = Some(new_session_token());
+                }
+                let packet = handshake::accept_packet(&accepted, client.user_id, client.session_token.unwrap_or(0));
+                self.send(&packet, from);
+                log_message(&format!(
+                    "Client joined: {} as '{}' (id {}, frame {}, {} bps, features 0x{:x}), {} online",
+                    from,
+                    client.nickname,
+                    client.user_id,
+                    accepted.frame_size,
+                    accepted.bitrate,
+                    accepted.features,
+                    self.clients.len() + 1
+                ));
+                self.add_client(from, client);
+            },
+        }
+    }
+
+    // Рассылает пакет остальным участникам канала отправителя. Голос уходит
+    // в MEDIA с ID участника-отправителя вместо его SSRC, старым клиентам -
+    // без заголовка. Клип остается клипом для тех, кто их различает, и идет
+    // обычным голосом остальным и в микшер. В режиме MCU голос уходит в
+    // микшер, в режиме SFU пересылается только от выбранных говорящих.
+    fn relay(&mut self, from: SocketAddr, data: &[u8]) {
+        let is_media = is_control_packet(data) && is_media_type(control_type(data));
+        let is_clip = is_media && control_type(data) == control_types::MEDIA_CLIP;
+        let is_voice = is_media || !is_control_packet(data);
+        let (opus, seq) = if is_media {
+            match parse_media_packet(data) {
+                Some((ssrc, seq, range)) => {
+                    // Чужой токен - голос пришел не от клиента, а с подделанного адреса
+                    if self.clients.get(&from).and_then(|c| c.session_token).is_some_and(|token| token != ssrc) {
+                        self.reject_packet(from, "wrong session token");
+                        return;
+                    }
+                    (&data[range], Some(seq))
+                },
+                None => return,
+            }
+        } else {
+            // Клиент с токеном шлет голос только в MEDIA, без заголовка - лишь тишину
+            if data.len() > 1 && self.clients.get(&from).is_some_and(|c| c.session_token.is_some()) {
+                self.reject_packet(from, "voice without session token");
+                return;
+            }
+            (data, None)
+        };
+
+        let attributed = match self.clients.get_mut(&from) {
+            Some(client) if is_voice => {
+                let seq = match seq {
+                    Some(seq) => {
+                        client.on_media_seq(seq);
+                        seq
+                    },
+                    None => {
+                        client.relay_seq = client.relay_seq.wrapping_add(1);
+                        client.relay_seq
+                    },
+                };
+                Some(media_packet(client.user_id, seq, opus))
+            },
+            Some(_) => None,
+            None => return,
+        };
+        let attributed_clip = attributed.as_ref().filter(|_| is_clip).map(|media| {
+            let mut clip = media.clone();
+            mark_as_clip(&mut clip);
+            clip
+        });
+
+        if is_voice && self.clients.get(&from).is_some_and(|client| client.muted) {
+            return;
+        }
+
+        let channel = match self.clients.get(&from).and_then(|client| self.channels.get_mut(&client.channel)) {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        if let Some(recording) = &mut channel.recording {
+            if is_voice && opus.len() > 1 {
+                recording.on_voice(from, opus);
+            }
+        }
+
+        // Старые клиенты декодируют весь поток от сервера одним декодером,
+        // поэтому в SFU получают только самого громкого говорящего
+        let mut legacy_allowed = true;
+        match &mut channel.mode {
+            Mode::Mcu(mixer) if is_voice => {
+                // Однобайтовые пакеты тишины не декодируем
+                if opus.len() > 1 {
+                    mixer.push(from, opus);
+                }
+                return;
+            },
+            // Пакеты тишины пропускаем всегда: по ним клиент понимает, что поток закончился
+            Mode::Sfu(selector) if is_voice && opus.len() > 1 => {
+                if !selector.on_voice(from, opus.len()) {
+                    return;
+                }
+                legacy_allowed = selector.loudest() == Some(from);
+            },
+            _ => {},
+        }
+
+        for addr in &channel.members {
+            if *addr == from {
+                continue;
+            }
+            let (legacy, clips) = match self.clients.get(addr) {
+                Some(client) => (!client.accepts_media_header(), client.accepts_clips()),
+                None => continue,
+            };
+            if is_voice && legacy && !legacy_allowed {
+                continue;
+            }
+            let packet: &[u8] = match (&attributed, &attributed_clip) {
+                (Some(_), _) if legacy => opus,
+                (Some(_), Some(clip)) if clips => clip,
+                (Some(media), _) => media,
+                (None, _) => data,
+            };
+            match send_to(&self.socket, self.websocket.as_ref(), packet, *addr) {
+                Ok(sent) => {
+                    self.traffic.packets_relayed += 1;
+                    self.traffic.bytes_relayed += sent as u64;
+                },
+                Err(e) => log_message(&format!("Send error to {}: {}", addr, e)),
+            }
+        }
+    }
+
+    // Шепот: голос только участникам канала из списка отправителя, обычным
+    // MEDIA. Мимо микшера MCU, выбора говорящих SFU и записи канала - его
+    // слышат только адресаты. Старым клиентам шепот не идет: у них один
+    // поток на весь канал.
+    fn relay_whisper(&mut self, from: SocketAddr, data: &[u8]) {
+        let Some((ssrc, seq, targets, opus)) = parse_whisper_packet(data) else {
+            return;
+        };
+        if self.clients.get(&from).and_then(|c| c.session_token).is_some_and(|token| token != ssrc) {
+            self.reject_packet(from, "wrong session token");
+            return;
+        }
+        let Some(sender) = self.clients.get_mut(&from) else {
+            return;
+        };
+        sender.on_media_seq(seq);
+        if sender.muted {
+            return;
+        }
+        let packet = media_packet(sender.user_id, seq, &data[opus]);
+        let Some(channel) = self.channels.get(&sender.channel) else {
+            return;
+        };
+
+        for addr in &channel.members {
+            let receives = self
+                .clients
+                .get(addr)
+                .is_some_and(|client| client.accepts_media_header() && targets.contains(&client.user_id));
+            if *addr == from || !receives {
+                continue;
+            }
+            match send_to(&self.socket, self.websocket.as_ref(), &packet, *addr) {
+                Ok(sent) => {
+                    self.traffic.packets_relayed += 1;
+                    self.traffic.bytes_relayed += sent as u64;
+                },
+                Err(e) => log_message(&format!("Send error to {}: {}", addr, e)),
+            }
+        }
+    }
+
+    // Такт MCU: смешанный звук каждому слушателю в каждом канале
+    fn mix(&mut self) {
+        let mut frames = Vec::new();
+        for channel in self.channels.values_mut() {
+            if let Mode::Mcu(mixer) = &mut channel.mode {
+                frames.extend(mixer.tick());
+            }
+            if let Some(recording) = &mut channel.recording {
+                recording.tick();
+            }
+        }
+
+        for (addr, opus) in frames {
+            let packet = match self.clients.get_mut(&addr) {
+                Some(client) if client.accepts_media_header() => {
+                    let seq = client.downstream_seq;
+                    client.downstream_seq = seq.wrapping_add(1);
+                    media_packet(self.ssrc, seq, &opus)
+                },
+                Some(_) => opus,
+                None => continue,
+            };
+            self.traffic.packets_relayed += 1;
+            self.traffic.bytes_relayed += packet.len() as u64;
+            self.send(&packet, addr);
+        }
+    }
+
+    // Перестраивает текст метрик для HTTP-потока
+    fn update_metrics(&mut self) {
+        let snapshot = match &self.metrics {
+            Some(snapshot) => snapshot.clone(),
+            None => return,
+        };
+        let (last_time, last) = self.last_metrics;
+        let seconds = last_time.elapsed().as_secs_f64().max(0.001);
+        self.last_metrics = (Instant::now(), self.traffic);
+
+        let mut out = metrics::Writer::default();
+        out.metric("nsvc_uptime_seconds", "gauge", "Seconds since server start");
+        out.value("nsvc_uptime_seconds", &[], self.started.elapsed().as_secs() as f64);
+        out.metric("nsvc_clients", "gauge", "Connected clients");
+        out.value("nsvc_clients", &[], self.clients.len() as f64);
+        out.metric("nsvc_channels", "gauge", "Open channels");
+        out.value("nsvc_channels", &[], self.channels.len() as f64);
+
+        out.metric("nsvc_channel_clients", "gauge", "Clients in a channel");
+        let mut channels: Vec<(&String, &Channel)> = self.channels.iter().collect();
+        channels.sort_by_key(|(name, _)| *name);
+        for (name, channel) in channels {
+            out.value("nsvc_channel_clients", &[("channel", name)], channel.members.len() as f64);
+        }
+
+        let counters = [
+            ("nsvc_packets_received_total", "Packets received", self.traffic.packets_received),
+            ("nsvc_bytes_received_total", "Bytes received", self.traffic.bytes_received),
+            ("nsvc_packets_relayed_total", "Voice packets sent to clients", self.traffic.packets_relayed),
+            ("nsvc_bytes_relayed_total", "Voice bytes sent to clients", self.traffic.bytes_relayed),
+            ("nsvc_packets_dropped_total", "Packets dropped by the rate limiter", self.traffic.packets_dropped),
+            ("nsvc_packets_invalid_total", "Packets dropped as malformed or spoofed", self.traffic.packets_invalid),
+        ];
+        for (name, help, value) in counters {
+            out.metric(name, "counter", help);
+            out.value(name, &[], value as f64);
+        }
+        out.metric("nsvc_packets_received_per_second", "gauge", "Packets received per second");
+        out.value(
+            "nsvc_packets_received_per_second",
+            &[],
+            (self.traffic.packets_received - last.packets_received) as f64 / seconds,
+        );
+        out.metric("nsvc_packets_relayed_per_second", "gauge", "Voice packets sent per second");
+        out.value(
+            "nsvc_packets_relayed_per_second",
+            &[],
+            (self.traffic.packets_relayed - last.packets_relayed) as f64 / seconds,
+        );
+
+        out.metric("nsvc_client_packet_loss_ratio", "gauge", "Share of a client's MEDIA packets lost on the way to the server");
+        let mut clients: Vec<(String, &Client)> = self.clients.iter().map(|(addr, c)| (addr.to_string(), c)).collect();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+        for (addr, client) in &clients {
+            if let Some(media) = &client.media {
+                out.value(
+                    "nsvc_client_packet_loss_ratio",
+                    &[("client", addr), ("channel", &client.channel)],
+                    media.loss(),
+                );
+            }
+        }
+        out.metric("nsvc_client_bytes_received_total", "counter", "Bytes received from a client");
+        for (addr, client) in &clients {
+            out.value(
+                "nsvc_client_bytes_received_total",
+                &[("client", addr), ("channel", &client.channel)],
+                client.bytes as f64,
+            );
+        }
+
+        *snapshot.lock().unwrap() = out.finish();
+    }
+
+    // Выполняет команды, пришедшие через управляющий канал
+    fn poll_admin(&mut self) {
+        let requests: Vec<admin::Request> = match &self.admin {
+            Some(admin) => admin.try_iter().collect(),
+            None => return,
+        };
+        for request in requests {
+            let result = self.on_admin_command(request.command);
+            let _ = request.reply.send(result);
+        }
+    }
+
+    fn on_admin_command(&mut self, command: admin::Command) -> Result<Vec<String>, String> {
+        match command {
+            admin::Command::List => {
+                let mut lines: Vec<String> = self
+                    .clients
+                    .iter()
+                    .map(|(addr, client)| {
+                        format!(
+                            "{} id={} nick={:?} channel={} key={} muted={} legacy={} online={}s idle={}ms packets={} bytes={}",
+                            addr,
+                            client.user_id,
+                            client.nickname,
+                            client.channel,
+                            if client.key.is_empty() { "-" } else { &client.key },
+                            client.muted,
+                            client.params.is_none(),
+                            client.connected_at.elapsed().as_secs(),
+                            client.last_seen.elapsed().as_millis(),
+                            client.packets,
+                            client.bytes
+                        )
+                    })
+                    .collect();
+                lines.sort();
+                Ok(lines)
+            },
+            admin::Command::Kick(addr) => {
+                let client = self.clients.remove(&addr).ok_or("no such client")?;
+                self.leave_channel(&addr, &client.channel);
+                self.banned.insert(addr, Instant::now() + KICK_BAN);
+                self.send(&handshake::reject_packet(&format!("{}: kicked by administrator", ACCESS_DENIED_PREFIX)), addr);
+                log_message(&format!("Admin kicked {}", addr));
+                Ok(Vec::new())
+            },
+            admin::Command::Mute(addr, muted) => {
+                let client = self.clients.get_mut(&addr).ok_or("no such client")?;
+                client.muted = muted;
+                log_message(&format!("Admin {} {}", if muted { "muted" } else { "unmuted" }, addr));
+                Ok(Vec::new())
+            },
+            admin::Command::Move(addr, name) => {
+                if !channels::is_valid_name(&name) {
+                    return Err("invalid channel name".to_string());
+                }
+                if !self.clients.contains_key(&addr) {
+                    return Err("no such client".to_string());
+                }
+                self.move_client(addr, &name, None);
+                self.send(&channels::joined_packet(&name), addr);
+                log_message(&format!("Admin moved {} to '{}'", addr, name));
+                Ok(Vec::new())
+            },
+            admin::Command::StartRecording(name, kind) => {
+                let dir = self.record_dir.as_ref().ok_or("recording is disabled (start the server with --record-dir)")?;
+                let channel = self.channels.get_mut(&name).ok_or("no such channel")?;
+                if channel.recording.is_some() {
+                    return Err("channel is already being recorded".to_string());
+                }
+                let recording = recording::Recording::start(dir, &name, kind).map_err(|e| e.to_string())?;
+                channel.recording = Some(recording);
+                Ok(Vec::new())
+            },
+            admin::Command::StopRecording(name) => {
+                let channel = self.channels.get_mut(&name).ok_or("no such channel")?;
+                channel.recording.take().ok_or("channel is not being recorded")?;
+                log_message(&format!("Recording of channel '{}' stopped", name));
+                Ok(Vec::new())
+            },
+        }
+    }
+
+    fn send(&self, data: &[u8], to: SocketAddr) {
+        if let Err(e) = send_to(&self.socket, self.websocket.as_ref(), data, to) {
+            log_message(&format!("Send error to {}: {}", to, e));
+        }
+    }
+
+    fn poll_websocket(&mut self) {
+        let incoming = match &mut self.websocket {
+            Some(ws) => ws.poll(),
+            None => return,
+        };
+        for event in incoming {
+            match event {
+                websocket::Incoming::Packet(from, data) => self.on_packet(from, &data),
+                // Закрытое соединение - клиент ушел, таймаута не ждем
+                websocket::Incoming::Closed(addr) => {
+                    if let Some(client) = self.clients.remove(&addr) {
+                        self.leave_channel(&addr, &client.channel);
+                        log_message(&format!("{} clients online", self.clients.len()));
+                    }
+                },
+            }
+        }
+    }
+
+    fn prune(&mut self) {
+        self.banned.retain(|_, until| Instant::now() < *until);
+        for (addr, dropped) in self.limiter.take_dropped() {
+            log_message(&format!("Rate limit: dropped {} packets from {}", dropped, addr));
+        }
+        let invalid = std::mem::take(&mut self.invalid);
+        if let Some((addr, reason)) = invalid.last {
+            log_message(&format!(
+                "Dropped {} invalid packets from {} addresses (last from {}: {})",
+                invalid.count,
+                invalid.sources.len(),
+                addr,
+                reason
+            ));
+        }
+
+        let expired: Vec<(SocketAddr, String)> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.last_seen.elapsed() >= self.client_timeout)
+            .map(|(addr, client)| (*addr, client.channel.clone()))
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+
+        for (addr, channel) in expired {
+            log_message(&format!("Client timed out: {}", addr));
+            self.clients.remove(&addr);
+            self.leave_channel(&addr, &channel);
+        }
+        log_message(&format!("{} clients online", self.clients.len()));
+    }
+
+    // Клиент ушел сам: место и канал освобождаются без ожидания таймаута
+    fn on_goodbye(&mut self, from: SocketAddr) {
+        if let Some(client) = self.clients.remove(&from) {
+            log_message(&format!("Client left: {}", from));
+            self.leave_channel(&from, &client.channel);
+            log_message(&format!("{} clients online", self.clients.len()));
+        }
+    }
+}
+
+// Клиентам WebSocket - через поток их соединения, остальным по UDP
+fn send_to(socket: &UdpSocket, websocket: Option<&websocket::Bridge>, data: &[u8], to: SocketAddr) -> std::io::Result<usize> {
+    if let Some(result) = websocket.and_then(|ws| ws.send(data, to)) {
+        return result;
+    }
+    socket.send_to(data, to)
+}
+
+fn print_usage() {
+    eprintln!(
+        "Usage: nsvc-server [--config FILE] [--check-config] [--mode relay|mcu|sfu] [--speakers N] \
+         [--channel NAME[=PASSWORD]]... [--password PASSWORD] [--allow KEY]... [--deny KEY]... \
+         [--max-clients N] [--max-per-ip N] [--packet-rate N] \
+         [--admin-token TOKEN] [--admin ADDR] [--record-dir DIR] [--metrics ADDR] [--websocket ADDR] [listen_addr]"
+    );
+}
+
+fn exit_usage() -> ! {
+    print_usage();
+    std::process::exit(2);
+}
+
+// Применяет ключи командной строки поверх настроек. Ok(false) - был --help.
+fn apply_args(config: &mut Config, args: &[String]) -> Result<bool, String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            // Файл уже прочитан
+            "--config" => {
+                value()?;
+            },
+            "--check-config" => {},
+            "--mode" => config.mode = value()?,
+            "--speakers" => config.speakers = value()?.parse().map_err(|_| "invalid --speakers value")?,
+            "--channel" => {
+                let spec = value()?;
+                let (name, password) = match spec.split_once('=') {
+                    Some((name, password)) => (name.to_string(), Some(password.to_string())),
+                    None => (spec, None),
+                };
+                config.channels.push(config::ChannelConfig { name, password });
+            },
+            "--password" => config.auth.password = Some(value()?),
+            "--allow" => config.auth.allow.push(value()?),
+            "--deny" => config.auth.deny.push(value()?),
+            "--max-clients" => config.max_clients = value()?.parse().map_err(|_| "invalid --max-clients value")?,
+            "--max-per-ip" => config.max_clients_per_ip = value()?.parse().map_err(|_| "invalid --max-per-ip value")?,
+            "--packet-rate" => config.packet_rate = value()?.parse().map_err(|_| "invalid --packet-rate value")?,
+            "--admin-token" => {
+                let token = value()?;
+                match &mut config.admin {
+                    Some(admin) => admin.token = token,
+                    None => {
+                        config.admin = Some(config::AdminConfig {
+                            listen: admin::DEFAULT_ADMIN_ADDR.to_string(),
+                            token,
+                        })
+                    },
+                }
+            },
+            "--admin" => {
+                let listen = value()?;
+                match &mut config.admin {
+                    Some(admin) => admin.listen = listen,
+                    None => return Err("--admin requires --admin-token".to_string()),
+                }
+            },
+            "--record-dir" => config.recording = Some(config::RecordingConfig { dir: PathBuf::from(value()?) }),
+            "--metrics" => config.metrics = Some(config::MetricsConfig { listen: value()? }),
+            "--websocket" => config.websocket = Some(config::WebSocketConfig { listen: value()? }),
+            "-h" | "--help" => return Ok(false),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => config.set_listen_addr(arg)?,
+        }
+    }
+    Ok(true)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Сначала файл, затем ключи командной строки поверх него
+    let mut config = match args.iter().position(|arg| arg == "--config") {
+        Some(i) => match args.get(i + 1) {
+            Some(path) => Config::load(Path::new(path)).unwrap_or_else(|e| {
+                eprintln!("Config error: {}", e);
+                std::process::exit(2);
+            }),
+            None => exit_usage(),
+        },
+        None => Config::default(),
+    };
+    match apply_args(&mut config, &args) {
+        Ok(true) => {},
+        Ok(false) => {
+            print_usage();
+            return;
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            exit_usage();
+        }
+    }
+
+    if let Err(errors) = config.validate() {
+        for error in errors {
+            eprintln!("Config error: {}", error);
+        }
+        std::process::exit(2);
+    }
+    if args.iter().any(|arg| arg == "--check-config") {
+        println!("Configuration is valid");
+        return;
+    }
+    // После validate режим заведомо известен
+    let mode = config.mode_kind().unwrap_or(ModeKind::Relay);
+
+    if let Some(path) = &config.log.file {
+        match File::options().create(true).append(true).open(path) {
+            Ok(file) => {
+                let _ = LOG_FILE.set(Mutex::new(file));
+            },
+            Err(e) => {
+                eprintln!("Failed to open log file {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let listen_addr = config.listen_addr();
+    let socket = match UdpSocket::bind(&listen_addr) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Failed to bind {}: {}", listen_addr, e);
+            std::process::exit(1);
+        }
+    };
+
+    log_message(&format!("NSVC server listening on {} ({} mode)", listen_addr, mode.name()));
+    if config.max_clients != 0 {
+        log_message(&format!("Client limit: {}", config.max_clients));
+    }
+    if config.max_clients_per_ip != 0 {
+        log_message(&format!("Client limit per IP address: {}", config.max_clients_per_ip));
+    }
+    if config.packet_rate != 0 {
+        log_message(&format!("Packet rate limit: {} per second per client", config.packet_rate));
+    }
+    let access = config.access_policy();
+    if access.password.is_some() || !access.allow.is_empty() || !access.deny.is_empty() {
+        log_message(&format!(
+            "Access control: password {}, {} allowed keys, {} denied keys",
+            if access.password.is_some() { "required" } else { "not required" },
+            access.allow.len(),
+            access.deny.len()
+        ));
+    }
+
+    let admin = config.admin.as_ref().map(|admin| match admin::spawn(&admin.listen, admin.token.clone()) {
+        Ok(requests) => {
+            log_message(&format!("Admin interface listening on {}", admin.listen));
+            requests
+        },
+        Err(e) => {
+            eprintln!("Failed to bind admin interface {}: {}", admin.listen, e);
+            std::process::exit(1);
+        }
+    });
+
+    let metrics = config.metrics.as_ref().map(|metrics| match metrics::spawn(&metrics.listen) {
+        Ok(snapshot) => {
+            log_message(&format!("Metrics available at http://{}/metrics", metrics.listen));
+            snapshot
+        },
+        Err(e) => {
+            eprintln!("Failed to bind metrics endpoint {}: {}", metrics.listen, e);
+            std::process::exit(1);
+        }
+    });
+
+    let websocket = config.websocket.as_ref().map(|websocket| {
+        match socket.local_addr().and_then(|server| websocket::spawn(&websocket.listen, server)) {
+            Ok(bridge) => {
+                log_message(&format!("WebSocket clients accepted at ws://{}", websocket.listen));
+                bridge
+            },
+            Err(e) => {
+                eprintln!("Failed to bind WebSocket endpoint {}: {}", websocket.listen, e);
+                std::process::exit(1);
+            }
+        }
+    });
+
+    if let Err(e) = Server::new(socket, &config, mode, admin, metrics, websocket).run() {
+        eprintln!("Server error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,202 @@
+// Режим MCU: сервер декодирует всех участников, для каждого слушателя
+// смешивает всех, кроме него самого, и отправляет ему один поток Opus.
+// Нагрузка на канал клиента не растет с числом говорящих.
+// Тот же микшер без слушателей дает общий микс канала для записи.
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use opus::{Application, Bitrate, Channels, Decoder, Encoder};
+
+use nsvc_core::SAMPLE_RATE;
+
+use crate::log_message;
+
+// Сервер смешивает кадрами по 10 мс
+pub const MIX_FRAME: usize = 480;
+pub const MIX_INTERVAL: Duration = Duration::from_millis(10);
+// Больше 100 мс звука на участника не копим: это уже не догнать
+const MAX_BUFFERED_SAMPLES: usize = MIX_FRAME * 10;
+// Самый длинный кадр Opus - 120 мс
+const MAX_DECODED_FRAME: usize = 5760;
+const MAX_ENCODED_SIZE: usize = 1200;
+
+struct Participant {
+    decoder: Decoder,
+    // None - только источник звука, сам ничего не слушает
+    encoder: Option<Encoder>,
+    pending: VecDeque<i16>,
+    // Кадр, снятый с очереди на текущем такте
+    frame: Vec<i16>,
+    active: bool,
+}
+
+pub struct Mixer {
+    participants: HashMap<SocketAddr, Participant>,
+    decoded: Vec<i16>,
+    // Кодер общего микса (для записи)
+    full_mix: Option<Encoder>,
+}
+
+fn create_encoder(bitrate: u32) -> Option<Encoder> {
+    let mut encoder = match Encoder::new(SAMPLE_RATE, Channels::Mono, Application::Voip) {
+        Ok(encoder) => encoder,
+        Err(e) => {
+            log_message(&format!("MCU: encoder creation error: {:?}", e));
+            return None;
+        }
+    };
+    if let Err(e) = encoder.set_bitrate(Bitrate::Bits(bitrate as i32)) {
+        log_message(&format!("MCU: failed to set bitrate: {:?}", e));
+    }
+    Some(encoder)
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Mixer {
+            participants: HashMap::new(),
+            decoded: vec![0; MAX_DECODED_FRAME],
+            full_mix: None,
+        }
+    }
+
+    // Микшер только для записи: участники - источники, на выходе общий микс
+    pub fn recording(bitrate: u32) -> Self {
+        Mixer {
+            full_mix: create_encoder(bitrate),
+            ..Mixer::new()
+        }
+    }
+
+    // Добавляет участника; bitrate - битрейт его нисходящего потока
+    pub fn add(&mut self, addr: SocketAddr, bitrate: u32) {
+        if self.participants.contains_key(&addr) {
+            return;
+        }
+        let encoder = match create_encoder(bitrate) {
+            Some(encoder) => encoder,
+            None => return,
+        };
+        self.insert(addr, Some(encoder));
+    }
+
+    // Добавляет участника, который только говорит
+    pub fn add_source(&mut self, addr: SocketAddr) {
+        if !self.participants.contains_key(&addr) {
+            self.insert(addr, None);
+        }
+    }
+
+    fn insert(&mut self, addr: SocketAddr, encoder: Option<Encoder>) {
+        let decoder = match Decoder::new(SAMPLE_RATE, Channels::Mono) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                log_message(&format!("MCU: decoder creation error for {}: {:?}", addr, e));
+                return;
+            }
+        };
+
+        self.participants.insert(addr, Participant {
+            decoder,
+            encoder,
+            pending: VecDeque::with_capacity(MAX_BUFFERED_SAMPLES),
+            frame: vec![0; MIX_FRAME],
+            active: false,
+        });
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        self.participants.remove(addr);
+    }
+
+    // Декодирует пакет участника в его очередь
+    pub fn push(&mut self, from: SocketAddr, opus: &[u8]) {
+        let participant = match self.participants.get_mut(&from) {
+            Some(participant) => participant,
+            None => return,
+        };
+
+        match participant.decoder.decode(opus, &mut self.decoded, false) {
+            Ok(samples) => {
+                participant.pending.extend(&self.decoded[..samples]);
+                while participant.pending.len() > MAX_BUFFERED_SAMPLES {
+                    participant.pending.pop_front();
+                }
+            },
+            Err(e) => log_message(&format!("MCU: decoding error from {}: {:?}", from, e)),
+        }
+    }
+
+    // Снимает с очередей по кадру и складывает всех говорящих
+    fn advance(&mut self) -> ([i32; MIX_FRAME], usize) {
+        for participant in self.participants.values_mut() {
+            participant.active = !participant.pending.is_empty();
+            let available = participant.pending.len().min(MIX_FRAME);
+            for (i, sample) in participant.frame.iter_mut().enumerate() {
+                *sample = if i < available { participant.pending.pop_front().unwrap_or(0) } else { 0 };
+            }
+        }
+
+        let mut mixed = [0i32; MIX_FRAME];
+        let mut active_count = 0;
+        for participant in self.participants.values().filter(|p| p.active) {
+            active_count += 1;
+            for (sum, sample) in mixed.iter_mut().zip(&participant.frame) {
+                *sum += *sample as i32;
+            }
+        }
+        (mixed, active_count)
+    }
+
+    // Один такт смешивания: пакеты Opus для слушателей, которым есть что слушать
+    pub fn tick(&mut self) -> Vec<(SocketAddr, Vec<u8>)> {
+        let (mixed, active_count) = self.advance();
+
+        let mut output = Vec::new();
+        let mut pcm = [0i16; MIX_FRAME];
+        let mut encoded = [0u8; MAX_ENCODED_SIZE];
+        for (addr, listener) in self.participants.iter_mut() {
+            let encoder = match &mut listener.encoder {
+                Some(encoder) => encoder,
+                None => continue,
+            };
+            // Никто, кроме самого слушателя, не говорит - ему ничего не шлем (DTX)
+            if active_count - listener.active as usize == 0 {
+                continue;
+            }
+
+            for ((out, sum), own) in pcm.iter_mut().zip(&mixed).zip(&listener.frame) {
+                let value = if listener.active { sum - *own as i32 } else { *sum };
+                *out = value.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            }
+
+            match encoder.encode(&pcm, &mut encoded) {
+                Ok(len) if len > 0 => output.push((*addr, encoded[..len].to_vec())),
+                Ok(_) => {},
+                Err(e) => log_message(&format!("MCU: encoding error for {}: {:?}", addr, e)),
+            }
+        }
+        output
+    }
+
+    // Такт микшера записи: кадр общего микса (тишина, если все молчат)
+    pub fn tick_full(&mut self) -> Option<Vec<u8>> {
+        let (mixed, _) = self.advance();
+        let encoder = self.full_mix.as_mut()?;
+
+        let pcm: Vec<i16> = mixed
+            .iter()
+            .map(|sum| (*sum).clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+            .collect();
+        let mut encoded = [0u8; MAX_ENCODED_SIZE];
+        match encoder.encode(&pcm, &mut encoded) {
+            Ok(len) if len > 0 => Some(encoded[..len].to_vec()),
+            Ok(_) => None,
+            Err(e) => {
+                log_message(&format!("MCU: full mix encoding error: {:?}", e));
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Метрики сервера по HTTP в текстовом формате Prometheus. Основной цикл
+// раз в секунду обновляет готовый текст, HTTP-поток только отдает его:
+// так опрос метрик не задерживает пересылку голоса.
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::log_message;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_REQUEST_SIZE: usize = 8192;
+
+// Текст метрик, который отдает HTTP-поток
+pub type Snapshot = Arc<Mutex<String>>;
+
+pub fn spawn(addr: &str) -> std::io::Result<Snapshot> {
+    let listener = TcpListener::bind(addr)?;
+    let snapshot: Snapshot = Arc::new(Mutex::new(String::new()));
+    let snapshot_http = snapshot.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve(stream, &snapshot_http) {
+                        log_message(&format!("Metrics request error: {}", e));
+                    }
+                },
+                Err(e) => log_message(&format!("Metrics accept error: {}", e)),
+            }
+        }
+    });
+
+    Ok(snapshot)
+}
+
+fn serve(mut stream: TcpStream, snapshot: &Snapshot) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    // Читаем до конца заголовков; тело у GET не бывает
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let received = stream.read(&mut buf)?;
+        if received == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..received]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", snapshot.lock().unwrap().clone()),
+        ("GET", _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+// Значение метки: кавычки, обратная косая черта и перевод строки экранируются
+pub fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Построитель текста метрик
+#[derive(Default)]
+pub struct Writer {
+    text: String,
+}
+
+impl Writer {
+    pub fn metric(&mut self, name: &str, kind: &str, help: &str) {
+        self.text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+    }
+
+    pub fn value(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            self.text.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        self.text.push_str(&format!(" {}\n", value));
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
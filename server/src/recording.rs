@@ -0,0 +1,172 @@
+// Запись каналов в файлы Ogg/Opus. Общий микс кодируется отдельным
+// микшером, дорожки участников пишутся без перекодирования - как пришли.
+// Паузы в дорожках заполняются пакетами тишины, поэтому все файлы одной
+// записи начинаются в момент ее старта и их можно сводить по времени.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use nsvc_core::ogg::{packet_samples, OggWriter};
+use nsvc_core::SAMPLE_RATE;
+
+use crate::log_message;
+use crate::mcu::{self, Mixer};
+
+// Битрейт кодирования общего микса
+const MIX_BITRATE: u32 = 64000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Mix,
+    Tracks,
+    Both,
+}
+
+impl Kind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mix" => Some(Kind::Mix),
+            "tracks" => Some(Kind::Tracks),
+            "both" => Some(Kind::Both),
+            _ => None,
+        }
+    }
+
+    fn mix(self) -> bool {
+        self != Kind::Tracks
+    }
+
+    fn tracks(self) -> bool {
+        self != Kind::Mix
+    }
+}
+
+// Запись одного канала
+pub struct Recording {
+    kind: Kind,
+    prefix: PathBuf,
+    started: Instant,
+    started_at: DateTime<Utc>,
+    mix: Option<(Mixer, OggWriter)>,
+    tracks: HashMap<SocketAddr, OggWriter>,
+}
+
+impl Recording {
+    // Файлы: <dir>/<канал>_<время начала>_mix.opus и ..._<адрес>.opus
+    pub fn start(dir: &Path, channel: &str, kind: Kind) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let started_at = Utc::now();
+        let safe_name: String = channel
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let prefix = dir.join(format!("{}_{}", safe_name, started_at.format("%Y%m%d-%H%M%S")));
+
+        let mix = if kind.mix() {
+            let path = with_suffix(&prefix, "_mix.opus");
+            Some((Mixer::recording(MIX_BITRATE), OggWriter::create(&path, &tags(started_at))?))
+        } else {
+            None
+        };
+
+        log_message(&format!("Recording channel '{}' to {}_*.opus", channel, prefix.display()));
+        Ok(Recording {
+            kind,
+            prefix,
+            started: Instant::now(),
+            started_at,
+            mix,
+            tracks: HashMap::new(),
+        })
+    }
+
+    pub fn has_mix(&self) -> bool {
+        self.mix.is_some()
+    }
+
+    // Текущая позиция записи в отсчетах
+    fn position(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64 * SAMPLE_RATE as u64 / 1_000_000
+    }
+
+    // Голосовой пакет участника канала
+    pub fn on_voice(&mut self, from: SocketAddr, opus: &[u8]) {
+        if let Some((mixer, _)) = &mut self.mix {
+            mixer.add_source(from);
+            mixer.push(from, opus);
+        }
+
+        if !self.kind.tracks() {
+            return;
+        }
+        let samples = match packet_samples(opus) {
+            Some(samples) => samples,
+            None => return,
+        };
+        let position = self.position();
+
+        if !self.tracks.contains_key(&from) {
+            let path = with_suffix(&self.prefix, &format!("_{}.opus", from.to_string().replace([':', '[', ']'], "_")));
+            match OggWriter::create(&path, &tags(self.started_at)) {
+                Ok(track) => {
+                    self.tracks.insert(from, track);
+                },
+                Err(e) => {
+                    log_message(&format!("Failed to create track {}: {}", path.display(), e));
+                    return;
+                }
+            }
+        }
+
+        if let Some(track) = self.tracks.get_mut(&from) {
+            let result = track
+                .pad_to(position.saturating_sub(samples))
+                .and_then(|_| track.write_packet(opus, samples));
+            if let Err(e) = result {
+                log_message(&format!("Track write error for {}: {}", from, e));
+            }
+        }
+    }
+
+    // Такт общего микса (каждые mcu::MIX_INTERVAL)
+    pub fn tick(&mut self) {
+        if let Some((mixer, writer)) = &mut self.mix {
+            if let Some(packet) = mixer.tick_full() {
+                if let Err(e) = writer.write_packet(&packet, mcu::MIX_FRAME as u64) {
+                    log_message(&format!("Mix write error: {}", e));
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        if let Some((mixer, _)) = &mut self.mix {
+            mixer.remove(addr);
+        }
+    }
+}
+
+// Комментарии OpusTags: время начала записи
+fn tags(started: DateTime<Utc>) -> Vec<String> {
+    vec![format!("DATE={}", started.format("%Y-%m-%dT%H:%M:%SZ"))]
+}
+
+// Файлы дописываются, когда запись останавливают или канал исчезает
+impl Drop for Recording {
+    fn drop(&mut self) {
+        let writers = self.mix.iter_mut().map(|(_, writer)| writer).chain(self.tracks.values_mut());
+        for writer in writers {
+            if let Err(e) = writer.finish() {
+                log_message(&format!("Recording finish error: {}", e));
+            }
+        }
+    }
+}
+
+fn with_suffix(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut path = prefix.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
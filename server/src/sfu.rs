@@ -0,0 +1,108 @@
+// Режим SFU: сервер не перекодирует звук, а пересылает каждому только
+// пакеты N самых активных говорящих. Клиент декодирует их раздельно по SSRC.
+// Активность оценивается без декодирования - по размеру пакетов Opus:
+// при VBR громкая речь дает заметно больше байт, чем фоновый шум.
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MAX_SPEAKERS: usize = 3;
+// Как часто пересматривается список говорящих
+const RESELECT_INTERVAL: Duration = Duration::from_millis(100);
+// Выбранный говорящий держится хотя бы столько, чтобы не дергать поток на паузах
+const MIN_HOLD: Duration = Duration::from_millis(500);
+// Без голосовых пакетов столько времени участник уже не говорит
+const SPEAKING_TIMEOUT: Duration = Duration::from_millis(300);
+// Сглаживание оценки громкости (доля нового пакета)
+const LEVEL_SMOOTHING: f32 = 0.2;
+
+struct Speaker {
+    level: f32,
+    last_voice: Instant,
+    selected_since: Option<Instant>,
+}
+
+pub struct SpeakerSelector {
+    max_speakers: usize,
+    speakers: HashMap<SocketAddr, Speaker>,
+    last_reselect: Instant,
+}
+
+impl SpeakerSelector {
+    pub fn new(max_speakers: usize) -> Self {
+        SpeakerSelector {
+            max_speakers: max_speakers.max(1),
+            speakers: HashMap::new(),
+            last_reselect: Instant::now(),
+        }
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        self.speakers.remove(addr);
+    }
+
+    // Учитывает голосовой пакет и решает, пересылать ли его
+    pub fn on_voice(&mut self, from: SocketAddr, opus_len: usize) -> bool {
+        let now = Instant::now();
+        let speaker = self.speakers.entry(from).or_insert(Speaker {
+            level: 0.0,
+            last_voice: now,
+            selected_since: None,
+        });
+        speaker.level += (opus_len as f32 - speaker.level) * LEVEL_SMOOTHING;
+        speaker.last_voice = now;
+
+        if self.last_reselect.elapsed() >= RESELECT_INTERVAL {
+            self.reselect();
+        }
+        self.is_selected(&from)
+    }
+
+    pub fn is_selected(&self, addr: &SocketAddr) -> bool {
+        self.speakers.get(addr).is_some_and(|s| s.selected_since.is_some())
+    }
+
+    // Самый активный из выбранных: его получают старые клиенты, которые
+    // не умеют разделять несколько потоков
+    pub fn loudest(&self) -> Option<SocketAddr> {
+        self.speakers
+            .iter()
+            .filter(|(_, s)| s.selected_since.is_some())
+            .max_by(|(_, a), (_, b)| a.level.total_cmp(&b.level))
+            .map(|(addr, _)| *addr)
+    }
+
+    fn reselect(&mut self) {
+        let now = Instant::now();
+        self.last_reselect = now;
+
+        // Замолчавшие освобождают место
+        for speaker in self.speakers.values_mut() {
+            if now.duration_since(speaker.last_voice) > SPEAKING_TIMEOUT {
+                speaker.selected_since = None;
+            }
+        }
+
+        let mut candidates: Vec<(SocketAddr, f32, bool)> = self
+            .speakers
+            .iter()
+            .filter(|(_, s)| now.duration_since(s.last_voice) <= SPEAKING_TIMEOUT)
+            .map(|(addr, s)| {
+                let held = s.selected_since.is_some_and(|t| now.duration_since(t) < MIN_HOLD);
+                (*addr, s.level, held)
+            })
+            .collect();
+        // Удерживаемые идут первыми, дальше по громкости
+        candidates.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.total_cmp(&a.1)));
+
+        for (rank, (addr, _, _)) in candidates.iter().enumerate() {
+            if let Some(speaker) = self.speakers.get_mut(addr) {
+                if rank < self.max_speakers {
+                    speaker.selected_since.get_or_insert(now);
+                } else {
+                    speaker.selected_since = None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Проверка пакетов до обработки. Порт сервера открыт всему интернету, и
+// сканеры шлют на него что попало; пересылать такое участникам нельзя.
+// Пропускается только то, что по размеру и заголовку похоже на управление
+// NSVC или на моно-Opus.
+use nsvc_core::ogg::packet_samples;
+use nsvc_core::{control_type, control_types, is_control_packet, parse_whisper_packet, CONTROL_HEADER_SIZE};
+
+// Больше не шлет ни один клиент; буфер приема на байт длиннее, чтобы
+// обрезанную датаграмму можно было отличить
+pub const MAX_PACKET_SIZE: usize = 4000;
+// Самый длинный пакет Opus - 120 мс при 48 кГц
+const MAX_OPUS_SAMPLES: u64 = 5760;
+// Заголовок MEDIA после управляющего: SSRC и номер пакета
+const MEDIA_HEADER_SIZE: usize = 8;
+
+// Err - причина, по которой пакет отброшен
+pub fn check(data: &[u8]) -> Result<(), &'static str> {
+    if data.is_empty() {
+        return Err("empty packet");
+    }
+    if data.len() > MAX_PACKET_SIZE {
+        return Err("oversized packet");
+    }
+    if !is_control_packet(data) {
+        // Голос старого клиента без заголовка
+        return check_opus(data);
+    }
+
+    let body = data.len() - CONTROL_HEADER_SIZE;
+    match control_type(data) {
+        control_types::MEDIA | control_types::MEDIA_CLIP if body <= MEDIA_HEADER_SIZE => Err("truncated MEDIA packet"),
+        control_types::MEDIA | control_types::MEDIA_CLIP => check_opus(&data[CONTROL_HEADER_SIZE + MEDIA_HEADER_SIZE..]),
+        control_types::MEDIA_WHISPER => match parse_whisper_packet(data) {
+            Some((_, _, _, opus)) if !opus.is_empty() => check_opus(&data[opus]),
+            _ => Err("truncated whisper packet"),
+        },
+        control_types::CHANNEL_JOIN if body == 0 => Err("empty channel join"),
+        // Токен P2P и число кандидатов
+        control_types::P2P_CANDIDATES if body < 5 => Err("truncated P2P candidates"),
+        // ID получателя, флаги и хотя бы один символ
+        control_types::TEXT_MESSAGE if body < 6 => Err("truncated text message"),
+        // ID участника и три координаты
+        control_types::POSITION if body < 16 => Err("truncated position"),
+        // Остальное разбирают обработчики; незнакомые типы сервер пропускает мимо
+        _ => Ok(()),
+    }
+}
+
+// Моно-Opus с длительностью в допустимых пределах. Однобайтовые пакеты -
+// это keep-alive и тишина старых клиентов, их не разбираем.
+fn check_opus(opus: &[u8]) -> Result<(), &'static str> {
+    if opus.len() == 1 {
+        return Ok(());
+    }
+    // Бит стерео в TOC: клиенты NSVC шлют только моно
+    if opus[0] & 0x04 != 0 {
+        return Err("not a mono Opus packet");
+    }
+    match packet_samples(opus) {
+        Some(samples) if (1..=MAX_OPUS_SAMPLES).contains(&samples) => Ok(()),
+        _ => Err("invalid Opus packet"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nsvc_core::{control_packet, media_packet, write_whisper_packet};
+
+    // 20 мс CELT, моно, один кадр
+    const OPUS: [u8; 3] = [0xF8, 0xFF, 0xFE];
+
+    #[test]
+    fn empty_and_oversized_packets_are_rejected() {
+        assert_eq!(check(&[]), Err("empty packet"));
+        assert_eq!(check(&vec![0xF8; MAX_PACKET_SIZE + 1]), Err("oversized packet"));
+        assert_eq!(check(&vec![0xF8; MAX_PACKET_SIZE]), Ok(()));
+    }
+
+    #[test]
+    fn bare_opus_must_be_mono() {
+        assert_eq!(check(&OPUS), Ok(()));
+        assert_eq!(check(&[0xFC, 0xFF, 0xFE]), Err("not a mono Opus packet"));
+        // 63 кадра по 20 мс - длиннее любого пакета Opus
+        assert_eq!(check(&[0xFB, 0x3F, 0x00]), Err("invalid Opus packet"));
+    }
+
+    #[test]
+    fn one_byte_keep_alive_passes() {
+        assert_eq!(check(&[0x00]), Ok(()));
+        assert_eq!(check(&[0xFC]), Ok(()));
+        assert_eq!(check(&media_packet(1, 2, &[0xFC])), Ok(()));
+    }
+
+    #[test]
+    fn media_packets() {
+        assert_eq!(check(&media_packet(1, 2, &OPUS)), Ok(()));
+        assert_eq!(check(&media_packet(1, 2, &[0xFC, 0xFF, 0xFE])), Err("not a mono Opus packet"));
+        assert_eq!(check(&media_packet(1, 2, &[])), Err("truncated MEDIA packet"));
+        assert_eq!(check(&control_packet(control_types::MEDIA_CLIP, &[0; 4])), Err("truncated MEDIA packet"));
+    }
+
+    #[test]
+    fn whisper_packets() {
+        let mut packet = Vec::new();
+        write_whisper_packet(&mut packet, 1, 2, &[3, 4], &OPUS);
+        assert_eq!(check(&packet), Ok(()));
+        write_whisper_packet(&mut packet, 1, 2, &[3, 4], &[]);
+        assert_eq!(check(&packet), Err("truncated whisper packet"));
+        // Получателей объявлено больше, чем пришло
+        write_whisper_packet(&mut packet, 1, 2, &[3, 4], &OPUS);
+        packet.truncate(CONTROL_HEADER_SIZE + MEDIA_HEADER_SIZE + 5);
+        assert_eq!(check(&packet), Err("truncated whisper packet"));
+    }
+
+    #[test]
+    fn truncated_control_packets_are_rejected() {
+        assert_eq!(check(&control_packet(control_types::CHANNEL_JOIN, &[])), Err("empty channel join"));
+        assert_eq!(check(&control_packet(control_types::P2P_CANDIDATES, &[0; 4])), Err("truncated P2P candidates"));
+        assert_eq!(check(&control_packet(control_types::TEXT_MESSAGE, &[0; 5])), Err("truncated text message"));
+        assert_eq!(check(&control_packet(control_types::TEXT_MESSAGE, &[0, 0, 0, 1, 0, b'a'])), Ok(()));
+        assert_eq!(check(&control_packet(control_types::POSITION, &[0; 15])), Err("truncated position"));
+        assert_eq!(check(&control_packet(control_types::POSITION, &[0; 16])), Ok(()));
+    }
+
+    #[test]
+    fn unknown_control_types_pass() {
+        assert_eq!(check(&control_packet(0xEE, &[])), Ok(()));
+    }
+}
@@ -0,0 +1,244 @@
+// Клиенты из браузера: UDP там недоступен, поэтому те же пакеты протокола
+// ходят по WebSocket, по одному в бинарном сообщении. Адрес такого клиента -
+// адрес его TCP-соединения, так что ограничения по IP, запреты и таймауты
+// работают как для UDP. Потоки соединений передают пакеты основному циклу
+// через канал и будят его пустой датаграммой на его же UDP-сокет.
+//
+// TLS (wss://) здесь нет: для страниц по HTTPS сервер ставят за обратный
+// прокси, и тогда все клиенты WebSocket видны с адреса прокси.
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use nsvc_core::websocket::{self, close_codes, opcodes, Frame};
+
+use crate::log_message;
+use crate::validation::MAX_PACKET_SIZE;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Клиент шлет keep-alive каждую секунду; молчащее дольше соединение мертво
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_REQUEST_SIZE: usize = 8192;
+// Пакетов в очереди записи одного клиента; лишние теряются, как в UDP
+const SEND_QUEUE: usize = 64;
+
+enum Event {
+    Opened(SocketAddr, SyncSender<Frame>),
+    Packet(SocketAddr, Vec<u8>),
+    Closed(SocketAddr),
+}
+
+// Что поток соединения сообщил основному циклу
+pub enum Incoming {
+    Packet(SocketAddr, Vec<u8>),
+    Closed(SocketAddr),
+}
+
+pub struct Bridge {
+    events: Receiver<Event>,
+    peers: HashMap<SocketAddr, SyncSender<Frame>>,
+    // Отсюда приходят пустые датаграммы-будильники
+    waker: SocketAddr,
+}
+
+// server - адрес UDP-сокета сервера, который надо будить
+pub fn spawn(addr: &str, server: SocketAddr) -> io::Result<Bridge> {
+    let listener = TcpListener::bind(addr)?;
+    let waker = UdpSocket::bind(if server.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" })?;
+    waker.connect(wake_target(server))?;
+    let waker_addr = waker.local_addr()?;
+    let waker = Arc::new(waker);
+    let (events_tx, events) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log_message(&format!("WebSocket accept error: {}", e));
+                    continue;
+                },
+            };
+            let events = events_tx.clone();
+            let waker = waker.clone();
+            thread::spawn(move || {
+                let peer = match stream.peer_addr() {
+                    Ok(peer) => peer,
+                    Err(_) => return,
+                };
+                if let Err(e) = serve(stream, peer, &events, &waker) {
+                    log_message(&format!("WebSocket {} error: {}", peer, e));
+                }
+            });
+        }
+    });
+
+    Ok(Bridge {
+        events,
+        peers: HashMap::new(),
+        waker: waker_addr,
+    })
+}
+
+// Сервер на 0.0.0.0 или [::] будим через loopback
+fn wake_target(server: SocketAddr) -> SocketAddr {
+    let mut target = server;
+    if server.ip().is_unspecified() {
+        target.set_ip(if server.is_ipv6() {
+            std::net::Ipv6Addr::LOCALHOST.into()
+        } else {
+            std::net::Ipv4Addr::LOCALHOST.into()
+        });
+    }
+    target
+}
+
+impl Bridge {
+    // Датаграммы с этих адресов не от клиентов UDP: будильник или подделка
+    // адреса клиента WebSocket
+    pub fn owns(&self, addr: SocketAddr) -> bool {
+        addr == self.waker || self.peers.contains_key(&addr)
+    }
+
+    // Все, что пришло от потоков соединений с прошлого вызова
+    pub fn poll(&mut self) -> Vec<Incoming> {
+        let mut incoming = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Opened(addr, sender) => {
+                    self.peers.insert(addr, sender);
+                },
+                Event::Packet(addr, data) => incoming.push(Incoming::Packet(addr, data)),
+                Event::Closed(addr) => {
+                    self.peers.remove(&addr);
+                    incoming.push(Incoming::Closed(addr));
+                },
+            }
+        }
+        incoming
+    }
+
+    // None - адрес не клиента WebSocket. Переполненная очередь теряет пакет
+    // молча, как буфер UDP-сокета.
+    pub fn send(&self, data: &[u8], to: SocketAddr) -> Option<io::Result<usize>> {
+        let sender = self.peers.get(&to)?;
+        let frame = Frame::new(opcodes::BINARY, data.to_vec());
+        Some(match sender.try_send(frame) {
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(data.len()),
+            Err(TrySendError::Disconnected(_)) => Err(io::ErrorKind::NotConnected.into()),
+        })
+    }
+}
+
+fn serve(mut stream: TcpStream, peer: SocketAddr, events: &Sender<Event>, waker: &UdpSocket) -> io::Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+    handshake(&mut stream)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    // Запись в своем потоке: медленный клиент не задерживает основной цикл
+    let (frames_tx, frames) = mpsc::sync_channel::<Frame>(SEND_QUEUE);
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for frame in frames {
+            if websocket::write_frame(&mut writer, &frame).is_err() || frame.opcode == opcodes::CLOSE {
+                break;
+            }
+        }
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    log_message(&format!("WebSocket client connected: {}", peer));
+    let _ = events.send(Event::Opened(peer, frames_tx.clone()));
+    let result = read_loop(&mut stream, peer, events, waker, &frames_tx);
+    let _ = events.send(Event::Closed(peer));
+    let _ = waker.send(&[]);
+    log_message(&format!("WebSocket client disconnected: {}", peer));
+    result
+}
+
+fn read_loop(
+    stream: &mut TcpStream,
+    peer: SocketAddr,
+    events: &Sender<Event>,
+    waker: &UdpSocket,
+    frames: &SyncSender<Frame>,
+) -> io::Result<()> {
+    loop {
+        // Кадр больше пакета протокола - InvalidData
+        let Frame { fin, opcode, payload } = match websocket::read_frame(stream, MAX_PACKET_SIZE) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                let _ = frames.try_send(Frame::close(close_codes::TOO_BIG));
+                return Err(e);
+            },
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                let _ = frames.try_send(Frame::close(close_codes::PROTOCOL_ERROR));
+                return Err(e);
+            },
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match opcode {
+            // Пакеты протокола малы: дробленые сообщения не принимаем
+            opcodes::BINARY if fin => {
+                if events.send(Event::Packet(peer, payload)).is_err() {
+                    return Ok(());
+                }
+                let _ = waker.send(&[]);
+            },
+            opcodes::BINARY | opcodes::CONTINUATION => {
+                let _ = frames.try_send(Frame::close(close_codes::TOO_BIG));
+                return Ok(());
+            },
+            opcodes::PING => {
+                let _ = frames.try_send(Frame::new(opcodes::PONG, payload));
+            },
+            opcodes::PONG => {},
+            opcodes::CLOSE => {
+                let payload = payload.get(..2).map(<[u8]>::to_vec).unwrap_or_default();
+                let _ = frames.try_send(Frame::new(opcodes::CLOSE, payload));
+                return Ok(());
+            },
+            // Текстовые сообщения протокол не использует
+            _ => {
+                let _ = frames.try_send(Frame::close(close_codes::UNSUPPORTED));
+                return Ok(());
+            },
+        }
+    }
+}
+
+// HTTP Upgrade по RFC 6455
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() >= MAX_REQUEST_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "request too large"));
+        }
+        let received = stream.read(&mut buf)?;
+        if received == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        request.extend_from_slice(&buf[..received]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut lines = request.lines();
+    let is_get = lines.next().is_some_and(|line| line.starts_with("GET "));
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let key = websocket::upgrade_key(&headers).filter(|_| is_get);
+    websocket::respond(stream, key)?;
+    if key.is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a WebSocket request"));
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+// Звук в браузере: микрофон и воспроизведение через AudioWorklet
+// (worklet.js), Opus через WebCodecs. Кадры микрофона приходят из потока
+// звука в главный поток, там кодируются и уходят в on_packet; принятые
+// пакеты декодируются своим декодером на каждый источник и отдаются
+// процессору воспроизведения.
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use js_sys::{Array, Float32Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AudioContext, AudioContextOptions, AudioData, AudioDataCopyToOptions, AudioDataInit, AudioDecoder,
+    AudioDecoderConfig, AudioDecoderInit, AudioEncoder, AudioEncoderConfig, AudioEncoderInit, AudioSampleFormat,
+    AudioWorkletNode, AudioWorkletNodeOptions, Blob, BlobPropertyBag, EncodedAudioChunk, EncodedAudioChunkInit,
+    EncodedAudioChunkType, MediaStream, MediaStreamAudioSourceNode, MediaStreamConstraints, MediaStreamTrack, MessageEvent, MessagePort, Url,
+};
+
+use crate::{log_message, SAMPLE_RATE};
+
+const WORKLET: &str = include_str!("worklet.js");
+// Сколько звука держать в очереди каждого источника, как DEFAULT_BUFFER_MS
+// у нативного клиента
+const BUFFER_MS: u32 = 200;
+
+type PacketCallback = Box<dyn FnMut(Vec<u8>)>;
+
+// Декодер источника и метка следующего пакета
+struct Source {
+    decoder: AudioDecoder,
+    timestamp: f64,
+    _output: Closure<dyn FnMut(AudioData)>,
+}
+
+pub struct Audio {
+    context: AudioContext,
+    stream: MediaStream,
+    // Узлы держим сами: иначе браузер может собрать их сборщиком мусора
+    source: MediaStreamAudioSourceNode,
+    capture: AudioWorkletNode,
+    playback: AudioWorkletNode,
+    playback_port: MessagePort,
+    encoder: AudioEncoder,
+    sources: RefCell<HashMap<u32, Source>>,
+    // Передавать ли кадры микрофона кодеру
+    transmitting: Rc<Cell<bool>>,
+    on_error: Closure<dyn FnMut(JsValue)>,
+    _on_frame: Closure<dyn FnMut(MessageEvent)>,
+    _on_encoded: Closure<dyn FnMut(EncodedAudioChunk)>,
+}
+
+impl Audio {
+    // Запрашивает микрофон: вызывать после жеста пользователя
+    pub async fn start(
+        frame_size: u32,
+        bitrate: u32,
+        transmitting: Rc<Cell<bool>>,
+        on_packet: PacketCallback,
+    ) -> Result<Audio, JsValue> {
+        let options = AudioContextOptions::new();
+        options.set_sample_rate(SAMPLE_RATE as f32);
+        let context = AudioContext::new_with_context_options(&options)?;
+
+        // Процессоры грузятся модулем по URL: отдаем им Blob с worklet.js
+        let blob_options = BlobPropertyBag::new();
+        blob_options.set_type("application/javascript");
+        let blob = Blob::new_with_str_sequence_and_options(&Array::of1(&WORKLET.into()), &blob_options)?;
+        let url = Url::create_object_url_with_blob(&blob)?;
+        JsFuture::from(context.audio_worklet()?.add_module(&url)?).await?;
+        Url::revoke_object_url(&url)?;
+
+        // Эхо- и шумоподавление браузера заменяют нативный шумодав
+        let audio = Object::new();
+        for key in ["echoCancellation", "noiseSuppression", "autoGainControl"] {
+            Reflect::set(&audio, &key.into(), &JsValue::TRUE)?;
+        }
+        Reflect::set(&audio, &"channelCount".into(), &1.into())?;
+        let constraints = MediaStreamConstraints::new();
+        constraints.set_audio(&audio);
+        let devices = web_sys::window().ok_or("no window")?.navigator().media_devices()?;
+        let stream: MediaStream = JsFuture::from(devices.get_user_media_with_constraints(&constraints)?).await?.dyn_into()?;
+
+        let on_error = Closure::<dyn FnMut(JsValue)>::new(|e: JsValue| {
+            log_message(&format!("Opus codec error: {:?}", e));
+        });
+
+        let mut on_packet = on_packet;
+        let on_encoded = Closure::<dyn FnMut(EncodedAudioChunk)>::new(move |chunk: EncodedAudioChunk| {
+            let mut packet = vec![0u8; chunk.byte_length() as usize];
+            if chunk.copy_to_with_u8_slice(&mut packet).is_ok() {
+                on_packet(packet);
+            }
+        });
+        let encoder = AudioEncoder::new(&AudioEncoderInit::new(
+            on_error.as_ref().unchecked_ref(),
+            on_encoded.as_ref().unchecked_ref(),
+        ))?;
+        encoder.configure(&encoder_config(frame_size, bitrate)?)?;
+
+        // Микрофон -> процессор захвата; выходов у него нет, звук уходит в port
+        let capture_options = AudioWorkletNodeOptions::new();
+        capture_options.set_number_of_outputs(0);
+        capture_options.set_processor_options(Some(&processor_options("frameSize", frame_size)?));
+        let capture = AudioWorkletNode::new_with_options(&context, "nsvc-capture", &capture_options)?;
+        let source = context.create_media_stream_source(&stream)?;
+        source.connect_with_audio_node(&capture)?;
+
+        let encoder_frame = encoder.clone();
+        let transmitting_frame = transmitting.clone();
+        let mut timestamp = 0.0;
+        let on_frame = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let Ok(frame) = event.data().dyn_into::<Float32Array>() else {
+                return;
+            };
+            let frames = frame.length();
+            let duration = frames as f64 * 1_000_000.0 / SAMPLE_RATE as f64;
+            if !transmitting_frame.get() {
+                timestamp += duration;
+                return;
+            }
+            let init = AudioDataInit::new(&frame, AudioSampleFormat::F32Planar, 1, frames, SAMPLE_RATE as f32, 0);
+            init.set_timestamp_f64(timestamp);
+            timestamp += duration;
+            if let Ok(data) = AudioData::new(&init) {
+                if let Err(e) = encoder_frame.encode(&data) {
+                    log_message(&format!("Encode error: {:?}", e));
+                }
+                data.close();
+            }
+        });
+        capture.port()?.set_onmessage(Some(on_frame.as_ref().unchecked_ref()));
+
+        // Процессор воспроизведения -> динамики
+        let playback_options = AudioWorkletNodeOptions::new();
+        playback_options.set_number_of_inputs(0);
+        playback_options.set_output_channel_count(&Array::of1(&1.into()));
+        playback_options.set_processor_options(Some(&processor_options(
+            "maxBuffered",
+            SAMPLE_RATE * BUFFER_MS / 1000,
+        )?));
+        let playback = AudioWorkletNode::new_with_options(&context, "nsvc-playback", &playback_options)?;
+        playback.connect_with_audio_node(&context.destination())?;
+
+        // Контекст, созданный без жеста пользователя, стоит на паузе
+        JsFuture::from(context.resume()?).await?;
+
+        Ok(Audio {
+            context,
+            stream,
+            playback_port: playback.port()?,
+            source,
+            capture,
+            playback,
+            encoder,
+            sources: RefCell::new(HashMap::new()),
+            transmitting,
+            on_error,
+            _on_frame: on_frame,
+            _on_encoded: on_encoded,
+        })
+    }
+
+    // Параметры, согласованные с сервером
+    pub fn configure(&self, frame_size: u32, bitrate: u32) -> Result<(), JsValue> {
+        self.encoder.configure(&encoder_config(frame_size, bitrate)?)?;
+        let message = processor_options("frameSize", frame_size)?;
+        self.capture.port()?.post_message(&message)
+    }
+
+    pub fn play(&self, ssrc: u32, opus: &[u8]) {
+        // Однобайтовые пакеты тишины не декодируем
+        if opus.len() <= 1 {
+            return;
+        }
+        let mut sources = self.sources.borrow_mut();
+        let source = match sources.entry(ssrc) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match self.new_source(ssrc) {
+                Ok(source) => entry.insert(source),
+                Err(e) => {
+                    log_message(&format!("Failed to create decoder for {}: {:?}", ssrc, e));
+                    return;
+                },
+            },
+        };
+
+        // Каждый пакет Opus декодируется независимо
+        let init = EncodedAudioChunkInit::new_with_u8_array(&Uint8Array::from(opus), 0, EncodedAudioChunkType::Key);
+        // Метка времени декодеру нужна только возрастающая
+        init.set_timestamp_f64(source.timestamp);
+        source.timestamp += 1.0;
+        match EncodedAudioChunk::new(&init) {
+            Ok(chunk) => {
+                if let Err(e) = source.decoder.decode(&chunk) {
+                    log_message(&format!("Decode error from {}: {:?}", ssrc, e));
+                }
+            },
+            Err(e) => log_message(&format!("Invalid packet from {}: {:?}", ssrc, e)),
+        }
+    }
+
+    // Источник ушел: декодер закрывается, очередь воспроизведения очищается
+    pub fn remove_source(&self, ssrc: u32) {
+        if let Some(source) = self.sources.borrow_mut().remove(&ssrc) {
+            let _ = source.decoder.close();
+        }
+        if let Ok(message) = processor_options("ssrc", ssrc) {
+            let _ = self.playback_port.post_message(&message);
+        }
+    }
+
+    pub fn close(&self) {
+        self.transmitting.set(false);
+        let _ = self.encoder.close();
+        for (_, source) in self.sources.borrow_mut().drain() {
+            let _ = source.decoder.close();
+        }
+        let tracks = self.stream.get_tracks();
+        for track in tracks.iter() {
+            if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+                track.stop();
+            }
+        }
+        self.source.disconnect().ok();
+        self.playback.disconnect().ok();
+        let _ = self.context.close();
+    }
+
+    fn new_source(&self, ssrc: u32) -> Result<Source, JsValue> {
+        let playback = self.playback_port.clone();
+        let output = Closure::<dyn FnMut(AudioData)>::new(move |data: AudioData| {
+            let samples = Float32Array::new_with_length(data.number_of_frames());
+            let options = AudioDataCopyToOptions::new(0);
+            options.set_format(AudioSampleFormat::F32Planar);
+            let copied = data.copy_to_with_buffer_source(&samples, &options);
+            data.close();
+            if copied.is_err() {
+                return;
+            }
+            let Ok(message) = processor_options("ssrc", ssrc) else {
+                return;
+            };
+            if Reflect::set(&message, &"samples".into(), &samples).is_ok() {
+                let _ = playback.post_message_with_transferable(&message, &Array::of1(&samples.buffer()));
+            }
+        });
+        let decoder = AudioDecoder::new(&AudioDecoderInit::new(
+            self.on_error.as_ref().unchecked_ref(),
+            output.as_ref().unchecked_ref(),
+        ))?;
+        decoder.configure(&AudioDecoderConfig::new("opus", 1, SAMPLE_RATE))?;
+        Ok(Source {
+            decoder,
+            timestamp: 0.0,
+            _output: output,
+        })
+    }
+}
+
+fn encoder_config(frame_size: u32, bitrate: u32) -> Result<AudioEncoderConfig, JsValue> {
+    let config = AudioEncoderConfig::new("opus", 1, SAMPLE_RATE);
+    config.set_bitrate(bitrate);
+    // Длительность кадра Opus в микросекундах
+    let opus = Object::new();
+    Reflect::set(&opus, &"frameDuration".into(), &(frame_size as f64 * 1_000_000.0 / SAMPLE_RATE as f64).into())?;
+    Reflect::set(&config, &"opus".into(), &opus)?;
+    Ok(config)
+}
+
+// Объект из одного числового поля: параметры процессоров и сообщения им
+fn processor_options(key: &str, value: u32) -> Result<Object, JsValue> {
+    let options = Object::new();
+    Reflect::set(&options, &key.into(), &value.into())?;
+    Ok(options)
+}
@@ -0,0 +1,487 @@
+// Клиент NSVC для браузера (wasm32). Протокол тот же, что у нативного
+// клиента, - его файлы берутся из основной библиотеки как есть, - но звук
+// идет через Web Audio и WebCodecs (audio.rs), а пакеты - по WebSocket:
+// сервер принимает таких клиентов с ключом --websocket. Поэтому страница
+// попадает в те же каналы, что и нативные клиенты.
+//
+// Сборка: wasm-pack build --target web (флаги - в .cargo/config.toml).
+// Из JS:
+//   import init, { WebClient } from "./pkg/nsvc_web.js";
+//   await init();
+//   const client = new WebClient("ws://example.org:40080");
+//   client.setEventCallback((event, userId, code, text) => { ... });
+//   await client.start(); // после жеста пользователя: нужен микрофон
+//   client.setTransmitting(true);
+//
+// События - те же event_types, что у нативного callback событий, кроме
+// начала и конца речи.
+mod audio;
+
+#[path = "../../core/src/protocol.rs"]
+#[allow(dead_code)]
+mod protocol;
+#[path = "../../core/src/channels.rs"]
+#[allow(dead_code)]
+mod channels;
+#[path = "../../core/src/events.rs"]
+#[allow(dead_code)]
+mod events;
+#[path = "../../core/src/handshake.rs"]
+#[allow(dead_code)]
+mod handshake;
+#[path = "../../core/src/text.rs"]
+mod text;
+#[path = "../../core/src/users.rs"]
+#[allow(dead_code)]
+mod users;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use js_sys::{ArrayBuffer, Function, Promise, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::future_to_promise;
+use web_sys::{BinaryType, CloseEvent, MessageEvent, WebSocket};
+
+use channels::ChannelState;
+use events::event_types;
+use handshake::{features, Credentials, Handshake, Outcome, SessionParams};
+use protocol::*;
+
+const FRAME_SIZE: u32 = 480; // 10 мс
+const BITRATE: u32 = 32000;
+const FEATURES: u32 = features::SEQUENCE | features::SESSION_TOKEN;
+const KEEP_ALIVE_INTERVAL_MS: i32 = 1000;
+
+// Состояния в событиях CONNECTED/DISCONNECTED - как connection_states
+// нативного клиента
+mod connection_states {
+    pub const DISCONNECTED: i32 = 0;
+    pub const CONNECTED: i32 = 2;
+    pub const FAILED: i32 = 4;
+}
+
+fn log_message(message: &str) {
+    web_sys::console::log_1(&message.into());
+}
+
+#[wasm_bindgen]
+pub struct WebClient {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    url: String,
+    socket: RefCell<Option<WebSocket>>,
+    audio: RefCell<Option<audio::Audio>>,
+    handshake: Handshake,
+    channels: ChannelState,
+    credentials: RefCell<Credentials>,
+    nickname: RefCell<String>,
+    // Участники канала: ID -> ник
+    roster: RefCell<HashMap<u32, String>>,
+    transmitting: Rc<Cell<bool>>,
+    media_seq: Cell<u32>,
+    on_event: RefCell<Option<Function>>,
+    timers: RefCell<Vec<i32>>,
+    // Обработчики событий WebSocket и таймеров живут, пока клиент запущен
+    closures: RefCell<Vec<Box<dyn std::any::Any>>>,
+}
+
+#[wasm_bindgen]
+impl WebClient {
+    // url - адрес WebSocket сервера, например "ws://example.org:40080"
+    #[wasm_bindgen(constructor)]
+    pub fn new(url: String) -> WebClient {
+        WebClient {
+            inner: Rc::new(Inner {
+                url,
+                socket: RefCell::new(None),
+                audio: RefCell::new(None),
+                handshake: Handshake::new(),
+                channels: ChannelState::default(),
+                credentials: RefCell::new(Credentials::default()),
+                nickname: RefCell::new(String::new()),
+                roster: RefCell::new(HashMap::new()),
+                transmitting: Rc::new(Cell::new(false)),
+                media_seq: Cell::new(0),
+                on_event: RefCell::new(None),
+                timers: RefCell::new(Vec::new()),
+                closures: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    // callback(event, userId, code, text); null отключает
+    #[wasm_bindgen(js_name = setEventCallback)]
+    pub fn set_event_callback(&self, callback: Option<Function>) {
+        *self.inner.on_event.borrow_mut() = callback;
+    }
+
+    // Действует со следующего start()
+    #[wasm_bindgen(js_name = setNickname)]
+    pub fn set_nickname(&self, nickname: String) -> Result<(), JsValue> {
+        if !nickname.is_empty() && !users::is_valid_nickname(&nickname) {
+            return Err("invalid nickname".into());
+        }
+        *self.inner.nickname.borrow_mut() = nickname;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = setCredentials)]
+    pub fn set_credentials(&self, key: String, password: String) -> Result<(), JsValue> {
+        let credentials = Credentials { key, password };
+        if !credentials.is_valid() {
+            return Err("credentials too long".into());
+        }
+        *self.inner.credentials.borrow_mut() = credentials;
+        Ok(())
+    }
+
+    // Открывает микрофон и подключается; Promise завершается, когда звук
+    // запущен, подключение продолжается в фоне
+    pub fn start(&self) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            inner.start().await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    pub fn stop(&self) {
+        self.inner.stop();
+    }
+
+    #[wasm_bindgen(js_name = setTransmitting)]
+    pub fn set_transmitting(&self, transmitting: bool) {
+        self.inner.transmitting.set(transmitting);
+    }
+
+    #[wasm_bindgen(js_name = isTransmitting)]
+    pub fn is_transmitting(&self) -> bool {
+        self.inner.transmitting.get()
+    }
+
+    #[wasm_bindgen(js_name = isConnected)]
+    pub fn is_connected(&self) -> bool {
+        self.inner.socket.borrow().is_some() && self.inner.handshake.may_stream()
+    }
+
+    // ID участника, выданный сервером; 0 - еще не подключены
+    #[wasm_bindgen(js_name = userId)]
+    pub fn user_id(&self) -> u32 {
+        self.inner.handshake.user_id()
+    }
+
+    #[wasm_bindgen(js_name = joinChannel)]
+    pub fn join_channel(&self, name: String, password: Option<String>) -> Result<(), JsValue> {
+        let password = password.unwrap_or_default();
+        if !channels::is_valid_name(&name) || !channels::is_valid_password(&password) {
+            return Err("invalid channel name or password".into());
+        }
+        self.inner.channels.set_wanted(Some((name, password)));
+        if let Some(join) = self.inner.channels.pending_join() {
+            self.inner.send(&join);
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = leaveChannel)]
+    pub fn leave_channel(&self) {
+        self.inner.channels.set_wanted(None);
+        self.inner.send(&channels::leave_packet());
+    }
+
+    #[wasm_bindgen(js_name = currentChannel)]
+    pub fn current_channel(&self) -> Option<String> {
+        self.inner.channels.current()
+    }
+
+    // toUser 0 - всему каналу
+    #[wasm_bindgen(js_name = sendText)]
+    pub fn send_text(&self, to_user: u32, text: String) -> Result<(), JsValue> {
+        if !text::is_valid_text(&text) {
+            return Err("invalid text message".into());
+        }
+        if !self.is_connected() {
+            return Err("not connected".into());
+        }
+        let flags = if to_user != 0 { text::FLAG_PRIVATE } else { 0 };
+        self.inner.send(&text::text_packet(to_user, flags, &text));
+        Ok(())
+    }
+}
+
+impl Inner {
+    async fn start(self: &Rc<Self>) -> Result<(), JsValue> {
+        if self.socket.borrow().is_some() {
+            return Err("client is already running".into());
+        }
+
+        let weak = Rc::downgrade(self);
+        let on_packet = Box::new(move |opus: Vec<u8>| {
+            if let Some(inner) = weak.upgrade() {
+                inner.send_media(&opus);
+            }
+        });
+        let audio = audio::Audio::start(FRAME_SIZE, BITRATE, self.transmitting.clone(), on_packet).await?;
+        *self.audio.borrow_mut() = Some(audio);
+
+        let socket = match WebSocket::new(&self.url) {
+            Ok(socket) => socket,
+            Err(e) => {
+                self.stop();
+                return Err(e);
+            },
+        };
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        self.handshake.set_outcome(Outcome::Pending);
+
+        let weak = Rc::downgrade(self);
+        let on_open = Closure::<dyn FnMut()>::new(move || {
+            if let Some(inner) = weak.upgrade() {
+                inner.send_hello();
+            }
+        });
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        let weak = Rc::downgrade(self);
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let (Some(inner), Ok(buffer)) = (weak.upgrade(), event.data().dyn_into::<ArrayBuffer>()) else {
+                return;
+            };
+            inner.on_packet(&Uint8Array::new(&buffer).to_vec());
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let weak = Rc::downgrade(self);
+        let on_close = Closure::<dyn FnMut(CloseEvent)>::new(move |event: CloseEvent| {
+            if let Some(inner) = weak.upgrade() {
+                log_message(&format!("WebSocket closed: {} {}", event.code(), event.reason()));
+                inner.emit(event_types::DISCONNECTED, 0, connection_states::DISCONNECTED, &event.reason());
+                inner.stop();
+            }
+        });
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        *self.socket.borrow_mut() = Some(socket);
+
+        // Повтор HELLO и JOIN, пока сервер не ответил, и keep-alive
+        let weak = Rc::downgrade(self);
+        let on_hello_timer = Closure::<dyn FnMut()>::new(move || {
+            if let Some(inner) = weak.upgrade() {
+                inner.on_hello_timer();
+            }
+        });
+        let weak = Rc::downgrade(self);
+        let on_keep_alive = Closure::<dyn FnMut()>::new(move || {
+            if let Some(inner) = weak.upgrade() {
+                inner.send_keep_alive();
+            }
+        });
+        let window = web_sys::window().ok_or("no window")?;
+        let hello_interval = handshake::HELLO_INTERVAL.as_millis() as i32;
+        let timers = [
+            window.set_interval_with_callback_and_timeout_and_arguments_0(
+                on_hello_timer.as_ref().unchecked_ref(),
+                hello_interval,
+            )?,
+            window.set_interval_with_callback_and_timeout_and_arguments_0(
+                on_keep_alive.as_ref().unchecked_ref(),
+                KEEP_ALIVE_INTERVAL_MS,
+            )?,
+        ];
+        self.timers.borrow_mut().extend(timers);
+
+        self.closures.borrow_mut().extend([
+            Box::new(on_open) as Box<dyn std::any::Any>,
+            Box::new(on_message),
+            Box::new(on_close),
+            Box::new(on_hello_timer),
+            Box::new(on_keep_alive),
+        ]);
+        Ok(())
+    }
+
+    fn stop(&self) {
+        if let Some(window) = web_sys::window() {
+            for timer in self.timers.borrow_mut().drain(..) {
+                window.clear_interval_with_handle(timer);
+            }
+        }
+        if let Some(socket) = self.socket.borrow_mut().take() {
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+        if let Some(audio) = self.audio.borrow_mut().take() {
+            audio.close();
+        }
+        self.channels.reset();
+        self.roster.borrow_mut().clear();
+        // Обработчик onclose мог вызвать stop сам: его замыкание удаляем последним
+        let closures = std::mem::take(&mut *self.closures.borrow_mut());
+        drop(closures);
+    }
+
+    fn send(&self, packet: &[u8]) {
+        if let Some(socket) = self.socket.borrow().as_ref() {
+            if socket.ready_state() == WebSocket::OPEN {
+                if let Err(e) = socket.send_with_u8_array(packet) {
+                    log_message(&format!("WebSocket send error: {:?}", e));
+                }
+            }
+        }
+    }
+
+    fn send_hello(&self) {
+        let requested = SessionParams {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            frame_size: FRAME_SIZE as u16,
+            bitrate: BITRATE,
+            features: FEATURES,
+        };
+        self.send(&handshake::hello_packet(&requested, &self.credentials.borrow(), &self.nickname.borrow()));
+    }
+
+    fn on_hello_timer(&self) {
+        match self.handshake.outcome() {
+            Outcome::Pending => self.send_hello(),
+            Outcome::Accepted(_) => {
+                if let Some(join) = self.channels.pending_join() {
+                    self.send(&join);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Keep-alive с ID участника и временем отправки, как у нативного клиента
+    fn send_keep_alive(&self) {
+        if !self.handshake.may_stream() {
+            return;
+        }
+        let mut body = Vec::with_capacity(12);
+        body.extend_from_slice(&self.handshake.user_id().to_be_bytes());
+        body.extend_from_slice(&(js_sys::Date::now() as u64).to_be_bytes());
+        self.send(&control_packet(control_types::KEEP_ALIVE, &body));
+    }
+
+    // Голос серверу: вместо SSRC токен сессии, если он согласован
+    fn send_media(&self, opus: &[u8]) {
+        if !self.handshake.may_stream() {
+            return;
+        }
+        let ssrc = match self.handshake.session_token() {
+            0 => self.handshake.user_id(),
+            token => token,
+        };
+        let seq = self.media_seq.get().wrapping_add(1);
+        self.media_seq.set(seq);
+        self.send(&media_packet(ssrc, seq, opus));
+    }
+
+    fn on_packet(&self, data: &[u8]) {
+        // Старый формат голоса сервер шлет только клиентам без SEQUENCE
+        if !is_control_packet(data) {
+            return;
+        }
+        match control_type(data) {
+            control_types::HELLO_ACCEPT | control_types::HELLO_REJECT | control_types::VERSION_MISMATCH
+                if self.handshake.on_reply(data) =>
+            {
+                self.on_handshake();
+            },
+            control_types::MEDIA => {
+                if let (Some((ssrc, _, opus)), Some(audio)) = (parse_media_packet(data), self.audio.borrow().as_ref()) {
+                    audio.play(ssrc, &data[opus]);
+                }
+            },
+            control_types::CHANNEL_DENIED => {
+                if let Some(reason) = self.channels.on_denied(data) {
+                    self.emit(event_types::ERROR, 0, 0, &reason);
+                }
+            },
+            control_types::CHANNEL_JOINED => {
+                self.channels.on_packet(data);
+                // Новый канал - новый список участников
+                self.send(&users::roster_request_packet());
+            },
+            control_types::CHANNEL_LIST => {
+                self.channels.on_packet(data);
+            },
+            control_types::ROSTER => {
+                if let Some(list) = users::parse_roster(data) {
+                    self.on_roster(list);
+                }
+            },
+            control_types::TEXT_MESSAGE => {
+                if let Some((sender, flags, text)) = text::parse_text(data) {
+                    self.emit(event_types::TEXT_MESSAGE, sender, (flags & text::FLAG_PRIVATE != 0) as i32, text);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn on_handshake(&self) {
+        match self.handshake.outcome() {
+            Outcome::Accepted(params) => {
+                log_message(&format!("Connected, user id {}", self.handshake.user_id()));
+                if params.frame_size as u32 != FRAME_SIZE || params.bitrate != BITRATE {
+                    if let Some(audio) = self.audio.borrow().as_ref() {
+                        if let Err(e) = audio.configure(params.frame_size as u32, params.bitrate) {
+                            log_message(&format!("Failed to apply session parameters: {:?}", e));
+                        }
+                    }
+                }
+                self.emit(event_types::CONNECTED, 0, connection_states::CONNECTED, "");
+                self.send(&users::roster_request_packet());
+                if let Some(join) = self.channels.pending_join() {
+                    self.send(&join);
+                }
+            },
+            Outcome::Rejected(reason) => {
+                self.emit(event_types::DISCONNECTED, 0, connection_states::FAILED, &reason);
+                self.stop();
+            },
+            Outcome::VersionMismatch { server_min, server_max } => {
+                let reason = format!("server supports protocol versions {}-{}", server_min, server_max);
+                self.emit(event_types::DISCONNECTED, 0, connection_states::FAILED, &reason);
+                self.stop();
+            },
+            Outcome::Pending | Outcome::Legacy => {},
+        }
+    }
+
+    // Список участников целиком: сравниваем с прежним
+    fn on_roster(&self, list: Vec<(u32, String)>) {
+        let own_id = self.handshake.user_id();
+        let new: HashMap<u32, String> = list.into_iter().filter(|(id, _)| *id != own_id).collect();
+        let old = std::mem::replace(&mut *self.roster.borrow_mut(), new.clone());
+        for (id, name) in &old {
+            if !new.contains_key(id) {
+                if let Some(audio) = self.audio.borrow().as_ref() {
+                    audio.remove_source(*id);
+                }
+                self.emit(event_types::USER_LEFT, *id, 0, name);
+            }
+        }
+        for (id, name) in &new {
+            if !old.contains_key(id) {
+                self.emit(event_types::USER_JOINED, *id, 0, name);
+            }
+        }
+    }
+
+    fn emit(&self, event: i32, user_id: u32, code: i32, text: &str) {
+        let callback = self.on_event.borrow().clone();
+        if let Some(callback) = callback {
+            let args = js_sys::Array::of4(&event.into(), &user_id.into(), &code.into(), &text.into());
+            if let Err(e) = callback.apply(&JsValue::NULL, &args) {
+                log_message(&format!("Event callback error: {:?}", e));
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// Процессоры AudioWorklet клиента NSVC (см. audio.rs). Работают в потоке
+// звука браузера на 48 кГц, моно.
+
+// Микрофон: копит отсчеты до кадра Opus и отдает кадр главному потоку
+class CaptureProcessor extends AudioWorkletProcessor {
+    constructor(options) {
+        super();
+        this.resize(options.processorOptions.frameSize);
+        // Новый размер кадра после рукопожатия
+        this.port.onmessage = (event) => this.resize(event.data.frameSize);
+    }
+
+    resize(frameSize) {
+        this.frameSize = frameSize;
+        this.frame = new Float32Array(frameSize);
+        this.filled = 0;
+    }
+
+    process(inputs) {
+        const input = inputs[0][0];
+        if (!input) {
+            return true;
+        }
+        let offset = 0;
+        while (offset < input.length) {
+            const count = Math.min(input.length - offset, this.frameSize - this.filled);
+            this.frame.set(input.subarray(offset, offset + count), this.filled);
+            this.filled += count;
+            offset += count;
+            if (this.filled === this.frameSize) {
+                this.port.postMessage(this.frame, [this.frame.buffer]);
+                this.frame = new Float32Array(this.frameSize);
+                this.filled = 0;
+            }
+        }
+        return true;
+    }
+}
+
+// Очередь звука одного источника
+class Source {
+    constructor() {
+        this.chunks = [];
+        this.offset = 0;
+        this.buffered = 0;
+    }
+
+    push(samples, maxBuffered) {
+        this.chunks.push(samples);
+        this.buffered += samples.length;
+        // Лишнее сверх буфера выбрасываем с начала: задержка не копится
+        while (this.buffered > maxBuffered && this.chunks.length > 1) {
+            this.buffered -= this.chunks[0].length - this.offset;
+            this.chunks.shift();
+            this.offset = 0;
+        }
+    }
+
+    // Прибавляет свой звук к out
+    mixInto(out) {
+        let written = 0;
+        while (written < out.length && this.chunks.length > 0) {
+            const chunk = this.chunks[0];
+            const count = Math.min(out.length - written, chunk.length - this.offset);
+            for (let i = 0; i < count; i++) {
+                out[written + i] += chunk[this.offset + i];
+            }
+            written += count;
+            this.offset += count;
+            this.buffered -= count;
+            if (this.offset === chunk.length) {
+                this.chunks.shift();
+                this.offset = 0;
+            }
+        }
+    }
+}
+
+// Воспроизведение: у каждого источника своя очередь, на выходе их сумма
+class PlaybackProcessor extends AudioWorkletProcessor {
+    constructor(options) {
+        super();
+        this.maxBuffered = options.processorOptions.maxBuffered;
+        this.sources = new Map();
+        this.port.onmessage = (event) => {
+            const { ssrc, samples } = event.data;
+            // Без отсчетов - источник ушел
+            if (!samples) {
+                this.sources.delete(ssrc);
+                return;
+            }
+            let source = this.sources.get(ssrc);
+            if (!source) {
+                source = new Source();
+                this.sources.set(ssrc, source);
+            }
+            source.push(samples, this.maxBuffered);
+        };
+    }
+
+    process(inputs, outputs) {
+        const out = outputs[0][0];
+        for (const source of this.sources.values()) {
+            source.mixInto(out);
+        }
+        for (let i = 0; i < out.length; i++) {
+            out[i] = Math.max(-1, Math.min(1, out[i]));
+        }
+        return true;
+    }
+}
+
+registerProcessor("nsvc-capture", CaptureProcessor);
+registerProcessor("nsvc-playback", PlaybackProcessor);